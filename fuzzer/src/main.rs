@@ -7,6 +7,8 @@ use std::convert::TryInto;
 #[link(name="User32")]
 extern "system" {
     fn FindWindowW(lpClassName: *mut u16, lpWindowName: *mut u16) -> usize;
+    fn FindWindowExW(hWndParent: usize, hWndChildAfter: usize,
+        lpszClass: *mut u16, lpszWindow: *mut u16) -> usize;
     fn PostMessageW(hWnd: usize, msg: u32, wParam: usize, lParam: usize)
         -> usize;
     fn GetForegroundWindow() -> usize;
@@ -133,7 +135,9 @@ struct Window {
 }
 
 impl Window {
-    /// Find a window with `title`, and return a new `Window` object
+    /// Find a window with `title`, and return a new `Window` object. Fails
+    /// if more than one window has `title`, as we can't tell which one is
+    /// ours and may end up fuzzing someone else's window.
     fn attach(title: &str) -> io::Result<Self> {
         // Convert the title to UTF-16
         let mut title = str_to_utf16(title); 
@@ -143,6 +147,13 @@ impl Window {
             FindWindowW(std::ptr::null_mut(), title.as_mut_ptr())
         };
 
+        // Refuse to guess between windows with the same title
+        if ret != 0 && unsafe {
+            FindWindowExW(0, ret, std::ptr::null_mut(), title.as_mut_ptr())
+        } != 0 {
+            return Err(io::Error::other("More than one window has this title"));
+        }
+
         // Generate some interesting keys
         let mut interesting_keys = Vec::new();
         interesting_keys.push(KeyCode::Left  as u8);
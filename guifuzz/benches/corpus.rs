@@ -0,0 +1,11 @@
+//! Benchmarks of the hot corpus paths over synthetic corpora of a few
+//! sizes, run with `cargo bench`
+
+fn main() {
+    for &size in &[100, 1000, 10000] {
+        println!("corpus of {} inputs", size);
+        for result in guifuzz::bench::run(size) {
+            println!("    {}", result);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Accessibility fuzzing, requesting `IAccessible` objects from the target
+//! with `WM_GETOBJECT` and exercising them
+
+use std::io;
+use crate::Window;
+use crate::winbindings::Guid;
+
+#[link(name="Oleacc")]
+extern "system" {
+    fn AccessibleObjectFromWindow(hwnd: usize, object_id: u32,
+        riid: *const Guid, object: *mut *mut IAccessible) -> i32;
+}
+
+#[link(name="Ole32")]
+extern "system" {
+    fn CoInitializeEx(reserved: usize, coinit: u32) -> i32;
+}
+
+#[link(name="OleAut32")]
+extern "system" {
+    fn SysFreeString(bstr: *mut u16);
+}
+
+/// Initialize COM for the thread as a single-threaded apartment
+const COINIT_APARTMENTTHREADED: u32 = 0x2;
+
+/// `VARIANT` type for a 32-bit signed integer
+const VT_I4: u16 = 3;
+
+/// `CHILDID_SELF`, refers to the object itself rather than a child
+const CHILDID_SELF: i32 = 0;
+
+/// `OBJID_*` values which can be requested with `WM_GETOBJECT`, including
+/// `OBJID_QUERYCLASSNAMEIDX` and `OBJID_NATIVEOM`
+pub const OBJECT_IDS: &[i32] = &[
+    0, -1, -2, -3, -4, -5, -6, -7, -8, -9, -10, -11, -12, -16,
+];
+
+/// Interface ID of `IAccessible`
+const IID_IACCESSIBLE: Guid = Guid {
+    data1: 0x618736e0,
+    data2: 0x3c3d,
+    data3: 0x11cf,
+    data4: [0x81, 0x0c, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71],
+};
+
+/// Rust implementation of `VARIANT`, only used for `VT_I4` child IDs
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Variant {
+    vt:       u16,
+    reserved: [u16; 3],
+    val:      [usize; 2],
+}
+
+impl Variant {
+    /// Create a `VT_I4` variant holding `val`
+    fn i4(val: i32) -> Self {
+        Variant {
+            vt:       VT_I4,
+            reserved: [0; 3],
+            val:      [val as u32 as usize, 0],
+        }
+    }
+}
+
+/// An `IAccessible` COM object
+#[repr(C)]
+struct IAccessible {
+    vtbl: *const IAccessibleVtbl,
+}
+
+/// Virtual function table of `IAccessible`, only the methods we call are
+/// typed
+#[repr(C)]
+struct IAccessibleVtbl {
+    query_interface: usize,
+    add_ref:         usize,
+    release: unsafe extern "system" fn(this: *mut IAccessible) -> u32,
+
+    /// `IDispatch` methods
+    dispatch: [usize; 4],
+
+    get_acc_parent: usize,
+    get_acc_child_count: unsafe extern "system" fn(this: *mut IAccessible,
+        count: *mut i32) -> i32,
+    get_acc_child: usize,
+    get_acc_name: unsafe extern "system" fn(this: *mut IAccessible,
+        child: Variant, name: *mut *mut u16) -> i32,
+    get_acc_value: unsafe extern "system" fn(this: *mut IAccessible,
+        child: Variant, value: *mut *mut u16) -> i32,
+
+    /// `get_accDescription()` through `accHitTest()`
+    unused: [usize; 13],
+
+    acc_do_default_action: unsafe extern "system" fn(this: *mut IAccessible,
+        child: Variant) -> i32,
+}
+
+/// Call a `BSTR` getter, freeing the returned string
+unsafe fn get_bstr(object: *mut IAccessible, child: Variant,
+        getter: unsafe extern "system" fn(*mut IAccessible, Variant,
+            *mut *mut u16) -> i32) {
+    let mut bstr = std::ptr::null_mut();
+    if getter(object, child, &mut bstr) >= 0 && !bstr.is_null() {
+        SysFreeString(bstr);
+    }
+}
+
+/// Request the accessible object `object_id` from `window` with
+/// `WM_GETOBJECT`, then get the name and value of, and perform the default
+/// action on, one of its children selected by `child`
+pub fn exercise(window: &Window, object_id: i32, child: u32)
+        -> io::Result<()> {
+    unsafe {
+        // This may fail if COM is already initialized differently for this
+        // thread, which is fine
+        CoInitializeEx(0, COINIT_APARTMENTTHREADED);
+
+        let mut object = std::ptr::null_mut();
+        let hr = AccessibleObjectFromWindow(window.hwnd(), object_id as u32,
+            &IID_IACCESSIBLE, &mut object);
+        if hr < 0 || object.is_null() {
+            return Err(io::Error::other(
+                format!("AccessibleObjectFromWindow() failed: {:#x}", hr)));
+        }
+        let vtbl = &*(*object).vtbl;
+
+        // Select the object itself or one of its children
+        let mut count = 0;
+        if (vtbl.get_acc_child_count)(object, &mut count) < 0 {
+            count = 0;
+        }
+        let child = match count {
+            x if x > 0 => Variant::i4((child % (x as u32 + 1)) as i32),
+            _          => Variant::i4(CHILDID_SELF),
+        };
+
+        get_bstr(object, child, vtbl.get_acc_name);
+        get_bstr(object, child, vtbl.get_acc_value);
+        (vtbl.acc_do_default_action)(object, child);
+
+        (vtbl.release)(object);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Generators of actions and action sequences for other fuzzing and testing
+//! frameworks. With the `arbitrary` feature all action types implement
+//! `arbitrary::Arbitrary`, so cargo-fuzz harnesses and types deriving it
+//! can use them. The `quickcheck` feature adds `quickcheck::Arbitrary` for
+//! model tests, with sequences shrinking by dropping actions.
+
+use arbitrary::{Arbitrary, Unstructured};
+use crate::{FuzzerAction, FuzzInput, MAX_INPUT_LEN};
+
+/// Number of random bytes an action is generated from for quickcheck,
+/// enough for any variant
+#[cfg(feature = "quickcheck")]
+const QUICKCHECK_ACTION_BYTES: usize = 32;
+
+/// A sequence of actions, at most `MAX_INPUT_LEN` long like the inputs the
+/// mutator produces
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ActionSequence(pub Vec<FuzzerAction>);
+
+impl ActionSequence {
+    /// Convert the sequence into an input which can be executed or inserted
+    /// into a corpus
+    pub fn into_input(self) -> FuzzInput {
+        FuzzInput::new(self.0)
+    }
+}
+
+impl<'a> Arbitrary<'a> for ActionSequence {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.arbitrary_len::<FuzzerAction>()?.min(MAX_INPUT_LEN);
+        let actions = (0..len).map(|_| u.arbitrary())
+            .collect::<arbitrary::Result<Vec<FuzzerAction>>>()?;
+        Ok(ActionSequence(actions))
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl quickcheck::Arbitrary for FuzzerAction {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let bytes: Vec<u8> = (0..QUICKCHECK_ACTION_BYTES)
+            .map(|_| <u8 as quickcheck::Arbitrary>::arbitrary(g)).collect();
+        Unstructured::new(&bytes).arbitrary().unwrap_or(FuzzerAction::Close)
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+impl quickcheck::Arbitrary for ActionSequence {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let len = <usize as quickcheck::Arbitrary>::arbitrary(g) %
+            (g.size().min(MAX_INPUT_LEN) + 1);
+        ActionSequence((0..len).map(|_| {
+            <FuzzerAction as quickcheck::Arbitrary>::arbitrary(g)
+        }).collect())
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        Box::new(quickcheck::Arbitrary::shrink(&self.0).map(ActionSequence))
+    }
+}
@@ -0,0 +1,82 @@
+//! Counts of how often each type of action fails or is dropped when it is
+//! performed, so users notice when an action type doesn't work against
+//! their target
+
+use std::fmt;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+thread_local! {
+    /// Action statistics of the current thread, collected with `take()`
+    static THREAD_STATS: RefCell<ActionStats> =
+        RefCell::new(ActionStats::default());
+}
+
+/// Number of attempts and failures of each type of action
+#[derive(Clone, Debug, Default)]
+pub struct ActionStats {
+    /// Maps action type names to their (attempts, failures)
+    counts: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl ActionStats {
+    /// Record an attempt to perform an action of type `kind`
+    pub fn add(&mut self, kind: &'static str, ok: bool) {
+        let counts = self.counts.entry(kind).or_insert((0, 0));
+        counts.0 += 1;
+        if !ok {
+            counts.1 += 1;
+        }
+    }
+
+    /// Add all of the counts in `other` to `self`
+    pub fn merge(&mut self, other: &ActionStats) {
+        for (kind, (attempts, failures)) in &other.counts {
+            let counts = self.counts.entry(kind).or_insert((0, 0));
+            counts.0 += attempts;
+            counts.1 += failures;
+        }
+    }
+
+    /// Fraction of attempts of action type `kind` which failed, `None` if
+    /// it was never attempted
+    pub fn failure_rate(&self, kind: &str) -> Option<f64> {
+        self.counts.get(kind).filter(|x| x.0 > 0)
+            .map(|&(attempts, failures)| failures as f64 / attempts as f64)
+    }
+
+    /// Get the (type, attempts, failures) of every action type attempted
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, u64, u64)> + '_ {
+        self.counts.iter().map(|(kind, x)| (*kind, x.0, x.1))
+    }
+}
+
+impl fmt::Display for ActionStats {
+    /// Failure rates of the action types which failed at least once
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (kind, attempts, failures) in self.iter() {
+            if failures == 0 {
+                continue;
+            }
+            if !first {
+                write!(f, " | ")?;
+            }
+            first = false;
+            write!(f, "{} {:.1}% of {}", kind,
+                failures as f64 / attempts as f64 * 100., attempts)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record an attempt to perform an action of type `kind` on this thread
+pub fn record(kind: &'static str, ok: bool) {
+    THREAD_STATS.with(|stats| stats.borrow_mut().add(kind, ok));
+}
+
+/// Take the action statistics recorded on this thread so far, resetting
+/// them
+pub fn take() -> ActionStats {
+    THREAD_STATS.with(|stats| std::mem::take(&mut *stats.borrow_mut()))
+}
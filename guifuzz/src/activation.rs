@@ -0,0 +1,120 @@
+//! Activation and focus transitions, switching the target to the background
+//! and back. Activation handlers are stateful and often assume they aren't
+//! run in the middle of another operation, such as a drag or an open menu.
+
+use std::io;
+use std::time::Duration;
+use crate::{Window, InputBackend};
+use crate::winbindings::MessageType;
+
+/// `WA_INACTIVE`
+const WA_INACTIVE: usize = 0;
+
+/// `WA_ACTIVE`
+const WA_ACTIVE: usize = 1;
+
+/// `WA_CLICKACTIVE`
+const WA_CLICKACTIVE: usize = 2;
+
+/// Time the target spends in the background when actually switched away
+const BACKGROUND_TIME: Duration = Duration::from_millis(50);
+
+#[link(name="User32")]
+extern "system" {
+    fn GetShellWindow() -> usize;
+    fn SetForegroundWindow(hwnd: usize) -> bool;
+}
+
+/// Activation and focus transitions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Transition {
+    /// `WM_ACTIVATE(WA_INACTIVE)`, `WM_ACTIVATEAPP(FALSE)`, and
+    /// `WM_KILLFOCUS`, as when another application is switched to. The
+    /// parameter is the window or thread said to be activated instead.
+    Deactivate,
+
+    /// `WM_ACTIVATEAPP(TRUE)`, `WM_ACTIVATE(WA_ACTIVE)`, and `WM_SETFOCUS`,
+    /// as when switching back. The parameter is the window or thread said
+    /// to be deactivated.
+    Activate,
+
+    /// `Deactivate` followed by an activation by a click,
+    /// `WM_ACTIVATE(WA_CLICKACTIVE)`
+    Cycle,
+
+    /// `WM_KILLFOCUS` to the sub-window selected by the low 16 bits of the
+    /// parameter and `WM_SETFOCUS` to the one selected by the high 16 bits
+    FocusSwap,
+}
+
+/// Post the messages of losing activation to `window`, with `other` as the
+/// window and thread activated instead
+fn post_deactivate(window: &Window, other: usize) -> io::Result<()> {
+    window.post_message(MessageType::Activate, WA_INACTIVE, other)?;
+    window.post_message(MessageType::ActivateApp, 0, other)?;
+    window.post_message(MessageType::KillFocus, other, 0)
+}
+
+/// Post the messages of gaining activation to `window` with `state`, with
+/// `other` as the window and thread deactivated
+fn post_activate(window: &Window, state: usize, other: usize)
+        -> io::Result<()> {
+    window.post_message(MessageType::ActivateApp, 1, other)?;
+    window.post_message(MessageType::Activate, state, other)?;
+    window.post_message(MessageType::SetFocus, other, 0)
+}
+
+/// Switch the foreground away from `window` to the shell
+fn switch_away(window: &Window) -> io::Result<()> {
+    let shell = unsafe { GetShellWindow() };
+    if shell == 0 || shell == window.hwnd() ||
+            !unsafe { SetForegroundWindow(shell) } {
+        return Err(io::Error::other("Failed to switch the foreground away"));
+    }
+    Ok(())
+}
+
+/// Perform the activation `transition` with `param` on `window`. With the
+/// `SendInput` backend the foreground is actually switched away and back,
+/// otherwise the messages of the transition are posted.
+pub fn perform(window: &Window, transition: Transition, param: u32,
+        backend: InputBackend) -> io::Result<()> {
+    let other = param as usize;
+
+    match (transition, backend) {
+        (Transition::FocusSwap, _) => {
+            let children = window.enumerate_subwindows()?;
+            if children.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                    "No sub-windows to move the focus between"));
+            }
+            let from = children[(param & 0xffff) as usize % children.len()];
+            let to   = children[(param >> 16) as usize % children.len()];
+            from.post_message(MessageType::KillFocus, to.hwnd(), 0)?;
+            to.post_message(MessageType::SetFocus, from.hwnd(), 0)
+        }
+        (Transition::Deactivate, InputBackend::SendInput) => {
+            switch_away(window)
+        }
+        (Transition::Activate, InputBackend::SendInput) => {
+            window.foreground()
+        }
+        (Transition::Cycle, InputBackend::SendInput) => {
+            switch_away(window)?;
+            std::thread::sleep(BACKGROUND_TIME);
+            window.foreground()
+        }
+        (Transition::Deactivate, InputBackend::PostMessage) => {
+            post_deactivate(window, other)
+        }
+        (Transition::Activate, InputBackend::PostMessage) => {
+            post_activate(window, WA_ACTIVE, other)
+        }
+        (Transition::Cycle, InputBackend::PostMessage) => {
+            post_deactivate(window, other)?;
+            post_activate(window, WA_CLICKACTIVE, other)
+        }
+    }
+}
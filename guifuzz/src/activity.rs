@@ -0,0 +1,192 @@
+//! Internal activity of the target as a novelty signal. Windows created
+//! during a case, counted with a WinEvent hook, and the GDI and USER
+//! objects the target holds afterwards are compared with the baseline of
+//! all cases so far. Cases deviating significantly from it triggered
+//! unusual activity even if they didn't reach new code.
+//!
+//! Timers would make a good signal too, but they can only be observed from
+//! inside the target, so they aren't counted.
+
+use std::cell::Cell;
+use std::thread::{self, JoinHandle};
+use std::sync::mpsc;
+
+/// Number of cases the baseline needs before cases are judged against it
+const MIN_SAMPLES: u64 = 32;
+
+/// `EVENT_OBJECT_CREATE`
+const EVENT_OBJECT_CREATE: u32 = 0x8000;
+
+/// Receive WinEvents in the hooking thread rather than in the target
+const WINEVENT_OUTOFCONTEXT: u32 = 0x0000;
+
+/// `OBJID_WINDOW`, events about the window itself
+const OBJID_WINDOW: i32 = 0;
+
+/// `CHILDID_SELF`
+const CHILDID_SELF: i32 = 0;
+
+/// `WM_QUIT`
+const WM_QUIT: u32 = 0x0012;
+
+/// WinEvent hook callback
+type WinEventProc = extern "system" fn(hook: usize, event: u32, hwnd: usize,
+    object: i32, child: i32, thread: u32, time: u32);
+
+#[link(name="User32")]
+extern "system" {
+    fn SetWinEventHook(min: u32, max: u32, module: usize,
+        callback: WinEventProc, pid: u32, thread: u32, flags: u32) -> usize;
+    fn UnhookWinEvent(hook: usize) -> i32;
+    fn GetMessageW(msg: *mut [usize; 6], hwnd: usize, min: u32, max: u32)
+        -> i32;
+    fn PostThreadMessageW(thread: u32, msg: u32, wparam: usize,
+        lparam: usize) -> i32;
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetCurrentThreadId() -> u32;
+}
+
+thread_local! {
+    /// Windows created, counted by the hook of the current thread
+    static WINDOWS_CREATED: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Count windows being created, called on the hooking thread
+extern "system" fn window_created(_hook: usize, _event: u32, _hwnd: usize,
+        object: i32, child: i32, _thread: u32, _time: u32) {
+    if object == OBJID_WINDOW && child == CHILDID_SELF {
+        WINDOWS_CREATED.with(|x| x.set(x.get() + 1));
+    }
+}
+
+/// Counter of the windows a process creates, including transient ones
+/// which are gone by the end of the case
+pub struct WindowCounter {
+    /// ID of the hooking thread, to tell it to stop
+    thread_id: u32,
+
+    /// Hooking thread, returning the number of windows created
+    thread: Option<JoinHandle<u32>>,
+}
+
+impl WindowCounter {
+    /// Start counting the windows `pid` creates. Returns `None` if the
+    /// hook couldn't be set.
+    pub fn start(pid: u32) -> Option<Self> {
+        let (sender, receiver) = mpsc::channel();
+
+        let thread = thread::spawn(move || unsafe {
+            let hook = SetWinEventHook(EVENT_OBJECT_CREATE,
+                EVENT_OBJECT_CREATE, 0, window_created, pid, 0,
+                WINEVENT_OUTOFCONTEXT);
+            let _ = sender.send((hook != 0).then(|| GetCurrentThreadId()));
+            if hook == 0 {
+                return 0;
+            }
+
+            // Events are delivered while pumping messages
+            let mut msg = [0usize; 6];
+            while GetMessageW(&mut msg, 0, 0, 0) > 0 {}
+
+            UnhookWinEvent(hook);
+            WINDOWS_CREATED.with(|x| x.get())
+        });
+
+        match receiver.recv().ok().flatten() {
+            Some(thread_id) => Some(WindowCounter {
+                thread_id,
+                thread: Some(thread),
+            }),
+            None => {
+                let _ = thread.join();
+                None
+            }
+        }
+    }
+
+    /// Stop counting, returning the number of windows created
+    pub fn finish(mut self) -> u32 {
+        self.stop().unwrap_or(0)
+    }
+
+    /// Stop the hooking thread, returning its count if it was running
+    fn stop(&mut self) -> Option<u32> {
+        let thread = self.thread.take()?;
+        unsafe { PostThreadMessageW(self.thread_id, WM_QUIT, 0, 0); }
+        thread.join().ok()
+    }
+}
+
+impl Drop for WindowCounter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Internal activity of the target during a case
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Activity {
+    /// Windows created during the case
+    pub windows_created: u32,
+
+    /// GDI objects held by the target after the case
+    pub gdi_objects: u32,
+
+    /// USER objects held by the target after the case
+    pub user_objects: u32,
+}
+
+impl Activity {
+    /// The measures of activity, in the order the baseline tracks them
+    fn measures(&self) -> [f64; 3] {
+        [self.windows_created as f64, self.gdi_objects as f64,
+            self.user_objects as f64]
+    }
+}
+
+/// Running mean and variance of the activity of all cases
+#[derive(Clone, Debug, Default)]
+pub struct ActivityBaseline {
+    /// Number of cases observed
+    samples: u64,
+
+    /// Mean of each measure
+    mean: [f64; 3],
+
+    /// Sum of squared differences from the mean of each measure
+    m2: [f64; 3],
+}
+
+impl ActivityBaseline {
+    /// Check whether `activity` deviates from the baseline by at least
+    /// `threshold` standard deviations in any measure, then add it to the
+    /// baseline. Never deviates until the baseline has enough samples.
+    pub fn observe(&mut self, activity: &Activity, threshold: f64) -> bool {
+        let measures = activity.measures();
+
+        let deviates = self.samples >= MIN_SAMPLES &&
+            (0..measures.len()).any(|ii| {
+                let stddev = (self.m2[ii] / (self.samples - 1) as f64).sqrt();
+                (measures[ii] - self.mean[ii]).abs() >=
+                    threshold * stddev.max(1.)
+            });
+
+        // Welford's online algorithm
+        self.samples += 1;
+        for (ii, &measure) in measures.iter().enumerate() {
+            let delta = measure - self.mean[ii];
+            self.mean[ii] += delta / self.samples as f64;
+            self.m2[ii] += delta * (measure - self.mean[ii]);
+        }
+
+        deviates
+    }
+
+    /// Number of cases observed
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+}
@@ -0,0 +1,123 @@
+//! Append-only audit log of every message and input the fuzzer injects, so
+//! what it sent on a shared machine can be proven and diagnosed afterwards.
+//! The log is rotated once it grows too large.
+
+use std::io::{self, Write};
+use std::fs::{File, OpenOptions};
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::msgnames::Message;
+
+/// The audit log, `None` while auditing is disabled
+static AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+thread_local! {
+    /// Worker the current thread is injecting input for
+    static WORKER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Open audit log and its rotation settings
+struct AuditLog {
+    /// Path of the current log, rotated logs get a `.1`, `.2`, ... suffix
+    path: String,
+
+    /// Current log file
+    file: File,
+
+    /// Size of the current log file in bytes
+    size: u64,
+
+    /// Size in bytes at which the log is rotated
+    max_size: u64,
+
+    /// Number of rotated logs kept
+    keep: usize,
+}
+
+impl AuditLog {
+    /// Rotate the logs, shifting every rotated log up by one and deleting
+    /// the oldest, then start a new, empty log
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |idx: usize| format!("{}.{}", self.path, idx);
+
+        let _ = std::fs::remove_file(rotated(self.keep));
+        for idx in (1..self.keep).rev() {
+            let _ = std::fs::rename(rotated(idx), rotated(idx + 1));
+        }
+        if self.keep > 0 {
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Append `line` to the log, rotating it first if it is full
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        // Written unbuffered so nothing is lost if the fuzzer dies
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Start auditing to the log at `path`, rotating it once it reaches
+/// `max_size` bytes and keeping `keep` rotated logs
+pub fn enable(path: &str, max_size: u64, keep: usize) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+
+    *AUDIT_LOG.lock().unwrap() = Some(AuditLog {
+        path: path.to_string(),
+        file,
+        size,
+        max_size,
+        keep,
+    });
+    Ok(())
+}
+
+/// Set the worker the current thread injects input for, recorded with
+/// everything it injects
+pub fn set_worker(worker: Option<usize>) {
+    WORKER.with(|x| x.set(worker));
+}
+
+/// Record an injection described by `what`, if auditing is enabled
+fn record(what: std::fmt::Arguments) {
+    let mut log = AUDIT_LOG.lock().unwrap();
+    let log = match log.as_mut() {
+        Some(log) => log,
+        None => return,
+    };
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let worker = WORKER.with(|x| x.get()).map(|x| x.to_string())
+        .unwrap_or("-".into());
+    let line = format!("{}.{:03} worker={} {}\n", time.as_secs(),
+        time.subsec_millis(), worker, what);
+
+    // Auditing must never take down a fuzz case
+    let _ = log.write_line(&line);
+}
+
+/// Record the window message `msg` with `wparam` and `lparam` delivered to
+/// `hwnd` with `how`, such as `post` or `send`
+pub fn message(how: &str, hwnd: usize, msg: u32, wparam: usize,
+        lparam: usize) {
+    record(format_args!("{} hwnd={:#x} {}", how, hwnd,
+        Message { msg, wparam, lparam }));
+}
+
+/// Record input synthesized through the system input queue, described by
+/// `what`
+pub fn input(what: &str) {
+    record(format_args!("input {}", what));
+}
@@ -0,0 +1,142 @@
+//! Benchmarks of the hot corpus paths, run over synthetic corpora so they
+//! don't need a target. Used by the `bench` subcommand and `cargo bench`.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::{Rng, Statistics, FuzzerAction, FuzzInput, Provenance, mutate};
+use crate::distance;
+use crate::{MenuId, ElementIdx, VirtualKey};
+
+/// Number of untimed iterations run before measuring
+const WARMUP_ITERS: usize = 16;
+
+/// Number of samples taken per benchmark
+const SAMPLES: usize = 20;
+
+/// Timing of one benchmark
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    /// Name of the benchmark
+    pub name: &'static str,
+
+    /// Number of iterations per sample
+    pub iters: usize,
+
+    /// Mean time per iteration of each sample
+    pub samples: Vec<Duration>,
+}
+
+impl BenchResult {
+    /// Mean time per iteration over all samples
+    pub fn mean(&self) -> Duration {
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// Median time per iteration over all samples
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        sorted[sorted.len() / 2]
+    }
+
+    /// Fastest and slowest time per iteration over all samples
+    pub fn range(&self) -> (Duration, Duration) {
+        (*self.samples.iter().min().unwrap(),
+         *self.samples.iter().max().unwrap())
+    }
+}
+
+impl std::fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (min, max) = self.range();
+        write!(f, "{:16} {:>12?} mean {:>12?} median [{:?} .. {:?}]",
+            self.name, self.mean(), self.median(), min, max)
+    }
+}
+
+/// Measure `func`, calling it `iters` times per sample
+pub fn measure<F: FnMut()>(name: &'static str, iters: usize, mut func: F)
+        -> BenchResult {
+    for _ in 0..WARMUP_ITERS {
+        func();
+    }
+
+    let samples = (0..SAMPLES).map(|_| {
+        let start = Instant::now();
+        for _ in 0..iters {
+            func();
+        }
+        start.elapsed() / iters.max(1) as u32
+    }).collect();
+
+    BenchResult { name, iters, samples }
+}
+
+/// Generate a random action which could have come from the generator
+pub fn random_action(rng: &Rng) -> FuzzerAction {
+    match rng.rand() % 4 {
+        0 => FuzzerAction::LeftClick { idx: ElementIdx(rng.rand() % 64) },
+        1 => FuzzerAction::KeyPress {
+            key:   VirtualKey(rng.rand() % 256),
+            focus: None,
+        },
+        2 => FuzzerAction::MenuAction {
+            menu_id:  MenuId(rng.rand() as u32 % 512),
+            menu_set: 0,
+        },
+        _ => FuzzerAction::DialogCommand {
+            control_id: rng.rand() as u32 % 512
+        },
+    }
+}
+
+/// Create statistics with a corpus of `size` random inputs of up to
+/// `max_len` actions each
+pub fn synthetic_stats(rng: &Rng, size: usize, max_len: usize)
+        -> Statistics {
+    let mut stats = Statistics::default();
+    for _ in 0..size {
+        let len = rng.rand() % max_len.max(1) + 1;
+        let input: FuzzInput = Arc::new(
+            (0..len).map(|_| random_action(rng)).collect());
+        let cost = Duration::from_millis(rng.rand() as u64 % 5000 + 1);
+        stats.add_input(&input, cost, Provenance::default());
+    }
+    stats
+}
+
+/// Run all benchmarks over a synthetic corpus of `corpus_size` inputs
+pub fn run(corpus_size: usize) -> Vec<BenchResult> {
+    let rng = Rng::seeded(0x9e37_79b9_7f4a_7c15);
+    let stats = Arc::new(Mutex::new(
+        synthetic_stats(&rng, corpus_size, 256)));
+    let inputs: Vec<FuzzInput> = stats.lock().unwrap().corpus.iter()
+        .take(64).collect();
+
+    let mut results = Vec::new();
+
+    results.push(measure("select_input", 64, || {
+        stats.lock().unwrap().select_input(&rng, None);
+    }));
+
+    results.push(measure("mutate", 64, || {
+        let _ = mutate(stats.clone(), None, &mut Provenance::default());
+    }));
+
+    let mut idx = 0;
+    results.push(measure("canonicalize", 256, || {
+        idx = (idx + 1) % inputs.len().max(1);
+        if let Some(input) = inputs.get(idx) {
+            distance::canonicalize(input);
+        }
+    }));
+
+    results.push(measure("serialize", 256, || {
+        idx = (idx + 1) % inputs.len().max(1);
+        if let Some(input) = inputs.get(idx) {
+            let _ = format!("{:#?}", input);
+        }
+    }));
+
+    results
+}
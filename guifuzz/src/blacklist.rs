@@ -0,0 +1,109 @@
+//! Adaptive blacklist of actions. Some actions, such as a key opening help
+//! in a browser, reliably hang the target or end the session without ever
+//! finding anything. Outcomes of the cases each action took part in are
+//! accumulated, and actions which keep causing hangs are down-weighted by
+//! the generator and mutator instead of having to be blacklisted by hand.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use crate::{FuzzerAction, Rng};
+
+/// Number of cases an action has to take part in before it is judged
+const MIN_CASES: u64 = 8;
+
+/// Lowest weight of an action, actions are never banned outright as the
+/// target may change its behavior later on
+const MIN_WEIGHT: f64 = 0.02;
+
+/// Outcome statistics, `None` while the blacklist is disabled. Shared
+/// between all threads so every worker learns from the others.
+static BLACKLIST: Mutex<Option<HashMap<FuzzerAction, Outcomes>>> =
+    Mutex::new(None);
+
+/// Outcomes of the cases an action took part in
+#[derive(Clone, Copy, Debug, Default)]
+struct Outcomes {
+    /// Number of cases which performed the action
+    cases: u64,
+
+    /// Number of cases which hung on the action without finding anything
+    hangs: u64,
+}
+
+/// Enable the adaptive blacklist for all threads
+pub fn enable() {
+    let mut blacklist = BLACKLIST.lock().unwrap();
+    if blacklist.is_none() {
+        *blacklist = Some(HashMap::new());
+    }
+}
+
+/// Identity of `action` for the blacklist. Keys are judged regardless of
+/// which window had focus, as it's the key which opens a browser.
+fn identity(action: &FuzzerAction) -> FuzzerAction {
+    match *action {
+        FuzzerAction::KeyPress { key, .. } => {
+            FuzzerAction::KeyPress { key, focus: None }
+        }
+        action => action,
+    }
+}
+
+/// Record the outcome of a case which performed `actions`. `hung` is the
+/// index of the action the case hung on, if it did, and `productive` is
+/// whether the case found new coverage or a new bug.
+pub fn record_case(actions: &[FuzzerAction], hung: Option<usize>,
+        productive: bool) {
+    let mut blacklist = BLACKLIST.lock().unwrap();
+    let blacklist = match blacklist.as_mut() {
+        Some(blacklist) => blacklist,
+        None => return,
+    };
+
+    // Actions after a hang were never performed
+    let performed = hung.map(|x| x + 1).unwrap_or(actions.len())
+        .min(actions.len());
+    let mut seen: Vec<FuzzerAction> =
+        actions[..performed].iter().map(identity).collect();
+    seen.sort();
+    seen.dedup();
+    for action in seen {
+        blacklist.entry(action).or_default().cases += 1;
+    }
+
+    if let (Some(action), false) = (hung.and_then(|x| actions.get(x)),
+            productive) {
+        blacklist.entry(identity(action)).or_default().hangs += 1;
+    }
+}
+
+/// Weight of `action` between `MIN_WEIGHT` and 1, the fraction of cases it
+/// took part in which it didn't hang. Always 1 if the blacklist is disabled
+/// or the action hasn't been judged yet.
+pub fn weight(action: &FuzzerAction) -> f64 {
+    BLACKLIST.lock().unwrap().as_ref()
+        .and_then(|x| x.get(&identity(action)).cloned())
+        .filter(|x| x.cases >= MIN_CASES)
+        .map(|x| (1. - x.hangs as f64 / x.cases as f64).max(MIN_WEIGHT))
+        .unwrap_or(1.)
+}
+
+/// Randomly decide, using `rng`, whether to use `action` based on its
+/// weight
+pub fn allow(action: &FuzzerAction, rng: &Rng) -> bool {
+    let weight = weight(action);
+    weight >= 1. || (rng.rand() as f64 / usize::MAX as f64) < weight
+}
+
+/// Get all down-weighted actions and their weights, lowest weight first
+pub fn down_weighted() -> Vec<(FuzzerAction, f64)> {
+    let actions: Vec<FuzzerAction> = BLACKLIST.lock().unwrap().as_ref()
+        .map(|x| x.keys().cloned().collect()).unwrap_or_default();
+
+    let mut weights: Vec<(FuzzerAction, f64)> = actions.into_iter()
+        .map(|action| (action, weight(&action)))
+        .filter(|x| x.1 < 1.)
+        .collect();
+    weights.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    weights
+}
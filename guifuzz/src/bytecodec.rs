@@ -0,0 +1,520 @@
+//! Byte stream codec of the action model, so byte-oriented fuzzers such as
+//! libFuzzer or an AFL++ custom mutator can drive the GUI executor. Any
+//! byte slice decodes to a sequence of actions, and encoding a sequence
+//! decodes back to the same sequence. Sequences which wouldn't, those with
+//! element indices past 32 bits, virtual keys past 16 bits, or more than
+//! `MAX_INPUT_LEN` actions, fail to encode.
+//!
+//! Each action is a tag byte selecting the variant, modulo the number of
+//! variants, followed by its fields in little endian. Enum fields are a
+//! byte indexing their variants, modulo the number of variants. An action
+//! cut short by the end of the data is dropped.
+//!
+//! Fields are decoded over their whole range and bounded when the action is
+//! performed, so a `MessagePressure` of any `count` posts at most
+//! `MAX_MESSAGE_PRESSURE` messages.
+
+use std::fmt;
+use std::error::Error;
+use std::convert::TryFrom;
+use crate::{FuzzerAction, DisplayEvent, SessionEvent, BroadcastEvent};
+use crate::{DropFormat, Placement, MdiCommand, DdeCommand, Transition};
+use crate::{PropSheetCommand, RangeBound};
+use crate::{MenuId, ElementIdx, VirtualKey, KeyMouseState, MAX_INPUT_LEN};
+use crate::uia;
+
+/// Number of tags, tag bytes are taken modulo this. Menu actions have a tag
+/// without and one with a menu set, so encodings predating menu sets still
+/// decode the same.
+const TAGS: u8 = 27;
+
+/// Variants of `DisplayEvent`, in encoding order
+const DISPLAY_EVENTS: &[DisplayEvent] = &[
+    DisplayEvent::DpiChanged, DisplayEvent::SettingChange,
+    DisplayEvent::ThemeChanged, DisplayEvent::DisplayChange,
+];
+
+/// Variants of `SessionEvent`, in encoding order
+const SESSION_EVENTS: &[SessionEvent] = &[
+    SessionEvent::PowerBroadcast, SessionEvent::QueryEndSession,
+    SessionEvent::EndSession, SessionEvent::DeviceChange,
+];
+
+/// Variants of `BroadcastEvent`, in encoding order
+const BROADCAST_EVENTS: &[BroadcastEvent] = &[
+    BroadcastEvent::InputLangChange, BroadcastEvent::Hotplug,
+    BroadcastEvent::ClipboardUpdate, BroadcastEvent::FontChange,
+];
+
+/// Variants of `DropFormat`, in encoding order
+const DROP_FORMATS: &[DropFormat] = &[
+    DropFormat::Text, DropFormat::Html, DropFormat::FileDescriptor,
+];
+
+/// Variants of `Placement`, in encoding order
+const PLACEMENTS: &[Placement] = &[
+    Placement::Extreme, Placement::Span, Placement::Maximize,
+    Placement::Minimize, Placement::Restore,
+];
+
+/// Variants of `MdiCommand`, in encoding order
+const MDI_COMMANDS: &[MdiCommand] = &[
+    MdiCommand::Create, MdiCommand::Activate, MdiCommand::Next,
+    MdiCommand::Destroy, MdiCommand::Maximize, MdiCommand::Restore,
+    MdiCommand::Cascade, MdiCommand::Tile, MdiCommand::IconArrange,
+];
+
+/// Variants of `PropSheetCommand`, in encoding order
+const PROP_SHEET_COMMANDS: &[PropSheetCommand] = &[
+    PropSheetCommand::SetPage, PropSheetCommand::UseControl,
+    PropSheetCommand::Changed, PropSheetCommand::Unchanged,
+    PropSheetCommand::Apply, PropSheetCommand::PressButton,
+    PropSheetCommand::QuerySiblings, PropSheetCommand::CancelToClose,
+];
+
+/// Variants of `RangeBound`, in encoding order
+const RANGE_BOUNDS: &[RangeBound] = &[
+    RangeBound::Min, RangeBound::Max, RangeBound::Absolute,
+];
+
+/// Variants of `DdeCommand`, in encoding order
+const DDE_COMMANDS: &[DdeCommand] = &[DdeCommand::Execute, DdeCommand::Poke];
+
+/// Variants of `Transition`, in encoding order
+const TRANSITIONS: &[Transition] = &[
+    Transition::Deactivate, Transition::Activate, Transition::Cycle,
+    Transition::FocusSwap,
+];
+
+/// Error returned by `encode()` for sequences which wouldn't decode back to
+/// themselves
+#[derive(Debug)]
+pub enum EncodeError {
+    /// The sequence has this many actions, more than `MAX_INPUT_LEN`
+    TooLong(usize),
+
+    /// The action at this index has a field out of the encodable range
+    Unencodable(usize, FuzzerAction),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::TooLong(len) => {
+                write!(f, "{} actions don't fit in an encoding", len)
+            }
+            EncodeError::Unencodable(index, action) => {
+                write!(f, "Action {} ({:?}) can't be encoded", index, action)
+            }
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+/// Cursor over the bytes being decoded
+struct Reader<'a> {
+    /// Bytes left to decode
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    /// Take the next `N` bytes, `None` if there aren't that many left
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.data.len() < N {
+            return None;
+        }
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(&self.data[..N]);
+        self.data = &self.data[N..];
+        Some(bytes)
+    }
+
+    /// Take the next byte
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|x| x[0])
+    }
+
+    /// Take the next little endian `u16`
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    /// Take the next little endian `u32`
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    /// Take the next little endian `i32`
+    fn i32(&mut self) -> Option<i32> {
+        self.take().map(i32::from_le_bytes)
+    }
+
+    /// Pick one of `variants` with the next byte
+    fn pick<T: Copy>(&mut self, variants: &[T]) -> Option<T> {
+        self.u8().map(|x| variants[x as usize % variants.len()])
+    }
+
+    /// Take the next element index, a little endian `u32`
+    fn element(&mut self) -> Option<ElementIdx> {
+        self.u32().map(|x| ElementIdx(x as usize))
+    }
+}
+
+/// Decode the next action from `reader`, `None` once the data runs out
+fn decode_action(reader: &mut Reader) -> Option<FuzzerAction> {
+    Some(match reader.u8()? % TAGS {
+        0 => FuzzerAction::LeftClick { idx: reader.element()? },
+        1 => FuzzerAction::Close,
+        2 => FuzzerAction::MenuAction {
+            menu_id:  MenuId(reader.u32()?),
+            menu_set: 0,
+        },
+        3 => {
+            let key = VirtualKey(reader.u16()? as usize);
+            let focus = if reader.u8()? & 1 != 0 {
+                Some(reader.element()?)
+            } else {
+                None
+            };
+            FuzzerAction::KeyPress { key, focus }
+        }
+        4 => FuzzerAction::DialogCommand { control_id: reader.u32()? },
+        5 => FuzzerAction::MessagePressure {
+            count: reader.u32()?,
+            seed:  reader.u32()?,
+        },
+        6 => FuzzerAction::DisplayEvent {
+            event: reader.pick(DISPLAY_EVENTS)?,
+            param: reader.u32()?,
+        },
+        7 => FuzzerAction::SessionEvent {
+            event: reader.pick(SESSION_EVENTS)?,
+            param: reader.u32()?,
+        },
+        8 => FuzzerAction::Accessibility {
+            object_id: reader.i32()?,
+            child:     reader.u32()?,
+        },
+        9 => FuzzerAction::OleDrop {
+            idx:    reader.element()?,
+            format: reader.pick(DROP_FORMATS)?,
+            seed:   reader.u32()?,
+        },
+        10 => FuzzerAction::Place {
+            placement: reader.pick(PLACEMENTS)?,
+            param:     reader.u32()?,
+        },
+        11 => FuzzerAction::KeyboardLayout { layout: reader.u8()? },
+        12 => FuzzerAction::UiaPattern {
+            idx:     reader.element()?,
+            pattern: reader.pick(uia::PATTERNS)?,
+        },
+        13 => FuzzerAction::PluginMacro {
+            plugin: reader.u8()?,
+            id:     reader.u32()?,
+            param:  reader.u32()?,
+        },
+        14 => FuzzerAction::Broadcast {
+            event: reader.pick(BROADCAST_EVENTS)?,
+            param: reader.u32()?,
+        },
+        15 => FuzzerAction::Mdi {
+            command: reader.pick(MDI_COMMANDS)?,
+            param:   reader.u32()?,
+        },
+        16 => FuzzerAction::CopyData {
+            tag:  reader.u32()?,
+            seed: reader.u32()?,
+            len:  reader.u16()?,
+        },
+        17 => FuzzerAction::CustomMessage {
+            idx:    reader.u16()?,
+            wparam: reader.u32()?,
+            lparam: reader.u32()?,
+        },
+        18 => FuzzerAction::Dde {
+            command: reader.pick(DDE_COMMANDS)?,
+            seed:    reader.u32()?,
+            len:     reader.u16()?,
+        },
+        19 => FuzzerAction::ModifiedClick {
+            idx:   reader.element()?,
+            state: KeyMouseState::from(reader.u8()? as usize),
+        },
+        20 => FuzzerAction::Hover {
+            idx:         reader.element()?,
+            x:           reader.u16()?,
+            y:           reader.u16()?,
+            duration_ms: reader.u16()?,
+        },
+        21 => {
+            let key = VirtualKey(reader.u16()? as usize);
+            let focus = if reader.u8()? & 1 != 0 {
+                Some(reader.element()?)
+            } else {
+                None
+            };
+            FuzzerAction::KeyRepeat { key, focus, repeats: reader.u16()? }
+        }
+        22 => FuzzerAction::Activation {
+            transition: reader.pick(TRANSITIONS)?,
+            param:      reader.u32()?,
+        },
+        23 => FuzzerAction::MenuAction {
+            menu_id:  MenuId(reader.u32()?),
+            menu_set: reader.u32()?,
+        },
+        24 => FuzzerAction::PropertySheet {
+            command: reader.pick(PROP_SHEET_COMMANDS)?,
+            param:   reader.u32()?,
+        },
+        25 => FuzzerAction::SwitchTab {
+            idx:   reader.element()?,
+            tab:   reader.u16()?,
+            focus: reader.u8()? & 1 != 0,
+        },
+        26 => FuzzerAction::SetRange {
+            idx:    reader.element()?,
+            bound:  reader.pick(RANGE_BOUNDS)?,
+            offset: reader.i32()?,
+        },
+        _ => unreachable!(),
+    })
+}
+
+/// Decode `data` into a sequence of at most `MAX_INPUT_LEN` actions. Never
+/// fails, trailing bytes which don't make up a whole action are ignored.
+pub fn decode(data: &[u8]) -> Vec<FuzzerAction> {
+    let mut reader = Reader { data };
+    let mut actions = Vec::new();
+    while actions.len() < MAX_INPUT_LEN {
+        match decode_action(&mut reader) {
+            Some(action) => actions.push(action),
+            None => break,
+        }
+    }
+    actions
+}
+
+/// Index of `variant` in `variants`, its encoding, `None` if it is missing
+/// from them
+fn index<T: PartialEq>(variants: &[T], variant: &T) -> Option<u8> {
+    variants.iter().position(|x| x == variant)
+        .and_then(|x| u8::try_from(x).ok())
+}
+
+/// Encoding of the element index `idx`, `None` if it doesn't fit
+fn element(idx: ElementIdx) -> Option<[u8; 4]> {
+    u32::try_from(idx.0).ok().map(u32::to_le_bytes)
+}
+
+/// Encoding of the virtual key `key`, `None` if it doesn't fit
+fn virtual_key(key: VirtualKey) -> Option<[u8; 2]> {
+    u16::try_from(key.0).ok().map(u16::to_le_bytes)
+}
+
+/// Encode `actions` into bytes which `decode()` turns back into them
+pub fn encode(actions: &[FuzzerAction]) -> Result<Vec<u8>, EncodeError> {
+    if actions.len() > MAX_INPUT_LEN {
+        return Err(EncodeError::TooLong(actions.len()));
+    }
+
+    let mut data = Vec::new();
+    for (idx, action) in actions.iter().enumerate() {
+        let fields = encode_action(action)
+            .ok_or(EncodeError::Unencodable(idx, *action))?;
+        data.extend_from_slice(&fields);
+    }
+    Ok(data)
+}
+
+/// Encode `action`, `None` if one of its fields is out of range
+fn encode_action(action: &FuzzerAction) -> Option<Vec<u8>> {
+    Some(match *action {
+        FuzzerAction::LeftClick { idx } => {
+            [&[0][..], &element(idx)?].concat()
+        }
+        FuzzerAction::Close => vec![1],
+        FuzzerAction::MenuAction { menu_id, menu_set: 0 } => {
+            [&[2][..], &menu_id.0.to_le_bytes()].concat()
+        }
+        FuzzerAction::MenuAction { menu_id, menu_set } => {
+            [&[23][..], &menu_id.0.to_le_bytes(), &menu_set.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::KeyPress { key, focus } => {
+            let focus = match focus {
+                Some(idx) => [&[1][..], &element(idx)?].concat(),
+                None => vec![0],
+            };
+            [&[3][..], &virtual_key(key)?, &focus].concat()
+        }
+        FuzzerAction::DialogCommand { control_id } => {
+            [&[4][..], &control_id.to_le_bytes()].concat()
+        }
+        FuzzerAction::MessagePressure { count, seed } => {
+            [&[5][..], &count.to_le_bytes(), &seed.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::DisplayEvent { event, param } => {
+            [&[6, index(DISPLAY_EVENTS, &event)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::SessionEvent { event, param } => {
+            [&[7, index(SESSION_EVENTS, &event)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::Accessibility { object_id, child } => {
+            [&[8][..], &object_id.to_le_bytes(), &child.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::OleDrop { idx, format, seed } => {
+            [&[9][..], &element(idx)?,
+                &[index(DROP_FORMATS, &format)?], &seed.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::Place { placement, param } => {
+            [&[10, index(PLACEMENTS, &placement)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::KeyboardLayout { layout } => vec![11, layout],
+        FuzzerAction::UiaPattern { idx, pattern } => {
+            [&[12][..], &element(idx)?,
+                &[index(uia::PATTERNS, &pattern)?]].concat()
+        }
+        FuzzerAction::PluginMacro { plugin, id, param } => {
+            [&[13, plugin][..], &id.to_le_bytes(), &param.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::Broadcast { event, param } => {
+            [&[14, index(BROADCAST_EVENTS, &event)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::Mdi { command, param } => {
+            [&[15, index(MDI_COMMANDS, &command)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::PropertySheet { command, param } => {
+            [&[24, index(PROP_SHEET_COMMANDS, &command)?][..],
+                &param.to_le_bytes()].concat()
+        }
+        FuzzerAction::SwitchTab { idx, tab, focus } => {
+            [&[25][..], &element(idx)?, &tab.to_le_bytes(),
+                &[focus as u8]].concat()
+        }
+        FuzzerAction::SetRange { idx, bound, offset } => {
+            [&[26][..], &element(idx)?,
+                &[index(RANGE_BOUNDS, &bound)?], &offset.to_le_bytes()]
+                .concat()
+        }
+        FuzzerAction::CopyData { tag, seed, len } => {
+            [&[16][..], &tag.to_le_bytes(), &seed.to_le_bytes(),
+                &len.to_le_bytes()].concat()
+        }
+        FuzzerAction::CustomMessage { idx, wparam, lparam } => {
+            [&[17][..], &idx.to_le_bytes(), &wparam.to_le_bytes(),
+                &lparam.to_le_bytes()].concat()
+        }
+        FuzzerAction::Dde { command, seed, len } => {
+            [&[18, index(DDE_COMMANDS, &command)?][..],
+                &seed.to_le_bytes(), &len.to_le_bytes()].concat()
+        }
+        FuzzerAction::ModifiedClick { idx, state } => {
+            let state: usize = state.into();
+            [&[19][..], &element(idx)?, &[state as u8]]
+                .concat()
+        }
+        FuzzerAction::Hover { idx, x, y, duration_ms } => {
+            [&[20][..], &element(idx)?, &x.to_le_bytes(),
+                &y.to_le_bytes(), &duration_ms.to_le_bytes()].concat()
+        }
+        FuzzerAction::KeyRepeat { key, focus, repeats } => {
+            let focus = match focus {
+                Some(idx) => [&[1][..], &element(idx)?].concat(),
+                None => vec![0],
+            };
+            [&[21][..], &virtual_key(key)?, &focus,
+                &repeats.to_le_bytes()].concat()
+        }
+        FuzzerAction::Activation { transition, param } => {
+            [&[22, index(TRANSITIONS, &transition)?][..],
+                &param.to_le_bytes()].concat()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+    use crate::bench::random_action;
+
+    /// Number of random inputs checked per property
+    const CASES: u64 = 2000;
+
+    /// Random bytes of a random length from `rng`
+    fn random_bytes(rng: &Rng) -> Vec<u8> {
+        let len = match rng.rand() % 8 {
+            0 => 0,
+            1 => rng.rand() % 8,
+            _ => rng.rand() % 512,
+        };
+        (0..len).map(|_| rng.rand() as u8).collect()
+    }
+
+    #[test]
+    fn decode_never_panics() {
+        for seed in 1..CASES {
+            decode(&random_bytes(&Rng::seeded(seed)));
+        }
+        decode(&[0xff; 4096]);
+        assert_eq!(decode(&vec![1; MAX_INPUT_LEN + 100]).len(),
+            MAX_INPUT_LEN);
+    }
+
+    #[test]
+    fn generated_inputs_round_trip() {
+        for seed in 1..CASES {
+            let rng = Rng::seeded(seed);
+            let actions: Vec<FuzzerAction> = (0..rng.rand() % 64)
+                .map(|_| random_action(&rng)).collect();
+            let data = encode(&actions).expect("Generated input unencodable");
+            assert_eq!(decode(&data), actions);
+        }
+    }
+
+    #[test]
+    fn decoded_inputs_round_trip() {
+        // Decoding reaches every variant, with any field values the
+        // encoding allows
+        for seed in 1..CASES {
+            let actions = decode(&random_bytes(&Rng::seeded(seed)));
+            let data = encode(&actions).expect("Decoded input unencodable");
+            assert_eq!(decode(&data), actions);
+        }
+    }
+
+    #[test]
+    fn out_of_range_fields_fail_to_encode() {
+        let key = FuzzerAction::KeyPress {
+            key:   VirtualKey(0x1_0000),
+            focus: None,
+        };
+        assert!(matches!(encode(&[FuzzerAction::Close, key]),
+            Err(EncodeError::Unencodable(1, _))));
+
+        let long = vec![FuzzerAction::Close; MAX_INPUT_LEN + 1];
+        assert!(matches!(encode(&long), Err(EncodeError::TooLong(_))));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn wide_element_indices_fail_to_encode() {
+        let click = FuzzerAction::LeftClick {
+            idx: ElementIdx(u32::MAX as usize + 1),
+        };
+        assert!(matches!(encode(&[click]),
+            Err(EncodeError::Unencodable(0, _))));
+    }
+}
@@ -0,0 +1,94 @@
+//! Thread-safe handle to the state of a running fuzzing campaign, intended
+//! for embedding applications such as dashboards and triage tools
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+use crate::{Statistics, FuzzInput, CrashBucket, Tags, FuzzEvent, events};
+
+/// A fuzzing campaign, sharing its `Statistics` between all workers
+///
+/// All accessors take the statistics lock for a short time and return
+/// snapshots, so the returned values may be stale by the time they are used.
+#[derive(Clone)]
+pub struct Campaign {
+    /// Statistics shared between all workers
+    stats: Arc<Mutex<Statistics>>,
+
+    /// Time the campaign was started at
+    start_time: Instant,
+}
+
+impl Campaign {
+    /// Create a new campaign with empty databases
+    pub fn new() -> Self {
+        Campaign {
+            stats:      Arc::new(Mutex::new(Statistics::default())),
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Get the shared statistics to hand to a worker
+    pub fn stats(&self) -> Arc<Mutex<Statistics>> {
+        self.stats.clone()
+    }
+
+    /// Lock the statistics for direct access. A worker panicking while
+    /// holding the lock poisons it, the statistics are still consistent
+    /// enough to use so the poison is ignored.
+    pub fn lock(&self) -> MutexGuard<'_, Statistics> {
+        self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Time elapsed since the campaign was started
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Number of fuzz cases executed so far
+    pub fn fuzz_cases(&self) -> u64 {
+        self.lock().fuzz_cases
+    }
+
+    /// Number of unique coverage entries observed so far
+    pub fn coverage(&self) -> usize {
+        self.lock().coverage_db.len()
+    }
+
+    /// Snapshot of all inputs in the corpus, in insertion order
+    pub fn corpus(&self) -> Vec<FuzzInput> {
+        self.lock().corpus.iter().collect()
+    }
+
+    /// Tag `input` with `tag`, for example to mark the feature it exercises
+    /// by hand. Returns `true` if the input is in the corpus.
+    pub fn tag_input(&self, input: &FuzzInput, tag: &str) -> bool {
+        let tags: Tags = std::iter::once(tag.to_string()).collect();
+        self.lock().tag_input(input, &tags)
+    }
+
+    /// Subscribe to the events of the campaign as they happen, rather than
+    /// polling the statistics
+    pub fn events(&self) -> Receiver<FuzzEvent> {
+        events::subscribe()
+    }
+
+    /// Total number of crashes observed, including duplicates
+    pub fn crashes(&self) -> u64 {
+        self.lock().crashes
+    }
+
+    /// Snapshot of all crash buckets, sorted by name
+    pub fn crash_buckets(&self) -> Vec<CrashBucket> {
+        let mut buckets: Vec<CrashBucket> =
+            self.lock().crash_db.iter().cloned().collect();
+        buckets.sort_by(|a, b| a.name.cmp(&b.name));
+        buckets
+    }
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Campaign::new()
+    }
+}
@@ -0,0 +1,182 @@
+//! Screen captures of target windows with GDI, for recording what the
+//! target looked like leading up to a crash or hang. Windows are rendered
+//! with `PrintWindow()`, so they are captured even while covered by the
+//! windows of other workers' targets.
+
+use std::io;
+use crate::Window;
+
+/// `PrintWindow()` flag to capture DirectComposition content as well
+const PW_RENDERFULLCONTENT: u32 = 0x2;
+
+/// `BI_RGB`, uncompressed bitmap
+const BI_RGB: u32 = 0;
+
+/// `DIB_RGB_COLORS`
+const DIB_RGB_COLORS: u32 = 0;
+
+#[link(name="User32")]
+extern "system" {
+    fn GetWindowRect(hwnd: usize, rect: *mut Rect) -> bool;
+    fn GetDC(hwnd: usize) -> usize;
+    fn ReleaseDC(hwnd: usize, dc: usize) -> i32;
+    fn PrintWindow(hwnd: usize, dc: usize, flags: u32) -> bool;
+}
+
+#[link(name="Gdi32")]
+extern "system" {
+    fn CreateCompatibleDC(dc: usize) -> usize;
+    fn CreateCompatibleBitmap(dc: usize, width: i32, height: i32) -> usize;
+    fn SelectObject(dc: usize, object: usize) -> usize;
+    fn DeleteObject(object: usize) -> i32;
+    fn DeleteDC(dc: usize) -> i32;
+    fn GetDIBits(dc: usize, bitmap: usize, start: u32, lines: u32,
+        bits: *mut u8, info: *mut BitmapInfo, usage: u32) -> i32;
+}
+
+/// Rust implementation of `RECT`
+#[repr(C)]
+#[derive(Default)]
+struct Rect {
+    left:   i32,
+    top:    i32,
+    right:  i32,
+    bottom: i32,
+}
+
+/// Rust implementation of `BITMAPINFOHEADER`
+#[repr(C)]
+#[derive(Default)]
+struct BitmapInfoHeader {
+    size:          u32,
+    width:         i32,
+    height:        i32,
+    planes:        u16,
+    bit_count:     u16,
+    compression:   u32,
+    size_image:    u32,
+    x_ppm:         i32,
+    y_ppm:         i32,
+    clr_used:      u32,
+    clr_important: u32,
+}
+
+/// Rust implementation of `BITMAPINFO`, with room for its color table
+#[repr(C)]
+#[derive(Default)]
+struct BitmapInfo {
+    header: BitmapInfoHeader,
+    colors: [u32; 4],
+}
+
+/// A captured image of a window
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// Width of the image in pixels
+    pub width: u32,
+
+    /// Height of the image in pixels
+    pub height: u32,
+
+    /// Pixels as 32-bit BGRA, top row first
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Encode the frame as a BMP file
+    pub fn to_bmp(&self) -> Vec<u8> {
+        let header_size = 14 + 40;
+        let file_size = header_size + self.pixels.len() as u32;
+
+        let mut bmp = Vec::with_capacity(file_size as usize);
+        bmp.extend_from_slice(b"BM");
+        bmp.extend_from_slice(&file_size.to_le_bytes());
+        bmp.extend_from_slice(&0u32.to_le_bytes());
+        bmp.extend_from_slice(&header_size.to_le_bytes());
+
+        // A negative height stores the top row first
+        bmp.extend_from_slice(&40u32.to_le_bytes());
+        bmp.extend_from_slice(&(self.width as i32).to_le_bytes());
+        bmp.extend_from_slice(&(-(self.height as i32)).to_le_bytes());
+        bmp.extend_from_slice(&1u16.to_le_bytes());
+        bmp.extend_from_slice(&32u16.to_le_bytes());
+        bmp.extend_from_slice(&BI_RGB.to_le_bytes());
+        bmp.extend_from_slice(&[0u8; 20]);
+
+        bmp.extend_from_slice(&self.pixels);
+        bmp
+    }
+}
+
+/// Capture an image of `window`, including its frame
+pub fn capture(window: &Window) -> io::Result<Frame> {
+    let hwnd = window.hwnd();
+
+    let mut rect = Rect::default();
+    if !unsafe { GetWindowRect(hwnd, &mut rect) } {
+        return Err(io::Error::last_os_error());
+    }
+    let width  = rect.right - rect.left;
+    let height = rect.bottom - rect.top;
+    if width <= 0 || height <= 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+            "Window has no area to capture"));
+    }
+
+    unsafe {
+        let screen = GetDC(0);
+        if screen == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let dc = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, width, height);
+        ReleaseDC(0, screen);
+        if dc == 0 || bitmap == 0 {
+            let err = io::Error::last_os_error();
+            if bitmap != 0 {
+                DeleteObject(bitmap);
+            }
+            if dc != 0 {
+                DeleteDC(dc);
+            }
+            return Err(err);
+        }
+
+        let old = SelectObject(dc, bitmap);
+        let printed = PrintWindow(hwnd, dc, PW_RENDERFULLCONTENT);
+        SelectObject(dc, old);
+
+        let mut info = BitmapInfo {
+            header: BitmapInfoHeader {
+                size:        std::mem::size_of::<BitmapInfoHeader>() as u32,
+                width,
+                height:      -height,
+                planes:      1,
+                bit_count:   32,
+                compression: BI_RGB,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        let lines = if printed {
+            GetDIBits(dc, bitmap, 0, height as u32, pixels.as_mut_ptr(),
+                &mut info, DIB_RGB_COLORS)
+        } else {
+            0
+        };
+        let err = io::Error::last_os_error();
+
+        DeleteObject(bitmap);
+        DeleteDC(dc);
+        if lines == 0 {
+            return Err(err);
+        }
+
+        Ok(Frame {
+            width:  width as u32,
+            height: height as u32,
+            pixels,
+        })
+    }
+}
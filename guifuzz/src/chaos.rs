@@ -0,0 +1,175 @@
+//! Failure injection for testing the harness itself, built with the `chaos`
+//! feature. Targets rarely misbehave in the ways which break a fuzzer, so
+//! in chaos mode Win32 calls randomly fail, windows show up late, targets
+//! are killed in the middle of their case and window titles come back as
+//! garbage. The harness then has to hold up: workers must keep finishing
+//! cases, no target may outlive its case, and the corpus must stay
+//! consistent. A violated invariant aborts the fuzzer.
+
+use std::io;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::rng::Rng;
+use crate::corpus::Corpus;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn TerminateProcess(process: usize, exit_code: u32) -> i32;
+    fn WaitForSingleObject(handle: usize, timeout: u32) -> u32;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+/// `OpenProcess()` access rights to terminate and wait on a process
+const PROCESS_TERMINATE: u32 = 0x0001;
+const SYNCHRONIZE:       u32 = 0x0010_0000;
+
+/// `WaitForSingleObject()` result when the process exited
+const WAIT_OBJECT_0: u32 = 0;
+
+/// Exit code of targets killed by chaos mode
+const KILLED_EXIT_CODE: u32 = 0xc4a0;
+
+/// One in this many chances to inject a failure injects it
+const FAILURE_RATE: usize = 16;
+
+/// Win32 error injected into failing calls, `ERROR_INVALID_WINDOW_HANDLE`.
+/// It has an OS error code, so it is retried like a real transient error.
+const INJECTED_ERROR: i32 = 1400;
+
+/// Longest delay injected before a window can be found
+const MAX_WINDOW_DELAY: Duration = Duration::from_millis(2000);
+
+/// Longest time into its case a target is killed
+const MAX_KILL_DELAY: Duration = Duration::from_millis(5000);
+
+/// Longest time a target may take to exit after its case
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest time the workers may go without finishing a case before they
+/// are considered deadlocked
+const STALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Number of cases finished by all workers, failed ones included
+static PROGRESS: AtomicU64 = AtomicU64::new(0);
+
+/// Decide whether to inject a failure
+fn inject() -> bool {
+    Rng::new().rand().is_multiple_of(FAILURE_RATE)
+}
+
+/// Random duration up to `max`
+fn random_duration(max: Duration) -> Duration {
+    Duration::from_millis(Rng::new().rand() as u64 %
+        (max.as_millis() as u64 + 1))
+}
+
+/// Report a violated invariant and abort, a broken harness must not keep
+/// fuzzing
+fn violation(what: &str) -> ! {
+    println!("Chaos invariant violated: {}", what);
+    std::process::abort();
+}
+
+/// Randomly fail a Win32 call which is about to be made
+pub fn fail_call() -> io::Result<()> {
+    if inject() {
+        Err(io::Error::from_raw_os_error(INJECTED_ERROR))
+    } else {
+        Ok(())
+    }
+}
+
+/// Randomly delay finding a window, as if it showed up late
+pub fn delay_window() {
+    if inject() {
+        std::thread::sleep(random_duration(MAX_WINDOW_DELAY));
+    }
+}
+
+/// Randomly replace the window title `title` with garbage
+pub fn garble_title(title: String) -> String {
+    if !inject() {
+        return title;
+    }
+
+    let rng = Rng::new();
+    (0..rng.rand() % 64)
+        .filter_map(|_| std::char::from_u32(rng.rand() as u32 % 0x11000))
+        .collect()
+}
+
+/// Randomly kill the target `pid` some time into its case. The process
+/// handle is opened right away, so the PID can't be reused by another
+/// process before the kill.
+pub fn kill_later(pid: u32) {
+    if !inject() {
+        return;
+    }
+
+    let process = unsafe { OpenProcess(PROCESS_TERMINATE, 0, pid) };
+    if process == 0 {
+        return;
+    }
+
+    let delay = random_duration(MAX_KILL_DELAY);
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        unsafe {
+            TerminateProcess(process, KILLED_EXIT_CODE);
+            CloseHandle(process);
+        }
+    });
+}
+
+/// Record that a worker finished a case, whether it succeeded or not
+pub fn progress() {
+    PROGRESS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Start a thread aborting the fuzzer if the workers stop finishing cases,
+/// as they are deadlocked
+pub fn watchdog() {
+    std::thread::spawn(|| {
+        let mut last = PROGRESS.load(Ordering::SeqCst);
+        let mut since = Instant::now();
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+
+            let progress = PROGRESS.load(Ordering::SeqCst);
+            if progress != last {
+                last = progress;
+                since = Instant::now();
+            } else if since.elapsed() >= STALL_TIMEOUT {
+                violation(&format!("No case finished in {:?}, the workers \
+                    are deadlocked", STALL_TIMEOUT));
+            }
+        }
+    });
+}
+
+/// Check that the target `pid`, whose case is over, exits, so targets
+/// aren't leaked
+pub fn check_exited(pid: u32) {
+    unsafe {
+        let process = OpenProcess(SYNCHRONIZE, 0, pid);
+        if process == 0 {
+            // Already gone
+            return;
+        }
+
+        let ret = WaitForSingleObject(process,
+            EXIT_TIMEOUT.as_millis() as u32);
+        CloseHandle(process);
+        if ret != WAIT_OBJECT_0 {
+            violation(&format!("Target {} outlived its case", pid));
+        }
+    }
+}
+
+/// Check that `corpus` is consistent
+pub fn check_corpus(corpus: &mut Corpus) {
+    if let Err(err) = corpus.check() {
+        violation(&format!("Corrupted corpus: {}", err));
+    }
+}
@@ -0,0 +1,895 @@
+//! Campaign configuration, settable from the command line as `--key value`
+//! or from a config file containing `key = value` lines
+
+use std::path::Path;
+use std::error::Error;
+
+/// Campaign configuration
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Novelty search: inputs whose mean distance to their nearest
+    /// neighbors in the corpus is at least this are saved even without new
+    /// coverage. `None` disables novelty search.
+    pub novelty_threshold: Option<f64>,
+
+    /// Number of nearest neighbors used to compute novelty
+    pub novelty_neighbors: usize,
+
+    /// Maximum number of target processes alive at once, `None`, set with a
+    /// value of 0, for no limit
+    pub max_targets: Option<usize>,
+
+    /// Minimum free disk space in MiB, crash dumps are paused below this
+    pub min_free_disk_mb: u64,
+
+    /// Maximum number of reproducers saved per crash bucket
+    pub max_crash_reproducers: usize,
+
+    /// Render an SVG chart of coverage over time next to the stats CSV
+    pub stats_chart: bool,
+
+    /// URL to POST notifications to on new crash buckets and stalls. The
+    /// payload is compatible with Slack and Discord incoming webhooks.
+    pub webhook_url: Option<String>,
+
+    /// Send a stall notification after this many seconds without new
+    /// coverage, `None` to only notify on new crashes
+    pub stall_notify_secs: Option<u64>,
+
+    /// Split the corpus into one shard per worker, with each worker favoring
+    /// inputs from its own shard to reduce duplicate discoveries
+    pub shard_corpus: bool,
+
+    /// Maximum number of times inputs descended from the same root seed may
+    /// be selected for mutation per lineage window, `None` for no limit
+    pub lineage_cap: Option<u64>,
+
+    /// Length of the window `lineage_cap` applies to, in seconds
+    pub lineage_window_secs: u64,
+
+    /// Favor inputs with shallow genealogies, weighting inputs by
+    /// `1 / (1 + depth)^penalty`. Zero disables the penalty.
+    pub lineage_depth_penalty: f64,
+
+    /// Interleave floods of `WM_TIMER`, `WM_PAINT`, and `WM_SIZE` messages
+    /// with generated actions to shake out reentrancy and repaint bugs
+    pub message_pressure: bool,
+
+    /// Generate `WM_DPICHANGED`, `WM_SETTINGCHANGE`, `WM_THEMECHANGED`, and
+    /// `WM_DISPLAYCHANGE` broadcasts to exercise layout recalculation
+    pub display_events: bool,
+
+    /// Generate `WM_POWERBROADCAST`, `WM_QUERYENDSESSION`, `WM_ENDSESSION`,
+    /// and `WM_DEVICECHANGE` broadcasts to exercise suspend and shutdown
+    /// handlers
+    pub session_events: bool,
+
+    /// Generate actions requesting accessible objects with `WM_GETOBJECT`
+    /// and exercising their `IAccessible` interfaces
+    pub accessibility: bool,
+
+    /// Generate OLE drag and drop actions with fuzzed `CF_TEXT`, `CF_HTML`,
+    /// and file descriptor data. These move the real mouse cursor.
+    pub ole_drops: bool,
+
+    /// Generate actions moving the window to extreme coordinates, across
+    /// monitors, and maximizing it on different monitors
+    pub placement: bool,
+
+    /// Generate actions switching the target between keyboard layouts,
+    /// including IME layouts
+    pub keyboard_layouts: bool,
+
+    /// Report a hang if the target's main window doesn't process a message
+    /// within this many milliseconds after the actions, `None` to disable
+    pub hang_timeout_ms: Option<u64>,
+
+    /// Report a leak if the target has at least this many handles, GDI
+    /// objects, or USER objects after the actions, `None` to disable
+    pub leak_threshold: Option<u32>,
+
+    /// Prefer invoking UI Automation control patterns (invoke, toggle,
+    /// select, expand, collapse) over raw clicks when UI Automation is
+    /// available
+    pub uia: bool,
+
+    /// Snapshot the target's UI Automation tree after each case, saving
+    /// inputs reaching new trees and reporting property invariant
+    /// violations
+    pub uia_snapshot: bool,
+
+    /// Paths of plugin DLLs providing target-specific setup, teardown,
+    /// oracles, and action macros. Can be given multiple times.
+    pub plugins: Vec<String>,
+
+    /// Calibrate a corpus input every this many fuzz cases per worker,
+    /// running it twice to measure how reliably it reproduces its coverage.
+    /// `None`, set with a value of 0, disables calibration.
+    pub calibration_interval: Option<u64>,
+
+    /// Only print errors to the console, everything is still logged to
+    /// files
+    pub quiet: bool,
+
+    /// Also print frequent events, such as new coverage, to the console
+    pub verbose: bool,
+
+    /// Color console events with ANSI escape sequences if supported
+    pub color: bool,
+
+    /// Every this many fuzz cases per worker, replay a corpus input with
+    /// both the `PostMessage` and `SendInput` backends and report
+    /// differences in coverage or UI state. `None` disables the check.
+    pub equivalence_interval: Option<u64>,
+
+    /// Dump the target's window tree before and after each case, saving
+    /// inputs which change the UI into a never before seen shape and
+    /// logging the UI state with crashes
+    pub window_tree: bool,
+
+    /// Number of attempts made at Win32 calls which fail transiently, such
+    /// as enumerating windows or menus while the target is repainting
+    pub win32_retries: u32,
+
+    /// Delay in milliseconds before retrying a failed Win32 call, doubled
+    /// with every retry
+    pub win32_retry_delay_ms: u64,
+
+    /// Generate actions from the system broadcast pack: input language
+    /// changes, volume hotplug, clipboard updates, and font changes
+    pub broadcasts: bool,
+
+    /// Validate menu IDs against the target's current menus before using
+    /// them, skipping stale IDs and teaching the mutator to avoid IDs which
+    /// are never valid
+    pub probe_menus: bool,
+
+    /// Create, switch between, and close MDI child documents of targets
+    /// with an MDI client area
+    pub mdi: bool,
+
+    /// Relaunch the harness elevated, so targets are spawned directly
+    /// elevated rather than stalling on UAC prompts
+    pub elevate: bool,
+
+    /// Learn which controls raise UAC prompts and stop clicking them
+    pub skip_elevation: bool,
+
+    /// Replay inputs which found something new in a coverage-isolated run
+    /// and save exactly the coverage they contribute
+    pub isolate_coverage: bool,
+
+    /// Send `WM_COPYDATA` messages with fuzzed payloads
+    pub copydata: bool,
+
+    /// Names of messages registered with `RegisterWindowMessage()` to
+    /// fuzz, each option adds one
+    pub custom_messages: Vec<String>,
+
+    /// Save the names of all messages registered in the session to
+    /// `registered_messages.txt`, to pick custom messages from
+    pub discover_messages: bool,
+
+    /// Start DDE conversations with the target and send fuzzed execute
+    /// commands and pokes
+    pub dde: bool,
+
+    /// Occasionally launch the target with a fuzzed command line or through
+    /// a shell verb on one of the `launch_files` instead of a plain spawn
+    pub launch_fuzzing: bool,
+
+    /// Directory of files to pass on fuzzed command lines and open with
+    /// shell verbs
+    pub launch_files: Option<String>,
+
+    /// Append every message and input injected into targets to the audit
+    /// log at this path
+    pub audit_log: Option<String>,
+
+    /// Size in megabytes at which the audit log is rotated
+    pub audit_log_max_mb: u64,
+
+    /// Number of rotated audit logs to keep
+    pub audit_log_keep: usize,
+
+    /// Deadline of each action in milliseconds. A case is aborted, after
+    /// dismissing the target's dialogs, when an action misses it.
+    pub action_timeout_ms: Option<u64>,
+
+    /// Number of crashed targets in new crash buckets to keep alive,
+    /// suspended at the crash, for attaching a debugger. 0 kills them all.
+    pub keep_crashes: usize,
+
+    /// Path of another build of the target to replay every case against,
+    /// reporting crashes and coverage which differ between the builds
+    pub differential: Option<String>,
+
+    /// Meso file of the `differential` build, `<file name>.meso` if not
+    /// given
+    pub differential_meso: Option<String>,
+
+    /// Attribute coverage to the action which found it, waiting for the
+    /// target to go idle after each action of mutated inputs
+    pub action_coverage: bool,
+
+    /// Megabytes of corpus inputs each corpus keeps in memory, the least
+    /// recently used inputs beyond that are paged out to `pages\`. `None`
+    /// keeps every input in memory.
+    pub corpus_memory_mb: Option<u64>,
+
+    /// Minimize the reproducer of every new crash bucket by replaying it
+    /// with runs of actions removed, saving it as `minimized.input` with a
+    /// readable `trace.txt`
+    pub minimize_crashes: bool,
+
+    /// Learn which actions keep hanging the target without finding
+    /// anything and use them less
+    pub adaptive_blacklist: bool,
+
+    /// Kill processes, such as browsers opened by help menu items, which
+    /// show up with a window during a case and aren't fuzz targets. Any
+    /// program started on the machine during a case is killed!
+    pub suppress_companions: bool,
+
+    /// Cores to pin workers and the targets they spawn to, as a comma
+    /// separated list. Worker N gets the Nth core, wrapping around. Empty
+    /// disables pinning.
+    pub pin_cores: Vec<usize>,
+
+    /// Scheduling priority of target processes, lowered so a campaign
+    /// doesn't make the host unusable. The harness itself keeps its normal
+    /// priority so its timeouts stay accurate.
+    pub target_priority: TargetPriority,
+
+    /// Record the target's main window during each case, keeping the last
+    /// this many seconds, and save the recording with new crashes and
+    /// hangs. `None` disables recording.
+    pub capture_seconds: Option<u64>,
+
+    /// Frames per second recorded with `capture_seconds`
+    pub capture_fps: u64,
+
+    /// Save inputs whose windows created, GDI objects, or USER objects
+    /// deviate from the baseline of all cases by at least this many standard
+    /// deviations, even without new coverage. `None` disables it.
+    pub activity_novelty: Option<f64>,
+
+    /// Stop the campaign after this many fuzz cases, `None` for no limit
+    pub max_cases: Option<u64>,
+
+    /// Stop the campaign after running this many seconds, `None` for no
+    /// limit
+    pub max_time: Option<u64>,
+
+    /// Stop the campaign once this many unique crashes were found, `None`
+    /// for no limit
+    pub stop_after_unique_crashes: Option<usize>,
+
+    /// Occasionally hold shift or control down while clicking, as many
+    /// targets bind special behavior to modified clicks
+    pub modifier_clicks: bool,
+
+    /// Chance of hovering the mouse over GUI elements, exercising tooltips,
+    /// hot-tracking, and hover previews
+    pub hover: bool,
+
+    /// Chance of holding keys down until they auto-repeat
+    pub key_repeat: bool,
+
+    /// Chance of switching the target to the background and back, or moving
+    /// the focus between its controls
+    pub activation: bool,
+
+    /// File of actions performed at the start of every case and replay,
+    /// encoded like the `.bytes` file saved with each input, so cases start
+    /// from a deeper UI state. The prologue is never mutated.
+    pub prologue: Option<String>,
+
+    /// File of actions performed after every case, encoded like the
+    /// prologue, which must change the target's window tree. Cases after
+    /// which the target doesn't respond to it are saved as state-corrupting.
+    pub epilogue: Option<String>,
+
+    /// Comma-separated tags, such as `window:Convert` or `menu:0x131`, an
+    /// input needs one of to be mutated, directing fuzzing at one area of
+    /// the UI. Inputs are tagged with the menu items they use and the titles
+    /// of the dialogs open after them.
+    pub schedule_tags: Option<String>,
+
+    /// Measure how fast the target answers messages at the start of the
+    /// campaign and derive the waits between actions from it, rather than
+    /// using fixed waits tuned for native targets
+    pub adaptive_pacing: bool,
+
+    /// Config file overriding options for the B arm of an A/B comparison of
+    /// two strategies. Every other worker runs the B arm with its own corpus
+    /// and coverage, and the arms are compared in `ab_report.txt` when the
+    /// campaign stops. Stopping conditions apply to the A arm.
+    pub ab_config: Option<String>,
+
+    /// Program of the target to fuzz, `calc.exe` with its defaults if not
+    /// given
+    pub target: Option<String>,
+
+    /// Arguments the target is started with, each option adds one
+    pub target_args: Vec<String>,
+
+    /// Title of the target's main window, the program's file name if not
+    /// given
+    pub target_title: Option<String>,
+
+    /// Class of the target's main window, to find it by its class
+    /// instead of its title
+    pub target_class: Option<String>,
+
+    /// Meso file of the target, `<file name>.meso` if not given
+    pub target_meso: Option<String>,
+
+    /// Commands run before every case to reset the target's persisted
+    /// state, each option adds one
+    pub target_reset: Vec<String>,
+
+    /// Files to extract the action dictionary from, each option adds one.
+    /// The target's program if none are given.
+    pub target_resources: Vec<String>,
+
+    /// Switch between the pages of open property sheets and use their
+    /// controls, apply them, and press their buttons
+    pub property_sheets: bool,
+
+    /// JSON snapshot of the corpus, coverage, and crashes to resume from
+    /// if it exists, and to save to when the campaign stops. Needs the
+    /// `serde` feature.
+    pub snapshot: Option<String>,
+
+    /// Cycle the tab controls of the target through all of their tabs
+    pub tab_controls: bool,
+
+    /// Set trackbars, up-down controls, and date-time pickers to values at
+    /// and past the bounds of their range
+    pub range_controls: bool,
+
+    /// Capture the UI state before and after each case, saving what changed
+    /// with inputs and favoring inputs which change it more
+    pub ui_state: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            novelty_threshold:     None,
+            novelty_neighbors:     5,
+            max_targets:           None,
+            min_free_disk_mb:      1024,
+            max_crash_reproducers: 5,
+            stats_chart:           false,
+            webhook_url:           None,
+            stall_notify_secs:     None,
+            shard_corpus:          false,
+            lineage_cap:           None,
+            lineage_window_secs:   60,
+            lineage_depth_penalty: 0.,
+            message_pressure:      false,
+            display_events:        false,
+            session_events:        false,
+            accessibility:         false,
+            ole_drops:             false,
+            placement:             false,
+            keyboard_layouts:      false,
+            hang_timeout_ms:       None,
+            leak_threshold:        None,
+            uia:                   false,
+            uia_snapshot:          false,
+            plugins:               Vec::new(),
+            calibration_interval:  Some(50),
+            quiet:                 false,
+            verbose:               false,
+            color:                 true,
+            equivalence_interval:  None,
+            window_tree:           false,
+            win32_retries:         3,
+            win32_retry_delay_ms:  10,
+            broadcasts:            false,
+            probe_menus:           false,
+            mdi:                   false,
+            elevate:               false,
+            skip_elevation:        false,
+            isolate_coverage:      false,
+            copydata:              false,
+            custom_messages:       Vec::new(),
+            discover_messages:     false,
+            dde:                   false,
+            launch_fuzzing:        false,
+            launch_files:          None,
+            audit_log:             None,
+            audit_log_max_mb:      64,
+            audit_log_keep:        4,
+            action_timeout_ms:     None,
+            keep_crashes:          0,
+            differential:          None,
+            differential_meso:     None,
+            action_coverage:       false,
+            corpus_memory_mb:      None,
+            minimize_crashes:      false,
+            adaptive_blacklist:    false,
+            suppress_companions:   false,
+            pin_cores:             Vec::new(),
+            target_priority:       TargetPriority::Normal,
+            capture_seconds:       None,
+            capture_fps:           2,
+            activity_novelty:      None,
+            max_cases:             None,
+            max_time:              None,
+            stop_after_unique_crashes: None,
+            modifier_clicks:       false,
+            hover:                 false,
+            key_repeat:            false,
+            activation:            false,
+            prologue:              None,
+            epilogue:              None,
+            schedule_tags:         None,
+            adaptive_pacing:       false,
+            ab_config:             None,
+            target:                None,
+            target_args:           Vec::new(),
+            target_title:          None,
+            target_class:          None,
+            target_meso:           None,
+            target_reset:          Vec::new(),
+            target_resources:      Vec::new(),
+            property_sheets:       false,
+            snapshot:              None,
+            tab_controls:          false,
+            range_controls:        false,
+            ui_state:              false,
+        }
+    }
+}
+
+/// Parse `value` for the config option `key`
+fn parse<T: std::str::FromStr>(key: &str, value: &str)
+        -> Result<T, Box<dyn Error>> {
+    value.parse().map_err(|_| {
+        format!("Invalid value {:?} for option {:?}", value, key).into()
+    })
+}
+
+impl Config {
+    /// Set the option `key` to `value`
+    pub fn set(&mut self, key: &str, value: &str)
+            -> Result<(), Box<dyn Error>> {
+        match key {
+            "novelty-threshold" => {
+                self.novelty_threshold = Some(parse(key, value)?);
+            }
+            "novelty-neighbors" => {
+                self.novelty_neighbors = parse(key, value)?;
+            }
+            "max-targets" => {
+                self.max_targets = Some(parse(key, value)?)
+                    .filter(|&x| x != 0);
+            }
+            "min-free-disk-mb" => {
+                self.min_free_disk_mb = parse(key, value)?;
+            }
+            "max-crash-reproducers" => {
+                self.max_crash_reproducers = parse(key, value)?;
+            }
+            "stats-chart" => {
+                self.stats_chart = parse(key, value)?;
+            }
+            "webhook-url" => {
+                self.webhook_url = Some(value.to_string());
+            }
+            "stall-notify-secs" => {
+                self.stall_notify_secs = Some(parse(key, value)?);
+            }
+            "shard-corpus" => {
+                self.shard_corpus = parse(key, value)?;
+            }
+            "lineage-cap" => {
+                self.lineage_cap = Some(parse(key, value)?);
+            }
+            "lineage-window-secs" => {
+                self.lineage_window_secs = parse(key, value)?;
+            }
+            "lineage-depth-penalty" => {
+                self.lineage_depth_penalty = parse(key, value)?;
+            }
+            "message-pressure" => {
+                self.message_pressure = parse(key, value)?;
+            }
+            "display-events" => {
+                self.display_events = parse(key, value)?;
+            }
+            "session-events" => {
+                self.session_events = parse(key, value)?;
+            }
+            "accessibility" => {
+                self.accessibility = parse(key, value)?;
+            }
+            "ole-drops" => {
+                self.ole_drops = parse(key, value)?;
+            }
+            "placement" => {
+                self.placement = parse(key, value)?;
+            }
+            "keyboard-layouts" => {
+                self.keyboard_layouts = parse(key, value)?;
+            }
+            "hang-timeout-ms" => {
+                self.hang_timeout_ms = Some(parse(key, value)?);
+            }
+            "leak-threshold" => {
+                self.leak_threshold = Some(parse(key, value)?);
+            }
+            "uia" => {
+                self.uia = parse(key, value)?;
+            }
+            "uia-snapshot" => {
+                self.uia_snapshot = parse(key, value)?;
+            }
+            "plugin" => {
+                self.plugins.push(value.to_string());
+            }
+            "calibration-interval" => {
+                let interval = parse(key, value)?;
+                self.calibration_interval = Some(interval)
+                    .filter(|&x| x != 0);
+            }
+            "quiet" => {
+                self.quiet = parse(key, value)?;
+            }
+            "verbose" => {
+                self.verbose = parse(key, value)?;
+            }
+            "color" => {
+                self.color = parse(key, value)?;
+            }
+            "equivalence-interval" => {
+                let interval = parse(key, value)?;
+                self.equivalence_interval = Some(interval)
+                    .filter(|&x| x != 0);
+            }
+            "window-tree" => {
+                self.window_tree = parse(key, value)?;
+            }
+            "win32-retries" => {
+                self.win32_retries = parse(key, value)?;
+            }
+            "win32-retry-delay-ms" => {
+                self.win32_retry_delay_ms = parse(key, value)?;
+            }
+            "broadcasts" => {
+                self.broadcasts = parse(key, value)?;
+            }
+            "probe-menus" => {
+                self.probe_menus = parse(key, value)?;
+            }
+            "mdi" => {
+                self.mdi = parse(key, value)?;
+            }
+            "elevate" => {
+                self.elevate = parse(key, value)?;
+            }
+            "skip-elevation" => {
+                self.skip_elevation = parse(key, value)?;
+            }
+            "isolate-coverage" => {
+                self.isolate_coverage = parse(key, value)?;
+            }
+            "copydata" => {
+                self.copydata = parse(key, value)?;
+            }
+            "custom-message" => {
+                self.custom_messages.push(value.to_string());
+            }
+            "discover-messages" => {
+                self.discover_messages = parse(key, value)?;
+            }
+            "dde" => {
+                self.dde = parse(key, value)?;
+            }
+            "launch-fuzzing" => {
+                self.launch_fuzzing = parse(key, value)?;
+            }
+            "launch-files" => {
+                self.launch_files = Some(value.to_string());
+            }
+            "audit-log" => {
+                self.audit_log = Some(value.to_string());
+            }
+            "audit-log-max-mb" => {
+                self.audit_log_max_mb = parse(key, value)?;
+            }
+            "audit-log-keep" => {
+                self.audit_log_keep = parse(key, value)?;
+            }
+            "action-timeout-ms" => {
+                self.action_timeout_ms = Some(parse(key, value)?);
+            }
+            "keep-crashes" => {
+                self.keep_crashes = parse(key, value)?;
+            }
+            "differential" => {
+                self.differential = Some(value.to_string());
+            }
+            "differential-meso" => {
+                self.differential_meso = Some(value.to_string());
+            }
+            "action-coverage" => {
+                self.action_coverage = parse(key, value)?;
+            }
+            "corpus-memory-mb" => {
+                self.corpus_memory_mb = Some(parse(key, value)?);
+            }
+            "minimize-crashes" => {
+                self.minimize_crashes = parse(key, value)?;
+            }
+            "adaptive-blacklist" => {
+                self.adaptive_blacklist = parse(key, value)?;
+            }
+            "suppress-companions" => {
+                self.suppress_companions = parse(key, value)?;
+            }
+            "pin-cores" => {
+                self.pin_cores = value.split(',').filter(|x| !x.is_empty())
+                    .map(|x| parse(key, x.trim()))
+                    .collect::<Result<_, _>>()?;
+            }
+            "target-priority" => {
+                self.target_priority = parse(key, value)?;
+            }
+            "capture-seconds" => {
+                self.capture_seconds = Some(parse(key, value)?)
+                    .filter(|&x| x != 0);
+            }
+            "capture-fps" => {
+                self.capture_fps = parse(key, value)?;
+            }
+            "activity-novelty" => {
+                self.activity_novelty = Some(parse(key, value)?);
+            }
+            "max-cases" => {
+                self.max_cases = Some(parse(key, value)?).filter(|&x| x != 0);
+            }
+            "max-time" => {
+                self.max_time = Some(parse(key, value)?).filter(|&x| x != 0);
+            }
+            "stop-after-unique-crashes" => {
+                self.stop_after_unique_crashes = Some(parse(key, value)?)
+                    .filter(|&x| x != 0);
+            }
+            "modifier-clicks" => {
+                self.modifier_clicks = parse(key, value)?;
+            }
+            "hover" => {
+                self.hover = parse(key, value)?;
+            }
+            "key-repeat" => {
+                self.key_repeat = parse(key, value)?;
+            }
+            "activation" => {
+                self.activation = parse(key, value)?;
+            }
+            "prologue" => {
+                self.prologue = Some(value.to_string());
+            }
+            "epilogue" => {
+                self.epilogue = Some(value.to_string());
+            }
+            "schedule-tags" => {
+                self.schedule_tags = Some(value.to_string());
+            }
+            "adaptive-pacing" => {
+                self.adaptive_pacing = parse(key, value)?;
+            }
+            "ab-config" => {
+                self.ab_config = Some(value.to_string());
+            }
+            "target" => {
+                self.target = Some(value.to_string());
+            }
+            "target-arg" => {
+                self.target_args.push(value.to_string());
+            }
+            "target-title" => {
+                self.target_title = Some(value.to_string());
+            }
+            "target-class" => {
+                self.target_class = Some(value.to_string());
+            }
+            "target-meso" => {
+                self.target_meso = Some(value.to_string());
+            }
+            "target-reset" => {
+                self.target_reset.push(value.to_string());
+            }
+            "target-resource" => {
+                self.target_resources.push(value.to_string());
+            }
+            "property-sheets" => {
+                self.property_sheets = parse(key, value)?;
+            }
+            "snapshot" => {
+                self.snapshot = Some(value.to_string());
+            }
+            "tab-controls" => {
+                self.tab_controls = parse(key, value)?;
+            }
+            "range-controls" => {
+                self.range_controls = parse(key, value)?;
+            }
+            "ui-state" => {
+                self.ui_state = parse(key, value)?;
+            }
+            _ => return Err(format!("Unknown option {:?}", key).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Load options from the config file at `path`. Blank lines and lines
+    /// starting with `#` are ignored.
+    pub fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut split = line.splitn(2, '=');
+            let key   = split.next().unwrap().trim();
+            let value = split.next().ok_or_else(|| {
+                format!("Expected `key = value`, got {:?}", line)
+            })?.trim();
+
+            self.set(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parse command line arguments. `--config <path>` loads a config file,
+    /// which is applied in order with the other options so later options
+    /// take precedence. Options without a value are set to `true`. Returns
+    /// all arguments which are not options.
+    pub fn parse_args(&mut self, args: &[String])
+            -> Result<Vec<String>, Box<dyn Error>> {
+        let mut positional = Vec::new();
+
+        let mut args = args.iter().peekable();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+                continue;
+            }
+
+            let key = &arg[2..];
+            let value = match args.peek() {
+                Some(value) if !value.starts_with("--") => {
+                    args.next().unwrap().as_str()
+                }
+                _ => "true",
+            };
+
+            if key == "config" {
+                self.load(Path::new(value))?;
+            } else {
+                self.set(key, value)?;
+            }
+        }
+
+        Ok(positional)
+    }
+}
+
+/// Scheduling priority of target processes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetPriority {
+    /// Normal priority, like any other program
+    Normal,
+
+    /// `BELOW_NORMAL_PRIORITY_CLASS`
+    BelowNormal,
+
+    /// `IDLE_PRIORITY_CLASS`, only scheduled when nothing else wants to run
+    Idle,
+
+    /// Idle priority with the lowest memory priority, approximating
+    /// `PROCESS_MODE_BACKGROUND_BEGIN` which a process can only enter
+    /// itself
+    Background,
+}
+
+impl std::str::FromStr for TargetPriority {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, ()> {
+        match name {
+            "normal"       => Ok(TargetPriority::Normal),
+            "below-normal" => Ok(TargetPriority::BelowNormal),
+            "idle"         => Ok(TargetPriority::Idle),
+            "background"   => Ok(TargetPriority::Background),
+            _              => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turn `args` into owned command line arguments
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn options_are_parsed_by_type() {
+        let mut config = Config::default();
+        config.set("max-targets", "4").unwrap();
+        config.set("novelty-threshold", "0.5").unwrap();
+        config.set("uia", "true").unwrap();
+        config.set("adaptive-pacing", "true").unwrap();
+        config.set("ab-config", "b.cfg").unwrap();
+        config.set("target-resource", "a.dll").unwrap();
+        config.set("target-resource", "a.dll.mui").unwrap();
+        config.set("schedule-tags", "window:Convert,menu:0x131").unwrap();
+
+        assert_eq!(config.max_targets, Some(4));
+        assert_eq!(config.novelty_threshold, Some(0.5));
+        assert!(config.uia);
+        assert!(config.adaptive_pacing);
+        assert_eq!(config.ab_config.as_deref(), Some("b.cfg"));
+        assert_eq!(config.target_resources, vec!["a.dll", "a.dll.mui"]);
+        assert_eq!(config.schedule_tags.as_deref(),
+            Some("window:Convert,menu:0x131"));
+
+        // Zero means no limit
+        config.set("max-cases", "0").unwrap();
+        assert_eq!(config.max_cases, None);
+        config.set("max-targets", "0").unwrap();
+        assert_eq!(config.max_targets, None);
+    }
+
+    #[test]
+    fn bad_options_name_the_key() {
+        let mut config = Config::default();
+        let err = config.set("max-targets", "lots").unwrap_err();
+        assert!(err.to_string().contains("\"max-targets\""), "{}", err);
+        let err = config.set("no-such-option", "1").unwrap_err();
+        assert!(err.to_string().contains("\"no-such-option\""), "{}", err);
+    }
+
+    #[test]
+    fn args_apply_in_order() {
+        let mut config = Config::default();
+        let positional = config.parse_args(&args(&["calc.exe", "--uia",
+            "--max-targets", "2", "--max-targets", "3", "corpus"])).unwrap();
+
+        assert_eq!(positional, vec!["calc.exe", "corpus"]);
+        assert!(config.uia);
+        assert_eq!(config.max_targets, Some(3));
+    }
+
+    #[test]
+    fn config_files_skip_comments() {
+        let path = std::env::temp_dir().join(format!("guifuzz_config_{}",
+            std::process::id()));
+        std::fs::write(&path, "# comment\n\n  max-targets = 5 \nuia=true\n")
+            .unwrap();
+
+        let mut config = Config::default();
+        config.parse_args(&args(&["--config", path.to_str().unwrap(),
+            "--max-targets", "6"])).unwrap();
+        assert_eq!(config.max_targets, Some(6));
+        assert!(config.uia);
+
+        std::fs::write(&path, "max-targets 5\n").unwrap();
+        assert!(Config::default().load(&path).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,30 @@
+//! Coordinates of actions, stored as fractions of the client area of the
+//! GUI element they target rather than in pixels. Inputs then mean the same
+//! on machines with a different DPI or window size, and corpora synced
+//! between machines need no conversion. Fractions are resolved to pixels
+//! on the local machine when the action is performed.
+
+use std::io;
+use crate::Window;
+
+/// A fraction `f` stands for `f / FRACTION_SCALE` of an extent
+pub const FRACTION_SCALE: i64 = 0x10000;
+
+/// Resolve `fraction` of `extent` pixels to a pixel offset, always inside
+/// the extent
+pub fn resolve(fraction: u16, extent: i32) -> i32 {
+    (fraction as i64 * extent.max(0) as i64 / FRACTION_SCALE) as i32
+}
+
+/// Resolve the fractions `x`, `y` of the client area of `window` to a point
+/// in its client area
+pub fn resolve_point(window: &Window, x: u16, y: u16)
+        -> io::Result<(i32, i32)> {
+    let (width, height) = window.client_size()?;
+    Ok((resolve(x, width), resolve(y, height)))
+}
+
+/// Fraction as a percentage, for printing
+pub fn percent(fraction: u16) -> f64 {
+    fraction as f64 * 100. / FRACTION_SCALE as f64
+}
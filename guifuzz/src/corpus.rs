@@ -0,0 +1,1005 @@
+//! Corpus, coverage, and crash databases making up the state of a campaign
+
+use std::io;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use std::collections::{HashMap, BTreeMap, BTreeSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use crate::{FuzzInput, FuzzerAction, costmodel};
+use crate::tags::Tags;
+use crate::pager::{Pager, Page};
+
+/// Small integer standing in for a module name, see `intern_module()`
+pub type ModuleId = u32;
+
+/// A coverage entry, a (module, offset) pair
+pub type CoverageKey = (ModuleId, usize);
+
+/// Interned module names and their IDs
+#[derive(Default)]
+struct ModuleNames {
+    /// Maps module names to their IDs
+    ids: HashMap<Arc<String>, ModuleId>,
+
+    /// Module names, indexed by ID
+    names: Vec<Arc<String>>,
+}
+
+/// Every module name interned so far. Coverage keys hold IDs rather than
+/// names so millions of them don't each carry, and hash, a string.
+static MODULE_NAMES: RwLock<Option<ModuleNames>> = RwLock::new(None);
+
+/// Get the ID of the module `name`, assigning it one if it has never been
+/// seen before
+pub fn intern_module(name: &Arc<String>) -> ModuleId {
+    if let Some(id) = MODULE_NAMES.read().unwrap().as_ref()
+            .and_then(|x| x.ids.get(name).cloned()) {
+        return id;
+    }
+
+    let mut modules = MODULE_NAMES.write().unwrap();
+    let modules = modules.get_or_insert_with(ModuleNames::default);
+    if let Some(&id) = modules.ids.get(name) {
+        return id;
+    }
+
+    let id = modules.names.len() as ModuleId;
+    modules.ids.insert(name.clone(), id);
+    modules.names.push(name.clone());
+    id
+}
+
+/// Get the name of the module interned as `id`
+pub fn module_name(id: ModuleId) -> Arc<String> {
+    MODULE_NAMES.read().unwrap().as_ref()
+        .and_then(|x| x.names.get(id as usize).cloned())
+        .expect("Module ID was never interned")
+}
+
+/// Stable index of every coverage entry seen by any worker. Indices are
+/// assigned on first sight and never change, so every `CoverageMap` can
+/// track what it has seen in a bitmap indexed the same way.
+static COVERAGE_INDICES: RwLock<Option<HashMap<CoverageKey, usize>>> =
+    RwLock::new(None);
+
+/// Get the stable index of the coverage entry `key`, assigning it one if it
+/// has never been seen before
+pub fn coverage_index(key: &CoverageKey) -> usize {
+    if let Some(idx) = find_coverage_index(key) {
+        return idx;
+    }
+
+    let mut indices = COVERAGE_INDICES.write().unwrap();
+    let indices = indices.get_or_insert_with(HashMap::new);
+    let next = indices.len();
+    *indices.entry(*key).or_insert(next)
+}
+
+/// Get the stable index of the coverage entry `key`, `None` if it has never
+/// been assigned one
+fn find_coverage_index(key: &CoverageKey) -> Option<usize> {
+    COVERAGE_INDICES.read().unwrap().as_ref()
+        .and_then(|x| x.get(key).cloned())
+}
+
+/// Where an input came from, persisted alongside the input for lineage
+/// analysis and strategy tuning
+#[derive(Clone, Debug, Default)]
+pub struct Provenance {
+    /// Input this was mutated from, `None` if it was generated from scratch
+    pub parent: Option<FuzzInput>,
+
+    /// Names of the mutation strategies applied to the parent, in order
+    pub mutations: Vec<&'static str>,
+
+    /// Seed of the RNG used to generate or mutate the input
+    pub seed: u64,
+
+    /// Unix time the input was created at
+    pub unix_time: u64,
+
+    /// Index of the worker which discovered the input
+    pub worker: Option<usize>,
+
+    /// How the target was launched, if it wasn't a plain spawn
+    pub launch: Option<String>,
+
+    /// Index of the action which missed its deadline and aborted the case
+    pub timed_out: Option<usize>,
+
+    /// Indices of the actions which found new coverage, if coverage was
+    /// attributed to actions
+    pub coverage_actions: Vec<usize>,
+}
+
+/// Hash of the actions of `input`, identifying it in the corpus and in
+/// coverage attribution without holding on to the input itself
+pub fn input_hash(input: &[FuzzerAction]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Maximum number of parents an input stored as a delta may have to be
+/// materialized through, bounding the cost of materializing it
+const MAX_DELTA_CHAIN: usize = 16;
+
+/// Where the actions of a corpus input are
+#[derive(Default)]
+struct Slot {
+    /// The stored actions, if they are in memory. These are all actions of
+    /// the input, or only the suffix following its parent's prefix if it is
+    /// stored as a delta.
+    resident: Option<FuzzInput>,
+
+    /// Where the stored actions are in the pager's backing file, if they
+    /// were ever paged out. Inputs never change, so they are only written
+    /// once.
+    page: Option<Page>,
+
+    /// If the input is stored as a delta, the index of its parent and the
+    /// number of leading actions shared with it
+    base: Option<(usize, usize)>,
+
+    /// Number of parents the input has to be materialized through
+    chain: usize,
+
+    /// Value of `Corpus::clock` when the input was last used
+    last_use: u64,
+}
+
+/// Deduplicated, insertion-ordered set of inputs
+///
+/// Inputs are compared by their action sequences, so the same sequence is
+/// only ever stored once. Inputs are never removed, thus the index of an
+/// input is stable for the lifetime of the corpus.
+///
+/// Inputs mutated from another corpus input are stored as a delta, only the
+/// actions following the prefix they share with their parent, and are
+/// materialized from their parent when used.
+///
+/// With a memory limit set, the least recently used inputs are paged out to
+/// disk once the inputs in memory exceed it, and paged back in on use.
+#[derive(Default)]
+pub struct Corpus {
+    /// Maps the hashes of all unique inputs to the indices of the inputs
+    /// with that hash
+    set: HashMap<u64, Vec<usize>>,
+
+    /// All unique inputs, in insertion order
+    list: Vec<Slot>,
+
+    /// Number of actions in each input, indexed the same as `list`
+    lens: Vec<usize>,
+
+    /// Wall-clock execution cost of each input, indexed the same as `list`
+    cost: Vec<Duration>,
+
+    /// Execution cost of each input estimated from its actions, indexed the
+    /// same as `list`
+    estimated_cost: Vec<Duration>,
+
+    /// Provenance of each input, indexed the same as `list`
+    provenance: Vec<Provenance>,
+
+    /// Genealogy of each input as (index of the root ancestor, depth below
+    /// the root), indexed the same as `list`
+    lineage: Vec<(usize, usize)>,
+
+    /// Stability of each input from calibration, the fraction of coverage
+    /// shared between two runs, or `None` if it hasn't been calibrated yet.
+    /// Indexed the same as `list`.
+    stability: Vec<Option<f64>>,
+
+    /// Tags of each input, indexed the same as `list`
+    tags: Vec<Tags>,
+
+    /// Size of the largest UI state diff each input caused, 0 if none was
+    /// captured. Indexed the same as `list`.
+    state_diff: Vec<usize>,
+
+    /// Indices of the inputs added, or whose cost, stability, tags, or UI
+    /// state diff changed, since `take_changed()` was last called
+    changed: BTreeSet<usize>,
+
+    /// Backing store of paged out inputs, `None` if all inputs are kept in
+    /// memory
+    pager: Option<Pager>,
+
+    /// Maximum number of bytes of inputs kept in memory with a pager
+    memory_limit: usize,
+
+    /// Number of bytes of stored actions currently in memory
+    resident_size: usize,
+
+    /// Inputs in memory by the time they were last used, as `last_use` to
+    /// index
+    lru: BTreeMap<u64, usize>,
+
+    /// Counter incremented on every use of an input, ordering uses
+    clock: u64,
+}
+
+impl Corpus {
+    /// Create a new, empty corpus
+    pub fn new() -> Self {
+        Corpus::default()
+    }
+
+    /// Keep at most `limit` bytes of inputs in memory, paging the rest out
+    /// to a backing file in `dir`
+    pub fn set_memory_limit(&mut self, limit: usize, dir: &Path)
+            -> io::Result<()> {
+        self.pager = Some(Pager::new(dir)?);
+        self.memory_limit = limit;
+        self.evict();
+        Ok(())
+    }
+
+    /// Number of bytes of memory the actions of an input `len` actions long
+    /// take up
+    fn input_size(len: usize) -> usize {
+        len * std::mem::size_of::<FuzzerAction>()
+    }
+
+    /// Mark the input at `idx`, which is in memory, as just used
+    fn touch(&mut self, idx: usize) {
+        let slot = &mut self.list[idx];
+        self.lru.remove(&slot.last_use);
+        self.clock += 1;
+        slot.last_use = self.clock;
+        self.lru.insert(self.clock, idx);
+    }
+
+    /// Page out the least recently used inputs until the inputs in memory
+    /// fit in the memory limit. Does nothing without a pager.
+    fn evict(&mut self) {
+        let pager = match self.pager.as_mut() {
+            Some(pager) => pager,
+            None => return,
+        };
+
+        // Inputs we failed to write, kept in memory rather than losing them
+        let mut kept = Vec::new();
+        while self.resident_size > self.memory_limit {
+            let idx = match self.lru.keys().next().cloned() {
+                Some(last_use) => self.lru.remove(&last_use).unwrap(),
+                None => break,
+            };
+
+            let slot  = &mut self.list[idx];
+            let input = slot.resident.take().unwrap();
+            if slot.page.is_none() {
+                match pager.page_out(&input) {
+                    Ok(page) => slot.page = Some(page),
+                    Err(_) => {
+                        slot.resident = Some(input);
+                        kept.push((slot.last_use, idx));
+                        continue;
+                    }
+                }
+            }
+            self.resident_size -= Self::input_size(input.len());
+        }
+        self.lru.extend(kept);
+    }
+
+    /// Insert `input` which took `cost` to execute and came from
+    /// `provenance`. Returns `true` if the input was new. If the input was
+    /// already present nothing is updated, including its cost.
+    ///
+    /// The parent is not kept in the stored provenance, as it would keep the
+    /// parent's actions in memory, it is reflected by `root()` and
+    /// `depth()` instead.
+    pub fn insert(&mut self, input: &FuzzInput, cost: Duration,
+                  mut provenance: Provenance) -> bool {
+        if self.contains(input) {
+            return false;
+        }
+
+        let idx = self.list.len();
+        let parent = provenance.parent.take().and_then(|parent| {
+            self.index_of(&parent).map(|idx| (idx, parent))
+        });
+
+        // Inputs descend from their parent's root, or are their own root if
+        // their parent isn't in the corpus
+        let lineage = parent.as_ref()
+            .map(|&(parent, _)| {
+                let (root, depth) = self.lineage[parent];
+                (root, depth + 1)
+            })
+            .unwrap_or((idx, 0));
+
+        // Inputs sharing a prefix with their parent only store what follows
+        // it, unless materializing them would go through too many parents
+        let mut slot = Slot::default();
+        let shared = parent.filter(|&(parent, _)| {
+            self.list[parent].chain < MAX_DELTA_CHAIN
+        }).map(|(parent, actions)| {
+            (parent, input.iter().zip(actions.iter())
+                .take_while(|(a, b)| a == b).count())
+        }).filter(|&(_, prefix)| prefix > 0);
+        let stored = match shared {
+            Some((parent, prefix)) => {
+                slot.base  = Some((parent, prefix));
+                slot.chain = self.list[parent].chain + 1;
+                Arc::new(input[prefix..].to_vec())
+            }
+            None => input.clone(),
+        };
+        self.resident_size += Self::input_size(stored.len());
+        slot.resident = Some(stored);
+
+        self.set.entry(input_hash(input)).or_default().push(idx);
+        self.list.push(slot);
+        self.lens.push(input.len());
+        self.cost.push(cost);
+        self.estimated_cost.push(costmodel::estimate(input));
+        self.provenance.push(provenance);
+        self.lineage.push(lineage);
+        self.stability.push(None);
+        self.tags.push(Tags::new());
+        self.state_diff.push(0);
+        self.changed.insert(idx);
+
+        self.touch(idx);
+        self.evict();
+        true
+    }
+
+    /// Returns `true` if `input` is in the corpus
+    pub fn contains(&mut self, input: &FuzzInput) -> bool {
+        self.index_of(input).is_some()
+    }
+
+    /// Get the index of `input` in the corpus
+    pub fn index_of(&mut self, input: &FuzzInput) -> Option<usize> {
+        let candidates = self.set.get(&input_hash(input))?.clone();
+        candidates.into_iter().find(|&idx| {
+            self.get(idx).map(|x| x == *input).unwrap_or(false)
+        })
+    }
+
+    /// Number of inputs in the corpus
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns `true` if the corpus has no inputs
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Get the input at `idx`, paging it in if it was paged out and
+    /// materializing it if it is stored as a delta. Returns `None` if there
+    /// is no such input or it failed to be paged in.
+    pub fn get(&mut self, idx: usize) -> Option<FuzzInput> {
+        self.load(idx).ok()
+    }
+
+    /// Get the input at `idx`, like `get()`, returning an error if there is
+    /// no such input or it failed to be paged in
+    pub fn load(&mut self, idx: usize) -> io::Result<FuzzInput> {
+        let stored = self.stored(idx)?;
+        let (parent, prefix) = match self.list[idx].base {
+            Some(base) => base,
+            None => return Ok(stored),
+        };
+
+        let parent = self.load(parent)?;
+        let mut input = Vec::with_capacity(self.lens[idx]);
+        input.extend_from_slice(&parent[..prefix]);
+        input.extend_from_slice(&stored);
+        Ok(Arc::new(input))
+    }
+
+    /// Get the stored actions of the input at `idx`, paging them in if they
+    /// were paged out
+    fn stored(&mut self, idx: usize) -> io::Result<FuzzInput> {
+        let slot = self.list.get_mut(idx).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound,
+                format!("No corpus input {}", idx))
+        })?;
+        let input = match &slot.resident {
+            Some(input) => input.clone(),
+            None => {
+                let page = slot.page.zip(self.pager.as_mut());
+                let (page, pager) = page.ok_or_else(|| io::Error::other(
+                    format!("Corpus input {} was never paged out", idx)))?;
+                let input = Arc::new(pager.page_in(page).map_err(|err| {
+                    io::Error::new(err.kind(), format!("Failed to page in \
+                        corpus input {}: {}", idx, err))
+                })?);
+                slot.resident = Some(input.clone());
+                self.resident_size += Self::input_size(input.len());
+                input
+            }
+        };
+
+        self.touch(idx);
+        self.evict();
+        Ok(input)
+    }
+
+    /// Get the number of actions in the input at `idx`, without paging it in
+    pub fn input_len(&self, idx: usize) -> Option<usize> {
+        self.lens.get(idx).cloned()
+    }
+
+    /// Get the execution cost of the input at `idx`
+    pub fn cost(&self, idx: usize) -> Option<Duration> {
+        self.cost.get(idx).cloned()
+    }
+
+    /// Get the execution cost of the input at `idx` estimated from its
+    /// actions
+    pub fn estimated_cost(&self, idx: usize) -> Option<Duration> {
+        self.estimated_cost.get(idx).cloned()
+    }
+
+    /// Get the best known execution cost of the input at `idx`, the measured
+    /// cost if it was timed and the estimated cost otherwise
+    pub fn expected_cost(&self, idx: usize) -> Option<Duration> {
+        self.cost(idx).filter(|x| *x > Duration::from_secs(0))
+            .or_else(|| self.estimated_cost(idx))
+    }
+
+    /// Mean estimated execution cost of all inputs, `None` if the corpus is
+    /// empty
+    pub fn mean_estimated_cost(&self) -> Option<Duration> {
+        if self.estimated_cost.is_empty() {
+            return None;
+        }
+        Some(self.estimated_cost.iter().sum::<Duration>() /
+            self.estimated_cost.len() as u32)
+    }
+
+    /// Get the provenance of the input at `idx`
+    pub fn provenance(&self, idx: usize) -> Option<&Provenance> {
+        self.provenance.get(idx)
+    }
+
+    /// Get the index of the root ancestor of the input at `idx`, the first
+    /// input in its lineage which was not mutated from another corpus input
+    pub fn root(&self, idx: usize) -> Option<usize> {
+        self.lineage.get(idx).map(|x| x.0)
+    }
+
+    /// Get the number of generations between the input at `idx` and its
+    /// root ancestor
+    pub fn depth(&self, idx: usize) -> Option<usize> {
+        self.lineage.get(idx).map(|x| x.1)
+    }
+
+    /// Get the stability of the input at `idx`, if it has been calibrated
+    pub fn stability(&self, idx: usize) -> Option<f64> {
+        self.stability.get(idx).cloned().flatten()
+    }
+
+    /// Record the stability of the input at `idx` measured by calibration
+    pub fn set_stability(&mut self, idx: usize, stability: f64) {
+        if let Some(x) = self.stability.get_mut(idx) {
+            *x = Some(stability);
+            self.changed.insert(idx);
+        }
+    }
+
+    /// Get the tags of the input at `idx`
+    pub fn tags(&self, idx: usize) -> Option<&Tags> {
+        self.tags.get(idx)
+    }
+
+    /// Add `tags` to the input at `idx`
+    pub fn add_tags(&mut self, idx: usize, tags: &Tags) {
+        if let Some(x) = self.tags.get_mut(idx) {
+            x.extend(tags.iter().cloned());
+            self.changed.insert(idx);
+        }
+    }
+
+    /// Get the size of the largest UI state diff the input at `idx` caused
+    pub fn state_diff(&self, idx: usize) -> Option<usize> {
+        self.state_diff.get(idx).cloned()
+    }
+
+    /// Record that the input at `idx` caused a UI state diff of `size`,
+    /// keeping the largest size recorded
+    pub fn set_state_diff(&mut self, idx: usize, size: usize) {
+        if let Some(x) = self.state_diff.get_mut(idx) {
+            *x = (*x).max(size);
+            self.changed.insert(idx);
+        }
+    }
+
+    /// Take the indices of the inputs added, or whose cost, stability, tags,
+    /// or UI state diff changed, since this was last called, so selection
+    /// weights derived from them can be updated
+    pub fn take_changed(&mut self) -> BTreeSet<usize> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Get the index of the oldest input which hasn't been calibrated
+    pub fn uncalibrated(&self) -> Option<usize> {
+        self.stability.iter().position(|x| x.is_none())
+    }
+
+    /// Mean stability of all calibrated inputs, `None` if none have been
+    /// calibrated
+    pub fn mean_stability(&self) -> Option<f64> {
+        let calibrated: Vec<f64> = self.stability.iter()
+            .filter_map(|&x| x).collect();
+        if calibrated.is_empty() {
+            return None;
+        }
+        Some(calibrated.iter().sum::<f64>() / calibrated.len() as f64)
+    }
+
+    /// Iterate over all inputs in insertion order, paging them in as
+    /// needed. Inputs which fail to be paged in are skipped.
+    pub fn iter(&mut self) -> impl Iterator<Item = FuzzInput> + '_ {
+        (0..self.len()).filter_map(move |idx| self.get(idx))
+    }
+
+    /// Check that the corpus is consistent: every input can be
+    /// materialized, has the recorded length, is only stored once, and
+    /// only deltas against an earlier input. Returns what is wrong
+    /// otherwise.
+    pub fn check(&mut self) -> Result<(), String> {
+        let len = self.list.len();
+        let lens = [self.lens.len(), self.cost.len(),
+            self.estimated_cost.len(), self.provenance.len(),
+            self.lineage.len(), self.stability.len(), self.tags.len(),
+            self.state_diff.len()];
+        if lens.iter().any(|&x| x != len) {
+            return Err(format!("{} inputs with per-input data of lengths \
+                {:?}", len, lens));
+        }
+
+        let indexed: usize = self.set.values().map(|x| x.len()).sum();
+        if indexed != len {
+            return Err(format!("{} inputs but {} indexed by hash", len,
+                indexed));
+        }
+
+        for idx in 0..len {
+            if let Some((parent, _)) = self.list[idx].base {
+                if parent >= idx {
+                    return Err(format!("Input {} deltas against later \
+                        input {}", idx, parent));
+                }
+            }
+
+            let input = self.get(idx)
+                .ok_or_else(|| format!("Input {} can't be loaded", idx))?;
+            if input.len() != self.lens[idx] {
+                return Err(format!("Input {} has {} actions, recorded as \
+                    {}", idx, input.len(), self.lens[idx]));
+            }
+            if self.index_of(&input) != Some(idx) {
+                return Err(format!("Input {} isn't found by its actions",
+                    idx));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Map of all coverage observed, attributing each entry to the input which
+/// first reached it
+#[derive(Default)]
+pub struct CoverageMap {
+    /// Bitmap of the coverage observed, indexed by `coverage_index()`
+    seen: Vec<u64>,
+
+    /// Maps (module, offset) to the hash of the input which first reached
+    /// it, from `input_hash()`. Only touched when coverage is new, lookups
+    /// go through `seen`.
+    map: HashMap<CoverageKey, u64>,
+}
+
+impl CoverageMap {
+    /// Create a new, empty coverage map
+    pub fn new() -> Self {
+        CoverageMap::default()
+    }
+
+    /// Record that `input` reached `key`. Returns `true` if the coverage was
+    /// new. Coverage is only ever attributed to the first input to reach it.
+    pub fn insert(&mut self, key: CoverageKey, input: &FuzzInput) -> bool {
+        let idx = coverage_index(&key);
+        self.insert_index(idx, key, input)
+    }
+
+    /// Same as `insert()`, for a `key` whose index from `coverage_index()`
+    /// is already known to be `idx`
+    pub fn insert_index(&mut self, idx: usize, key: CoverageKey,
+            input: &FuzzInput) -> bool {
+        if self.contains_index(idx) {
+            return false;
+        }
+
+        let word = idx / 64;
+        if word >= self.seen.len() {
+            self.seen.resize(word + 1, 0);
+        }
+        self.seen[word] |= 1 << (idx % 64);
+
+        self.map.insert(key, input_hash(input));
+        true
+    }
+
+    /// Returns `true` if `key` has been observed
+    pub fn contains(&self, key: &CoverageKey) -> bool {
+        find_coverage_index(key).map(|idx| self.contains_index(idx))
+            .unwrap_or(false)
+    }
+
+    /// Returns `true` if the coverage entry with the index `idx` from
+    /// `coverage_index()` has been observed
+    pub fn contains_index(&self, idx: usize) -> bool {
+        self.seen.get(idx / 64).map(|x| x & (1 << (idx % 64)) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Get the hash of the input which first reached `key`, from
+    /// `input_hash()`
+    pub fn get(&self, key: &CoverageKey) -> Option<u64> {
+        self.map.get(key).cloned()
+    }
+
+    /// Number of unique coverage entries
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no coverage has been observed
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterate over all coverage entries and the hashes of their attributed
+    /// inputs, in no particular order
+    pub fn iter(&self)
+            -> std::collections::hash_map::Iter<'_, CoverageKey, u64> {
+        self.map.iter()
+    }
+}
+
+/// Machine state captured by the debugger when the target crashed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrashInfo {
+    /// Exception code, such as `0xc0000005` for an access violation
+    pub exception_code: u32,
+
+    /// Address of the faulting instruction
+    pub pc: u64,
+
+    /// For access violations, the type of access (0 read, 1 write, 8
+    /// execute) and the address accessed
+    pub access: Option<(u8, u64)>,
+
+    /// General purpose registers of the crashing thread, as (name, value)
+    pub registers: Vec<(String, u64)>,
+
+    /// First bytes of code at the faulting instruction
+    pub code_bytes: Vec<u8>,
+}
+
+impl CrashInfo {
+    /// Name of the type of access of an access violation, `None` for other
+    /// exceptions
+    pub fn access_kind(&self) -> Option<&'static str> {
+        self.access.map(|(kind, _)| match kind {
+            0 => "read",
+            1 => "write",
+            8 => "execute",
+            _ => "unknown",
+        })
+    }
+
+    /// Whether this is an access violation within the first 64 KiB, which is
+    /// typically a NULL pointer dereference
+    pub fn is_near_null(&self) -> bool {
+        self.access.map(|(_, addr)| addr < 0x10000).unwrap_or(false)
+    }
+
+    /// Value of the register `name`
+    pub fn register(&self, name: &str) -> Option<u64> {
+        self.registers.iter().find(|(x, _)| x == name).map(|x| x.1)
+    }
+}
+
+impl fmt::Display for CrashInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "exception {:#010x} at {:#x}", self.exception_code,
+            self.pc)?;
+        if let (Some(kind), Some((_, addr))) = (self.access_kind(),
+                self.access) {
+            writeln!(f, "{} of {:#x}", kind, addr)?;
+        }
+        for (name, value) in &self.registers {
+            writeln!(f, "{:>6} = {:#018x}", name, value)?;
+        }
+        write!(f, "code:")?;
+        for byte in &self.code_bytes {
+            write!(f, " {:02x}", byte)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// A set of crashes which share the same crash name
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrashBucket {
+    /// Name of the crash, as reported by the debugger
+    pub name: String,
+
+    /// Machine state of the first crash in this bucket, if it was a crash
+    /// caught by the debugger
+    pub info: Option<CrashInfo>,
+
+    /// Shortest inputs which produced this crash, sorted by length
+    pub inputs: Vec<FuzzInput>,
+
+    /// Number of times this crash has been observed
+    pub hits: u64,
+}
+
+impl CrashBucket {
+    /// Get the shortest known input which produces this crash
+    pub fn reproducer(&self) -> &FuzzInput {
+        &self.inputs[0]
+    }
+}
+
+/// Result of inserting a crash into a `CrashDb`
+#[derive(Clone, Debug, Default)]
+pub struct CrashInsert {
+    /// The crash created a new bucket
+    pub new_bucket: bool,
+
+    /// The input was kept as one of the bucket's reproducers
+    pub kept: bool,
+
+    /// A previous reproducer which was evicted to make room for the input
+    pub evicted: Option<FuzzInput>,
+}
+
+/// Database of crash buckets keyed by crash name
+#[derive(Default)]
+pub struct CrashDb {
+    /// Maps crash names to their buckets
+    buckets: HashMap<String, CrashBucket>,
+}
+
+impl CrashDb {
+    /// Create a new, empty crash database
+    pub fn new() -> Self {
+        CrashDb::default()
+    }
+
+    /// Record a crash named `name` caused by `input`, with the machine state
+    /// `info` if it was captured. Every crash increments the hit count of
+    /// its bucket, but only the `max_inputs` shortest distinct inputs are
+    /// kept as reproducers. Ties keep the older input.
+    pub fn insert(&mut self, name: &str, input: &FuzzInput,
+            info: Option<&CrashInfo>, max_inputs: usize) -> CrashInsert {
+        let mut ret = CrashInsert::default();
+
+        let bucket = self.buckets.entry(name.to_string()).or_insert_with(|| {
+            ret.new_bucket = true;
+            CrashBucket {
+                name:   name.to_string(),
+                info:   None,
+                inputs: Vec::new(),
+                hits:   0,
+            }
+        });
+        bucket.hits += 1;
+        if bucket.info.is_none() {
+            bucket.info = info.cloned();
+        }
+
+        // Buckets always keep at least one reproducer
+        let max_inputs = max_inputs.max(1);
+
+        if bucket.inputs.contains(input) {
+            return ret;
+        }
+
+        // Insert the input sorted by length, after inputs of the same length
+        let pos = bucket.inputs.iter()
+            .position(|x| x.len() > input.len())
+            .unwrap_or(bucket.inputs.len());
+        if pos >= max_inputs {
+            return ret;
+        }
+
+        bucket.inputs.insert(pos, input.clone());
+        ret.kept = true;
+
+        if bucket.inputs.len() > max_inputs {
+            ret.evicted = bucket.inputs.pop();
+        }
+
+        ret
+    }
+
+    /// Add `bucket`, such as one saved by an earlier run, merging it into
+    /// the bucket with the same name if there is one. Buckets without
+    /// reproducers are ignored.
+    pub fn insert_bucket(&mut self, bucket: CrashBucket) {
+        if bucket.inputs.is_empty() {
+            return;
+        }
+
+        let merged = match self.buckets.get_mut(&bucket.name) {
+            Some(merged) => merged,
+            None => {
+                self.buckets.insert(bucket.name.clone(), bucket);
+                return;
+            }
+        };
+
+        merged.hits += bucket.hits;
+        if merged.info.is_none() {
+            merged.info = bucket.info;
+        }
+        for input in bucket.inputs {
+            if !merged.inputs.contains(&input) {
+                merged.inputs.push(input);
+            }
+        }
+        merged.inputs.sort_by_key(|x| x.len());
+    }
+
+    /// Get the bucket for the crash named `name`
+    pub fn get(&self, name: &str) -> Option<&CrashBucket> {
+        self.buckets.get(name)
+    }
+
+    /// Number of unique crash buckets
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns `true` if no crashes have been observed
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Iterate over all crash buckets, in no particular order
+    pub fn iter(&self)
+            -> std::collections::hash_map::Values<'_, String, CrashBucket> {
+        self.buckets.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ElementIdx;
+
+    /// An input of clicks on the elements `elements`
+    fn clicks(elements: &[usize]) -> FuzzInput {
+        Arc::new(elements.iter().map(|&idx| {
+            FuzzerAction::LeftClick { idx: ElementIdx(idx) }
+        }).collect())
+    }
+
+    /// Provenance of an input mutated from `parent`
+    fn child_of(parent: &FuzzInput) -> Provenance {
+        Provenance { parent: Some(parent.clone()), ..Provenance::default() }
+    }
+
+    #[test]
+    fn children_are_stored_as_deltas() {
+        let mut corpus = Corpus::new();
+        let parent = clicks(&[1, 2, 3, 4]);
+        let child  = clicks(&[1, 2, 9]);
+        assert!(corpus.insert(&parent, Duration::from_millis(1),
+            Provenance::default()));
+        assert!(corpus.insert(&child, Duration::from_millis(1),
+            child_of(&parent)));
+
+        assert_eq!(corpus.list[1].base, Some((0, 2)));
+        assert_eq!(corpus.list[1].resident.as_ref().unwrap().len(), 1);
+        assert_eq!(corpus.get(1), Some(child.clone()));
+        assert_eq!(corpus.index_of(&child), Some(1));
+        assert_eq!((corpus.root(1), corpus.depth(1)), (Some(0), Some(1)));
+
+        // The same actions are only stored once
+        assert!(!corpus.insert(&child, Duration::from_millis(1),
+            Provenance::default()));
+        corpus.check().unwrap();
+    }
+
+    #[test]
+    fn unrelated_children_are_stored_whole() {
+        let mut corpus = Corpus::new();
+        let parent = clicks(&[1, 2]);
+        let child  = clicks(&[3, 4]);
+        corpus.insert(&parent, Duration::from_millis(1),
+            Provenance::default());
+        corpus.insert(&child, Duration::from_millis(1), child_of(&parent));
+
+        assert_eq!(corpus.list[1].base, None);
+        assert_eq!(corpus.get(1), Some(child));
+        corpus.check().unwrap();
+    }
+
+    #[test]
+    fn delta_chains_are_bounded() {
+        let mut corpus = Corpus::new();
+        let mut parent = clicks(&[0]);
+        corpus.insert(&parent, Duration::from_millis(1),
+            Provenance::default());
+        for len in 2..MAX_DELTA_CHAIN * 3 {
+            let child = clicks(&(0..len).collect::<Vec<usize>>());
+            corpus.insert(&child, Duration::from_millis(1),
+                child_of(&parent));
+            parent = child;
+        }
+
+        assert!(corpus.list.iter().all(|x| x.chain <= MAX_DELTA_CHAIN));
+        assert_eq!(corpus.get(corpus.len() - 1), Some(parent));
+        corpus.check().unwrap();
+    }
+
+    #[test]
+    fn deltas_survive_paging() {
+        let dir = std::env::temp_dir().join("guifuzz_corpus_test");
+        let mut corpus = Corpus::new();
+        corpus.set_memory_limit(0, &dir).unwrap();
+
+        let mut inputs = vec![clicks(&[1, 2, 3])];
+        corpus.insert(&inputs[0], Duration::from_millis(1),
+            Provenance::default());
+        for idx in 0..32 {
+            let parent = inputs[idx / 2].clone();
+            let mut child = parent[..parent.len() / 2].to_vec();
+            child.push(FuzzerAction::LeftClick { idx: ElementIdx(100 + idx) });
+            let child: FuzzInput = Arc::new(child);
+            corpus.insert(&child, Duration::from_millis(1),
+                child_of(&parent));
+            inputs.push(child);
+        }
+
+        // Everything was paged out, and comes back the same
+        assert_eq!(corpus.resident_size, 0);
+        for (idx, input) in inputs.iter().enumerate() {
+            assert_eq!(corpus.get(idx).as_ref(), Some(input));
+        }
+        corpus.check().unwrap();
+    }
+
+    #[test]
+    fn unpageable_inputs_stay_in_memory() {
+        let dir = std::env::temp_dir().join("guifuzz_corpus_test");
+        let mut corpus = Corpus::new();
+        corpus.set_memory_limit(0, &dir).unwrap();
+
+        // Element indices past 32 bits can't be encoded in a page
+        let inputs = [clicks(&[usize::MAX]), clicks(&[1]), clicks(&[2])];
+        for input in &inputs {
+            corpus.insert(input, Duration::from_millis(1),
+                Provenance::default());
+        }
+
+        // Only the input which couldn't be paged out is left in memory, and
+        // it didn't keep the others from being paged out
+        assert_eq!(corpus.resident_size, Corpus::input_size(1));
+        for (idx, input) in inputs.iter().enumerate() {
+            assert_eq!(&corpus.load(idx).unwrap(), input);
+        }
+        assert_eq!(corpus.load(inputs.len()).unwrap_err().kind(),
+            io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,64 @@
+//! Estimation of how long an input takes to execute from the actions it is
+//! made of, so the scheduler can account for cost before an input's
+//! execution was ever timed
+
+use std::time::Duration;
+use crate::{FuzzerAction, MAX_MESSAGE_PRESSURE};
+use crate::dde::MAX_DDE_LEN;
+use crate::winbindings::{MAX_HOVER_MS, MAX_KEY_REPEATS, KEY_REPEAT_INTERVAL};
+
+/// Estimated time to perform `action`. Dominated by sleeps and round trips
+/// to the target, posted messages are nearly free.
+pub fn action_cost(action: &FuzzerAction) -> Duration {
+    let ms = Duration::from_millis;
+    let us = Duration::from_micros;
+
+    match action {
+        // Menu clicks sleep so the menu's command can run
+        FuzzerAction::MenuAction { .. } => ms(250),
+        FuzzerAction::LeftClick { .. } => ms(5),
+        FuzzerAction::ModifiedClick { .. } => ms(5),
+        // Hovers wait out their duration
+        FuzzerAction::Hover { duration_ms, .. } => {
+            ms((*duration_ms % (MAX_HOVER_MS + 1)) as u64)
+        }
+        // Held keys wait between their repeats
+        FuzzerAction::KeyRepeat { repeats, .. } => {
+            KEY_REPEAT_INTERVAL * (*repeats % (MAX_KEY_REPEATS + 1)) as u32
+        }
+        FuzzerAction::Close => us(100),
+        FuzzerAction::KeyPress { focus, .. } => {
+            if focus.is_some() { ms(3) } else { us(200) }
+        }
+        FuzzerAction::DialogCommand { .. } => ms(10),
+        FuzzerAction::MessagePressure { count, .. } => {
+            us(20) * (*count).min(MAX_MESSAGE_PRESSURE)
+        }
+        FuzzerAction::DisplayEvent { .. } => ms(5),
+        FuzzerAction::SessionEvent { .. } => ms(5),
+        FuzzerAction::Accessibility { .. } => ms(20),
+        FuzzerAction::OleDrop { .. } => ms(50),
+        FuzzerAction::Place { .. } => ms(10),
+        FuzzerAction::KeyboardLayout { .. } => ms(20),
+        FuzzerAction::UiaPattern { .. } => ms(50),
+        FuzzerAction::PluginMacro { .. } => ms(50),
+        FuzzerAction::Broadcast { .. } => ms(10),
+        FuzzerAction::Mdi { .. } => ms(10),
+        // Finding property sheets queries every dialog of the target
+        FuzzerAction::PropertySheet { .. } => ms(30),
+        FuzzerAction::SwitchTab { .. } => ms(10),
+        FuzzerAction::SetRange { .. } => ms(10),
+        FuzzerAction::Activation { .. } => ms(10),
+        FuzzerAction::CopyData { .. } => ms(20),
+        FuzzerAction::CustomMessage { .. } => us(200),
+        // Conversations are started with every window of the target
+        FuzzerAction::Dde { len, .. } => {
+            ms(100) + us(10) * (*len as usize % (MAX_DDE_LEN + 1)) as u32
+        }
+    }
+}
+
+/// Estimated time to perform all of `input`
+pub fn estimate(input: &[FuzzerAction]) -> Duration {
+    input.iter().map(action_cost).sum()
+}
@@ -0,0 +1,285 @@
+//! Dynamic Data Exchange fuzzing. A conversation is started with the
+//! target's DDE servers with a `WM_DDE_INITIATE` handshake from a hidden
+//! client window, then fuzzed `WM_DDE_EXECUTE` commands and `WM_DDE_POKE`
+//! data are sent to one of them.
+
+use std::io;
+use std::cell::RefCell;
+use std::sync::Once;
+use crate::{Rng, Window, audit};
+use crate::winbindings::str_to_utf16;
+
+#[link(name="User32")]
+extern "system" {
+    fn RegisterClassExW(class: *const WndClassEx) -> u16;
+    fn CreateWindowExW(ex_style: u32, class: *const u16, name: *const u16,
+        style: u32, x: i32, y: i32, width: i32, height: i32, parent: usize,
+        menu: usize, instance: usize, param: usize) -> usize;
+    fn DestroyWindow(hwnd: usize) -> i32;
+    fn DefWindowProcW(hwnd: usize, msg: u32, wparam: usize, lparam: isize)
+        -> isize;
+    fn SendMessageTimeoutW(hwnd: usize, msg: u32, wparam: usize,
+        lparam: usize, flags: u32, timeout: u32, result: *mut usize) -> usize;
+    fn PostMessageW(hwnd: usize, msg: u32, wparam: usize, lparam: usize)
+        -> bool;
+    fn PackDDElParam(msg: u32, low: usize, high: usize) -> usize;
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetModuleHandleW(name: *const u16) -> usize;
+    fn GlobalAddAtomW(name: *const u16) -> u16;
+    fn GlobalAlloc(flags: u32, size: usize) -> usize;
+    fn GlobalLock(mem: usize) -> *mut u8;
+    fn GlobalUnlock(mem: usize) -> i32;
+}
+
+/// DDE messages
+const WM_DDE_INITIATE:  u32 = 0x03e0;
+const WM_DDE_TERMINATE: u32 = 0x03e1;
+const WM_DDE_ACK:       u32 = 0x03e4;
+const WM_DDE_POKE:      u32 = 0x03e7;
+const WM_DDE_EXECUTE:   u32 = 0x03e8;
+
+/// `GMEM_MOVEABLE | GMEM_DDESHARE`, memory handed to DDE servers
+const GMEM_DDE: u32 = 0x0002 | 0x2000;
+
+/// `HWND_MESSAGE`, parent of message-only windows
+const HWND_MESSAGE: usize = !2;
+
+/// `SMTO_ABORTIFHUNG`, don't wait on a target which is already hung
+const SMTO_ABORTIFHUNG: u32 = 0x0002;
+
+/// Timeout of the `WM_DDE_INITIATE` handshake with each window
+const INITIATE_TIMEOUT_MS: u32 = 500;
+
+/// `fRelease` flag of `DDEPOKE`, the server frees the data
+const DDE_RELEASE: u16 = 0x2000;
+
+/// `CF_TEXT` and `CF_UNICODETEXT`
+const POKE_FORMATS: &[u16] = &[1, 13];
+
+/// Class name of the DDE client window
+const CLIENT_CLASS: &str = "guifuzz_dde_client";
+
+/// Execute commands in the common `[command(args)]` syntax, the arguments
+/// are fuzzed
+const COMMANDS: &[&str] = &[
+    "Open", "FileOpen", "Print", "PrintTo", "New", "Close", "Run",
+    "ShowWindow", "AddItem", "CreateGroup",
+];
+
+/// Item names used for pokes
+const ITEMS: &[&str] = &["Item", "Data", "Value", "Text", "R1C1", "Topic"];
+
+/// Maximum length of an execute command or poke payload
+pub const MAX_DDE_LEN: usize = 2048;
+
+thread_local! {
+    /// Servers which acknowledged a `WM_DDE_INITIATE` from this thread
+    static ACKS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// `WNDCLASSEXW`
+#[repr(C)]
+struct WndClassEx {
+    size:       u32,
+    style:      u32,
+    wnd_proc:   unsafe extern "system" fn(usize, u32, usize, isize) -> isize,
+    cls_extra:  i32,
+    wnd_extra:  i32,
+    instance:   usize,
+    icon:       usize,
+    cursor:     usize,
+    background: usize,
+    menu_name:  *const u16,
+    class_name: *const u16,
+    icon_small: usize,
+}
+
+/// DDE messages sent to a server
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DdeCommand {
+    /// `WM_DDE_EXECUTE` a fuzzed command string
+    Execute,
+
+    /// `WM_DDE_POKE` fuzzed data to an item
+    Poke,
+}
+
+/// Window procedure of the client window, recording acknowledgements
+unsafe extern "system" fn client_proc(hwnd: usize, msg: u32, wparam: usize,
+        lparam: isize) -> isize {
+    if msg == WM_DDE_ACK {
+        ACKS.with(|acks| acks.borrow_mut().push(wparam));
+        return 0;
+    }
+    DefWindowProcW(hwnd, msg, wparam, lparam)
+}
+
+/// Hidden message-only window on the DDE client side of conversations,
+/// destroyed on drop
+struct Client {
+    hwnd: usize,
+}
+
+impl Client {
+    /// Create a new client window
+    fn new() -> io::Result<Self> {
+        static REGISTER: Once = Once::new();
+
+        let class = str_to_utf16(CLIENT_CLASS);
+        unsafe {
+            let instance = GetModuleHandleW(std::ptr::null());
+
+            REGISTER.call_once(|| {
+                let wndclass = WndClassEx {
+                    size:       std::mem::size_of::<WndClassEx>() as u32,
+                    style:      0,
+                    wnd_proc:   client_proc,
+                    cls_extra:  0,
+                    wnd_extra:  0,
+                    instance,
+                    icon:       0,
+                    cursor:     0,
+                    background: 0,
+                    menu_name:  std::ptr::null(),
+                    class_name: class.as_ptr(),
+                    icon_small: 0,
+                };
+                RegisterClassExW(&wndclass);
+            });
+
+            let hwnd = CreateWindowExW(0, class.as_ptr(), std::ptr::null(),
+                0, 0, 0, 0, 0, HWND_MESSAGE, 0, instance, 0);
+            if hwnd == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Client { hwnd })
+        }
+    }
+
+    /// Start conversations with any application and topic with all
+    /// top-level windows of `pid`, returning the servers which answered
+    fn initiate(&self, pid: u32) -> io::Result<Vec<usize>> {
+        ACKS.with(|acks| acks.borrow_mut().clear());
+
+        // Acknowledgements are sent back during the send, so they arrive
+        // before it returns
+        for window in Window::enumerate_pid_windows(pid)?.iter() {
+            let mut result = 0;
+            audit::message("send", window.hwnd(), WM_DDE_INITIATE, self.hwnd,
+                0);
+            unsafe {
+                SendMessageTimeoutW(window.hwnd(), WM_DDE_INITIATE,
+                    self.hwnd, 0, SMTO_ABORTIFHUNG, INITIATE_TIMEOUT_MS,
+                    &mut result);
+            }
+        }
+
+        Ok(ACKS.with(|acks| acks.borrow_mut().split_off(0)))
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        unsafe { DestroyWindow(self.hwnd); }
+    }
+}
+
+/// Copy `data` into newly allocated DDE shared memory
+fn global_bytes(data: &[u8]) -> io::Result<usize> {
+    unsafe {
+        let mem = GlobalAlloc(GMEM_DDE, data.len().max(1));
+        if mem == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = GlobalLock(mem);
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+        GlobalUnlock(mem);
+        Ok(mem)
+    }
+}
+
+/// Encode `text` as NUL-terminated UTF-16 bytes
+fn utf16_bytes(text: &str) -> Vec<u8> {
+    str_to_utf16(text).iter().flat_map(|x| x.to_le_bytes().to_vec())
+        .collect()
+}
+
+/// Build a fuzzed execute command of up to `len` characters from `rng`,
+/// one or more `[command(args)]` with odd arguments
+fn execute_string(rng: &Rng, len: usize) -> String {
+    let mut ret = String::new();
+    while ret.len() < len {
+        let command = COMMANDS[rng.rand() % COMMANDS.len()];
+        let arg = match rng.rand() % 5 {
+            0 => String::new(),
+            1 => "A".repeat(rng.rand() % len.max(1) + 1),
+            2 => format!("\"C:\\{}\"", "..\\".repeat(rng.rand() % 32)),
+            3 => format!("{}", rng.rand() as i32),
+            _ => (0..rng.rand() % 16).map(|_| {
+                ["\"", ",", "(", ")", "[", "]", "%s", "\\"][rng.rand() % 8]
+            }).collect(),
+        };
+        ret += &format!("[{}({})]", command, arg);
+    }
+    ret.chars().take(len).collect()
+}
+
+/// Start a DDE conversation with a server of `pid` and send it the fuzzed
+/// `command` built from `seed` and `len`
+pub fn perform(pid: u32, command: DdeCommand, seed: u32, len: u16)
+        -> io::Result<()> {
+    let client = Client::new()?;
+    let servers = client.initiate(pid)?;
+    if servers.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+            "No DDE servers answered"));
+    }
+
+    let rng = Rng::seeded(seed as u64 | 1);
+    let server = servers[rng.rand() % servers.len()];
+    let len = len as usize % (MAX_DDE_LEN + 1);
+
+    let (msg, lparam) = match command {
+        DdeCommand::Execute => {
+            let string = execute_string(&rng, len);
+            (WM_DDE_EXECUTE, global_bytes(&utf16_bytes(&string))?)
+        }
+        DdeCommand::Poke => {
+            // `DDEPOKE` header followed by the value
+            let format = POKE_FORMATS[rng.rand() % POKE_FORMATS.len()];
+            let mut poke = Vec::new();
+            poke.extend_from_slice(&DDE_RELEASE.to_le_bytes());
+            poke.extend_from_slice(&format.to_le_bytes());
+            poke.extend((0..len).map(|_| rng.rand() as u8));
+
+            let item = str_to_utf16(ITEMS[rng.rand() % ITEMS.len()]);
+            let atom = unsafe { GlobalAddAtomW(item.as_ptr()) };
+            let data = global_bytes(&poke)?;
+            (WM_DDE_POKE, unsafe {
+                PackDDElParam(WM_DDE_POKE, data, atom as usize)
+            })
+        }
+    };
+
+    audit::message("post", server, msg, client.hwnd, lparam);
+    audit::message("post", server, WM_DDE_TERMINATE, client.hwnd, 0);
+    unsafe {
+        if !PostMessageW(server, msg, client.hwnd, lparam) {
+            return Err(io::Error::last_os_error());
+        }
+
+        // End the conversation, the server's replies go nowhere once the
+        // client window is destroyed
+        PostMessageW(server, WM_DDE_TERMINATE, client.hwnd, 0);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+//! Distance metric between inputs, used for novelty search
+
+use crate::{FuzzerAction, FuzzInput};
+
+/// Canonicalize an action sequence so that inputs which differ only in the
+/// length of runs of the same action compare as equal. Repeating an action
+/// rarely changes what it does to the UI, and the repeat mutation would
+/// otherwise make inputs look very far apart.
+pub fn canonicalize(input: &[FuzzerAction]) -> Vec<FuzzerAction> {
+    let mut ret: Vec<FuzzerAction> = Vec::with_capacity(input.len());
+    for &action in input {
+        if ret.last() != Some(&action) {
+            ret.push(action);
+        }
+    }
+    ret
+}
+
+/// Compute the edit (Levenshtein) distance between the canonicalized forms
+/// of `a` and `b`
+pub fn distance(a: &[FuzzerAction], b: &[FuzzerAction]) -> usize {
+    let a = canonicalize(a);
+    let b = canonicalize(b);
+
+    // Only two rows of the edit distance matrix are needed at a time
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (ii, action_a) in a.iter().enumerate() {
+        cur[0] = ii + 1;
+        for (jj, action_b) in b.iter().enumerate() {
+            let substitute = prev[jj] + (action_a != action_b) as usize;
+            cur[jj + 1] = substitute.min(prev[jj + 1] + 1).min(cur[jj] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+/// Compute the novelty of `input` as the mean distance to its `k` nearest
+/// neighbors in `others`. Returns `None` if `others` is empty.
+pub fn novelty(input: &[FuzzerAction], others: &[FuzzInput], k: usize)
+        -> Option<f64> {
+    if others.is_empty() || k == 0 {
+        return None;
+    }
+
+    let mut distances: Vec<usize> = others.iter()
+        .map(|other| distance(input, other)).collect();
+    distances.sort();
+
+    let nearest = &distances[..k.min(distances.len())];
+    Some(nearest.iter().sum::<usize>() as f64 / nearest.len() as f64)
+}
@@ -0,0 +1,516 @@
+//! OLE drag and drop of fuzzed data objects onto the target, reaching the
+//! embedded object and rich content parsing behind `IDropTarget`
+
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use crate::{Rng, Window, audit};
+use crate::winbindings::{self, Guid, str_to_utf16};
+
+#[link(name="Ole32")]
+extern "system" {
+    fn OleInitialize(reserved: usize) -> i32;
+    fn DoDragDrop(data: usize, source: usize, ok_effects: u32,
+        effect: *mut u32) -> i32;
+}
+
+#[link(name="Shell32")]
+extern "system" {
+    fn SHCreateStdEnumFmtEtc(count: u32, formats: *const FormatEtc,
+        enum_formats: *mut usize) -> i32;
+}
+
+#[link(name="User32")]
+extern "system" {
+    fn RegisterClipboardFormatW(name: *const u16) -> u32;
+    fn SetCursorPos(x: i32, y: i32) -> i32;
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GlobalAlloc(flags: u32, size: usize) -> usize;
+    fn GlobalLock(mem: usize) -> *mut u8;
+    fn GlobalUnlock(mem: usize) -> i32;
+}
+
+/// `HRESULT` success
+const S_OK: i32 = 0;
+
+/// Interface is not supported
+const E_NOINTERFACE: i32 = 0x8000_4002u32 as i32;
+
+/// Method is not implemented
+const E_NOTIMPL: i32 = 0x8000_4001u32 as i32;
+
+/// Out of memory
+const E_OUTOFMEMORY: i32 = 0x8007_000eu32 as i32;
+
+/// Requested format is not available from the data object
+const DV_E_FORMATETC: i32 = 0x8004_0064u32 as i32;
+
+/// Advisory connections are not supported
+const OLE_E_ADVISENOTSUPPORTED: i32 = 0x8004_0003u32 as i32;
+
+/// Returned from `QueryContinueDrag()` to drop immediately
+const DRAGDROP_S_DROP: i32 = 0x0004_0100;
+
+/// Returned from `GiveFeedback()` to use the default cursors
+const DRAGDROP_S_USEDEFAULTCURSORS: i32 = 0x0004_0102;
+
+/// Data is passed in a global memory handle
+const TYMED_HGLOBAL: u32 = 1;
+
+/// Content aspect of the data
+const DVASPECT_CONTENT: u32 = 1;
+
+/// Direction for `EnumFormatEtc()` getting formats from the data object
+const DATADIR_GET: u32 = 1;
+
+/// Allocate moveable global memory
+const GMEM_MOVEABLE: u32 = 0x0002;
+
+/// Allow copying, moving, and linking the dropped data
+const DROPEFFECT_ALL: u32 = 1 | 2 | 4;
+
+/// ANSI text clipboard format
+const CF_TEXT: u32 = 1;
+
+/// Interface ID of `IUnknown`
+const IID_IUNKNOWN: Guid = Guid {
+    data1: 0x00000000,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// Interface ID of `IDataObject`
+const IID_IDATAOBJECT: Guid = Guid {
+    data1: 0x0000010e,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// Interface ID of `IDropSource`
+const IID_IDROPSOURCE: Guid = Guid {
+    data1: 0x00000121,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// Format of the data dropped onto the target
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DropFormat {
+    /// `CF_TEXT`, NUL-terminated ANSI text
+    Text,
+
+    /// `CF_HTML`, an HTML fragment with a header of offsets
+    Html,
+
+    /// `CFSTR_FILEDESCRIPTORW`, a `FILEGROUPDESCRIPTORW` describing virtual
+    /// files
+    FileDescriptor,
+}
+
+impl DropFormat {
+    /// Get the clipboard format ID for this format
+    fn clipboard_format(&self) -> u32 {
+        let name = match self {
+            DropFormat::Text           => return CF_TEXT,
+            DropFormat::Html           => "HTML Format",
+            DropFormat::FileDescriptor => "FileGroupDescriptorW",
+        };
+
+        unsafe { RegisterClipboardFormatW(str_to_utf16(name).as_ptr()) }
+    }
+
+    /// Generate fuzzed contents for this format from `seed`
+    fn generate(&self, seed: u32) -> Vec<u8> {
+        // Offset the seed so it's never zero, xorshift gets stuck on zero
+        let rng = Rng::seeded(seed as u64 ^ 0x9e37_79b9_7f4a_7c15);
+
+        // Random text, mostly printable
+        let text = |len: usize| -> Vec<u8> {
+            (0..len).map(|_| if rng.rand() & 0xf == 0 {
+                rng.rand() as u8
+            } else {
+                (rng.rand() % 0x5f) as u8 + 0x20
+            }).collect()
+        };
+
+        match self {
+            DropFormat::Text => {
+                let mut data = text(rng.rand() % 4096);
+                data.push(0);
+                data
+            }
+            DropFormat::Html => {
+                let prefix = b"<html><body><!--StartFragment-->";
+                let suffix = b"<!--EndFragment--></body></html>";
+                let body   = text(rng.rand() % 4096);
+
+                // Header is a fixed size as the offsets are fixed width
+                let header = 105;
+                let mut offsets = [
+                    header,
+                    header + prefix.len() + body.len() + suffix.len(),
+                    header + prefix.len(),
+                    header + prefix.len() + body.len(),
+                ];
+
+                // Sometimes lie about the offsets
+                if rng.rand() & 0x3 == 0 {
+                    offsets[rng.rand() % 4] = rng.rand() % 10_000_000_000;
+                }
+
+                let mut data = format!("Version:0.9\r\nStartHTML:{:010}\r\n\
+                    EndHTML:{:010}\r\nStartFragment:{:010}\r\n\
+                    EndFragment:{:010}\r\n",
+                    offsets[0], offsets[1], offsets[2], offsets[3])
+                    .into_bytes();
+                data.extend_from_slice(prefix);
+                data.extend_from_slice(&body);
+                data.extend_from_slice(suffix);
+                data.push(0);
+                data
+            }
+            DropFormat::FileDescriptor => {
+                // Describe up to 4 files, sometimes lying about the count
+                let items = rng.rand() % 5;
+                let count = if rng.rand() & 0xf == 0 {
+                    rng.rand() as u32
+                } else {
+                    items as u32
+                };
+
+                let mut data = count.to_le_bytes().to_vec();
+                for _ in 0..items {
+                    // Random `FILEDESCRIPTORW` up to the file name
+                    for _ in 0..72 {
+                        data.push(rng.rand() as u8);
+                    }
+
+                    // File name, usually NUL-terminated
+                    let name = text(rng.rand() % 260);
+                    let mut name: Vec<u16> =
+                        name.iter().map(|&x| x as u16).collect();
+                    name.resize(260, 0);
+                    if rng.rand() & 0xf == 0 {
+                        name[259] = b'A' as u16;
+                    }
+                    data.extend(name.iter().flat_map(|x| x.to_le_bytes()));
+                }
+                data
+            }
+        }
+    }
+}
+
+/// Rust implementation of `FORMATETC`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FormatEtc {
+    format: u16,
+    ptd:    usize,
+    aspect: u32,
+    index:  i32,
+    tymed:  u32,
+}
+
+/// Rust implementation of `STGMEDIUM`
+#[repr(C)]
+struct StgMedium {
+    tymed:          u32,
+    hglobal:        usize,
+    unk_release:    usize,
+}
+
+/// Virtual function table of `IDataObject`
+#[repr(C)]
+struct DataObjectVtbl {
+    query_interface: unsafe extern "system" fn(*mut DataObject, *const Guid,
+        *mut usize) -> i32,
+    add_ref: unsafe extern "system" fn(*mut DataObject) -> u32,
+    release: unsafe extern "system" fn(*mut DataObject) -> u32,
+    get_data: unsafe extern "system" fn(*mut DataObject, *const FormatEtc,
+        *mut StgMedium) -> i32,
+    get_data_here: unsafe extern "system" fn(*mut DataObject,
+        *const FormatEtc, *mut StgMedium) -> i32,
+    query_get_data: unsafe extern "system" fn(*mut DataObject,
+        *const FormatEtc) -> i32,
+    get_canonical_format_etc: unsafe extern "system" fn(*mut DataObject,
+        *const FormatEtc, *mut FormatEtc) -> i32,
+    set_data: unsafe extern "system" fn(*mut DataObject, *const FormatEtc,
+        *mut StgMedium, i32) -> i32,
+    enum_format_etc: unsafe extern "system" fn(*mut DataObject, u32,
+        *mut usize) -> i32,
+    d_advise: unsafe extern "system" fn(*mut DataObject, *const FormatEtc,
+        u32, usize, *mut u32) -> i32,
+    d_unadvise: unsafe extern "system" fn(*mut DataObject, u32) -> i32,
+    enum_d_advise: unsafe extern "system" fn(*mut DataObject, *mut usize)
+        -> i32,
+}
+
+/// `IDataObject` offering a single format of fuzzed data
+#[repr(C)]
+struct DataObject {
+    /// Must be first, COM objects start with their vtable
+    vtbl: *const DataObjectVtbl,
+
+    /// Reference count, the object is freed when this hits zero
+    refs: AtomicU32,
+
+    /// Format offered
+    format: FormatEtc,
+
+    /// Contents offered
+    data: Vec<u8>,
+}
+
+/// Virtual function table of `IDropSource`
+#[repr(C)]
+struct DropSourceVtbl {
+    query_interface: unsafe extern "system" fn(*mut DropSource, *const Guid,
+        *mut usize) -> i32,
+    add_ref: unsafe extern "system" fn(*mut DropSource) -> u32,
+    release: unsafe extern "system" fn(*mut DropSource) -> u32,
+    query_continue_drag: unsafe extern "system" fn(*mut DropSource, i32,
+        u32) -> i32,
+    give_feedback: unsafe extern "system" fn(*mut DropSource, u32) -> i32,
+}
+
+/// `IDropSource` which drops as soon as the drag starts
+#[repr(C)]
+struct DropSource {
+    /// Must be first, COM objects start with their vtable
+    vtbl: *const DropSourceVtbl,
+
+    /// Reference count, the object is freed when this hits zero
+    refs: AtomicU32,
+}
+
+/// Compare two GUIDs
+fn guid_eq(a: &Guid, b: &Guid) -> bool {
+    a.data1 == b.data1 && a.data2 == b.data2 && a.data3 == b.data3 &&
+        a.data4 == b.data4
+}
+
+unsafe extern "system" fn data_query_interface(this: *mut DataObject,
+        riid: *const Guid, object: *mut usize) -> i32 {
+    if guid_eq(&*riid, &IID_IUNKNOWN) || guid_eq(&*riid, &IID_IDATAOBJECT) {
+        data_add_ref(this);
+        *object = this as usize;
+        S_OK
+    } else {
+        *object = 0;
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn data_add_ref(this: *mut DataObject) -> u32 {
+    (*this).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+unsafe extern "system" fn data_release(this: *mut DataObject) -> u32 {
+    let refs = (*this).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+    if refs == 0 {
+        drop(Box::from_raw(this));
+    }
+    refs
+}
+
+/// Check if `format` asks for what the data object offers
+unsafe fn format_matches(this: *mut DataObject, format: *const FormatEtc)
+        -> bool {
+    (*format).format == (*this).format.format &&
+        (*format).tymed & TYMED_HGLOBAL != 0
+}
+
+unsafe extern "system" fn data_get_data(this: *mut DataObject,
+        format: *const FormatEtc, medium: *mut StgMedium) -> i32 {
+    if !format_matches(this, format) {
+        return DV_E_FORMATETC;
+    }
+
+    // Copy the data into global memory, which the receiver frees
+    let data = &(*this).data;
+    let mem = GlobalAlloc(GMEM_MOVEABLE, data.len().max(1));
+    if mem == 0 {
+        return E_OUTOFMEMORY;
+    }
+    let ptr = GlobalLock(mem);
+    if !ptr.is_null() {
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+        GlobalUnlock(mem);
+    }
+
+    *medium = StgMedium {
+        tymed:       TYMED_HGLOBAL,
+        hglobal:     mem,
+        unk_release: 0,
+    };
+    S_OK
+}
+
+unsafe extern "system" fn data_get_data_here(_this: *mut DataObject,
+        _format: *const FormatEtc, _medium: *mut StgMedium) -> i32 {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn data_query_get_data(this: *mut DataObject,
+        format: *const FormatEtc) -> i32 {
+    if format_matches(this, format) { S_OK } else { DV_E_FORMATETC }
+}
+
+unsafe extern "system" fn data_get_canonical_format_etc(
+        _this: *mut DataObject, _format: *const FormatEtc,
+        out: *mut FormatEtc) -> i32 {
+    (*out).ptd = 0;
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn data_set_data(_this: *mut DataObject,
+        _format: *const FormatEtc, _medium: *mut StgMedium,
+        _release: i32) -> i32 {
+    E_NOTIMPL
+}
+
+unsafe extern "system" fn data_enum_format_etc(this: *mut DataObject,
+        direction: u32, enum_formats: *mut usize) -> i32 {
+    if direction != DATADIR_GET {
+        return E_NOTIMPL;
+    }
+    SHCreateStdEnumFmtEtc(1, &(*this).format, enum_formats)
+}
+
+unsafe extern "system" fn data_d_advise(_this: *mut DataObject,
+        _format: *const FormatEtc, _flags: u32, _sink: usize,
+        _connection: *mut u32) -> i32 {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+unsafe extern "system" fn data_d_unadvise(_this: *mut DataObject,
+        _connection: u32) -> i32 {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+unsafe extern "system" fn data_enum_d_advise(_this: *mut DataObject,
+        _enum_advise: *mut usize) -> i32 {
+    OLE_E_ADVISENOTSUPPORTED
+}
+
+/// Vtable shared by all data objects
+static DATA_OBJECT_VTBL: DataObjectVtbl = DataObjectVtbl {
+    query_interface:          data_query_interface,
+    add_ref:                  data_add_ref,
+    release:                  data_release,
+    get_data:                 data_get_data,
+    get_data_here:            data_get_data_here,
+    query_get_data:           data_query_get_data,
+    get_canonical_format_etc: data_get_canonical_format_etc,
+    set_data:                 data_set_data,
+    enum_format_etc:          data_enum_format_etc,
+    d_advise:                 data_d_advise,
+    d_unadvise:               data_d_unadvise,
+    enum_d_advise:            data_enum_d_advise,
+};
+
+unsafe extern "system" fn source_query_interface(this: *mut DropSource,
+        riid: *const Guid, object: *mut usize) -> i32 {
+    if guid_eq(&*riid, &IID_IUNKNOWN) || guid_eq(&*riid, &IID_IDROPSOURCE) {
+        source_add_ref(this);
+        *object = this as usize;
+        S_OK
+    } else {
+        *object = 0;
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn source_add_ref(this: *mut DropSource) -> u32 {
+    (*this).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+unsafe extern "system" fn source_release(this: *mut DropSource) -> u32 {
+    let refs = (*this).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+    if refs == 0 {
+        drop(Box::from_raw(this));
+    }
+    refs
+}
+
+unsafe extern "system" fn source_query_continue_drag(_this: *mut DropSource,
+        _escape: i32, _key_state: u32) -> i32 {
+    DRAGDROP_S_DROP
+}
+
+unsafe extern "system" fn source_give_feedback(_this: *mut DropSource,
+        _effect: u32) -> i32 {
+    DRAGDROP_S_USEDEFAULTCURSORS
+}
+
+/// Vtable shared by all drop sources
+static DROP_SOURCE_VTBL: DropSourceVtbl = DropSourceVtbl {
+    query_interface:     source_query_interface,
+    add_ref:             source_add_ref,
+    release:             source_release,
+    query_continue_drag: source_query_continue_drag,
+    give_feedback:       source_give_feedback,
+};
+
+/// Drop fuzzed data of `format` generated from `seed` onto `window`.
+///
+/// OLE picks the drop target from the cursor position, so this moves the
+/// real cursor to the center of `window`, holding the desktop so other
+/// workers don't move it away. If another window is on top the data is
+/// dropped there instead.
+pub fn ole_drop(window: &Window, format: DropFormat, seed: u32)
+        -> io::Result<()> {
+    let _desktop = winbindings::lock_desktop();
+    let (x, y) = window.center()?;
+    audit::input(&format!("ole drop hwnd={:#x} x={} y={} format={:?}",
+        window.hwnd(), x, y, format));
+
+    unsafe {
+        // This fails if OLE is already initialized for this thread, which
+        // is fine
+        OleInitialize(0);
+
+        if SetCursorPos(x, y) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let data = Box::into_raw(Box::new(DataObject {
+            vtbl:   &DATA_OBJECT_VTBL,
+            refs:   AtomicU32::new(1),
+            format: FormatEtc {
+                format: format.clipboard_format() as u16,
+                ptd:    0,
+                aspect: DVASPECT_CONTENT,
+                index:  -1,
+                tymed:  TYMED_HGLOBAL,
+            },
+            data:   format.generate(seed),
+        }));
+        let source = Box::into_raw(Box::new(DropSource {
+            vtbl: &DROP_SOURCE_VTBL,
+            refs: AtomicU32::new(1),
+        }));
+
+        let mut effect = 0;
+        let hr = DoDragDrop(data as usize, source as usize, DROPEFFECT_ALL,
+            &mut effect);
+
+        data_release(data);
+        source_release(source);
+
+        if hr < 0 {
+            return Err(io::Error::other(
+                format!("DoDragDrop() failed: {:#x}", hr)));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,253 @@
+//! Handling of targets which require administrator rights. UAC prompts are
+//! shown on the secure desktop where the fuzzer can't reach them, stalling
+//! the target until they time out, so the harness can either run elevated
+//! itself, which makes targets spawn directly elevated, or learn which
+//! controls raise a prompt and stop clicking them.
+
+use std::io;
+use std::path::Path;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use crate::Window;
+use crate::winbindings::str_to_utf16;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetCurrentProcess() -> usize;
+    fn CloseHandle(handle: usize) -> i32;
+    fn CreateToolhelp32Snapshot(flags: u32, pid: u32) -> usize;
+    fn Process32FirstW(snapshot: usize, entry: *mut ProcessEntry) -> i32;
+    fn Process32NextW(snapshot: usize, entry: *mut ProcessEntry) -> i32;
+}
+
+#[link(name="Advapi32")]
+extern "system" {
+    fn OpenProcessToken(process: usize, access: u32, token: *mut usize)
+        -> i32;
+    fn GetTokenInformation(token: usize, class: u32, info: *mut u32,
+        len: u32, ret_len: *mut u32) -> i32;
+}
+
+#[link(name="Shell32")]
+extern "system" {
+    fn ShellExecuteExW(info: *mut ShellExecuteInfo) -> i32;
+}
+
+/// `TOKEN_QUERY` access right
+const TOKEN_QUERY: u32 = 0x0008;
+
+/// `TokenElevation` token information class
+const TOKEN_ELEVATION: u32 = 20;
+
+/// `TH32CS_SNAPPROCESS`, include all processes in a snapshot
+const TH32CS_SNAPPROCESS: u32 = 0x2;
+
+/// `INVALID_HANDLE_VALUE`
+const INVALID_HANDLE_VALUE: usize = !0;
+
+/// `SW_SHOWNORMAL`
+const SW_SHOWNORMAL: i32 = 1;
+
+/// Image name of the process showing UAC prompts
+const CONSENT_EXE: &str = "consent.exe";
+
+/// `PROCESSENTRY32W`
+#[repr(C)]
+struct ProcessEntry {
+    size:          u32,
+    usage:         u32,
+    pid:           u32,
+    heap_id:       usize,
+    module_id:     u32,
+    threads:       u32,
+    parent_pid:    u32,
+    priority_base: i32,
+    flags:         u32,
+    exe_file:      [u16; 260],
+}
+
+/// `SHELLEXECUTEINFOW`
+#[repr(C)]
+struct ShellExecuteInfo {
+    size:        u32,
+    mask:        u32,
+    hwnd:        usize,
+    verb:        *const u16,
+    file:        *const u16,
+    parameters:  *const u16,
+    directory:   *const u16,
+    show:        i32,
+    inst_app:    usize,
+    id_list:     usize,
+    class:       *const u16,
+    hkey_class:  usize,
+    hot_key:     u32,
+    icon:        usize,
+    process:     usize,
+}
+
+/// Check whether the current process is running elevated
+pub fn is_elevated() -> io::Result<bool> {
+    unsafe {
+        let mut token = 0;
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY,
+                &mut token) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut elevated = 0u32;
+        let mut ret_len  = 0u32;
+        let ret = GetTokenInformation(token, TOKEN_ELEVATION, &mut elevated,
+            std::mem::size_of::<u32>() as u32, &mut ret_len);
+        CloseHandle(token);
+
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(elevated != 0)
+    }
+}
+
+/// Check whether the executable at `path` requires elevation to run
+pub fn target_requires_elevation<P: AsRef<Path>>(path: P)
+        -> io::Result<bool> {
+    crate::resources::requires_elevation(&std::fs::read(path)?)
+}
+
+/// Launch a new elevated instance of the current process with the same
+/// arguments, showing a single UAC prompt. The caller should exit once this
+/// returns successfully.
+pub fn relaunch_elevated() -> io::Result<()> {
+    let exe = std::env::current_exe()?;
+    let args: Vec<String> = std::env::args().skip(1)
+        .map(|x| format!("\"{}\"", x)).collect();
+
+    let verb       = str_to_utf16("runas");
+    let file       = str_to_utf16(&exe.to_string_lossy());
+    let parameters = str_to_utf16(&args.join(" "));
+    let directory  = str_to_utf16(&std::env::current_dir()?
+        .to_string_lossy());
+
+    let mut info = ShellExecuteInfo {
+        size:       std::mem::size_of::<ShellExecuteInfo>() as u32,
+        mask:       0,
+        hwnd:       0,
+        verb:       verb.as_ptr(),
+        file:       file.as_ptr(),
+        parameters: parameters.as_ptr(),
+        directory:  directory.as_ptr(),
+        show:       SW_SHOWNORMAL,
+        inst_app:   0,
+        id_list:    0,
+        class:      std::ptr::null(),
+        hkey_class: 0,
+        hot_key:    0,
+        icon:       0,
+        process:    0,
+    };
+
+    unsafe {
+        if ShellExecuteExW(&mut info) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether a UAC prompt is currently being shown
+pub fn uac_prompt_open() -> bool {
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return false;
+        }
+
+        let mut entry: ProcessEntry = std::mem::zeroed();
+        entry.size = std::mem::size_of::<ProcessEntry>() as u32;
+
+        let mut found = false;
+        let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+        while more && !found {
+            let len = entry.exe_file.iter().position(|&x| x == 0)
+                .unwrap_or(entry.exe_file.len());
+            found = String::from_utf16_lossy(&entry.exe_file[..len])
+                .eq_ignore_ascii_case(CONSENT_EXE);
+            more = Process32NextW(snapshot, &mut entry) != 0;
+        }
+
+        CloseHandle(snapshot);
+        found
+    }
+}
+
+/// Controls which raise a UAC prompt, identified by class and text, shared
+/// between all threads. `None` while skipping them is disabled.
+static SKIP_POLICY: Mutex<Option<SkipPolicy>> = Mutex::new(None);
+
+/// State for learning which controls raise UAC prompts
+#[derive(Default)]
+struct SkipPolicy {
+    /// Controls which raised a prompt, as (class, text)
+    elevating: HashSet<(String, String)>,
+
+    /// Whether a prompt was open at the last click
+    prompt_open: bool,
+
+    /// The control clicked last
+    last_click: Option<(String, String)>,
+}
+
+/// Identify the control `window` the same way across target instances
+fn control_key(window: &Window) -> (String, String) {
+    (window.class_name().unwrap_or_default(),
+     window.window_text().unwrap_or_default())
+}
+
+/// Enable skipping of clicks on controls which raise UAC prompts
+pub fn enable_skip() {
+    let mut policy = SKIP_POLICY.lock().unwrap();
+    if policy.is_none() {
+        *policy = Some(SkipPolicy::default());
+    }
+}
+
+/// Called before clicking on `window`. Returns `false` if the click should
+/// be skipped as the control is known to raise a UAC prompt. A prompt which
+/// appeared since the last click is blamed on that click, as prompts take a
+/// moment to appear.
+pub fn allow_click(window: &Window) -> bool {
+    if SKIP_POLICY.lock().unwrap().is_none() {
+        return true;
+    }
+
+    // Query outside of the lock, both are slow
+    let key  = control_key(window);
+    let open = uac_prompt_open();
+
+    let mut policy = SKIP_POLICY.lock().unwrap();
+    let policy = match policy.as_mut() {
+        Some(policy) => policy,
+        None => return true,
+    };
+
+    if open && !policy.prompt_open {
+        if let Some(last) = policy.last_click.take() {
+            policy.elevating.insert(last);
+        }
+    }
+    policy.prompt_open = open;
+
+    if policy.elevating.contains(&key) {
+        return false;
+    }
+    policy.last_click = Some(key);
+    true
+}
+
+/// Number of controls found to raise UAC prompts
+pub fn elevating_controls() -> usize {
+    SKIP_POLICY.lock().unwrap().as_ref()
+        .map(|policy| policy.elevating.len())
+        .unwrap_or(0)
+}
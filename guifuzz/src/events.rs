@@ -0,0 +1,83 @@
+//! Stream of live fuzzing events, so embedding applications and dashboards
+//! can react to what the workers find instead of polling the statistics.
+//! Every subscriber gets its own copy of each event emitted after it
+//! subscribed.
+
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use crate::FuzzInput;
+
+/// Channels of all subscribers to the event stream
+static SUBSCRIBERS: Mutex<Vec<Sender<FuzzEvent>>> = Mutex::new(Vec::new());
+
+/// Something which happened during fuzzing
+#[derive(Clone, Debug)]
+pub enum FuzzEvent {
+    /// An input was added to the corpus
+    NewInput {
+        /// The input
+        input: FuzzInput,
+
+        /// Worker which found it, if it came from a worker
+        worker: Option<usize>,
+    },
+
+    /// Coverage was reached for the first time
+    NewCoverage {
+        /// Module the coverage is in
+        module: Arc<String>,
+
+        /// Offset of the coverage in the module
+        offset: usize,
+
+        /// Worker which reached it
+        worker: usize,
+    },
+
+    /// An oracle found a bug, hangs included
+    Crash {
+        /// Name of the bucket the bug is saved in
+        name: String,
+
+        /// Input which caused the bug
+        input: FuzzInput,
+
+        /// The bug created a new bucket
+        new_bucket: bool,
+
+        /// Worker which found it
+        worker: usize,
+    },
+
+    /// The target stopped processing window messages
+    Hang {
+        /// Input which caused the hang
+        input: FuzzInput,
+
+        /// Worker which found it
+        worker: usize,
+    },
+
+    /// A worker lost its case to an internal error and started over
+    WorkerRestart {
+        /// The worker
+        worker: usize,
+
+        /// What went wrong
+        reason: String,
+    },
+}
+
+/// Subscribe to all events emitted from now on. Dropping the receiver
+/// unsubscribes.
+pub fn subscribe() -> Receiver<FuzzEvent> {
+    let (sender, receiver) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+    receiver
+}
+
+/// Send `event` to all subscribers, forgetting the ones which went away
+pub fn emit(event: FuzzEvent) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain(|x| x.send(event.clone()).is_ok());
+}
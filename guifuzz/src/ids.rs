@@ -0,0 +1,96 @@
+//! Typed identifiers used by actions and the `Window` API, so menu IDs,
+//! indices of GUI elements, and virtual keys can't be mixed up. Each
+//! converts to and from its raw value, and prints as its raw value so saved
+//! inputs and traces read the same as with raw integers.
+
+use std::fmt;
+
+/// ID of a menu item, sent to the window in a `WM_COMMAND`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct MenuId(pub u32);
+
+/// Index of a GUI element in the list of sub-windows of a window, from
+/// `Window::enumerate_subwindows()`, or of UI Automation elements, from
+/// `Uia::elements()`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ElementIdx(pub usize);
+
+/// Windows virtual-key code, such as `0x41` for the A key
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct VirtualKey(pub usize);
+
+impl ElementIdx {
+    /// Get the element at this index of `elements`
+    pub fn get<T>(self, elements: &[T]) -> Option<&T> {
+        elements.get(self.0)
+    }
+}
+
+impl From<u32> for MenuId {
+    fn from(id: u32) -> Self {
+        MenuId(id)
+    }
+}
+
+impl From<MenuId> for u32 {
+    fn from(id: MenuId) -> Self {
+        id.0
+    }
+}
+
+impl From<usize> for ElementIdx {
+    fn from(idx: usize) -> Self {
+        ElementIdx(idx)
+    }
+}
+
+impl From<ElementIdx> for usize {
+    fn from(idx: ElementIdx) -> Self {
+        idx.0
+    }
+}
+
+impl From<usize> for VirtualKey {
+    fn from(key: usize) -> Self {
+        VirtualKey(key)
+    }
+}
+
+impl From<u8> for VirtualKey {
+    fn from(key: u8) -> Self {
+        VirtualKey(key as usize)
+    }
+}
+
+impl From<VirtualKey> for usize {
+    fn from(key: VirtualKey) -> Self {
+        key.0
+    }
+}
+
+impl fmt::Debug for MenuId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for ElementIdx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Debug for VirtualKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
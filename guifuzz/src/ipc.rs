@@ -0,0 +1,170 @@
+//! Fuzzing of the IPC interfaces targets expose through window messages,
+//! `WM_COPYDATA` and messages registered with `RegisterWindowMessage()`
+
+use std::io;
+use std::sync::Mutex;
+use crate::{Rng, Window, audit};
+use crate::winbindings::str_to_utf16;
+
+#[link(name="User32")]
+extern "system" {
+    fn RegisterWindowMessageW(name: *const u16) -> u32;
+    fn GetClipboardFormatNameW(format: u32, name: *mut u16, len: i32) -> i32;
+    fn SendMessageTimeoutW(hwnd: usize, msg: u32, wparam: usize,
+        lparam: usize, flags: u32, timeout: u32, result: *mut usize) -> usize;
+}
+
+/// `WM_COPYDATA`
+const WM_COPYDATA: u32 = 0x004a;
+
+/// `SMTO_ABORTIFHUNG`, don't wait on a target which is already hung
+const SMTO_ABORTIFHUNG: u32 = 0x0002;
+
+/// Timeout for messages which must be sent rather than posted
+const SEND_TIMEOUT_MS: u32 = 1000;
+
+/// Range of message numbers handed out by `RegisterWindowMessage()`, which
+/// shares its atom table with registered clipboard formats
+const REGISTERED_MESSAGES: std::ops::RangeInclusive<u32> = 0xc000..=0xffff;
+
+/// Maximum length of a `WM_COPYDATA` payload
+pub const MAX_COPYDATA_LEN: usize = 4096;
+
+/// Names of the registered messages targeted by
+/// `FuzzerAction::CustomMessage`, which refers to them by index so inputs
+/// replay across sessions where the message numbers differ
+static CUSTOM_MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// `COPYDATASTRUCT`
+#[repr(C)]
+struct CopyDataStruct {
+    data:     usize,
+    data_len: u32,
+    ptr:      *const u8,
+}
+
+/// Set the names of the registered messages to fuzz
+pub fn set_custom_messages(names: &[String]) {
+    *CUSTOM_MESSAGES.lock().unwrap() = names.to_vec();
+}
+
+/// Number of registered messages being fuzzed
+pub fn custom_message_count() -> usize {
+    CUSTOM_MESSAGES.lock().unwrap().len()
+}
+
+/// Name of the custom registered message at index `idx`, as picked by
+/// `FuzzerAction::CustomMessage`
+pub fn custom_message_name(idx: u16) -> Option<String> {
+    let names = CUSTOM_MESSAGES.lock().unwrap();
+    if names.is_empty() {
+        return None;
+    }
+    Some(names[idx as usize % names.len()].clone())
+}
+
+/// Discover the names of all messages registered in this session, as
+/// (message, name). This includes registered clipboard formats, which share
+/// the same atom table.
+pub fn registered_messages() -> Vec<(u32, String)> {
+    let mut ret = Vec::new();
+    let mut name = [0u16; 256];
+
+    for msg in REGISTERED_MESSAGES {
+        let len = unsafe {
+            GetClipboardFormatNameW(msg, name.as_mut_ptr(), name.len() as i32)
+        };
+        if len > 0 {
+            ret.push((msg, String::from_utf16_lossy(&name[..len as usize])));
+        }
+    }
+
+    ret
+}
+
+/// Get the message number of the registered message `name`
+pub fn register_message(name: &str) -> io::Result<u32> {
+    let name = str_to_utf16(name);
+    match unsafe { RegisterWindowMessageW(name.as_ptr()) } {
+        0 => Err(io::Error::last_os_error()),
+        msg => Ok(msg),
+    }
+}
+
+/// Build a `WM_COPYDATA` payload of up to `len` bytes from `seed`, mixing
+/// random bytes with strings and integers handlers often parse
+pub fn copydata_payload(seed: u32, len: u16) -> Vec<u8> {
+    let rng = Rng::seeded(seed as u64 | 1);
+    let len = len as usize % (MAX_COPYDATA_LEN + 1);
+
+    let mut payload = Vec::with_capacity(len);
+    while payload.len() < len {
+        match rng.rand() % 4 {
+            0 => payload.push(rng.rand() as u8),
+            1 => {
+                // Interesting 32-bit integer, such as a length or count
+                let value = [0u32, 1, 0x7fff_ffff, 0x8000_0000, !0,
+                    len as u32][rng.rand() % 6];
+                payload.extend_from_slice(&value.to_le_bytes());
+            }
+            2 => {
+                // ASCII or UTF-16 text, sometimes NUL-terminated
+                let text = b"C:\\AAAAAAAA%s%n\"<>&";
+                let text = &text[..rng.rand() % text.len() + 1];
+                if rng.rand() & 1 == 0 {
+                    payload.extend_from_slice(text);
+                } else {
+                    for &byte in text {
+                        payload.extend_from_slice(&(byte as u16)
+                            .to_le_bytes());
+                    }
+                }
+                if rng.rand() & 1 == 0 {
+                    payload.push(0);
+                }
+            }
+            _ => payload.extend(std::iter::repeat_n(0x41, rng.rand() % 64)),
+        }
+    }
+    payload.truncate(len);
+    payload
+}
+
+/// Send `window` a `WM_COPYDATA` with the application-defined `tag` and a
+/// payload built from `seed` and `len`
+pub fn send_copydata(window: &Window, tag: u32, seed: u32, len: u16)
+        -> io::Result<()> {
+    let payload = copydata_payload(seed, len);
+    let copydata = CopyDataStruct {
+        data:     tag as usize,
+        data_len: payload.len() as u32,
+        ptr:      payload.as_ptr(),
+    };
+
+    // `WM_COPYDATA` has to be sent so the system can marshal the payload
+    // into the target
+    let mut result = 0;
+    audit::message("send", window.hwnd(), WM_COPYDATA, tag as usize,
+        payload.len());
+    unsafe {
+        if SendMessageTimeoutW(window.hwnd(), WM_COPYDATA, 0,
+                &copydata as *const CopyDataStruct as usize,
+                SMTO_ABORTIFHUNG, SEND_TIMEOUT_MS, &mut result) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Post `window` the custom registered message at index `idx` with
+/// `wparam` and `lparam`
+pub fn post_custom_message(window: &Window, idx: u16, wparam: u32,
+        lparam: u32) -> io::Result<()> {
+    let name = custom_message_name(idx).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound,
+            "No custom messages configured")
+    })?;
+
+    let msg = register_message(&name)?;
+    window.post_raw_message(msg, wparam as usize, lparam as usize)
+}
@@ -1,29 +1,164 @@
 pub mod winbindings;
 pub mod rng;
+pub mod resources;
+pub mod corpus;
+pub mod campaign;
+pub mod distance;
+pub mod config;
+pub mod lineage;
+pub mod accessibility;
+pub mod dragdrop;
+pub mod placement;
+pub mod uia;
+pub mod plugin;
+pub mod bench;
+pub mod windowtree;
+pub mod menuprobe;
+pub mod mdi;
+pub mod elevation;
+pub mod actionstats;
+pub mod ipc;
+pub mod dde;
+pub mod audit;
+pub mod timeout;
+pub mod costmodel;
+pub mod pager;
+pub mod blacklist;
+pub mod ids;
+pub mod bytecodec;
+pub mod capture;
+pub mod activity;
+pub mod msgnames;
+pub mod activation;
+pub mod tags;
+pub mod events;
+pub mod pacing;
+pub mod coords;
+pub mod menusets;
+pub mod targetconfig;
+pub mod propsheet;
+pub mod tabs;
+pub mod ranges;
+pub mod uistate;
+pub mod schedule;
+pub mod triage;
+#[cfg(feature = "serde")]
+pub mod snapshot;
+#[cfg(feature = "chaos")]
+pub mod chaos;
+#[cfg(feature = "arbitrary")]
+pub mod actiongen;
 
+use std::io;
+use std::fmt;
 use std::error::Error;
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashMap, HashSet};
 use std::sync::{Mutex, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 pub use rng::Rng;
-pub use winbindings::Window;
+pub use winbindings::{Window, MessageType, InputBackend, RetryPolicy};
+pub use winbindings::{GuiThreadInfo, KeyMouseState};
+pub use resources::Resources;
+pub use corpus::{Corpus, CoverageMap, CrashBucket, CrashDb, CrashInsert};
+pub use corpus::{Provenance, CrashInfo, coverage_index};
+pub use corpus::{ModuleId, CoverageKey, intern_module, module_name};
+pub use campaign::Campaign;
+pub use config::{Config, TargetPriority};
+pub use lineage::{LineagePolicy, LineageScheduler};
+pub use schedule::Schedule;
+pub use dragdrop::DropFormat;
+pub use placement::Placement;
+pub use uia::{Uia, UiaPattern, UiaSnapshot};
+pub use plugin::{Plugin, PluginCase};
+pub use windowtree::WindowNode;
+pub use mdi::MdiCommand;
+pub use propsheet::PropSheetCommand;
+pub use ranges::RangeBound;
+pub use actionstats::ActionStats;
+pub use dde::DdeCommand;
+pub use timeout::ActionTimeout;
+pub use ids::{MenuId, ElementIdx, VirtualKey};
+pub use activity::{Activity, ActivityBaseline};
+pub use activation::Transition;
+pub use tags::{Tags, TagFilter};
+pub use events::FuzzEvent;
+pub use pacing::{Latency, Pacing};
+pub use targetconfig::TargetConfig;
+pub use triage::CrashSignature;
+pub use uistate::{UiState, StateDiff};
+
+/// Weight multiplier for inputs in a worker's own corpus shard
+const SHARD_WEIGHT: f64 = 8.;
+
+/// Maximum number of actions in an input produced by `mutate()`
+pub const MAX_INPUT_LEN: usize = 4096;
+
+/// Maximum number of messages a `MessagePressure` action posts, whatever its
+/// `count`
+pub const MAX_MESSAGE_PRESSURE: u32 = 256;
+
+/// Minimum weight multiplier for unstable inputs, so inputs which never
+/// reproduce their coverage are still occasionally selected
+const MIN_STABILITY_WEIGHT: f64 = 0.05;
+
+/// Number of selections of inputs from capped lineages rejected before
+/// falling back to computing the weights of all inputs
+const MAX_REJECTIONS: usize = 16;
+
+/// Virtual keys typed into edit controls, letters, digits, and space
+const TEXT_KEYS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+/// Virtual keys held down to auto-repeat: backspace, page up and down, the
+/// arrows, delete, and a letter and digit
+const REPEAT_KEYS: &[u8] = &[0x08, 0x21, 0x22, 0x25, 0x26, 0x27, 0x28, 0x2e,
+    b'A', b'0'];
+
+/// Print each action performed along with the target's GUI thread state
+static TRACE_ACTIONS: Mutex<bool> = Mutex::new(false);
+
+/// Time to wait after each action performed, to watch a replay in slow
+/// motion
+static ACTION_DELAY: Mutex<Duration> = Mutex::new(Duration::from_secs(0));
+
+/// Actions performed at the start of every case to reach a deeper UI state,
+/// kept out of the inputs so they are never mutated
+static PROLOGUE: Mutex<Vec<FuzzerAction>> = Mutex::new(Vec::new());
+
+/// Actions performed after every case to check the target still works, such
+/// as clicking a button which changes the display
+static EPILOGUE: Mutex<Vec<FuzzerAction>> = Mutex::new(Vec::new());
+
+/// PIDs of targets which must no longer be acted on, such as crashed
+/// targets kept alive rather than killed, whose windows never go away
+static ABANDONED_TARGETS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
 
 /// Sharable fuzz input
 pub type FuzzInput = Arc<Vec<FuzzerAction>>;
 
+/// Coverage entries and input claimed by one worker with
+/// `Statistics::reserve_coverage()`, which it alone has to record
+#[derive(Clone, Debug, Default)]
+pub struct Reservation {
+    /// Coverage entries nobody had reached before
+    pub coverage: Vec<CoverageKey>,
+
+    /// The input was added to the corpus
+    pub input_added: bool,
+}
+
 /// Fuzz case statistics
 #[derive(Default)]
 pub struct Statistics {
     /// Number of fuzz cases
     pub fuzz_cases: u64,
 
-    /// Coverage database. Maps (module, offset) to `FuzzInput`s
-    pub coverage_db: HashMap<(Arc<String>, usize), FuzzInput>,
-
-    /// Set of all unique inputs
-    pub input_db: HashSet<FuzzInput>,
+    /// Coverage database. Maps (module, offset) to the hashes of the
+    /// `FuzzInput`s which first reached them
+    pub coverage_db: CoverageMap,
 
-    /// List of all unique inputs
-    pub input_list: Vec<FuzzInput>,
+    /// All unique inputs
+    pub corpus: Corpus,
 
     /// Unique set of fuzzer actions
     pub unique_action_set: HashSet<FuzzerAction>,
@@ -34,66 +169,1027 @@ pub struct Statistics {
     /// Number of crashes
     pub crashes: u64,
 
-    /// Database of crash file names to `FuzzInput`s
-    pub crash_db: HashMap<String, FuzzInput>,
+    /// Database of crash file names to crash buckets
+    pub crash_db: CrashDb,
+
+    /// Number of fuzz cases lost to errors or panics in the fuzzer itself,
+    /// rather than in the target
+    pub internal_errors: u64,
+
+    /// Lineage-aware scheduling applied when selecting inputs
+    pub lineage: LineageScheduler,
+
+    /// Hashes of all UI Automation trees seen after fuzz cases
+    pub uia_trees: HashSet<u64>,
+
+    /// Shape hashes of all window trees seen after fuzz cases
+    pub window_trees: HashSet<u64>,
+
+    /// Attempts and failures of each type of action
+    pub action_stats: ActionStats,
+
+    /// Baseline of the internal activity of the target across fuzz cases
+    pub activity: ActivityBaseline,
+
+    /// Tags inputs need to be selected as the basis of a fuzz case
+    pub tag_filter: TagFilter,
+
+    /// Selection weights of the corpus inputs, brought up to date with the
+    /// corpus on every selection
+    pub schedule: Schedule,
+
+    /// Tag filter and lineage depth penalty `schedule` was computed with, it
+    /// is recomputed for all inputs when either changes
+    schedule_basis: Option<(TagFilter, f64)>,
+}
+
+impl Statistics {
+    /// Add `input` which took `cost` to execute and came from `provenance`
+    /// to the corpus and record all of its actions as known-feasible actions.
+    /// Returns `true` if the input was not already present
+    pub fn add_input(&mut self, input: &FuzzInput, cost: Duration,
+                     provenance: Provenance) -> bool {
+        if !self.corpus.insert(input, cost, provenance) {
+            return false;
+        }
+
+        // Update the action database with known-feasible actions
+        self.add_actions(input);
+
+        true
+    }
+
+    /// Reserve the coverage entries of `keys`, as (index from
+    /// `coverage_index()`, key), which nobody reached before, attributing
+    /// them to `input`, and add `input` which took `cost` to execute and
+    /// came from `provenance` to the corpus if any were new.
+    ///
+    /// This is the first phase of recording new coverage, done in a single
+    /// lock acquisition so when workers race on the same coverage exactly
+    /// one of them gets each entry and each input. The second phase, saving
+    /// them to disk, is up to the returned reservation's owner and is done
+    /// without the lock held.
+    pub fn reserve_coverage(&mut self, keys: &[(usize, CoverageKey)],
+            input: &FuzzInput, cost: Duration, provenance: &Provenance)
+            -> Reservation {
+        let mut reservation = Reservation::default();
+        for &(idx, key) in keys {
+            if self.coverage_db.insert_index(idx, key, input) {
+                reservation.coverage.push(key);
+            }
+        }
+
+        if !reservation.coverage.is_empty() {
+            reservation.input_added =
+                self.add_input(input, cost, provenance.clone());
+        }
+        reservation
+    }
+
+    /// Add `tags` to `input` if it is in the corpus. Returns `true` if it
+    /// was.
+    pub fn tag_input(&mut self, input: &FuzzInput, tags: &Tags) -> bool {
+        match self.corpus.index_of(input) {
+            Some(idx) => {
+                self.corpus.add_tags(idx, tags);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record that `input` changed the UI state by `diff`, if it is in the
+    /// corpus. Returns `true` if it was.
+    pub fn record_state_diff(&mut self, input: &FuzzInput, diff: &StateDiff)
+            -> bool {
+        match self.corpus.index_of(input) {
+            Some(idx) => {
+                self.corpus.set_state_diff(idx, diff.size());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add `actions` to the known-feasible actions, for example from a
+    /// dictionary extracted from the target's resources
+    pub fn add_actions(&mut self, actions: &[FuzzerAction]) {
+        for &action in actions {
+            if self.unique_action_set.insert(action) {
+                self.unique_actions.push(action);
+            }
+        }
+    }
+
+    /// Select the index of an input from the corpus to use as the basis of
+    /// a fuzz case. Inputs are weighted by the inverse of their execution
+    /// cost so cheap inputs get mutated more often per unit of time.
+    ///
+    /// If `shard` is `Some((shard, num_shards))` the corpus is split into
+    /// `num_shards` disjoint subsets and inputs in subset `shard` are
+    /// favored, so workers explore from different seeds.
+    ///
+    /// Weights are further adjusted by the lineage scheduling policy and by
+    /// the input's stability from calibration, inputs which changed the UI
+    /// state more are favored, and the selection is recorded
+    /// against the input's lineage. Inputs not passing the tag filter are
+    /// never selected, unless no input passes it yet.
+    pub fn select_input(&mut self, rng: &Rng, shard: Option<(usize, usize)>)
+            -> usize {
+        let idx = self.pick_input(rng, shard);
+        self.lineage.record(&self.corpus, idx);
+        idx
+    }
+
+    /// Weight of the input at `idx` apart from its lineage's selection cap
+    /// and its shard, which change with every selection
+    fn base_weight(&self, idx: usize) -> f64 {
+        // Weight is the number of executions per second, clamped so inputs
+        // which took (almost) no time don't dominate everything. Inputs
+        // which were never timed use their estimated cost.
+        let cost = self.corpus.expected_cost(idx).map(|x| x.as_secs_f64())
+            .unwrap_or(0.);
+        if !self.corpus.tags(idx)
+                .map(|x| self.tag_filter.matches(x)).unwrap_or(false) {
+            return 0.;
+        }
+
+        let stability = self.corpus.stability(idx).unwrap_or(1.);
+        let state_diff = self.corpus.state_diff(idx).unwrap_or(0);
+        1. / cost.max(0.001) *
+            self.lineage.depth_weight(&self.corpus, idx) *
+            stability.max(MIN_STABILITY_WEIGHT) *
+            (1. + (1. + state_diff as f64).log2())
+    }
+
+    /// Bring the selection weights in `schedule` up to date with the corpus
+    fn update_schedule(&mut self) {
+        let basis = Some((self.tag_filter.clone(),
+            self.lineage.policy().depth_penalty));
+        let changed = self.corpus.take_changed();
+        if basis != self.schedule_basis {
+            self.schedule.clear();
+            for idx in 0..self.corpus.len() {
+                let weight = self.base_weight(idx);
+                self.schedule.set(idx, weight);
+            }
+            self.schedule_basis = basis;
+            return;
+        }
+
+        for idx in changed {
+            let weight = self.base_weight(idx);
+            self.schedule.set(idx, weight);
+        }
+    }
+
+    /// Weighted random selection of an input for `select_input()`, in time
+    /// logarithmic in the size of the corpus unless most of the weight is
+    /// in lineages which hit their selection cap
+    fn pick_input(&mut self, rng: &Rng, shard: Option<(usize, usize)>)
+            -> usize {
+        self.update_schedule();
+
+        // Rejecting inputs from capped lineages selects among the others
+        // in proportion to their weights, as if capped ones weighed nothing
+        for _ in 0..MAX_REJECTIONS {
+            let point = rng.rand() as f64 / usize::MAX as f64;
+            match self.schedule.sample(point, shard, SHARD_WEIGHT) {
+                Some(idx) if !self.lineage.capped(&self.corpus, idx) => {
+                    return idx;
+                }
+                Some(_) => {}
+                None => return rng.rand() % self.corpus.len(),
+            }
+        }
+
+        self.pick_input_linear(rng, shard)
+    }
+
+    /// Weighted random selection of an input computing the weights of all
+    /// inputs, for when `pick_input()` keeps hitting capped lineages
+    fn pick_input_linear(&self, rng: &Rng, shard: Option<(usize, usize)>)
+            -> usize {
+        let weight = |idx: usize| -> f64 {
+            if self.lineage.capped(&self.corpus, idx) {
+                return 0.;
+            }
+            let weight = self.schedule.weight(idx);
+
+            match shard {
+                Some((shard, num_shards)) if idx % num_shards == shard => {
+                    weight * SHARD_WEIGHT
+                }
+                _ => weight,
+            }
+        };
+
+        let total: f64 = (0..self.corpus.len()).map(weight).sum();
+
+        // Pick a random point in the total weight and find the input it
+        // falls into
+        let mut point = (rng.rand() as f64 / usize::MAX as f64) * total;
+        for idx in 0..self.corpus.len() {
+            let weight = weight(idx);
+            if point < weight {
+                return idx;
+            }
+            point -= weight;
+        }
+
+        rng.rand() % self.corpus.len()
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FuzzerAction {
-    LeftClick { idx: usize },
+    LeftClick { idx: ElementIdx },
     Close,
-    MenuAction { menu_id: u32 },
-    KeyPress { key: usize },
+    MenuAction { menu_id: MenuId, menu_set: u32 },
+    KeyPress { key: VirtualKey, focus: Option<ElementIdx> },
+    DialogCommand { control_id: u32 },
+    MessagePressure { count: u32, seed: u32 },
+    DisplayEvent { event: DisplayEvent, param: u32 },
+    SessionEvent { event: SessionEvent, param: u32 },
+    Accessibility { object_id: i32, child: u32 },
+    OleDrop { idx: ElementIdx, format: DropFormat, seed: u32 },
+    Place { placement: Placement, param: u32 },
+    KeyboardLayout { layout: u8 },
+    UiaPattern { idx: ElementIdx, pattern: UiaPattern },
+    PluginMacro { plugin: u8, id: u32, param: u32 },
+    Broadcast { event: BroadcastEvent, param: u32 },
+    Mdi { command: MdiCommand, param: u32 },
+    CopyData { tag: u32, seed: u32, len: u16 },
+    CustomMessage { idx: u16, wparam: u32, lparam: u32 },
+    Dde { command: DdeCommand, seed: u32, len: u16 },
+    ModifiedClick { idx: ElementIdx, state: KeyMouseState },
+    Hover { idx: ElementIdx, x: u16, y: u16, duration_ms: u16 },
+    KeyRepeat { key: VirtualKey, focus: Option<ElementIdx>, repeats: u16 },
+    Activation { transition: Transition, param: u32 },
+    PropertySheet { command: PropSheetCommand, param: u32 },
+    SwitchTab { idx: ElementIdx, tab: u16, focus: bool },
+    SetRange { idx: ElementIdx, bound: RangeBound, offset: i32 },
 }
 
-pub fn perform_actions(pid: u32,
-        actions: &[FuzzerAction]) -> Result<(), Box<dyn Error>>{
-    // Attach to the Calculator window
-    let primary_window = Window::attach_pid(pid, "Calculator")?;
-
-    for &action in actions {
-        match action {
-            FuzzerAction::LeftClick { idx } => {
-                // Click on the GUI element
-                let sub_windows = primary_window.enumerate_subwindows();
-                if sub_windows.is_err() {
-                    return Ok(());
+impl FuzzerAction {
+    /// Name of the type of this action, without its parameters
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FuzzerAction::LeftClick { .. }       => "LeftClick",
+            FuzzerAction::Close                  => "Close",
+            FuzzerAction::MenuAction { .. }      => "MenuAction",
+            FuzzerAction::KeyPress { .. }        => "KeyPress",
+            FuzzerAction::DialogCommand { .. }   => "DialogCommand",
+            FuzzerAction::MessagePressure { .. } => "MessagePressure",
+            FuzzerAction::DisplayEvent { .. }    => "DisplayEvent",
+            FuzzerAction::SessionEvent { .. }    => "SessionEvent",
+            FuzzerAction::Accessibility { .. }   => "Accessibility",
+            FuzzerAction::OleDrop { .. }         => "OleDrop",
+            FuzzerAction::Place { .. }           => "Place",
+            FuzzerAction::KeyboardLayout { .. }  => "KeyboardLayout",
+            FuzzerAction::UiaPattern { .. }      => "UiaPattern",
+            FuzzerAction::PluginMacro { .. }     => "PluginMacro",
+            FuzzerAction::Broadcast { .. }       => "Broadcast",
+            FuzzerAction::Mdi { .. }             => "Mdi",
+            FuzzerAction::CopyData { .. }        => "CopyData",
+            FuzzerAction::CustomMessage { .. }   => "CustomMessage",
+            FuzzerAction::Dde { .. }             => "Dde",
+            FuzzerAction::ModifiedClick { .. }   => "ModifiedClick",
+            FuzzerAction::Hover { .. }           => "Hover",
+            FuzzerAction::KeyRepeat { .. }       => "KeyRepeat",
+            FuzzerAction::Activation { .. }      => "Activation",
+            FuzzerAction::PropertySheet { .. }   => "PropertySheet",
+            FuzzerAction::SwitchTab { .. }       => "SwitchTab",
+            FuzzerAction::SetRange { .. }        => "SetRange",
+        }
+    }
+}
+
+/// Keyboard layouts switched between by `FuzzerAction::KeyboardLayout`,
+/// covering different scripts, right-to-left languages, dead keys, and IMEs
+const KEYBOARD_LAYOUTS: &[&str] = &[
+    "00000409", // English (US)
+    "00010409", // English (US, Dvorak)
+    "00000407", // German, dead keys
+    "0000040c", // French (AZERTY)
+    "00000419", // Russian
+    "0000040d", // Hebrew
+    "00000401", // Arabic
+    "00000439", // Hindi
+    "00000411", // Japanese IME
+    "00000804", // Chinese (Simplified) IME
+    "00000412", // Korean IME
+];
+
+/// Switch `window` and the calling thread to keyboard layout `layout`, an
+/// index into `KEYBOARD_LAYOUTS`
+fn switch_keyboard_layout(window: &Window, layout: u8) -> io::Result<()> {
+    let klid = KEYBOARD_LAYOUTS[layout as usize % KEYBOARD_LAYOUTS.len()];
+    let hkl  = winbindings::load_keyboard_layout(klid)?;
+    winbindings::activate_keyboard_layout(hkl)?;
+    window.request_keyboard_layout(hkl)
+}
+
+/// System-wide display events which can be broadcast to the target, each
+/// exercising layout recalculation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DisplayEvent {
+    /// `WM_DPICHANGED`, the parameter is the new DPI
+    DpiChanged,
+
+    /// `WM_SETTINGCHANGE`, the parameter selects the `SPI_*` action in the
+    /// upper 24 bits and the changed settings area in the low 8 bits
+    SettingChange,
+
+    /// `WM_THEMECHANGED`, the parameter is unused
+    ThemeChanged,
+
+    /// `WM_DISPLAYCHANGE`, the parameter selects the bits per pixel and
+    /// resolution
+    DisplayChange,
+}
+
+/// Settings areas sent with `WM_SETTINGCHANGE`, selected by the low byte of
+/// the parameter. Out of range selects no area.
+const SETTING_SECTIONS: &[&str] = &[
+    "ImmersiveColorSet", "WindowMetrics", "intl", "Environment", "Policy",
+    "TraySettings", "WindowsThemeElement",
+];
+
+/// Power and session events which can be broadcast to the target, these
+/// exercise the rarely tested suspend and shutdown handlers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SessionEvent {
+    /// `WM_POWERBROADCAST`, the low 4 bits of the parameter select the
+    /// `PBT_*` event and the rest seed any power setting payload
+    PowerBroadcast,
+
+    /// `WM_QUERYENDSESSION`, the low 2 bits of the parameter select the
+    /// `ENDSESSION_*` flags
+    QueryEndSession,
+
+    /// `WM_ENDSESSION`, bit 0 of the parameter is whether the session is
+    /// ending and the next 2 bits select the `ENDSESSION_*` flags
+    EndSession,
+
+    /// `WM_DEVICECHANGE`, the low 4 bits of the parameter select the `DBT_*`
+    /// event and the rest craft the `DEV_BROADCAST_HDR` sent with it
+    DeviceChange,
+}
+
+/// `PBT_*` power events, including undefined ones
+const POWER_EVENTS: &[usize] = &[
+    0x0004, 0x0007, 0x0012, 0x000a, 0x000b, 0x8013, 0x0000, 0xffff,
+];
+
+/// `ENDSESSION_*` flag combinations
+const END_SESSION_FLAGS: &[usize] = &[
+    0x0000_0000, 0x0000_0001, 0x4000_0000, 0x8000_0000,
+];
+
+/// `DBT_*` device events, including undefined ones
+const DEVICE_EVENTS: &[usize] = &[
+    0x8000, 0x8004, 0x8001, 0x8003, 0x0007, 0x0018, 0x0017, 0x0019,
+    0x8006, 0xffff,
+];
+
+/// System broadcasts sent to targets which register for global
+/// notifications
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BroadcastEvent {
+    /// `WM_INPUTLANGCHANGE`, the parameter selects the keyboard layout and
+    /// character set
+    InputLangChange,
+
+    /// `WM_DEVICECHANGE` for a volume being plugged in or removed, the
+    /// parameter selects arrival or removal and the drive letters
+    Hotplug,
+
+    /// `WM_CLIPBOARDUPDATE`
+    ClipboardUpdate,
+
+    /// `WM_FONTCHANGE`
+    FontChange,
+}
+
+/// `DBT_DEVICEARRIVAL` and `DBT_DEVICEREMOVECOMPLETE`
+const HOTPLUG_EVENTS: &[usize] = &[0x8000, 0x8004];
+
+/// Send the system broadcast `event` with `param` to all top-level windows
+/// of `pid`
+fn broadcast_event(pid: u32, event: BroadcastEvent, param: u32)
+        -> Result<(), Box<dyn Error>> {
+    for window in Window::enumerate_pid_windows(pid)?.iter() {
+        let _ = match event {
+            BroadcastEvent::InputLangChange => {
+                let klid = KEYBOARD_LAYOUTS[
+                    (param & 0xff) as usize % KEYBOARD_LAYOUTS.len()];
+                let hkl = winbindings::load_keyboard_layout(klid)?;
+                window.post_message(MessageType::InputLangChange,
+                    ((param >> 8) & 0xff) as usize, hkl)
+            }
+            BroadcastEvent::Hotplug => {
+                // `DEV_BROADCAST_VOLUME` with the unit mask of the drives
+                // and the media flag
+                let mut volume = Vec::new();
+                volume.extend_from_slice(&0x12u32.to_le_bytes());
+                volume.extend_from_slice(&2u32.to_le_bytes());
+                volume.extend_from_slice(&0u32.to_le_bytes());
+                volume.extend_from_slice(&(param >> 1).to_le_bytes());
+                volume.extend_from_slice(&((param & 1) as u16).to_le_bytes());
+                window.send_remote(MessageType::DeviceChange,
+                    HOTPLUG_EVENTS[(param >> 31) as usize], &volume)
+                    .map(|_| ())
+            }
+            BroadcastEvent::ClipboardUpdate => {
+                window.post_message(MessageType::ClipboardUpdate, 0, 0)
+            }
+            BroadcastEvent::FontChange => {
+                window.post_message(MessageType::FontChange, 0, 0)
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Broadcast the power or session `event` with `param` to all top-level
+/// windows of `pid`
+fn session_event(pid: u32, event: SessionEvent, param: u32)
+        -> Result<(), Box<dyn Error>> {
+    let pick = |table: &[usize], bits: u32| {
+        table[(param & ((1 << bits) - 1)) as usize % table.len()]
+    };
+
+    for window in Window::enumerate_pid_windows(pid)?.iter() {
+        let _ = match event {
+            SessionEvent::PowerBroadcast => {
+                let pbt = pick(POWER_EVENTS, 4);
+
+                // `PBT_POWERSETTINGCHANGE` carries a pointer to a
+                // `POWERBROADCAST_SETTING`, craft one from the parameter
+                if pbt == 0x8013 {
+                    let mut setting = vec![0u8; 16];
+                    setting.extend_from_slice(&4u32.to_le_bytes());
+                    setting.extend_from_slice(&(param >> 4).to_le_bytes());
+                    window.send_remote(MessageType::PowerBroadcast, pbt,
+                        &setting).map(|_| ())
+                } else {
+                    window.post_message(MessageType::PowerBroadcast, pbt, 0)
                 }
-                let sub_windows = sub_windows.unwrap();
+            }
+            SessionEvent::QueryEndSession => {
+                window.post_message(MessageType::QueryEndSession, 0,
+                    pick(END_SESSION_FLAGS, 2))
+            }
+            SessionEvent::EndSession => {
+                window.post_message(MessageType::EndSession,
+                    (param & 1) as usize,
+                    END_SESSION_FLAGS[((param >> 1) & 3) as usize])
+            }
+            SessionEvent::DeviceChange => {
+                let dbt = pick(DEVICE_EVENTS, 4);
 
-                if let Some(window) = sub_windows.get(idx) {
-                    let _ = window.left_click(None);
+                // Events for a specific device carry a pointer to a
+                // `DEV_BROADCAST_HDR`. Craft the size and device type, but
+                // keep the buffer large enough for any size we claim.
+                if dbt & 0x8000 != 0 {
+                    let mut header = vec![0u8; 0x100];
+                    header[0..4].copy_from_slice(
+                        &((param >> 4) & 0xff).to_le_bytes());
+                    header[4..8].copy_from_slice(
+                        &((param >> 12) & 0x7).to_le_bytes());
+                    window.send_remote(MessageType::DeviceChange, dbt,
+                        &header).map(|_| ())
+                } else {
+                    window.post_message(MessageType::DeviceChange, dbt, 0)
                 }
             }
-            FuzzerAction::Close => {
-                let _ = primary_window.close();
+        };
+    }
+
+    Ok(())
+}
+
+/// Broadcast the display `event` with `param` to all top-level windows of
+/// `pid`
+fn display_event(pid: u32, event: DisplayEvent, param: u32)
+        -> Result<(), Box<dyn Error>> {
+    for window in Window::enumerate_pid_windows(pid)?.iter() {
+        let _ = match event {
+            DisplayEvent::DpiChanged => {
+                // Suggest a window size scaled to the new DPI
+                let dpi   = (param & 0x3ff) as u16;
+                let scale = dpi as i32;
+                window.send_dpi_changed(dpi,
+                    (0, 0, 640 * scale / 96, 480 * scale / 96))
+            }
+            DisplayEvent::SettingChange => {
+                let section = SETTING_SECTIONS.get((param & 0xff) as usize);
+                window.send_setting_change(param >> 8, section.cloned())
             }
-            FuzzerAction::MenuAction { menu_id } => {
-                // Select a random menu item and click it
-                let _ = primary_window.use_menu_id(menu_id);
-                std::thread::sleep(std::time::Duration::from_millis(250));
+            DisplayEvent::ThemeChanged => {
+                window.post_message(MessageType::ThemeChanged, 0, 0)
             }
-            FuzzerAction::KeyPress { key } => {
-                // Press a key on the keyboard
-                let _ = primary_window.press_key(key);
+            DisplayEvent::DisplayChange => {
+                let bpp    = (param & 0x3f) as usize;
+                let width  = ((param >> 6) & 0x1fff) as usize;
+                let height = ((param >> 19) & 0x1fff) as usize;
+                window.post_message(MessageType::DisplayChange, bpp,
+                    (height << 16) | width)
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Flood `window` and its sub-windows with `count` `WM_TIMER`, `WM_PAINT`,
+/// and `WM_SIZE` messages, at most `MAX_MESSAGE_PRESSURE`. The messages and
+/// their parameters are picked deterministically from `seed` so the flood
+/// can be reproduced.
+fn message_pressure(window: &Window, count: u32, seed: u32) {
+    // Offset the seed so it's never zero, xorshift gets stuck on zero
+    let rng = Rng::seeded(seed as u64 ^ 0x9e37_79b9_7f4a_7c15);
+
+    let mut targets = vec![*window];
+    if let Ok(sub_windows) = window.enumerate_subwindows() {
+        targets.extend_from_slice(&sub_windows);
+    }
+
+    for _ in 0..count.min(MAX_MESSAGE_PRESSURE) {
+        let target = targets[rng.rand() % targets.len()];
+        let _ = match rng.rand() % 3 {
+            0 => target.post_message(MessageType::Timer, rng.rand() % 16, 0),
+            1 => target.post_message(MessageType::Paint, 0, 0),
+            _ => {
+                // Random resize type and new client width and height
+                let width  = rng.rand() & 0xffff;
+                let height = rng.rand() & 0xffff;
+                target.post_message(MessageType::Size, rng.rand() % 5,
+                    (height << 16) | width)
+            }
+        };
+    }
+}
+
+/// Find the sub-window of `window` with the keyboard focus, so key presses
+/// reach the control which would get them from a real keyboard. Returns
+/// the window to press keys in and the sub-window index to record, or
+/// `window` itself and `None` if no sub-window has the focus.
+fn focused_subwindow(window: &Window) -> (Window, Option<ElementIdx>) {
+    let focus = match window.focus() {
+        Some(focus) => focus,
+        None => return (*window, None),
+    };
+
+    window.enumerate_subwindows().ok()
+        .and_then(|sub_windows| {
+            sub_windows.iter().position(|x| x.hwnd() == focus.hwnd())
+        })
+        .map(|idx| (focus, Some(ElementIdx(idx))))
+        .unwrap_or((*window, None))
+}
+
+/// Perform `action` on the target `pid` with the main window
+/// `primary_window`, delivering clicks and key presses with `backend`.
+/// Returns `Ok(false)` if the action failed or was dropped. Errors are
+/// counted as failed actions too, the rest of the input is still performed.
+fn perform_action(pid: u32, primary_window: &Window, uia: &mut Option<Uia>,
+        action: FuzzerAction, backend: InputBackend)
+        -> Result<bool, Box<dyn Error>> {
+    match action {
+        FuzzerAction::LeftClick { idx } => {
+            // Click on the GUI element
+            // Skip the click rather than cutting the case short if the
+            // windows still can't be enumerated after retrying
+            let sub_windows = primary_window.enumerate_subwindows();
+            if sub_windows.is_err() {
+                return Ok(false);
+            }
+            let sub_windows = sub_windows.unwrap();
+
+            Ok(idx.get(&sub_windows)
+                .filter(|window| elevation::allow_click(window))
+                .map(|window| window.left_click_with(backend, None).is_ok())
+                .unwrap_or(false))
+        }
+        FuzzerAction::ModifiedClick { idx, state } => {
+            // Click on the GUI element with shift or control held down
+            let sub_windows = match primary_window.enumerate_subwindows() {
+                Ok(sub_windows) => sub_windows,
+                Err(_) => return Ok(false),
+            };
+
+            Ok(idx.get(&sub_windows)
+                .filter(|window| elevation::allow_click(window))
+                .map(|window| {
+                    window.left_click_with(backend, Some(state)).is_ok()
+                })
+                .unwrap_or(false))
+        }
+        FuzzerAction::Hover { idx, x, y, duration_ms } => {
+            // Hover over the GUI element, or the main window if it's gone
+            let window = primary_window.enumerate_subwindows().ok()
+                .and_then(|x| idx.get(&x).cloned())
+                .unwrap_or(*primary_window);
+            Ok(hover(&window, x, y, duration_ms).is_ok())
+        }
+        FuzzerAction::Close => {
+            Ok(primary_window.close().is_ok())
+        }
+        FuzzerAction::MenuAction { menu_id, menu_set } => {
+            // Find the item the action was captured as if the menus
+            // changed since, then click it, unless probing shows it isn't
+            // in the current menus
+            let menu_id = menusets::resolve(primary_window, menu_id,
+                menu_set);
+            if !menuprobe::validate(primary_window, menu_id) {
+                return Ok(false);
+            }
+            let ok = primary_window.use_menu_id(menu_id).is_ok();
+            std::thread::sleep(Pacing::global().menu_settle);
+            Ok(ok)
+        }
+        FuzzerAction::KeyPress { key, focus } => {
+            // Press a key on the keyboard, in the sub-window which had the
+            // focus when the input was created
+            let window = focus.and_then(|idx| {
+                idx.get(&primary_window.enumerate_subwindows().ok()?)
+                    .cloned()
+            }).unwrap_or(*primary_window);
+            Ok(window.press_key_with(backend, key).is_ok())
+        }
+        FuzzerAction::KeyRepeat { key, focus, repeats } => {
+            // Hold a key down in the sub-window which had the focus when
+            // the input was created
+            let window = focus.and_then(|idx| {
+                idx.get(&primary_window.enumerate_subwindows().ok()?)
+                    .cloned()
+            }).unwrap_or(*primary_window);
+            let repeats = repeats % (winbindings::MAX_KEY_REPEATS + 1);
+            Ok(window.hold_key_with(backend, key, repeats).is_ok())
+        }
+        FuzzerAction::DialogCommand { control_id } => {
+            // Send the command to all open dialogs with this control, none
+            // if they went away while being enumerated
+            let mut ok = false;
+            for dialog in Window::enumerate_dialogs(pid).unwrap_or_default() {
+                ok |= dialog.use_control_id(control_id).is_ok();
+            }
+            Ok(ok)
+        }
+        FuzzerAction::MessagePressure { count, seed } => {
+            message_pressure(primary_window, count, seed);
+            Ok(true)
+        }
+        FuzzerAction::DisplayEvent { event, param } => {
+            Ok(display_event(pid, event, param).is_ok())
+        }
+        FuzzerAction::SessionEvent { event, param } => {
+            Ok(session_event(pid, event, param).is_ok())
+        }
+        FuzzerAction::Accessibility { object_id, child } => {
+            Ok(accessibility::exercise(primary_window, object_id,
+                child).is_ok())
+        }
+        FuzzerAction::OleDrop { idx, format, seed } => {
+            // Drop onto the GUI element, or the main window if it's
+            // gone
+            let window = primary_window.enumerate_subwindows().ok()
+                .and_then(|x| idx.get(&x).cloned())
+                .unwrap_or(*primary_window);
+            Ok(dragdrop::ole_drop(&window, format, seed).is_ok())
+        }
+        FuzzerAction::Place { placement, param } => {
+            Ok(placement::place(primary_window, placement, param)
+                .is_ok())
+        }
+        FuzzerAction::KeyboardLayout { layout } => {
+            Ok(switch_keyboard_layout(primary_window, layout).is_ok())
+        }
+        FuzzerAction::UiaPattern { idx, pattern } => {
+            // Drop the action if UI Automation is unavailable, it is
+            // connected again for the next one
+            if uia.is_none() {
+                *uia = Uia::new().ok();
             }
+            let elements = match uia.as_ref()
+                    .map(|uia| uia.elements(primary_window)) {
+                Some(Ok(elements)) => elements,
+                _ => return Ok(false),
+            };
+
+            Ok(idx.get(&elements)
+                .map(|element| element.invoke(pattern).is_ok())
+                .unwrap_or(false))
+        }
+        FuzzerAction::Broadcast { event, param } => {
+            Ok(broadcast_event(pid, event, param).is_ok())
+        }
+        FuzzerAction::Mdi { command, param } => {
+            Ok(mdi::perform(primary_window, command, param).is_ok())
+        }
+        FuzzerAction::PropertySheet { command, param } => {
+            Ok(propsheet::perform(pid, command, param).is_ok())
+        }
+        FuzzerAction::SwitchTab { idx, tab, focus } => {
+            // Only switch tabs of the tab control, the element may be
+            // another control if the window's layout changed
+            let window = primary_window.enumerate_subwindows().ok()
+                .and_then(|x| idx.get(&x).cloned())
+                .filter(tabs::is_tab_control);
+            Ok(window.map(|x| tabs::switch(&x, tab, focus).is_ok())
+                .unwrap_or(false))
+        }
+        FuzzerAction::SetRange { idx, bound, offset } => {
+            let window = primary_window.enumerate_subwindows().ok()
+                .and_then(|x| idx.get(&x).cloned());
+            Ok(window.map(|x| ranges::set(&x, bound, offset).is_ok())
+                .unwrap_or(false))
+        }
+        FuzzerAction::Activation { transition, param } => {
+            Ok(activation::perform(primary_window, transition, param,
+                backend).is_ok())
+        }
+        FuzzerAction::CopyData { tag, seed, len } => {
+            Ok(ipc::send_copydata(primary_window, tag, seed, len).is_ok())
+        }
+        FuzzerAction::CustomMessage { idx, wparam, lparam } => {
+            Ok(ipc::post_custom_message(primary_window, idx, wparam, lparam)
+                .is_ok())
+        }
+        FuzzerAction::Dde { command, seed, len } => {
+            Ok(dde::perform(pid, command, seed, len).is_ok())
+        }
+        FuzzerAction::PluginMacro { plugin, id, param } => {
+            Ok(plugin::plugins().get(plugin as usize).map(|plugin| {
+                plugin.perform_macro(pid, primary_window, id, param)
+            }).is_some())
+        }
+    }
+}
+
+pub fn perform_actions(pid: u32,
+        actions: &[FuzzerAction]) -> Result<(), Box<dyn Error>>{
+    perform_actions_with(pid, actions, InputBackend::PostMessage)
+}
+
+/// Set the prologue performed at the start of every case by
+/// `perform_prologue()`
+pub fn set_prologue(actions: Vec<FuzzerAction>) {
+    *PROLOGUE.lock().unwrap() = actions;
+}
+
+/// Get the prologue set with `set_prologue()`
+pub fn prologue() -> Vec<FuzzerAction> {
+    PROLOGUE.lock().unwrap().clone()
+}
+
+/// Perform the prologue on the target `pid` with `backend`, bringing it to
+/// the UI state cases start from. Inputs are performed after it both when
+/// fuzzing and when replaying.
+pub fn perform_prologue(pid: u32, backend: InputBackend)
+        -> Result<(), Box<dyn Error>> {
+    let prologue = PROLOGUE.lock().unwrap().clone();
+    if prologue.is_empty() {
+        return Ok(());
+    }
+    perform_actions_with(pid, &prologue, backend)
+}
+
+/// Set the actions performed by `verify_epilogue()`, empty to disable it
+pub fn set_epilogue(actions: Vec<FuzzerAction>) {
+    *EPILOGUE.lock().unwrap() = actions;
+}
+
+/// Perform the epilogue on the target `pid` with the main window `window`
+/// using `backend`, and check the target still responds to it by changing
+/// its window tree. Returns why the target didn't respond, or `None` if it
+/// did or no epilogue is set.
+pub fn verify_epilogue(pid: u32, window: &Window, backend: InputBackend)
+        -> Option<String> {
+    let epilogue = EPILOGUE.lock().unwrap().clone();
+    if epilogue.is_empty() {
+        return None;
+    }
+
+    let before = window.dump_tree();
+    if let Err(err) = perform_actions_with(pid, &epilogue, backend) {
+        return Some(format!("Epilogue failed: {}", err));
+    }
+    let _ = window.wait_for_idle(Pacing::global().idle_timeout);
+
+    if windowtree::diff(&before, &window.dump_tree()).is_empty() {
+        return Some("Epilogue didn't change the window tree".into());
+    }
+    None
+}
+
+/// Mark the target `pid` as abandoned, making `perform_actions()` and
+/// `generator()` stop acting on it, or clear the mark once they returned
+pub fn abandon_target(pid: u32, abandoned: bool) {
+    let mut targets = ABANDONED_TARGETS.lock().unwrap();
+    targets.retain(|&x| x != pid);
+    if abandoned {
+        targets.push(pid);
+    }
+}
+
+/// Check whether the target `pid` was abandoned
+fn target_abandoned(pid: u32) -> bool {
+    ABANDONED_TARGETS.lock().unwrap().contains(&pid)
+}
+
+/// Print each action performed by `perform_actions()` along with the
+/// target's focus, capture, and caret afterwards
+pub fn set_trace_actions(enabled: bool) {
+    *TRACE_ACTIONS.lock().unwrap() = enabled;
+}
+
+/// Make `perform_actions()` wait `delay` after each action, zero to not
+/// wait
+pub fn set_action_delay(delay: Duration) {
+    *ACTION_DELAY.lock().unwrap() = delay;
+}
+
+/// Get the delay set with `set_action_delay()`
+pub fn action_delay() -> Duration {
+    *ACTION_DELAY.lock().unwrap()
+}
+
+/// Perform `actions` on the target `pid`, delivering clicks and key presses
+/// with `backend`. Returns an `ActionTimeout` error if an action missed its
+/// deadline, after dismissing the target's dialogs.
+pub fn perform_actions_with(pid: u32, actions: &[FuzzerAction],
+        backend: InputBackend) -> Result<(), Box<dyn Error>> {
+    perform_actions_impl(pid, actions, backend, None)
+}
+
+/// Perform `actions` on the target `pid` like `perform_actions()`, storing
+/// the index of the action being performed in `current` so coverage can be
+/// attributed to it. The target is given time to go idle after each
+/// action, so what it does in response happens under the action's index.
+pub fn perform_actions_tracked(pid: u32, actions: &[FuzzerAction],
+        current: &AtomicUsize) -> Result<(), Box<dyn Error>> {
+    perform_actions_impl(pid, actions, InputBackend::PostMessage,
+        Some(current))
+}
+
+/// Implementation of `perform_actions_with()` and
+/// `perform_actions_tracked()`
+fn perform_actions_impl(pid: u32, actions: &[FuzzerAction],
+        backend: InputBackend, current: Option<&AtomicUsize>)
+        -> Result<(), Box<dyn Error>> {
+    // Attach to the target's main window
+    let primary_window = TargetConfig::global().attach(pid)?;
+
+    // Real input goes to whichever window is in the foreground, keep the
+    // other workers from taking it
+    let _desktop = if backend == InputBackend::SendInput {
+        Some(winbindings::lock_desktop())
+    } else {
+        None
+    };
+
+    // UI Automation connection, created on first use
+    let mut uia = None;
+
+    for (index, &action) in actions.iter().enumerate() {
+        if target_abandoned(pid) {
+            break;
+        }
+        if let Some(current) = current {
+            current.store(index, Ordering::SeqCst);
+        }
+
+        // Never send anything to a window which isn't the target's, its
+        // handle may have been reused after the target went away
+        primary_window.verify_owner(pid)?;
+
+        // Count failed and dropped actions so it's visible when an action
+        // type doesn't work against the target, and go on with the rest of
+        // the input
+        let start = Instant::now();
+        let watchdog = timeout::Watchdog::arm(pid);
+        let performed = perform_action(pid, &primary_window, &mut uia, action,
+            backend).unwrap_or(false);
+        actionstats::record(action.kind(), performed);
+
+        if current.is_some() {
+            let _ = primary_window.wait_for_idle(Pacing::global().idle_timeout);
+        }
+
+        if *TRACE_ACTIONS.lock().unwrap() {
+            let state = primary_window.gui_thread_info()
+                .map(|x| x.to_string())
+                .unwrap_or_else(|err| err.to_string());
+            println!("Action {} {}: {}", index, msgnames::describe(&action),
+                state);
+        }
+
+        // Don't waste the rest of the case on a target stuck in a modal
+        // loop
+        if watchdog.expired() ||
+                timeout::action_expired(&primary_window, start) {
+            timeout::dismiss_dialogs(pid);
+            return Err(Box::new(ActionTimeout {
+                index,
+                kind: action.kind(),
+            }));
+        }
+        drop(watchdog);
+
+        let delay = action_delay();
+        if delay > Duration::from_secs(0) {
+            std::thread::sleep(delay);
         }
     }
 
     Ok(())
 }
 
-pub fn mutate(stats: Arc<Mutex<Statistics>>)
-        -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
-    // Create a new RNG
-    let rng = Rng::new();
+/// Hover over `window` at the fractions `x`, `y` of its client area, for
+/// `duration_ms` milliseconds, wrapped to at most `MAX_HOVER_MS`
+fn hover(window: &Window, x: u16, y: u16, duration_ms: u16)
+        -> io::Result<()> {
+    let (x, y) = coords::resolve_point(window, x, y)?;
+    let duration = duration_ms % (winbindings::MAX_HOVER_MS + 1);
+    window.hover(x, y, Duration::from_millis(duration as u64))
+}
+
+/// Pick a random UI Automation element below `window` and invoke one of the
+/// patterns it supports, returning the action performed. Returns `None` if
+/// the element supports no patterns.
+fn uia_action(uia: &Uia, window: &Window, rng: &Rng) -> Option<FuzzerAction> {
+    let elements = uia.elements(window).ok()?;
+    if elements.is_empty() {
+        return None;
+    }
 
+    let idx = rng.rand() % elements.len();
+    let supported: Vec<UiaPattern> = uia::PATTERNS.iter().cloned()
+        .filter(|&pattern| elements[idx].supports(pattern)).collect();
+    if supported.is_empty() {
+        return None;
+    }
+
+    let pattern = supported[rng.rand() % supported.len()];
+    let _ = elements[idx].invoke(pattern);
+    Some(FuzzerAction::UiaPattern { idx: ElementIdx(idx), pattern })
+}
+
+/// Error returned by `mutate()` when there is nothing in the corpus to
+/// mutate, and a new input should be generated instead
+#[derive(Debug)]
+pub struct NeedsGeneration;
+
+impl fmt::Display for NeedsGeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Corpus has nothing to mutate, an input must be generated")
+    }
+}
+
+impl Error for NeedsGeneration {}
+
+/// Create a new input by mutating inputs from the corpus, favoring inputs
+/// from the corpus `shard` if one is given (see `Statistics::select_input()`).
+/// The parent, mutations, and seed are recorded in `provenance`.
+/// Returns a `NeedsGeneration` error if the corpus has no usable inputs.
+pub fn mutate(stats: Arc<Mutex<Statistics>>, shard: Option<(usize, usize)>,
+              provenance: &mut Provenance)
+        -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
     // Get access to the global database
-    let stats = stats.lock().unwrap();
+    let mut stats = stats.lock().unwrap();
+    mutate_with_rng(&mut stats, &Rng::new(), shard, provenance)
+}
+
+/// Implementation of `mutate()` using `rng` for all random decisions, so
+/// a mutation can be reproduced from the RNG's seed. Outputs are at most
+/// `MAX_INPUT_LEN` actions long.
+pub fn mutate_with_rng(stats: &mut Statistics, rng: &Rng,
+                       shard: Option<(usize, usize)>,
+                       provenance: &mut Provenance)
+        -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
+    provenance.seed = rng.seed();
+
+    if stats.corpus.is_empty() {
+        return Err(Box::new(NeedsGeneration));
+    }
 
     // Pick an input to use as the basis of this fuzz case
-    let input_sel = rng.rand() % stats.input_list.len();
-    let mut input: Vec<FuzzerAction> = (*stats.input_list[input_sel]).clone();
+    let input_sel = stats.select_input(rng, shard);
+    let parent = stats.corpus.load(input_sel)?;
+    let mut input: Vec<FuzzerAction> = (*parent).clone();
+    provenance.parent = Some(parent);
 
     // Make up to n modifications, minimum of one
     for _ in 0..((rng.rand() & 0x1f) + 1) {
@@ -111,8 +1207,8 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
                     input.len());
 
                 // Select a random slice from a random input
-                let donor_idx    = rng.rand() % stats.input_list.len();
-                let donor_input  = &stats.input_list[donor_idx];
+                let donor_idx    = rng.rand() % stats.corpus.len();
+                let donor_input  = stats.corpus.load(donor_idx)?;
                 if donor_input.len() == 0 { continue; }
 
                 let donor_start  = rng.rand() % donor_input.len();
@@ -124,6 +1220,7 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
                 input.splice(inp_start..inp_end, 
                     donor_input[donor_start..donor_end]
                     .iter().cloned());
+                provenance.mutations.push("splice");
             }
             1 => {
                 // Delete a random portion from the input
@@ -137,6 +1234,7 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
 
                 // Delete this slice from the input
                 input.splice(inp_start..inp_end, [].iter().cloned());
+                provenance.mutations.push("delete");
             }
             2 => {
                 // Repeat a certain part of the slice many times
@@ -145,6 +1243,7 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
                 for _ in 0..rng.rand() % (rng.rand() % 64 + 1) {
                     input.insert(sel, input[sel]);
                 }
+                provenance.mutations.push("repeat");
             }
             3 => {
                 // Insert a random slice into the vector
@@ -154,8 +1253,8 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
                 let inp_index = rng.rand() % input.len();
 
                 // Select a random slice from a random input
-                let donor_idx    = rng.rand() % stats.input_list.len();
-                let donor_input  = &stats.input_list[donor_idx];
+                let donor_idx    = rng.rand() % stats.corpus.len();
+                let donor_input  = stats.corpus.load(donor_idx)?;
                 if donor_input.len() == 0 { continue; }
                 let donor_start  = rng.rand() % donor_input.len();
                 let donor_length = rng.rand() % (rng.rand() % 64 + 1);
@@ -169,64 +1268,465 @@ pub fn mutate(stats: Arc<Mutex<Statistics>>)
 
                 // Replace the input with this newly created input
                 input = new_inp;
+                provenance.mutations.push("insert_slice");
             }
             4 => {
                 if stats.unique_actions.len() == 0 ||
                     input.len() == 0 { continue; }
 
-                // Get a random action
-                let rand_action = stats.unique_actions[
+                // Get a random action, avoiding menu IDs which probing
+                // found to never be valid and actions which keep hanging
+                // the target
+                let mut rand_action = stats.unique_actions[
                     rng.rand() % stats.unique_actions.len()];
+                for _ in 0..4 {
+                    match rand_action {
+                        FuzzerAction::MenuAction { menu_id, .. }
+                                if menuprobe::is_wasted(menu_id) => {}
+                        _ if !blacklist::allow(&rand_action, rng) => {}
+                        _ => break,
+                    }
+                    rand_action = stats.unique_actions[
+                        rng.rand() % stats.unique_actions.len()];
+                }
 
                 // Add the action to the input
                 input.insert(rng.rand() % input.len(), rand_action);
+                provenance.mutations.push("insert_action");
             }
             _ => panic!("Unreachable"),
         }
     }
 
+    // Only empty inputs in the corpus, or we deleted everything
+    if input.is_empty() {
+        return Err(Box::new(NeedsGeneration));
+    }
+
+    // Don't let inputs grow without bound over generations
+    input.truncate(MAX_INPUT_LEN);
+
     Ok(input)
 }
 
-pub fn generator(pid: u32) -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
+/// Generate a new input by performing random actions on the target, using
+/// the actions enabled in `config`. The seed is recorded in `provenance`.
+pub fn generator(pid: u32, resources: &Resources, config: &Config,
+                 provenance: &mut Provenance)
+        -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
     // Log of all actions performed
     let mut actions = Vec::new();
 
     // Create an RNG
     let rng = Rng::new();
+    provenance.seed = rng.seed();
 
-    // Attach to the Calculator window
-    let primary_window = Window::attach_pid(pid, "Calculator")?;
+    // Attach to the target's main window
+    let primary_window = TargetConfig::global().attach(pid)?;
 
-    loop {
-        {
+    // Connect to UI Automation if enabled, falling back to raw clicks if it
+    // is unavailable
+    let uia = if config.uia { Uia::new().ok() } else { None };
+
+    // Page each open property sheet is switched to next, by its window
+    let mut next_pages: HashMap<usize, u32> = HashMap::new();
+
+    // Tab each tab control is switched to next, by its window
+    let mut next_tabs: HashMap<usize, u16> = HashMap::new();
+
+    // Plugins with macros to run, by their index
+    let plugins = plugin::plugins();
+    let with_macros: Vec<usize> = (0..plugins.len())
+        .filter(|&idx| plugins[idx].macro_count() > 0).collect();
+
+    while !target_abandoned(pid) {
+        // Stop once the window is no longer the target's, its handle may
+        // have been reused after the target went away
+        if primary_window.verify_owner(pid).is_err() {
+            break;
+        }
+
+        // Most of the time, prefer operating a control through one of its
+        // UI Automation patterns over clicking on it
+        let semantic = uia.as_ref().filter(|_| rng.rand() & 3 != 0)
+            .and_then(|uia| uia_action(uia, &primary_window, &rng));
+        if let Some(action) = semantic {
+            actions.push(action);
+        } else {
             // Pick a random GUI element to click on
             let sub_windows = primary_window.enumerate_subwindows();
             if sub_windows.is_err() {
                 return Ok(actions);
             }
             let sub_windows = sub_windows.unwrap();
+            if sub_windows.is_empty() {
+                return Ok(actions);
+            }
 
             let sel = rng.rand() % sub_windows.len();
             let window = sub_windows[sel];
 
-            // Click on the GUI element
-            actions.push(FuzzerAction::LeftClick { idx: sel });
-            let _ = window.left_click(None);
+            // Click on the GUI element, unless it raises a UAC prompt.
+            // Occasionally hold shift or control down, which many apps
+            // bind special behavior to.
+            if elevation::allow_click(&window) {
+                if config.modifier_clicks && (rng.rand() & 0x7) == 0 {
+                    let modifiers = rng.rand() % 3;
+                    let state = KeyMouseState {
+                        shift:   modifiers != 1,
+                        control: modifiers != 0,
+                        ..Default::default()
+                    };
+                    actions.push(FuzzerAction::ModifiedClick {
+                        idx: ElementIdx(sel),
+                        state,
+                    });
+                    let _ = window.left_click(Some(state));
+                } else {
+                    actions.push(FuzzerAction::LeftClick {
+                        idx: ElementIdx(sel)
+                    });
+                    let _ = window.left_click(None);
+                }
+            }
+        }
+
+        // Type text when an edit control has the focus, plain key presses
+        // rarely get its parsing anywhere
+        let (focus, focus_idx) = focused_subwindow(&primary_window);
+        if focus_idx.is_some() && focus.is_edit() && rng.rand() & 0x3 == 0 {
+            for _ in 0..rng.rand() % 16 + 1 {
+                let key = VirtualKey::from(
+                    TEXT_KEYS[rng.rand() % TEXT_KEYS.len()]);
+                actions.push(FuzzerAction::KeyPress { key, focus: focus_idx });
+                let _ = focus.press_key(key);
+            }
         }
 
         {
             // Press a random key on the keyboard
-            let key = ((rng.rand() % 10) as u8 + b'0') as usize;
-            actions.push(FuzzerAction::KeyPress { key });
-            let _ = primary_window.press_key(key);
+            let key = VirtualKey::from((rng.rand() % 10) as u8 + b'0');
+            let (window, focus) = focused_subwindow(&primary_window);
+            let action = FuzzerAction::KeyPress { key, focus };
+            if blacklist::allow(&action, &rng) {
+                actions.push(action);
+                let _ = window.press_key(key);
+            }
         }
 
         if rng.rand() & 0x1f == 0 {
             // Press a random key on the keyboard
-            let key = rng.rand() as u8 as usize;
-            actions.push(FuzzerAction::KeyPress { key });
-            let _ = primary_window.press_key(key);
+            let key = VirtualKey::from(rng.rand() as u8);
+            let (window, focus) = focused_subwindow(&primary_window);
+            let action = FuzzerAction::KeyPress { key, focus };
+            if blacklist::allow(&action, &rng) {
+                actions.push(action);
+                let _ = window.press_key(key);
+            }
+        }
+
+        // Chance of holding a key down until it auto-repeats, for the
+        // repeat handling of editors and spinners
+        if config.key_repeat && (rng.rand() & 0x1f) == 0 {
+            let key = VirtualKey::from(
+                REPEAT_KEYS[rng.rand() % REPEAT_KEYS.len()]);
+            let repeats = (rng.rand() %
+                (winbindings::MAX_KEY_REPEATS as usize + 1)) as u16;
+            let (window, focus) = focused_subwindow(&primary_window);
+            let action = FuzzerAction::KeyRepeat { key, focus, repeats };
+            if blacklist::allow(&action, &rng) {
+                actions.push(action);
+                let _ = window.hold_key_with(InputBackend::PostMessage, key,
+                    repeats);
+            }
+        }
+
+        // Chance of switching the target to the background and back, or
+        // moving the focus, possibly in the middle of an operation
+        if config.activation && (rng.rand() & 0x1f) == 0 {
+            let transition = match rng.rand() % 4 {
+                0 => Transition::Deactivate,
+                1 => Transition::Activate,
+                2 => Transition::Cycle,
+                _ => Transition::FocusSwap,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::Activation { transition, param });
+            let _ = activation::perform(&primary_window, transition, param,
+                InputBackend::PostMessage);
+        }
+
+        // Chance of flooding the target with timer, paint, and size messages
+        if config.message_pressure && (rng.rand() & 0x7) == 0 {
+            let count = rng.rand() as u32 % MAX_MESSAGE_PRESSURE + 1;
+            let seed  = rng.rand() as u32;
+            actions.push(FuzzerAction::MessagePressure { count, seed });
+            message_pressure(&primary_window, count, seed);
+        }
+
+        // Chance of broadcasting a DPI, setting, theme, or display change
+        if config.display_events && (rng.rand() & 0x1f) == 0 {
+            let event = match rng.rand() % 4 {
+                0 => DisplayEvent::DpiChanged,
+                1 => DisplayEvent::SettingChange,
+                2 => DisplayEvent::ThemeChanged,
+                _ => DisplayEvent::DisplayChange,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::DisplayEvent { event, param });
+            let _ = display_event(pid, event, param);
+        }
+
+        // Chance of broadcasting a power or session event
+        if config.session_events && (rng.rand() & 0x1f) == 0 {
+            let event = match rng.rand() % 4 {
+                0 => SessionEvent::PowerBroadcast,
+                1 => SessionEvent::QueryEndSession,
+                2 => SessionEvent::EndSession,
+                _ => SessionEvent::DeviceChange,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::SessionEvent { event, param });
+            let _ = session_event(pid, event, param);
+        }
+
+        // Chance of sending a system broadcast from the broadcast pack
+        if config.broadcasts && (rng.rand() & 0x1f) == 0 {
+            let event = match rng.rand() % 4 {
+                0 => BroadcastEvent::InputLangChange,
+                1 => BroadcastEvent::Hotplug,
+                2 => BroadcastEvent::ClipboardUpdate,
+                _ => BroadcastEvent::FontChange,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::Broadcast { event, param });
+            let _ = broadcast_event(pid, event, param);
+        }
+
+        // Chance of exercising an accessible object from `WM_GETOBJECT`
+        if config.accessibility && (rng.rand() & 0x1f) == 0 {
+            let object_id = accessibility::OBJECT_IDS[
+                rng.rand() % accessibility::OBJECT_IDS.len()];
+            let child = rng.rand() as u32;
+            actions.push(FuzzerAction::Accessibility { object_id, child });
+            let _ = accessibility::exercise(&primary_window, object_id,
+                child);
+        }
+
+        // Chance of dropping a fuzzed OLE data object on a GUI element
+        if config.ole_drops && (rng.rand() & 0x1f) == 0 {
+            if let Ok(sub_windows) = primary_window.enumerate_subwindows() {
+                let idx = ElementIdx(rng.rand() % (sub_windows.len() + 1));
+                let window = idx.get(&sub_windows).cloned()
+                    .unwrap_or(primary_window);
+                let format = match rng.rand() % 3 {
+                    0 => DropFormat::Text,
+                    1 => DropFormat::Html,
+                    _ => DropFormat::FileDescriptor,
+                };
+                let seed = rng.rand() as u32;
+                actions.push(FuzzerAction::OleDrop { idx, format, seed });
+                let _ = dragdrop::ole_drop(&window, format, seed);
+            }
+        }
+
+        // Chance of hovering over a GUI element to bring up its tooltip or
+        // hover preview
+        if config.hover && (rng.rand() & 0x1f) == 0 {
+            if let Ok(sub_windows) = primary_window.enumerate_subwindows() {
+                let idx = ElementIdx(rng.rand() % (sub_windows.len() + 1));
+                let window = idx.get(&sub_windows).cloned()
+                    .unwrap_or(primary_window);
+                let x = rng.rand() as u16;
+                let y = rng.rand() as u16;
+                let duration_ms = rng.rand() as u16;
+                actions.push(FuzzerAction::Hover { idx, x, y, duration_ms });
+                let _ = hover(&window, x, y, duration_ms);
+            }
+        }
+
+        // Chance of moving the window somewhere unusual
+        if config.placement && (rng.rand() & 0x1f) == 0 {
+            let placement = match rng.rand() % 5 {
+                0 => Placement::Extreme,
+                1 => Placement::Span,
+                2 => Placement::Maximize,
+                3 => Placement::Minimize,
+                _ => Placement::Restore,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::Place { placement, param });
+            let _ = placement::place(&primary_window, placement, param);
+        }
+
+        // Chance of creating, switching between, or closing MDI child
+        // documents, if the target has an MDI client area
+        if config.mdi && (rng.rand() & 0xf) == 0 &&
+                mdi::find_client(&primary_window).is_some() {
+            let command = match rng.rand() % 12 {
+                0..=2     => MdiCommand::Create,
+                3 | 4     => MdiCommand::Activate,
+                5         => MdiCommand::Next,
+                6         => MdiCommand::Destroy,
+                7         => MdiCommand::Maximize,
+                8         => MdiCommand::Restore,
+                9         => MdiCommand::Cascade,
+                10        => MdiCommand::Tile,
+                _         => MdiCommand::IconArrange,
+            };
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::Mdi { command, param });
+            let _ = mdi::perform(&primary_window, command, param);
+        }
+
+        // Chance of working through an open property sheet, switching to
+        // each of its pages in turn and using their controls, which clicks
+        // on the first page never reach
+        if config.property_sheets && (rng.rand() & 0x3) == 0 {
+            let sheets = propsheet::find(pid);
+            if !sheets.is_empty() {
+                let sel = rng.rand() % sheets.len();
+                let next_page = next_pages.entry(sheets[sel].hwnd())
+                    .or_insert(0u32);
+                let (command, payload) = match rng.rand() % 16 {
+                    0..=3 => {
+                        // Sheets open on their first page, start from the
+                        // second
+                        *next_page += 1;
+                        (PropSheetCommand::SetPage, *next_page)
+                    }
+                    4..=9 => (PropSheetCommand::UseControl, rng.rand() as u32),
+                    10 => (PropSheetCommand::Changed, 0),
+                    11 => (PropSheetCommand::Unchanged, 0),
+                    12 => (PropSheetCommand::Apply, 0),
+                    13 => (PropSheetCommand::PressButton, rng.rand() as u32),
+                    14 => (PropSheetCommand::QuerySiblings, rng.rand() as u32),
+                    _  => (PropSheetCommand::CancelToClose, 0),
+                };
+                let param = ((sel as u32) << 24) | (payload & 0xff_ffff);
+                actions.push(FuzzerAction::PropertySheet { command, param });
+                let _ = propsheet::perform(pid, command, param);
+            }
+        }
+
+        // Chance of cycling a tab control to its next tab, so the content
+        // behind every tab is reached rather than only the default one's
+        if config.tab_controls && (rng.rand() & 0x3) == 0 {
+            let tab_controls: Vec<(usize, Window)> = primary_window
+                .enumerate_subwindows().map(|x| x.iter().cloned()
+                    .enumerate().filter(|(_, x)| tabs::is_tab_control(x))
+                    .collect())
+                .unwrap_or_default();
+            if !tab_controls.is_empty() {
+                let (sel, window) =
+                    tab_controls[rng.rand() % tab_controls.len()];
+
+                // Controls start on their first tab, start from the second.
+                // Occasionally change the selection without notifying the
+                // parent, desynchronizing the tab from its content.
+                let next_tab = next_tabs.entry(window.hwnd()).or_insert(0);
+                *next_tab = next_tab.wrapping_add(1);
+                let tab = *next_tab;
+                let focus = (rng.rand() & 0x7) != 0;
+
+                actions.push(FuzzerAction::SwitchTab {
+                    idx: ElementIdx(sel),
+                    tab,
+                    focus,
+                });
+                let _ = tabs::switch(&window, tab, focus);
+            }
+        }
+
+        // Chance of setting a trackbar, up-down control, or date-time
+        // picker to a value at or just past the bounds of its range
+        if config.range_controls && (rng.rand() & 0x3) == 0 {
+            let range_controls: Vec<(usize, Window)> = primary_window
+                .enumerate_subwindows().map(|x| x.iter().cloned()
+                    .enumerate().filter(|(_, x)| ranges::is_range_control(x))
+                    .collect())
+                .unwrap_or_default();
+            if !range_controls.is_empty() {
+                let (sel, window) =
+                    range_controls[rng.rand() % range_controls.len()];
+                let bound = match rng.rand() % 5 {
+                    0 | 1 => RangeBound::Min,
+                    2 | 3 => RangeBound::Max,
+                    _     => RangeBound::Absolute,
+                };
+                let offset = if (rng.rand() & 0x7) == 0 {
+                    rng.rand() as i32
+                } else {
+                    ranges::BOUNDARY_OFFSETS[
+                        rng.rand() % ranges::BOUNDARY_OFFSETS.len()]
+                };
+
+                actions.push(FuzzerAction::SetRange {
+                    idx: ElementIdx(sel),
+                    bound,
+                    offset,
+                });
+                let _ = ranges::set(&window, bound, offset);
+            }
+        }
+
+        // Chance of sending a `WM_COPYDATA` with a fuzzed payload, usually
+        // with a small application-defined tag
+        if config.copydata && (rng.rand() & 0x1f) == 0 {
+            let tag = if rng.rand() & 3 == 0 {
+                rng.rand() as u32
+            } else {
+                (rng.rand() % 16) as u32
+            };
+            let seed = rng.rand() as u32;
+            let len  = (rng.rand() % (ipc::MAX_COPYDATA_LEN + 1)) as u16;
+            actions.push(FuzzerAction::CopyData { tag, seed, len });
+            let _ = ipc::send_copydata(&primary_window, tag, seed, len);
+        }
+
+        // Chance of starting a DDE conversation and sending a fuzzed
+        // command or poke
+        if config.dde && (rng.rand() & 0x1f) == 0 {
+            let command = if rng.rand() & 1 == 0 {
+                DdeCommand::Execute
+            } else {
+                DdeCommand::Poke
+            };
+            let seed = rng.rand() as u32;
+            let len  = (rng.rand() % (dde::MAX_DDE_LEN + 1)) as u16;
+            actions.push(FuzzerAction::Dde { command, seed, len });
+            let _ = dde::perform(pid, command, seed, len);
+        }
+
+        // Chance of posting one of the configured registered messages
+        let custom_messages = ipc::custom_message_count();
+        if custom_messages > 0 && (rng.rand() & 0x1f) == 0 {
+            let idx    = (rng.rand() % custom_messages) as u16;
+            let wparam = rng.rand() as u32;
+            let lparam = rng.rand() as u32;
+            actions.push(FuzzerAction::CustomMessage { idx, wparam, lparam });
+            let _ = ipc::post_custom_message(&primary_window, idx, wparam,
+                lparam);
+        }
+
+        // Chance of switching to a different keyboard layout
+        if config.keyboard_layouts && (rng.rand() & 0x3f) == 0 {
+            let layout = (rng.rand() % KEYBOARD_LAYOUTS.len()) as u8;
+            actions.push(FuzzerAction::KeyboardLayout { layout });
+            let _ = switch_keyboard_layout(&primary_window, layout);
+        }
+
+        // Chance of running a macro from a target-specific plugin. Only
+        // draw from the RNG if there are any, so the same seed generates
+        // the same input with and without macro-less plugins loaded.
+        if !with_macros.is_empty() && (rng.rand() & 0xf) == 0 {
+            let idx   = with_macros[rng.rand() % with_macros.len()];
+            let id    = rng.rand() as u32 % plugins[idx].macro_count();
+            let param = rng.rand() as u32;
+            actions.push(FuzzerAction::PluginMacro {
+                plugin: idx as u8, id, param
+            });
+            plugins[idx].perform_macro(pid, &primary_window, id, param);
         }
 
         // Chance of randomly closing the application
@@ -237,18 +1737,64 @@ pub fn generator(pid: u32) -> Result<Vec<FuzzerAction>, Box<dyn Error>> {
 
         // Chance of randomly clicking a menu item
         if (rng.rand() & 0x1f) == 0 {
-            if let Ok(menus) = primary_window.enum_menus() {
+            let menus = primary_window.enum_menus().ok()
+                .filter(|x| !x.is_empty());
+            if let Some(menus) = menus {
                 // Get a list of all of the menu items in calc
-                let menus: Vec<u32> = menus.iter().cloned().collect();
+                let menus: Vec<MenuId> = menus.iter().cloned().collect();
+
+                // Menu set the item is picked from, so replays in another
+                // mode can find the same item
+                let menu_set = menusets::capture(&primary_window);
 
-                // Select a random menu item and click it
-                let sel = menus[rng.rand() % menus.len()];
-                actions.push(FuzzerAction::MenuAction { menu_id: sel });
+                // Select a random menu item and click it, rerolling menu
+                // items which keep hanging the target
+                let mut sel = menus[rng.rand() % menus.len()];
+                for _ in 0..4 {
+                    let action = FuzzerAction::MenuAction {
+                        menu_id: sel,
+                        menu_set,
+                    };
+                    if blacklist::allow(&action, &rng) {
+                        break;
+                    }
+                    sel = menus[rng.rand() % menus.len()];
+                }
+                actions.push(FuzzerAction::MenuAction {
+                    menu_id: sel,
+                    menu_set,
+                });
                 let _ = primary_window.use_menu_id(sel);
 
-                std::thread::sleep(std::time::Duration::from_millis(250));
+                std::thread::sleep(Pacing::global().menu_settle);
+            }
+        }
+
+        // Chance of using a control in an open dialog, based on the control
+        // IDs from the dialog templates in the target's resources
+        if (rng.rand() & 0xf) == 0 {
+            for dialog in Window::enumerate_dialogs(pid).unwrap_or_default() {
+                // Find the dialog template matching this dialog, skipping
+                // dialogs which closed since they were enumerated
+                let title = match dialog.window_text() {
+                    Ok(title) => title,
+                    Err(_) => continue,
+                };
+                let template = resources.dialogs.iter().find(|x| {
+                    x.title == title && !x.controls.is_empty()
+                });
+
+                if let Some(template) = template {
+                    // Select a random control and click it
+                    let control_id = template.controls[
+                        rng.rand() % template.controls.len()];
+                    actions.push(FuzzerAction::DialogCommand { control_id });
+                    let _ = dialog.use_control_id(control_id);
+                }
             }
         }
     }
+
+    Ok(actions)
 }
 
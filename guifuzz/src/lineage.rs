@@ -0,0 +1,111 @@
+//! Lineage-aware scheduling, preventing the descendants of one seed from
+//! dominating which inputs get mutated
+
+use std::time::{Duration, Instant};
+use std::collections::HashMap;
+use crate::{Config, Corpus};
+
+/// Rules limiting how often inputs from one lineage are selected
+#[derive(Clone, Debug, Default)]
+pub struct LineagePolicy {
+    /// Maximum number of selections of inputs sharing a root ancestor per
+    /// window, `None` for no limit
+    pub max_selections: Option<u64>,
+
+    /// Length of the window `max_selections` applies to
+    pub window: Duration,
+
+    /// Inputs are weighted by `1 / (1 + depth)^depth_penalty`, favoring
+    /// shallow genealogies. Zero disables the penalty.
+    pub depth_penalty: f64,
+}
+
+impl LineagePolicy {
+    /// Create a lineage policy from the options in `config`
+    pub fn from_config(config: &Config) -> Self {
+        LineagePolicy {
+            max_selections: config.lineage_cap,
+            window:         Duration::from_secs(config.lineage_window_secs),
+            depth_penalty:  config.lineage_depth_penalty,
+        }
+    }
+}
+
+/// Tracks selections per lineage and applies a `LineagePolicy` to input
+/// selection weights
+#[derive(Default)]
+pub struct LineageScheduler {
+    /// Policy to enforce
+    policy: LineagePolicy,
+
+    /// Time the current window started, `None` if no window is active
+    window_start: Option<Instant>,
+
+    /// Number of selections in the current window keyed by root ancestor
+    selections: HashMap<usize, u64>,
+}
+
+impl LineageScheduler {
+    /// Create a new scheduler enforcing `policy`
+    pub fn new(policy: LineagePolicy) -> Self {
+        LineageScheduler {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Replace the policy being enforced
+    pub fn set_policy(&mut self, policy: LineagePolicy) {
+        self.policy = policy;
+    }
+
+    /// Get the policy being enforced
+    pub fn policy(&self) -> &LineagePolicy {
+        &self.policy
+    }
+
+    /// Get the weight multiplier for selecting the input at `idx` in
+    /// `corpus`. Zero if its lineage has hit its selection cap.
+    pub fn weight(&self, corpus: &Corpus, idx: usize) -> f64 {
+        if self.capped(corpus, idx) {
+            return 0.;
+        }
+        self.depth_weight(corpus, idx)
+    }
+
+    /// Get the weight multiplier from the depth penalty alone for the input
+    /// at `idx` in `corpus`, which only changes with the policy
+    pub fn depth_weight(&self, corpus: &Corpus, idx: usize) -> f64 {
+        let depth = corpus.depth(idx).unwrap_or(0);
+        1. / (1. + depth as f64).powf(self.policy.depth_penalty)
+    }
+
+    /// Returns `true` if the lineage of the input at `idx` in `corpus` has
+    /// hit its selection cap in the current window
+    pub fn capped(&self, corpus: &Corpus, idx: usize) -> bool {
+        let max = match self.policy.max_selections {
+            Some(max) => max,
+            None => return false,
+        };
+        let root = corpus.root(idx).unwrap_or(idx);
+        self.selections.get(&root).cloned().unwrap_or(0) >= max
+    }
+
+    /// Record that the input at `idx` in `corpus` was selected
+    pub fn record(&mut self, corpus: &Corpus, idx: usize) {
+        if self.policy.max_selections.is_none() {
+            return;
+        }
+
+        // Start a new window if the current one has expired
+        let expired = self.window_start
+            .map(|x| x.elapsed() >= self.policy.window).unwrap_or(true);
+        if expired {
+            self.window_start = Some(Instant::now());
+            self.selections.clear();
+        }
+
+        let root = corpus.root(idx).unwrap_or(idx);
+        *self.selections.entry(root).or_insert(0) += 1;
+    }
+}
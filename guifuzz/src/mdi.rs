@@ -0,0 +1,163 @@
+//! Multiple document interface fuzzing, creating, switching between, and
+//! closing the child documents of targets with an MDI client area
+
+use std::io;
+use crate::Window;
+use crate::winbindings::{MessageType, str_to_utf16};
+
+/// Class name of the window hosting MDI child windows
+const MDI_CLIENT_CLASS: &str = "MDIClient";
+
+/// `CW_USEDEFAULT`, letting the MDI client pick a position or size
+const CW_USEDEFAULT: i32 = i32::MIN;
+
+/// Styles for new MDI children, selected by the parameter: plain, maximized,
+/// minimized, hidden, and with scroll bars
+const CHILD_STYLES: &[u32] = &[
+    0x00000000, 0x01000000, 0x20000000, 0x10000000, 0x00300000,
+];
+
+/// Operations on the children of an MDI client
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MdiCommand {
+    /// `WM_MDICREATE` a new child of the same class as an existing one. The
+    /// parameter selects the template child, style, and size.
+    Create,
+
+    /// `WM_MDIACTIVATE` the child selected by the parameter
+    Activate,
+
+    /// `WM_MDINEXT`, the top bit of the parameter selects the previous
+    /// child rather than the next one
+    Next,
+
+    /// `WM_MDIDESTROY` the child selected by the parameter
+    Destroy,
+
+    /// `WM_MDIMAXIMIZE` the child selected by the parameter
+    Maximize,
+
+    /// `WM_MDIRESTORE` the child selected by the parameter
+    Restore,
+
+    /// `WM_MDICASCADE`, the parameter selects the `MDITILE_*` flags
+    Cascade,
+
+    /// `WM_MDITILE`, the parameter selects the `MDITILE_*` flags
+    Tile,
+
+    /// `WM_MDIICONARRANGE`
+    IconArrange,
+}
+
+/// `MDICREATESTRUCTW`, passed by pointer with `WM_MDICREATE`
+#[repr(C)]
+struct MdiCreateStruct {
+    class:  usize,
+    title:  usize,
+    owner:  usize,
+    x:      i32,
+    y:      i32,
+    cx:     i32,
+    cy:     i32,
+    style:  u32,
+    lparam: usize,
+}
+
+/// Find the MDI client area of `window`, if the target has one
+pub fn find_client(window: &Window) -> Option<Window> {
+    window.enumerate_subwindows().ok()?.iter().find(|x| {
+        x.class_name().map(|x| x == MDI_CLIENT_CLASS).unwrap_or(false)
+    }).cloned()
+}
+
+/// Perform the MDI `command` with `param` on the MDI client of `window`
+pub fn perform(window: &Window, command: MdiCommand, param: u32)
+        -> io::Result<()> {
+    let client = find_client(window).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "No MDI client area")
+    })?;
+
+    // Select the child to operate on
+    let children = client.children();
+    let child = if children.is_empty() {
+        None
+    } else {
+        Some(children[param as usize % children.len()])
+    };
+    let child = || child.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "No MDI children")
+    });
+
+    match command {
+        MdiCommand::Create => {
+            // New children can only be created from a class the target has
+            // registered, so borrow the class of an existing child
+            let template = child()?;
+            let class = str_to_utf16(&template.class_name()
+                .map_err(|x| io::Error::other(x.to_string()))?);
+            let title = str_to_utf16(&format!("guifuzz {}", param));
+
+            let to_bytes = |x: &[u16]| -> Vec<u8> {
+                x.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+            };
+            let class = client.remote_write(&to_bytes(&class))?;
+            let title = client.remote_write(&to_bytes(&title))?;
+
+            let size = if param & 0x100 == 0 {
+                CW_USEDEFAULT
+            } else {
+                ((param >> 16) & 0xfff) as i32
+            };
+            let create = MdiCreateStruct {
+                class,
+                title,
+                owner:  template.instance(),
+                x:      CW_USEDEFAULT,
+                y:      CW_USEDEFAULT,
+                cx:     size,
+                cy:     size,
+                style:  CHILD_STYLES[
+                    (param >> 9) as usize % CHILD_STYLES.len()],
+                lparam: 0,
+            };
+            let bytes = unsafe {
+                std::slice::from_raw_parts(
+                    &create as *const MdiCreateStruct as *const u8,
+                    std::mem::size_of::<MdiCreateStruct>())
+            };
+            let create = client.remote_write(bytes)?;
+
+            client.post_message(MessageType::MdiCreate, 0, create)
+        }
+        MdiCommand::Activate => {
+            client.post_message(MessageType::MdiActivate, child()?.hwnd(), 0)
+        }
+        MdiCommand::Next => {
+            client.post_message(MessageType::MdiNext, child()?.hwnd(),
+                (param >> 31) as usize)
+        }
+        MdiCommand::Destroy => {
+            client.post_message(MessageType::MdiDestroy, child()?.hwnd(), 0)
+        }
+        MdiCommand::Maximize => {
+            client.post_message(MessageType::MdiMaximize, child()?.hwnd(), 0)
+        }
+        MdiCommand::Restore => {
+            client.post_message(MessageType::MdiRestore, child()?.hwnd(), 0)
+        }
+        MdiCommand::Cascade => {
+            client.post_message(MessageType::MdiCascade,
+                (param & 0x6) as usize, 0)
+        }
+        MdiCommand::Tile => {
+            client.post_message(MessageType::MdiTile,
+                (param & 0x7) as usize, 0)
+        }
+        MdiCommand::IconArrange => {
+            client.post_message(MessageType::MdiIconArrange, 0, 0)
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Menu ID validity probing. Menu IDs from the resource dictionary or from
+//! older inputs are often stale once the target switches modes, so they are
+//! checked against the menus actually present before being used. Menu sets
+//! are cached per window state, and IDs which are never valid are avoided
+//! by the mutator.
+
+use std::collections::{BTreeSet, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use crate::{Window, MenuId};
+
+/// Number of invalid attempts, without any valid ones, after which a menu
+/// ID is considered a waste of an action
+const WASTED_ATTEMPTS: u64 = 4;
+
+/// Probing state, `None` while probing is disabled. Shared between all
+/// threads so every worker learns from the others.
+static MENU_PROBE: Mutex<Option<MenuProbe>> = Mutex::new(None);
+
+/// Cached menu sets and validity statistics of menu IDs
+#[derive(Default)]
+struct MenuProbe {
+    /// Menu IDs present in each window state
+    menus: HashMap<u64, BTreeSet<MenuId>>,
+
+    /// Number of (valid, invalid) attempts to use each menu ID
+    attempts: HashMap<MenuId, (u64, u64)>,
+}
+
+/// Enable menu ID probing for all threads
+pub fn enable() {
+    let mut probe = MENU_PROBE.lock().unwrap();
+    if probe.is_none() {
+        *probe = Some(MenuProbe::default());
+    }
+}
+
+/// Identify the menu state of `window` by its class, title, and number of
+/// top-level menu items, which is the same across target instances
+fn menu_state(window: &Window) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    window.class_name().unwrap_or_default().hash(&mut hasher);
+    window.window_text().unwrap_or_default().hash(&mut hasher);
+    window.menu_item_count().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Check whether `menu_id` is present in the current menus of `window`,
+/// recording the attempt. Always returns `true` if probing is disabled.
+pub fn validate(window: &Window, menu_id: MenuId) -> bool {
+    if MENU_PROBE.lock().unwrap().is_none() {
+        return true;
+    }
+
+    // Enumerate outside of the lock, menus of a hung target can take a
+    // while
+    let state = menu_state(window);
+    let cached = MENU_PROBE.lock().unwrap().as_ref()
+        .and_then(|probe| probe.menus.get(&state).cloned());
+    let menus = match cached {
+        Some(menus) => menus,
+        None => window.enum_menus().unwrap_or_default(),
+    };
+
+    let valid = menus.contains(&menu_id);
+
+    let mut probe = MENU_PROBE.lock().unwrap();
+    if let Some(probe) = probe.as_mut() {
+        probe.menus.entry(state).or_insert(menus);
+
+        let attempts = probe.attempts.entry(menu_id).or_insert((0, 0));
+        if valid {
+            attempts.0 += 1;
+        } else {
+            attempts.1 += 1;
+        }
+    }
+
+    valid
+}
+
+/// Returns `true` if `menu_id` has repeatedly been invalid and never valid,
+/// so actions using it are likely wasted
+pub fn is_wasted(menu_id: MenuId) -> bool {
+    MENU_PROBE.lock().unwrap().as_ref()
+        .and_then(|probe| probe.attempts.get(&menu_id).cloned())
+        .map(|(valid, invalid)| valid == 0 && invalid >= WASTED_ATTEMPTS)
+        .unwrap_or(false)
+}
+
+/// Total number of invalid menu ID attempts recorded
+pub fn invalid_attempts() -> u64 {
+    MENU_PROBE.lock().unwrap().as_ref()
+        .map(|probe| probe.attempts.values().map(|x| x.1).sum())
+        .unwrap_or(0)
+}
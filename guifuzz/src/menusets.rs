@@ -0,0 +1,134 @@
+//! Menu sets, the menus a window has in one of its modes. Applications swap
+//! menus when switching modes, after which a menu ID may stand for another
+//! item or for nothing at all. Each `MenuAction` records the menu set it was
+//! captured under, and if the window's menus differ when it is performed,
+//! its menu ID is re-resolved through the text of the item it was captured
+//! as.
+//!
+//! Menu sets are identified by a hash of their IDs and item texts which is
+//! the same across builds, so they can be persisted and used by later runs.
+
+use std::io::{self, Write};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::collections::{BTreeMap, HashMap};
+use crate::{Window, MenuId};
+
+/// Menu IDs of a menu set and the text of their items
+type MenuSet = BTreeMap<MenuId, String>;
+
+/// All menu sets seen, keyed by their hash
+static MENU_SETS: Mutex<Option<HashMap<u32, MenuSet>>> = Mutex::new(None);
+
+/// File new menu sets are appended to, if persisted
+static MENU_SETS_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Hash of `set`, FNV-1a so it is the same across builds. Never 0, which
+/// stands for an unknown menu set.
+fn set_hash(set: &MenuSet) -> u32 {
+    let hash = set.iter()
+        .flat_map(|(id, text)| {
+            id.0.to_le_bytes().to_vec().into_iter()
+                .chain(text.bytes()).chain(std::iter::once(0))
+        })
+        .fold(0x811c_9dc5u32, |hash, x| {
+            (hash ^ x as u32).wrapping_mul(0x0100_0193)
+        });
+    hash.max(1)
+}
+
+/// Record `set` with the hash `hash`, appending it to the persisted menu
+/// sets if it is new
+fn insert(hash: u32, set: MenuSet) {
+    let mut sets = MENU_SETS.lock().unwrap();
+    let sets = sets.get_or_insert_with(HashMap::new);
+    if sets.contains_key(&hash) {
+        return;
+    }
+
+    if let Some(path) = MENU_SETS_FILE.lock().unwrap().as_ref() {
+        let lines: Vec<String> = set.iter()
+            .map(|(id, text)| format!("{:08x} {:#x} {}\n", hash, id.0, text))
+            .collect();
+        let _ = OpenOptions::new().create(true).append(true).open(path)
+            .and_then(|mut file| file.write_all(lines.concat().as_bytes()));
+    }
+    sets.insert(hash, set);
+}
+
+/// Load the menu sets persisted in `path`, if it exists, and append menu
+/// sets seen from now on to it
+pub fn persist(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        let mut loaded: HashMap<u32, MenuSet> = HashMap::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let mut fields = line.splitn(3, ' ');
+            let hash = fields.next()
+                .and_then(|x| u32::from_str_radix(x, 16).ok());
+            let id = fields.next()
+                .and_then(|x| u32::from_str_radix(
+                    x.trim_start_matches("0x"), 16).ok());
+            if let (Some(hash), Some(id)) = (hash, id) {
+                loaded.entry(hash).or_default()
+                    .insert(MenuId(id), fields.next().unwrap_or("").into());
+            }
+        }
+
+        let mut sets = MENU_SETS.lock().unwrap();
+        let sets = sets.get_or_insert_with(HashMap::new);
+        for (hash, set) in loaded {
+            sets.entry(hash).or_insert(set);
+        }
+    }
+
+    *MENU_SETS_FILE.lock().unwrap() = Some(path.to_path_buf());
+    Ok(())
+}
+
+/// Capture the current menu set of `window`, returning its hash, or 0 if
+/// the window has no menu
+pub fn capture(window: &Window) -> u32 {
+    let set: MenuSet = match window.enum_menu_items() {
+        Ok(items) if !items.is_empty() => items.into_iter().collect(),
+        _ => return 0,
+    };
+
+    let hash = set_hash(&set);
+    insert(hash, set);
+    hash
+}
+
+/// Resolve `menu_id`, captured under the menu set `menu_set`, to the ID of
+/// the same menu item in the current menus of `window`. The ID is kept if
+/// the menus didn't change, the menu set is unknown, or no item with the
+/// same text is found.
+pub fn resolve(window: &Window, menu_id: MenuId, menu_set: u32) -> MenuId {
+    if menu_set == 0 {
+        return menu_id;
+    }
+
+    let text = MENU_SETS.lock().unwrap().as_ref()
+        .and_then(|sets| sets.get(&menu_set))
+        .and_then(|set| set.get(&menu_id).cloned());
+    let text = match text {
+        Some(text) if !text.is_empty() => text,
+        _ => return menu_id,
+    };
+
+    let current = capture(window);
+    if current == menu_set {
+        return menu_id;
+    }
+
+    let sets = MENU_SETS.lock().unwrap();
+    let set = match sets.as_ref().and_then(|sets| sets.get(&current)) {
+        Some(set) => set,
+        None => return menu_id,
+    };
+    if set.get(&menu_id) == Some(&text) {
+        return menu_id;
+    }
+    set.iter().find(|(_, x)| **x == text).map(|(&id, _)| id)
+        .unwrap_or(menu_id)
+}
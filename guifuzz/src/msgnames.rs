@@ -0,0 +1,411 @@
+//! Names of window messages and virtual keys, so traces, audit logs, and
+//! crash bundles read `WM_COMMAND(id=0x131)` rather than bare numbers.
+//! Messages without a known name print as hex, and every non-zero field is
+//! printed, so nothing is lost over the raw numbers.
+
+use std::fmt;
+use crate::{FuzzerAction, ipc, coords};
+
+/// Names of window messages, sorted by message number
+const MESSAGES: &[(u32, &str)] = &[
+    (0x0000, "WM_NULL"),
+    (0x0001, "WM_CREATE"),
+    (0x0002, "WM_DESTROY"),
+    (0x0003, "WM_MOVE"),
+    (0x0005, "WM_SIZE"),
+    (0x0006, "WM_ACTIVATE"),
+    (0x0007, "WM_SETFOCUS"),
+    (0x0008, "WM_KILLFOCUS"),
+    (0x000a, "WM_ENABLE"),
+    (0x000b, "WM_SETREDRAW"),
+    (0x000c, "WM_SETTEXT"),
+    (0x000d, "WM_GETTEXT"),
+    (0x000e, "WM_GETTEXTLENGTH"),
+    (0x000f, "WM_PAINT"),
+    (0x0010, "WM_CLOSE"),
+    (0x0011, "WM_QUERYENDSESSION"),
+    (0x0012, "WM_QUIT"),
+    (0x0014, "WM_ERASEBKGND"),
+    (0x0015, "WM_SYSCOLORCHANGE"),
+    (0x0016, "WM_ENDSESSION"),
+    (0x0018, "WM_SHOWWINDOW"),
+    (0x001a, "WM_SETTINGCHANGE"),
+    (0x001c, "WM_ACTIVATEAPP"),
+    (0x001d, "WM_FONTCHANGE"),
+    (0x0020, "WM_SETCURSOR"),
+    (0x0021, "WM_MOUSEACTIVATE"),
+    (0x0024, "WM_GETMINMAXINFO"),
+    (0x002b, "WM_DRAWITEM"),
+    (0x002c, "WM_MEASUREITEM"),
+    (0x0030, "WM_SETFONT"),
+    (0x0031, "WM_GETFONT"),
+    (0x003d, "WM_GETOBJECT"),
+    (0x0046, "WM_WINDOWPOSCHANGING"),
+    (0x0047, "WM_WINDOWPOSCHANGED"),
+    (0x004a, "WM_COPYDATA"),
+    (0x004e, "WM_NOTIFY"),
+    (0x0050, "WM_INPUTLANGCHANGEREQUEST"),
+    (0x0051, "WM_INPUTLANGCHANGE"),
+    (0x0053, "WM_HELP"),
+    (0x007b, "WM_CONTEXTMENU"),
+    (0x007e, "WM_DISPLAYCHANGE"),
+    (0x0080, "WM_SETICON"),
+    (0x0081, "WM_NCCREATE"),
+    (0x0082, "WM_NCDESTROY"),
+    (0x0083, "WM_NCCALCSIZE"),
+    (0x0084, "WM_NCHITTEST"),
+    (0x0085, "WM_NCPAINT"),
+    (0x0086, "WM_NCACTIVATE"),
+    (0x0087, "WM_GETDLGCODE"),
+    (0x00a0, "WM_NCMOUSEMOVE"),
+    (0x00a1, "WM_NCLBUTTONDOWN"),
+    (0x00a2, "WM_NCLBUTTONUP"),
+    (0x00b0, "EM_GETSEL"),
+    (0x00b1, "EM_SETSEL"),
+    (0x00c2, "EM_REPLACESEL"),
+    (0x00c5, "EM_LIMITTEXT"),
+    (0x00c7, "EM_UNDO"),
+    (0x00f0, "BM_GETCHECK"),
+    (0x00f1, "BM_SETCHECK"),
+    (0x00f2, "BM_GETSTATE"),
+    (0x00f3, "BM_SETSTATE"),
+    (0x00f5, "BM_CLICK"),
+    (0x00ff, "WM_INPUT"),
+    (0x0100, "WM_KEYDOWN"),
+    (0x0101, "WM_KEYUP"),
+    (0x0102, "WM_CHAR"),
+    (0x0104, "WM_SYSKEYDOWN"),
+    (0x0105, "WM_SYSKEYUP"),
+    (0x0106, "WM_SYSCHAR"),
+    (0x0110, "WM_INITDIALOG"),
+    (0x0111, "WM_COMMAND"),
+    (0x0112, "WM_SYSCOMMAND"),
+    (0x0113, "WM_TIMER"),
+    (0x0114, "WM_HSCROLL"),
+    (0x0115, "WM_VSCROLL"),
+    (0x0116, "WM_INITMENU"),
+    (0x0117, "WM_INITMENUPOPUP"),
+    (0x011f, "WM_MENUSELECT"),
+    (0x0120, "WM_MENUCHAR"),
+    (0x0121, "WM_ENTERIDLE"),
+    (0x0132, "WM_CTLCOLORMSGBOX"),
+    (0x0133, "WM_CTLCOLOREDIT"),
+    (0x0134, "WM_CTLCOLORLISTBOX"),
+    (0x0135, "WM_CTLCOLORBTN"),
+    (0x0136, "WM_CTLCOLORDLG"),
+    (0x0137, "WM_CTLCOLORSCROLLBAR"),
+    (0x0138, "WM_CTLCOLORSTATIC"),
+    (0x0143, "CB_ADDSTRING"),
+    (0x0144, "CB_DELETESTRING"),
+    (0x0146, "CB_GETCOUNT"),
+    (0x0147, "CB_GETCURSEL"),
+    (0x014b, "CB_RESETCONTENT"),
+    (0x014e, "CB_SETCURSEL"),
+    (0x014f, "CB_SHOWDROPDOWN"),
+    (0x0170, "STM_SETICON"),
+    (0x0172, "STM_SETIMAGE"),
+    (0x0180, "LB_ADDSTRING"),
+    (0x0182, "LB_DELETESTRING"),
+    (0x0184, "LB_RESETCONTENT"),
+    (0x0186, "LB_SETCURSEL"),
+    (0x0188, "LB_GETCURSEL"),
+    (0x018b, "LB_GETCOUNT"),
+    (0x0200, "WM_MOUSEMOVE"),
+    (0x0201, "WM_LBUTTONDOWN"),
+    (0x0202, "WM_LBUTTONUP"),
+    (0x0203, "WM_LBUTTONDBLCLK"),
+    (0x0204, "WM_RBUTTONDOWN"),
+    (0x0205, "WM_RBUTTONUP"),
+    (0x0206, "WM_RBUTTONDBLCLK"),
+    (0x0207, "WM_MBUTTONDOWN"),
+    (0x0208, "WM_MBUTTONUP"),
+    (0x020a, "WM_MOUSEWHEEL"),
+    (0x0210, "WM_PARENTNOTIFY"),
+    (0x0211, "WM_ENTERMENULOOP"),
+    (0x0212, "WM_EXITMENULOOP"),
+    (0x0216, "WM_MOVING"),
+    (0x0218, "WM_POWERBROADCAST"),
+    (0x0219, "WM_DEVICECHANGE"),
+    (0x0220, "WM_MDICREATE"),
+    (0x0221, "WM_MDIDESTROY"),
+    (0x0222, "WM_MDIACTIVATE"),
+    (0x0223, "WM_MDIRESTORE"),
+    (0x0224, "WM_MDINEXT"),
+    (0x0225, "WM_MDIMAXIMIZE"),
+    (0x0226, "WM_MDITILE"),
+    (0x0227, "WM_MDICASCADE"),
+    (0x0228, "WM_MDIICONARRANGE"),
+    (0x0229, "WM_MDIGETACTIVE"),
+    (0x0231, "WM_ENTERSIZEMOVE"),
+    (0x0232, "WM_EXITSIZEMOVE"),
+    (0x0233, "WM_DROPFILES"),
+    (0x0281, "WM_IME_SETCONTEXT"),
+    (0x0282, "WM_IME_NOTIFY"),
+    (0x02a1, "WM_MOUSEHOVER"),
+    (0x02a3, "WM_MOUSELEAVE"),
+    (0x02e0, "WM_DPICHANGED"),
+    (0x0300, "WM_CUT"),
+    (0x0301, "WM_COPY"),
+    (0x0302, "WM_PASTE"),
+    (0x0303, "WM_CLEAR"),
+    (0x0304, "WM_UNDO"),
+    (0x030f, "WM_QUERYNEWPALETTE"),
+    (0x0311, "WM_PALETTECHANGED"),
+    (0x0312, "WM_HOTKEY"),
+    (0x031a, "WM_THEMECHANGED"),
+    (0x031d, "WM_CLIPBOARDUPDATE"),
+    (0x03e0, "WM_DDE_INITIATE"),
+    (0x03e1, "WM_DDE_TERMINATE"),
+    (0x03e2, "WM_DDE_ADVISE"),
+    (0x03e3, "WM_DDE_UNADVISE"),
+    (0x03e4, "WM_DDE_ACK"),
+    (0x03e5, "WM_DDE_DATA"),
+    (0x03e6, "WM_DDE_REQUEST"),
+    (0x03e7, "WM_DDE_POKE"),
+    (0x03e8, "WM_DDE_EXECUTE"),
+];
+
+/// `WM_USER`, private messages of window classes start here
+const WM_USER: u32 = 0x0400;
+
+/// `WM_APP`, private messages of applications start here
+const WM_APP: u32 = 0x8000;
+
+/// First message number handed out by `RegisterWindowMessage()`
+const REGISTERED: u32 = 0xc000;
+
+/// Names of virtual keys which aren't a letter or digit
+const VIRTUAL_KEYS: &[(usize, &str)] = &[
+    (0x01, "VK_LBUTTON"),
+    (0x02, "VK_RBUTTON"),
+    (0x03, "VK_CANCEL"),
+    (0x04, "VK_MBUTTON"),
+    (0x08, "VK_BACK"),
+    (0x09, "VK_TAB"),
+    (0x0c, "VK_CLEAR"),
+    (0x0d, "VK_RETURN"),
+    (0x10, "VK_SHIFT"),
+    (0x11, "VK_CONTROL"),
+    (0x12, "VK_MENU"),
+    (0x13, "VK_PAUSE"),
+    (0x14, "VK_CAPITAL"),
+    (0x1b, "VK_ESCAPE"),
+    (0x20, "VK_SPACE"),
+    (0x21, "VK_PRIOR"),
+    (0x22, "VK_NEXT"),
+    (0x23, "VK_END"),
+    (0x24, "VK_HOME"),
+    (0x25, "VK_LEFT"),
+    (0x26, "VK_UP"),
+    (0x27, "VK_RIGHT"),
+    (0x28, "VK_DOWN"),
+    (0x2c, "VK_SNAPSHOT"),
+    (0x2d, "VK_INSERT"),
+    (0x2e, "VK_DELETE"),
+    (0x2f, "VK_HELP"),
+    (0x5b, "VK_LWIN"),
+    (0x5c, "VK_RWIN"),
+    (0x5d, "VK_APPS"),
+    (0x60, "VK_NUMPAD0"),
+    (0x61, "VK_NUMPAD1"),
+    (0x62, "VK_NUMPAD2"),
+    (0x63, "VK_NUMPAD3"),
+    (0x64, "VK_NUMPAD4"),
+    (0x65, "VK_NUMPAD5"),
+    (0x66, "VK_NUMPAD6"),
+    (0x67, "VK_NUMPAD7"),
+    (0x68, "VK_NUMPAD8"),
+    (0x69, "VK_NUMPAD9"),
+    (0x6a, "VK_MULTIPLY"),
+    (0x6b, "VK_ADD"),
+    (0x6c, "VK_SEPARATOR"),
+    (0x6d, "VK_SUBTRACT"),
+    (0x6e, "VK_DECIMAL"),
+    (0x6f, "VK_DIVIDE"),
+    (0x90, "VK_NUMLOCK"),
+    (0x91, "VK_SCROLL"),
+    (0xa0, "VK_LSHIFT"),
+    (0xa1, "VK_RSHIFT"),
+    (0xa2, "VK_LCONTROL"),
+    (0xa3, "VK_RCONTROL"),
+    (0xa4, "VK_LMENU"),
+    (0xa5, "VK_RMENU"),
+    (0xba, "VK_OEM_1"),
+    (0xbb, "VK_OEM_PLUS"),
+    (0xbc, "VK_OEM_COMMA"),
+    (0xbd, "VK_OEM_MINUS"),
+    (0xbe, "VK_OEM_PERIOD"),
+    (0xbf, "VK_OEM_2"),
+    (0xc0, "VK_OEM_3"),
+    (0xdb, "VK_OEM_4"),
+    (0xdc, "VK_OEM_5"),
+    (0xdd, "VK_OEM_6"),
+    (0xde, "VK_OEM_7"),
+];
+
+/// Get the name of the window message `msg`, if it has a well-known one
+pub fn message_name(msg: u32) -> Option<&'static str> {
+    MESSAGES.iter().find(|&&(x, _)| x == msg).map(|&(_, name)| name)
+}
+
+/// Get the name of the virtual key `key`, such as `VK_RETURN`, or the
+/// character for letters and digits
+pub fn key_name(key: usize) -> String {
+    match key {
+        0x30..=0x39 | 0x41..=0x5a => format!("'{}'", key as u8 as char),
+        0x70..=0x87 => format!("VK_F{}", key - 0x6f),
+        _ => VIRTUAL_KEYS.iter().find(|&&(x, _)| x == key)
+            .map(|&(_, name)| name.to_string())
+            .unwrap_or_else(|| format!("{:#04x}", key)),
+    }
+}
+
+/// A window message with its parameters, printed with its name and its
+/// parameters decoded for the common messages
+#[derive(Clone, Copy, Debug)]
+pub struct Message {
+    /// Message number
+    pub msg: u32,
+
+    /// `WPARAM` of the message
+    pub wparam: usize,
+
+    /// `LPARAM` of the message
+    pub lparam: usize,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (wparam, lparam) = (self.wparam, self.lparam);
+        let lo = |x: usize| x & 0xffff;
+        let hi = |x: usize| (x >> 16) & 0xffff;
+
+        match message_name(self.msg) {
+            Some(name) => write!(f, "{}", name)?,
+            None if self.msg >= REGISTERED => {
+                write!(f, "registered {:#06x}", self.msg)?
+            }
+            None if self.msg >= WM_APP => {
+                write!(f, "WM_APP+{:#x}", self.msg - WM_APP)?
+            }
+            None if self.msg >= WM_USER => {
+                write!(f, "WM_USER+{:#x}", self.msg - WM_USER)?
+            }
+            None => write!(f, "{:#06x}", self.msg)?,
+        }
+
+        let mut fields = Vec::new();
+        match self.msg {
+            // WM_COMMAND
+            0x0111 => {
+                fields.push(format!("id={:#x}", lo(wparam)));
+                if hi(wparam) != 0 || wparam >> 32 != 0 {
+                    fields.push(format!("code={:#x}", wparam >> 16));
+                }
+                if lparam != 0 {
+                    fields.push(format!("hwnd={:#x}", lparam));
+                }
+            }
+
+            // WM_KEYDOWN, WM_KEYUP, WM_SYSKEYDOWN, WM_SYSKEYUP
+            0x0100 | 0x0101 | 0x0104 | 0x0105 => {
+                fields.push(key_name(wparam));
+                if lparam != 0 {
+                    fields.push(format!("lparam={:#x}", lparam));
+                }
+            }
+
+            // Mouse messages with the cursor position in `LPARAM`
+            0x0200..=0x0209 => {
+                if wparam != 0 {
+                    fields.push(format!("keys={:#x}", wparam));
+                }
+                fields.push(format!("x={}", lo(lparam) as i16));
+                fields.push(format!("y={}", hi(lparam) as i16));
+                if lparam >> 32 != 0 {
+                    fields.push(format!("lparam={:#x}", lparam));
+                }
+            }
+
+            _ => {
+                if wparam != 0 {
+                    fields.push(format!("wparam={:#x}", wparam));
+                }
+                if lparam != 0 {
+                    fields.push(format!("lparam={:#x}", lparam));
+                }
+            }
+        }
+
+        if !fields.is_empty() {
+            write!(f, "({})", fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe `action` for humans, by the message it sends where there is
+/// one, falling back to its `Debug` representation
+pub fn describe(action: &FuzzerAction) -> String {
+    let command = |id: u32| Message {
+        msg: 0x0111, wparam: id as usize, lparam: 0,
+    };
+
+    match *action {
+        FuzzerAction::LeftClick { idx } => {
+            format!("WM_LBUTTONDOWN/WM_LBUTTONUP on element {:?}", idx)
+        }
+        FuzzerAction::ModifiedClick { idx, state } => {
+            let keys: usize = state.into();
+            format!("WM_LBUTTONDOWN/WM_LBUTTONUP(keys={:#x}) on element {:?}",
+                keys, idx)
+        }
+        FuzzerAction::Hover { idx, x, y, duration_ms } => {
+            format!("WM_MOUSEMOVE/WM_MOUSEHOVER(x={:.1}%, y={:.1}%) for {} \
+                ms on element {:?}", coords::percent(x), coords::percent(y),
+                duration_ms, idx)
+        }
+        FuzzerAction::Close => "WM_CLOSE".into(),
+        FuzzerAction::MenuAction { menu_id, .. } => {
+            command(menu_id.0).to_string()
+        }
+        FuzzerAction::DialogCommand { control_id } => {
+            format!("{} to dialogs", command(control_id))
+        }
+        FuzzerAction::KeyPress { key, focus } => {
+            let press = format!("WM_KEYDOWN/WM_KEYUP({})", key_name(key.0));
+            match focus {
+                Some(idx) => format!("{} on element {:?}", press, idx),
+                None => press,
+            }
+        }
+        FuzzerAction::KeyRepeat { key, focus, repeats } => {
+            let hold = format!("WM_KEYDOWN x{}/WM_KEYUP({})",
+                repeats as u32 + 1, key_name(key.0));
+            match focus {
+                Some(idx) => format!("{} on element {:?}", hold, idx),
+                None => hold,
+            }
+        }
+        FuzzerAction::CopyData { tag, seed, len } => {
+            format!("WM_COPYDATA(tag={:#x}, seed={:#x}, len={})", tag, seed,
+                len)
+        }
+        FuzzerAction::CustomMessage { idx, wparam, lparam } => {
+            let name = ipc::custom_message_name(idx)
+                .unwrap_or_else(|| format!("custom message {}", idx));
+            format!("{:?}(wparam={:#x}, lparam={:#x})", name, wparam,
+                lparam)
+        }
+        FuzzerAction::SwitchTab { idx, tab, focus } => {
+            format!("{}(tab={}) on element {:?}",
+                if focus { "TCM_SETCURFOCUS" } else { "TCM_SETCURSEL" }, tab,
+                idx)
+        }
+        FuzzerAction::SetRange { idx, bound, offset } => {
+            format!("TBM_SETPOS/UDM_SETPOS32/DTM_SETSYSTEMTIME({:?}{:+}) on \
+                element {:?}", bound, offset, idx)
+        }
+        _ => format!("{:?}", action),
+    }
+}
@@ -0,0 +1,110 @@
+//! Pacing of the waits between actions, derived from how fast the target
+//! answers Win32 calls. Native targets handle messages in microseconds and
+//! can be driven with tight pacing, while slow targets, such as Electron
+//! apps, need longer waits for their UI to settle. The defaults are the
+//! waits used before pacing was measured.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::Window;
+
+/// Number of samples taken of each latency
+const SAMPLES: usize = 32;
+
+/// Longest time to wait for the target to answer a message while measuring
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Waits between actions, shared between all threads
+static PACING: Mutex<Pacing> = Mutex::new(Pacing::DEFAULT);
+
+/// Latency of the Win32 calls the fuzzer makes against a target
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Latency {
+    /// Median time for the target to process a message sent to its window
+    pub message: Duration,
+
+    /// Median time to enumerate the sub-windows of the target's window
+    pub enumeration: Duration,
+}
+
+impl Latency {
+    /// Measure the latencies of the target owning `window`
+    pub fn measure(window: &Window) -> Self {
+        let median = |mut samples: Vec<Duration>| {
+            samples.sort();
+            samples[samples.len() / 2]
+        };
+
+        let message = (0..SAMPLES).map(|_| {
+            let start = Instant::now();
+            window.is_hung(MESSAGE_TIMEOUT);
+            start.elapsed()
+        }).collect();
+        let enumeration = (0..SAMPLES).map(|_| {
+            let start = Instant::now();
+            let _ = window.enumerate_subwindows();
+            start.elapsed()
+        }).collect();
+
+        Latency {
+            message:     median(message),
+            enumeration: median(enumeration),
+        }
+    }
+}
+
+/// Waits between actions
+#[derive(Clone, Copy, Debug)]
+pub struct Pacing {
+    /// Time for the UI to settle after clicking a menu item
+    pub menu_settle: Duration,
+
+    /// Interval between attempts to attach to a starting target's window
+    pub attach_poll: Duration,
+
+    /// Longest time to wait for the target to go idle after an action when
+    /// attributing coverage to actions
+    pub idle_timeout: Duration,
+}
+
+impl Pacing {
+    /// Pacing used until it is measured
+    pub const DEFAULT: Pacing = Pacing {
+        menu_settle:  Duration::from_millis(250),
+        attach_poll:  Duration::from_millis(200),
+        idle_timeout: Duration::from_millis(500),
+    };
+
+    /// Derive the pacing for a target with `latency`. Each wait is a
+    /// multiple of the time a round trip of a message and an enumeration
+    /// takes, clamped so pacing never gets absurdly tight or slow.
+    pub fn from_latency(latency: &Latency) -> Self {
+        let base = latency.message + latency.enumeration;
+        let scaled = |factor: u32, min: u64, max: u64| {
+            (base * factor).max(Duration::from_millis(min))
+                .min(Duration::from_millis(max))
+        };
+
+        Pacing {
+            menu_settle:  scaled(40, 20, 1000),
+            attach_poll:  scaled(20, 20, 500),
+            idle_timeout: scaled(80, 50, 2000),
+        }
+    }
+
+    /// Get the pacing used by all threads
+    pub fn global() -> Self {
+        *PACING.lock().unwrap()
+    }
+
+    /// Use this pacing in all threads
+    pub fn set_global(self) {
+        *PACING.lock().unwrap() = self;
+    }
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing::DEFAULT
+    }
+}
@@ -0,0 +1,146 @@
+//! On-disk backing of corpus inputs, so a corpus can keep only a bounded
+//! amount of its inputs in memory. Inputs are paged out in the encoding of
+//! `bytecodec`, so nothing about a paged out input stays in memory but where
+//! it is in the backing file.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::FuzzerAction;
+use crate::bytecodec;
+
+/// Number of pagers created by this process, used to give each a unique
+/// backing file
+static PAGERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Location of a paged out input in the backing file, as (byte offset,
+/// number of bytes, number of actions)
+pub type Page = (u64, usize, usize);
+
+/// Backing file of paged out inputs, deleted when the pager is dropped
+pub struct Pager {
+    /// Path of the backing file
+    path: PathBuf,
+
+    /// Backing file, only `None` while the pager is dropped
+    file: Option<File>,
+
+    /// Number of bytes written to the backing file
+    len: u64,
+}
+
+impl Pager {
+    /// Create a pager with a new, empty backing file in `dir`
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+
+        let path = dir.join(format!("{}_{}.pages", std::process::id(),
+            PAGERS.fetch_add(1, Ordering::SeqCst)));
+        let file = OpenOptions::new().read(true).write(true).create(true)
+            .truncate(true).open(&path)?;
+
+        Ok(Pager {
+            path,
+            file: Some(file),
+            len:  0,
+        })
+    }
+
+    /// Write `input` to the backing file, returning where it was written.
+    /// Fails if `input` can't be encoded, in which case it has to stay in
+    /// memory.
+    pub fn page_out(&mut self, input: &[FuzzerAction]) -> io::Result<Page> {
+        let bytes = bytecodec::encode(input).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidInput, err.to_string())
+        })?;
+
+        let offset = self.len;
+        let file = self.file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&bytes)?;
+        self.len += bytes.len() as u64;
+        Ok((offset, bytes.len(), input.len()))
+    }
+
+    /// Read the input at `page` back from the backing file
+    pub fn page_in(&mut self, page: Page) -> io::Result<Vec<FuzzerAction>> {
+        let (offset, size, count) = page;
+        let mut bytes = vec![0u8; size];
+        let file = self.file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut bytes)?;
+
+        let input = bytecodec::decode(&bytes);
+        if input.len() != count {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Page decoded to {} actions, expected {}",
+                    input.len(), count)));
+        }
+        Ok(input)
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        // The file has to be closed before it can be deleted on Windows
+        self.file = None;
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ElementIdx;
+
+    /// Directory test pagers put their backing files in
+    fn test_dir() -> PathBuf {
+        std::env::temp_dir().join("guifuzz_pager_test")
+    }
+
+    /// An input of clicks on the elements `elements`
+    fn clicks(elements: &[usize]) -> Vec<FuzzerAction> {
+        elements.iter().map(|&idx| {
+            FuzzerAction::LeftClick { idx: ElementIdx(idx) }
+        }).collect()
+    }
+
+    #[test]
+    fn pages_round_trip() {
+        let mut pager = Pager::new(&test_dir()).unwrap();
+        let inputs = [clicks(&[1, 2, 3]), clicks(&[]), clicks(&[3, 3, 1, 7])];
+        let pages: Vec<Page> = inputs.iter()
+            .map(|x| pager.page_out(x).unwrap()).collect();
+
+        // Read back out of order, and more than once
+        for &idx in &[2, 0, 1, 2, 0] {
+            assert_eq!(pager.page_in(pages[idx]).unwrap(), inputs[idx]);
+        }
+    }
+
+    #[test]
+    fn truncated_pages_are_invalid_data() {
+        let mut pager = Pager::new(&test_dir()).unwrap();
+        let (offset, size, count) = pager.page_out(&clicks(&[1, 2])).unwrap();
+        let err = pager.page_in((offset, size - 1, count)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unencodable_inputs_are_not_paged_out() {
+        let mut pager = Pager::new(&test_dir()).unwrap();
+        let err = pager.page_out(&clicks(&[usize::MAX])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(pager.len, 0);
+    }
+
+    #[test]
+    fn backing_file_is_deleted_on_drop() {
+        let pager = Pager::new(&test_dir()).unwrap();
+        let path = pager.path.clone();
+        assert!(path.is_file());
+        drop(pager);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,77 @@
+//! Window placement fuzzing, moving the target to extreme coordinates and
+//! across monitors to catch coordinate handling bugs
+
+use std::io;
+use crate::Window;
+use crate::winbindings::ShowCommand;
+
+/// Ways to place the target window
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Placement {
+    /// Move to extreme coordinates, negative or far beyond the virtual
+    /// screen. The parameter selects the coordinates and size.
+    Extreme,
+
+    /// Straddle the edge between a monitor and its neighbor. The parameter
+    /// selects the monitor.
+    Span,
+
+    /// Move onto a monitor and maximize there. The parameter selects the
+    /// monitor.
+    Maximize,
+
+    /// Minimize the window
+    Minimize,
+
+    /// Restore the window from being minimized or maximized
+    Restore,
+}
+
+/// Place `window` according to `placement` with `param`
+pub fn place(window: &Window, placement: Placement, param: u32)
+        -> io::Result<()> {
+    let (vleft, vtop, vright, vbottom) = Window::virtual_screen();
+
+    let mut monitors = Window::monitors()?;
+    if monitors.is_empty() {
+        monitors.push((vleft, vtop, vright, vbottom));
+    }
+    let monitor = monitors[param as usize % monitors.len()];
+
+    match placement {
+        Placement::Extreme => {
+            let extremes = [
+                i32::MIN / 2, -32000, -32768, -1, 0,
+                vright - 1, vright + 10000, 32767, i32::MAX / 2,
+                vleft - 1, vbottom, vtop - 1,
+            ];
+            let pick = |shift: u32| {
+                extremes[(param >> shift) as usize % extremes.len()]
+            };
+
+            window.set_position(pick(0), pick(4), pick(8).abs(),
+                pick(12).abs())
+        }
+        Placement::Span => {
+            // Put the center of the window on the right edge of the monitor
+            let (left, top, right, bottom) = monitor;
+            let width  = (right - left) / 2;
+            let height = (bottom - top) / 2;
+            window.set_position(right - width / 2, top + height / 2, width,
+                height)
+        }
+        Placement::Maximize => {
+            // Restore first, Windows maximizes on the monitor the window is
+            // on
+            let (left, top, right, bottom) = monitor;
+            window.show(ShowCommand::Restore)?;
+            window.set_position(left, top, (right - left) / 2,
+                (bottom - top) / 2)?;
+            window.show(ShowCommand::Maximize)
+        }
+        Placement::Minimize => window.show(ShowCommand::Minimize),
+        Placement::Restore  => window.show(ShowCommand::Restore),
+    }
+}
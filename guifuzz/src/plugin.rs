@@ -0,0 +1,194 @@
+//! Target-specific plugins, DLLs exporting a C ABI which are loaded per
+//! campaign to provide setup and teardown, oracles, and action macros
+//! without recompiling the fuzzer
+//!
+//! A plugin exports any of the following functions:
+//!
+//! ```c
+//! // Called once the target's main window is up, before any actions
+//! void guifuzz_setup(uint32_t pid, uintptr_t hwnd);
+//!
+//! // Called after the target has been torn down
+//! void guifuzz_teardown(uint32_t pid);
+//!
+//! // Number of macros provided, macro IDs are 0 up to this
+//! uint32_t guifuzz_macro_count(void);
+//!
+//! // Perform macro `id` with the random `param` on the target
+//! void guifuzz_macro(uint32_t pid, uintptr_t hwnd, uint32_t id,
+//!                    uint32_t param);
+//!
+//! // Check a finished case, writing a crash bucket name to `name` and
+//! // returning its length if a bug was found, otherwise returning 0
+//! uintptr_t guifuzz_check(const PluginCase *result, uint8_t *name,
+//!                         uintptr_t name_len);
+//! ```
+
+use std::io;
+use std::sync::{Arc, Mutex};
+use crate::Window;
+use crate::winbindings::str_to_utf16;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn LoadLibraryW(filename: *const u16) -> usize;
+    fn GetProcAddress(module: usize, name: *const u8) -> usize;
+}
+
+/// `guifuzz_setup()`
+type SetupFn = unsafe extern "C" fn(pid: u32, hwnd: usize);
+
+/// `guifuzz_teardown()`
+type TeardownFn = unsafe extern "C" fn(pid: u32);
+
+/// `guifuzz_macro_count()`
+type MacroCountFn = unsafe extern "C" fn() -> u32;
+
+/// `guifuzz_macro()`
+type MacroFn = unsafe extern "C" fn(pid: u32, hwnd: usize, id: u32,
+    param: u32);
+
+/// `guifuzz_check()`
+type CheckFn = unsafe extern "C" fn(result: *const PluginCase,
+    name: *mut u8, name_len: usize) -> usize;
+
+/// Maximum length of a crash bucket name returned by a plugin
+const MAX_NAME_LEN: usize = 128;
+
+/// All loaded plugins, shared between all threads
+static PLUGINS: Mutex<Vec<Arc<Plugin>>> = Mutex::new(Vec::new());
+
+/// Result of a fuzz case passed to a plugin's `guifuzz_check()`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PluginCase {
+    /// PID of the target, which has exited
+    pub pid: u32,
+
+    /// Non-zero if the target crashed
+    pub crashed: u32,
+
+    /// Non-zero if the target's main window stopped processing messages
+    pub hung: u32,
+
+    /// Number of open kernel handles, or 0 if unknown
+    pub handles: u32,
+
+    /// Number of GDI objects, or 0 if unknown
+    pub gdi_objects: u32,
+
+    /// Number of USER objects, or 0 if unknown
+    pub user_objects: u32,
+}
+
+/// A loaded plugin
+pub struct Plugin {
+    /// Path the plugin was loaded from
+    pub path: String,
+
+    setup:       Option<SetupFn>,
+    teardown:    Option<TeardownFn>,
+    macro_count: u32,
+    macro_fn:    Option<MacroFn>,
+    check:       Option<CheckFn>,
+}
+
+impl Plugin {
+    /// Load the plugin DLL at `path`. The DLL is never unloaded.
+    pub fn load(path: &str) -> io::Result<Self> {
+        unsafe {
+            let module = LoadLibraryW(str_to_utf16(path).as_ptr());
+            if module == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let export = |name: &[u8]| {
+                Some(GetProcAddress(module, name.as_ptr()))
+                    .filter(|&x| x != 0)
+            };
+
+            let setup = export(b"guifuzz_setup\0")
+                .map(|x| std::mem::transmute::<usize, SetupFn>(x));
+            let teardown = export(b"guifuzz_teardown\0")
+                .map(|x| std::mem::transmute::<usize, TeardownFn>(x));
+            let macro_count = export(b"guifuzz_macro_count\0")
+                .map(|x| std::mem::transmute::<usize, MacroCountFn>(x)());
+            let macro_fn = export(b"guifuzz_macro\0")
+                .map(|x| std::mem::transmute::<usize, MacroFn>(x));
+            let check = export(b"guifuzz_check\0")
+                .map(|x| std::mem::transmute::<usize, CheckFn>(x));
+
+            Ok(Plugin {
+                path: path.to_string(),
+                setup,
+                teardown,
+                macro_count: if macro_fn.is_some() {
+                    macro_count.unwrap_or(0)
+                } else {
+                    0
+                },
+                macro_fn,
+                check,
+            })
+        }
+    }
+
+    /// Number of macros this plugin provides
+    pub fn macro_count(&self) -> u32 {
+        self.macro_count
+    }
+
+    /// Run the plugin's setup for the target `pid` with main window `window`
+    pub fn setup(&self, pid: u32, window: &Window) {
+        if let Some(setup) = self.setup {
+            unsafe { setup(pid, window.hwnd()); }
+        }
+    }
+
+    /// Run the plugin's teardown for the target `pid`
+    pub fn teardown(&self, pid: u32) {
+        if let Some(teardown) = self.teardown {
+            unsafe { teardown(pid); }
+        }
+    }
+
+    /// Perform macro `id` with `param` on the target `pid`
+    pub fn perform_macro(&self, pid: u32, window: &Window, id: u32,
+                         param: u32) {
+        if let Some(macro_fn) = self.macro_fn {
+            if id < self.macro_count {
+                unsafe { macro_fn(pid, window.hwnd(), id, param); }
+            }
+        }
+    }
+
+    /// Ask the plugin whether `result` is a bug, returning the crash bucket
+    /// name if it is
+    pub fn check(&self, result: &PluginCase) -> Option<String> {
+        let check = self.check?;
+
+        let mut name = [0u8; MAX_NAME_LEN];
+        let len = unsafe { check(result, name.as_mut_ptr(), name.len()) };
+        if len == 0 {
+            return None;
+        }
+
+        // Keep the name safe to use as a directory name
+        Some(String::from_utf8_lossy(&name[..len.min(name.len())]).chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect())
+    }
+}
+
+/// Load the plugin at `path` and make it available to all threads
+pub fn register(path: &str) -> io::Result<()> {
+    let plugin = Plugin::load(path)?;
+    PLUGINS.lock().unwrap().push(Arc::new(plugin));
+    Ok(())
+}
+
+/// Get all loaded plugins. Indices into this list are used to refer to
+/// plugins in actions.
+pub fn plugins() -> Vec<Arc<Plugin>> {
+    PLUGINS.lock().unwrap().clone()
+}
@@ -0,0 +1,127 @@
+//! Property sheet fuzzing, switching between the pages of tabbed settings
+//! dialogs and applying them. Clicks only ever reach the first page of a
+//! property sheet, so the pages are visited in turn and their controls
+//! used, along with the sheet's own Apply, OK, and Cancel handling.
+
+use std::io;
+use std::time::Duration;
+use crate::{Window, tabs};
+use crate::winbindings::MessageType;
+
+/// Number of `PSBTN_*` buttons: back, next, finish, OK, apply now, cancel,
+/// and help
+const PSBTN_COUNT: u32 = 7;
+
+/// Longest time to wait for a property sheet to answer a query
+const QUERY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Operations on a property sheet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PropSheetCommand {
+    /// `PSM_SETCURSEL` the page selected by the parameter
+    SetPage,
+
+    /// Click the control of the current page selected by the parameter
+    UseControl,
+
+    /// `PSM_CHANGED` for the current page, enabling Apply
+    Changed,
+
+    /// `PSM_UNCHANGED` for the current page
+    Unchanged,
+
+    /// `PSM_APPLY`, applying the settings of all pages
+    Apply,
+
+    /// `PSM_PRESSBUTTON` the `PSBTN_*` button selected by the parameter
+    PressButton,
+
+    /// `PSM_QUERYSIBLINGS` with the parameter split into both parameters
+    QuerySiblings,
+
+    /// `PSM_CANCELTOCLOSE`
+    CancelToClose,
+}
+
+/// Find the tab control of `dialog`, if it is a property sheet
+fn tab_control(dialog: &Window) -> Option<Window> {
+    dialog.children().into_iter().find(tabs::is_tab_control)
+}
+
+/// Get the current page of the property sheet `sheet`
+pub fn current_page(sheet: &Window) -> Option<Window> {
+    let page = sheet.send_message_timeout(MessageType::PsmGetCurrentPageHwnd,
+        0, 0, QUERY_TIMEOUT).ok()?;
+    sheet.children().into_iter().find(|x| page != 0 && x.hwnd() == page)
+}
+
+/// Returns `true` if `dialog` is a property sheet: it has a tab control
+/// and answers for its current page
+pub fn is_property_sheet(dialog: &Window) -> bool {
+    tab_control(dialog).is_some() && current_page(dialog).is_some()
+}
+
+/// Find all property sheets `pid` has open
+pub fn find(pid: u32) -> Vec<Window> {
+    Window::enumerate_dialogs(pid).unwrap_or_default().into_iter()
+        .filter(is_property_sheet).collect()
+}
+
+/// Number of pages of the property sheet `sheet`
+pub fn page_count(sheet: &Window) -> usize {
+    tab_control(sheet).map(|tab| tabs::tab_count(&tab)).unwrap_or(0)
+}
+
+/// Perform `command` with `param` on the property sheet of `pid` selected
+/// by the top byte of `param`
+pub fn perform(pid: u32, command: PropSheetCommand, param: u32)
+        -> io::Result<()> {
+    let sheets = find(pid);
+    if sheets.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+            "No property sheets"));
+    }
+    let sheet = sheets[(param >> 24) as usize % sheets.len()];
+    let page = || current_page(&sheet).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "No current page")
+    });
+
+    match command {
+        PropSheetCommand::SetPage => {
+            let pages = page_count(&sheet).max(1);
+            sheet.post_message(MessageType::PsmSetCurSel,
+                (param & 0xffff) as usize % pages, 0)
+        }
+        PropSheetCommand::UseControl => {
+            let controls = page()?.enumerate_subwindows()?;
+            if controls.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                    "No controls on the current page"));
+            }
+            controls[(param & 0xffff) as usize % controls.len()]
+                .left_click(None)
+        }
+        PropSheetCommand::Changed => {
+            sheet.post_message(MessageType::PsmChanged, page()?.hwnd(), 0)
+        }
+        PropSheetCommand::Unchanged => {
+            sheet.post_message(MessageType::PsmUnchanged, page()?.hwnd(), 0)
+        }
+        PropSheetCommand::Apply => {
+            sheet.post_message(MessageType::PsmApply, 0, 0)
+        }
+        PropSheetCommand::PressButton => {
+            sheet.post_message(MessageType::PsmPressButton,
+                ((param & 0xff) % PSBTN_COUNT) as usize, 0)
+        }
+        PropSheetCommand::QuerySiblings => {
+            sheet.post_message(MessageType::PsmQuerySiblings,
+                (param & 0xfff) as usize, ((param >> 12) & 0xfff) as usize)
+        }
+        PropSheetCommand::CancelToClose => {
+            sheet.post_message(MessageType::PsmCancelToClose, 0, 0)
+        }
+    }
+}
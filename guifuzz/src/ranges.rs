@@ -0,0 +1,153 @@
+//! Range control manipulation, setting trackbars, up-down controls, and
+//! date-time pickers to values at and just past the bounds of their range.
+//! Dragging and clicking only ever produces values the control allows, so
+//! the numeric range handling behind these controls is reached by setting
+//! their position directly.
+
+use std::io;
+use std::time::Duration;
+use crate::Window;
+use crate::winbindings::MessageType;
+
+/// Class name of trackbars
+pub const TRACKBAR_CLASS: &str = "msctls_trackbar32";
+
+/// Class name of up-down controls
+pub const UP_DOWN_CLASS: &str = "msctls_updown32";
+
+/// Class name of date-time pickers
+pub const DATE_TIME_PICKER_CLASS: &str = "SysDateTimePick32";
+
+/// `UDM_SETPOS32`, which shares its value with `PSM_PRESSBUTTON` and so
+/// can't be a `MessageType`
+const UDM_SETPOS32: u32 = 0x0471;
+
+/// `DTM_SETSYSTEMTIME` flag for a valid time
+const GDT_VALID: usize = 0;
+
+/// Earliest time a `SYSTEMTIME` can hold, field by field: year, month, day
+/// of the week, day, hour, minute, second, and millisecond
+const MIN_SYSTEMTIME: [u16; 8] = [1601, 1, 0, 1, 0, 0, 0, 0];
+
+/// Latest time a `SYSTEMTIME` can hold
+const MAX_SYSTEMTIME: [u16; 8] = [30827, 12, 0, 31, 23, 59, 59, 999];
+
+/// Longest time to wait for a control to report its range
+const QUERY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Offsets from a bound used when generating values: the bound itself, just
+/// past it on either side, and the extremes of the parameter
+pub const BOUNDARY_OFFSETS: &[i32] =
+    &[0, 0, 0, -1, 1, -2, 2, i32::MIN, i32::MAX, -0x8000, 0x8000];
+
+/// Bound of its range a value is set relative to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RangeBound {
+    /// The minimum of the control's range
+    Min,
+
+    /// The maximum of the control's range
+    Max,
+
+    /// Zero, making the offset the value itself
+    Absolute,
+}
+
+/// Kinds of range controls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeControl {
+    /// Trackbar, set with `TBM_SETPOS`
+    Trackbar,
+
+    /// Up-down control, set with `UDM_SETPOS32`
+    UpDown,
+
+    /// Date-time picker, set with `DTM_SETSYSTEMTIME`
+    DateTimePicker,
+}
+
+impl RangeControl {
+    /// Kind of range control of `window`, `None` if it isn't one
+    pub fn of(window: &Window) -> Option<Self> {
+        match window.class_name().ok()?.as_str() {
+            TRACKBAR_CLASS         => Some(RangeControl::Trackbar),
+            UP_DOWN_CLASS          => Some(RangeControl::UpDown),
+            DATE_TIME_PICKER_CLASS => Some(RangeControl::DateTimePicker),
+            _ => None,
+        }
+    }
+}
+
+/// Returns `true` if `window` is a range control
+pub fn is_range_control(window: &Window) -> bool {
+    RangeControl::of(window).is_some()
+}
+
+/// Range of the trackbar or up-down control `window` of the kind `kind`
+fn range(window: &Window, kind: RangeControl) -> io::Result<(i32, i32)> {
+    let query = |msg| {
+        window.send_message_timeout(msg, 0, 0, QUERY_TIMEOUT)
+    };
+    match kind {
+        RangeControl::Trackbar => {
+            Ok((query(MessageType::TbmGetRangeMin)? as i32,
+                query(MessageType::TbmGetRangeMax)? as i32))
+        }
+        RangeControl::UpDown => {
+            // The maximum is in the low word and the minimum in the high
+            // word, both signed
+            let range = query(MessageType::UdmGetRange)?;
+            Ok(((range >> 16) as i16 as i32, range as i16 as i32))
+        }
+        RangeControl::DateTimePicker => unreachable!(),
+    }
+}
+
+/// Set the `SYSTEMTIME` `bound` with `offset` added to each of its fields,
+/// so an offset past the bound makes every field invalid at once
+fn set_system_time(window: &Window, bound: RangeBound, offset: i32)
+        -> io::Result<()> {
+    let base = match bound {
+        RangeBound::Min      => MIN_SYSTEMTIME,
+        RangeBound::Max      => MAX_SYSTEMTIME,
+        RangeBound::Absolute => [0; 8],
+    };
+    let time: Vec<u8> = base.iter()
+        .flat_map(|x| x.wrapping_add(offset as u16).to_le_bytes().to_vec())
+        .collect();
+
+    // The time is passed by pointer
+    let remote = window.remote_write(&time)?;
+    window.post_message(MessageType::DtmSetSystemTime, GDT_VALID, remote)
+}
+
+/// Set the range control `window` to `bound` plus `offset`. Offsets are
+/// added with wrapping, so they can land anywhere outside of the range.
+pub fn set(window: &Window, bound: RangeBound, offset: i32)
+        -> io::Result<()> {
+    let kind = RangeControl::of(window).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "Not a range control")
+    })?;
+    if kind == RangeControl::DateTimePicker {
+        return set_system_time(window, bound, offset);
+    }
+
+    let (min, max) = range(window, kind)?;
+    let value = match bound {
+        RangeBound::Min      => min,
+        RangeBound::Max      => max,
+        RangeBound::Absolute => 0,
+    }.wrapping_add(offset);
+
+    match kind {
+        RangeControl::Trackbar => {
+            // Redraw the thumb at its new position
+            window.post_message(MessageType::TbmSetPos, 1,
+                value as isize as usize)
+        }
+        _ => window.post_raw_message(UDM_SETPOS32, 0,
+            value as isize as usize),
+    }
+}
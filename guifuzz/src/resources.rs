@@ -0,0 +1,683 @@
+//! Extraction of menus, dialogs, accelerators, and string tables from the
+//! resources of PE files, used to build a target-aware action dictionary
+
+use std::io;
+use std::path::Path;
+use std::collections::BTreeSet;
+use crate::{FuzzerAction, MenuId, VirtualKey};
+
+/// Resource type for menus
+const RT_MENU: u32 = 4;
+
+/// Resource type for dialog templates
+const RT_DIALOG: u32 = 5;
+
+/// Resource type for string tables
+const RT_STRING: u32 = 6;
+
+/// Resource type for accelerator tables
+const RT_ACCELERATOR: u32 = 9;
+
+/// Resource type for side-by-side assembly manifests
+const RT_MANIFEST: u32 = 24;
+
+/// Menu item opens a popup (sub menu)
+const MF_POPUP: u16 = 0x0010;
+
+/// Last item in a menu or accelerator table
+const MF_END: u16 = 0x0080;
+
+/// Accelerator key is a virtual key code rather than a character
+const FVIRTKEY: u16 = 0x0001;
+
+/// All accelerator flags besides `MF_END`: `FVIRTKEY`, `FNOINVERT`,
+/// `FSHIFT`, `FCONTROL`, and `FALT`
+const ACCEL_FLAGS: u16 = 0x001f;
+
+/// Dialog template contains font information
+const DS_SETFONT: u32 = 0x0040;
+
+/// A dialog template found in the resources
+#[derive(Clone, Debug, Default)]
+pub struct DialogResource {
+    /// Resource ID of the dialog, `None` if the dialog is named by a string
+    pub id: Option<u32>,
+
+    /// Title of the dialog
+    pub title: String,
+
+    /// Control IDs of all of the controls in the dialog
+    pub controls: Vec<u32>,
+}
+
+/// An accelerator table entry found in the resources
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Accelerator {
+    /// Accelerator flags (`FVIRTKEY`, `FSHIFT`, `FCONTROL`, `FALT`)
+    pub flags: u16,
+
+    /// Virtual key code or character of the accelerator
+    pub key: u16,
+
+    /// Command ID sent via `WM_COMMAND` when the accelerator is used
+    pub id: u32,
+}
+
+/// All of the interesting resources extracted from one or more PE files
+#[derive(Clone, Debug, Default)]
+pub struct Resources {
+    /// Menu item IDs from all menus
+    pub menu_ids: BTreeSet<u32>,
+
+    /// All dialog templates
+    pub dialogs: Vec<DialogResource>,
+
+    /// All accelerator table entries
+    pub accelerators: Vec<Accelerator>,
+
+    /// IDs of all non-empty string table entries. Many applications share
+    /// IDs between commands and their status bar prompt strings
+    pub string_ids: BTreeSet<u32>,
+}
+
+/// Read a little-endian u16 from `buf` at `off`
+fn u16_at(buf: &[u8], off: usize) -> Option<u16> {
+    let bytes = buf.get(off..off.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Read a little-endian u32 from `buf` at `off`
+fn u32_at(buf: &[u8], off: usize) -> Option<u32> {
+    let bytes = buf.get(off..off.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Read a NUL-terminated UTF-16 string from `buf` at `off`. Returns the
+/// string and the offset of the first byte after the terminator
+fn utf16z_at(buf: &[u8], mut off: usize) -> Option<(String, usize)> {
+    let mut chars = Vec::new();
+    loop {
+        let chr = u16_at(buf, off)?;
+        off += 2;
+        if chr == 0 { break; }
+        chars.push(chr);
+    }
+
+    Some((String::from_utf16_lossy(&chars), off))
+}
+
+/// Read a `sz_Or_Ord` field from a dialog template. Returns the string (empty
+/// for ordinals) and the offset of the first byte after the field
+fn sz_or_ord_at(buf: &[u8], off: usize) -> Option<(String, usize)> {
+    match u16_at(buf, off)? {
+        0x0000 => Some((String::new(), off + 2)),
+        0xffff => Some((String::new(), off + 4)),
+        _      => utf16z_at(buf, off),
+    }
+}
+
+/// Round `off` up to the next DWORD boundary
+fn align4(off: usize) -> usize {
+    (off + 3) & !3
+}
+
+/// Create an invalid data error for malformed PE files
+fn malformed(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,
+        format!("Malformed PE file: {}", what))
+}
+
+/// A resource in a PE image as (type, name ID, data)
+type Resource<'a> = (u32, Option<u32>, &'a [u8]);
+
+/// Locate every resource in the PE image `pe`
+fn enum_resources(pe: &[u8]) -> io::Result<Vec<Resource<'_>>> {
+    // Validate this is a PE
+    if pe.get(0..2) != Some(b"MZ") {
+        return Err(malformed("not MZ"));
+    }
+    let pe_ptr = u32_at(pe, 0x3c).ok_or_else(|| malformed("e_lfanew"))?
+        as usize;
+    if pe.get(pe_ptr..pe_ptr + 4) != Some(b"PE\0\0") {
+        return Err(malformed("not PE"));
+    }
+
+    let num_sections = u16_at(pe, pe_ptr + 6)
+        .ok_or_else(|| malformed("file header"))? as usize;
+    let opt_size = u16_at(pe, pe_ptr + 20)
+        .ok_or_else(|| malformed("file header"))? as usize;
+    let opt = pe_ptr + 24;
+
+    // Find the data directories based on the optional header type
+    let data_dirs = match u16_at(pe, opt) {
+        Some(0x10b) => opt + 96,
+        Some(0x20b) => opt + 112,
+        _ => return Err(malformed("optional header magic")),
+    };
+
+    // Get the resource directory, which is data directory index 2
+    let rsrc_rva = u32_at(pe, data_dirs + 2 * 8)
+        .ok_or_else(|| malformed("data directories"))? as usize;
+    if rsrc_rva == 0 {
+        // No resources
+        return Ok(Vec::new());
+    }
+
+    // Gather the section table so we can convert RVAs to file offsets
+    let mut sections = Vec::new();
+    for ii in 0..num_sections {
+        let sec = opt + opt_size + ii * 40;
+        let field = |off: usize| u32_at(pe, sec + off).map(|x| x as usize)
+            .ok_or_else(|| malformed("section table"));
+
+        // (VirtualAddress, SizeOfRawData, PointerToRawData)
+        sections.push((field(12)?, field(16)?, field(20)?));
+    }
+
+    let rva_to_offset = |rva: usize| -> Option<usize> {
+        sections.iter().find(|(vaddr, rawsz, _)| {
+            rva >= *vaddr && rva < vaddr + rawsz
+        }).map(|(vaddr, _, rawptr)| rva - vaddr + rawptr)
+    };
+
+    let rsrc = rva_to_offset(rsrc_rva)
+        .ok_or_else(|| malformed("resource directory RVA"))?;
+
+    // Get the entries of the resource directory at `dir` (relative to the
+    // resource section), as (name, offset, is_directory) tuples
+    let dir_entries = |dir: usize| -> Option<Vec<(u32, usize, bool)>> {
+        let named = u16_at(pe, rsrc + dir + 12)? as usize;
+        let ids   = u16_at(pe, rsrc + dir + 14)? as usize;
+
+        let mut entries = Vec::new();
+        for ii in 0..named + ids {
+            let entry = rsrc + dir + 16 + ii * 8;
+            let name  = u32_at(pe, entry)?;
+            let data  = u32_at(pe, entry + 4)?;
+            entries.push((name, (data & 0x7fff_ffff) as usize,
+                data & 0x8000_0000 != 0));
+        }
+        Some(entries)
+    };
+
+    // Walk the type -> name -> language tree
+    let mut resources = Vec::new();
+    for (typ, typ_dir, typ_is_dir) in dir_entries(0)
+            .ok_or_else(|| malformed("resource directory"))? {
+        if !typ_is_dir { continue; }
+
+        for (name, name_dir, name_is_dir) in dir_entries(typ_dir)
+                .ok_or_else(|| malformed("resource directory"))? {
+            if !name_is_dir { continue; }
+
+            // Names with the high bit set are strings rather than IDs
+            let name = if name & 0x8000_0000 == 0 { Some(name) } else { None };
+
+            for (_, data_entry, is_dir) in dir_entries(name_dir)
+                    .ok_or_else(|| malformed("resource directory"))? {
+                if is_dir { continue; }
+
+                let data_rva = u32_at(pe, rsrc + data_entry)
+                    .ok_or_else(|| malformed("resource data entry"))?;
+                let data_len = u32_at(pe, rsrc + data_entry + 4)
+                    .ok_or_else(|| malformed("resource data entry"))?;
+
+                let data = rva_to_offset(data_rva as usize)
+                    .and_then(|off| pe.get(off..off + data_len as usize))
+                    .ok_or_else(|| malformed("resource data"))?;
+
+                resources.push((typ, name, data));
+            }
+        }
+    }
+
+    Ok(resources)
+}
+
+/// Check whether the manifest embedded in the PE image `pe` requests to
+/// always run as an administrator, in which case launching it unelevated
+/// shows a UAC prompt or fails
+pub fn requires_elevation(pe: &[u8]) -> io::Result<bool> {
+    Ok(enum_resources(pe)?.iter().any(|(typ, _, data)| {
+        *typ == RT_MANIFEST && String::from_utf8_lossy(data)
+            .split("requestedExecutionLevel").skip(1)
+            .any(|x| x.split('>').next().unwrap_or("")
+                .contains("requireAdministrator"))
+    }))
+}
+
+impl Resources {
+    /// Parse the resources of every file in `paths` which exists. For
+    /// applications using MUI, pass both the executable and its `.mui` file
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let mut ret = Resources::default();
+
+        for path in paths {
+            if !path.as_ref().is_file() { continue; }
+
+            let pe = std::fs::read(path)?;
+            ret.parse_pe(&pe).map_err(|err| {
+                io::Error::new(err.kind(), format!("{}: {}",
+                    path.as_ref().display(), err))
+            })?;
+        }
+
+        Ok(ret)
+    }
+
+    /// Parse all of the resources in the PE image `pe` into `self`.
+    /// Malformed menus, dialogs, and string tables are skipped, malformed
+    /// accelerator tables are an error naming the bad entry.
+    pub fn parse_pe(&mut self, pe: &[u8]) -> io::Result<()> {
+        for (typ, name, data) in enum_resources(pe)? {
+            match typ {
+                RT_MENU => {
+                    self.parse_menu(data);
+                }
+                RT_DIALOG => {
+                    if let Some(mut dialog) = Self::parse_dialog(data) {
+                        dialog.id = name;
+                        self.dialogs.push(dialog);
+                    }
+                }
+                RT_STRING => {
+                    if let Some(block) = name {
+                        self.parse_string_table(block, data);
+                    }
+                }
+                RT_ACCELERATOR => {
+                    self.parse_accelerators(name, data)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse a menu template, adding all menu IDs to `menu_ids`
+    fn parse_menu(&mut self, data: &[u8]) -> Option<()> {
+        let version = u16_at(data, 0)?;
+        let header  = u16_at(data, 2)? as usize;
+
+        // Each open menu level tracks whether the popup which opened it was
+        // the last item of its parent, in which case the parent ends too
+        let mut levels = vec![false];
+
+        if version == 0 {
+            // Standard MENUITEMTEMPLATEs follow the header, popups are
+            // followed by their items
+            let mut off = 4 + header;
+            while !levels.is_empty() {
+                let flags = u16_at(data, off)?;
+                off += 2;
+
+                if flags & MF_POPUP == 0 {
+                    let id = u16_at(data, off)?;
+                    off += 2;
+                    if id != 0 {
+                        self.menu_ids.insert(id as u32);
+                    }
+                }
+
+                off = utf16z_at(data, off)?.1;
+
+                if flags & MF_POPUP != 0 {
+                    levels.push(flags & MF_END != 0);
+                } else if flags & MF_END != 0 {
+                    while let Some(parent_ended) = levels.pop() {
+                        if !parent_ended { break; }
+                    }
+                }
+            }
+        } else if version == 1 {
+            // MENUEX_TEMPLATE_ITEMs follow the header, each DWORD aligned
+            let mut off = 4 + header;
+            while !levels.is_empty() {
+                let id    = u32_at(data, off + 8)?;
+                let flags = u16_at(data, off + 12)?;
+                off = align4(utf16z_at(data, off + 14)?.1);
+
+                if flags & 0x01 != 0 {
+                    // Popup, skip the help ID and descend
+                    off += 4;
+                    levels.push(flags & MF_END != 0);
+                } else {
+                    if id != 0 {
+                        self.menu_ids.insert(id);
+                    }
+
+                    if flags & MF_END != 0 {
+                        while let Some(parent_ended) = levels.pop() {
+                            if !parent_ended { break; }
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    /// Parse a `DLGTEMPLATE` or `DLGTEMPLATEEX` dialog template
+    fn parse_dialog(data: &[u8]) -> Option<DialogResource> {
+        let mut dialog = DialogResource::default();
+
+        let extended = u16_at(data, 0)? == 1 && u16_at(data, 2)? == 0xffff;
+
+        let (style, num_items, mut off) = if extended {
+            (u32_at(data, 12)?, u16_at(data, 16)?, 26)
+        } else {
+            (u32_at(data, 0)?, u16_at(data, 8)?, 18)
+        };
+
+        // Menu and class
+        off = sz_or_ord_at(data, off)?.1;
+        off = sz_or_ord_at(data, off)?.1;
+
+        // Title
+        let (title, next) = utf16z_at(data, off)?;
+        dialog.title = title;
+        off = next;
+
+        // Font
+        if style & DS_SETFONT != 0 {
+            off += if extended { 6 } else { 2 };
+            off = utf16z_at(data, off)?.1;
+        }
+
+        // Dialog items
+        for _ in 0..num_items {
+            off = align4(off);
+
+            let id = if extended {
+                let id = u32_at(data, off + 20)?;
+                off += 24;
+                id
+            } else {
+                let id = u16_at(data, off + 16)? as u32;
+                off += 18;
+                id
+            };
+
+            // Class and title
+            off = sz_or_ord_at(data, off)?.1;
+            off = sz_or_ord_at(data, off)?.1;
+
+            // Creation data
+            let extra = u16_at(data, off)? as usize;
+            off += 2 + extra;
+
+            // Skip static controls with `IDC_STATIC` (-1) identifiers
+            if id != !0 && id != 0xffff {
+                dialog.controls.push(id);
+            }
+        }
+
+        Some(dialog)
+    }
+
+    /// Parse a block of 16 strings from a string table
+    fn parse_string_table(&mut self, block: u32, data: &[u8]) -> Option<()> {
+        // String blocks are numbered starting at 1
+        let base = block.checked_sub(1)? * 16;
+
+        let mut off = 0;
+        for ii in 0..16 {
+            let len = u16_at(data, off)? as usize;
+            off += 2 + len * 2;
+
+            if len != 0 {
+                self.string_ids.insert(base + ii);
+            }
+        }
+
+        Some(())
+    }
+
+    /// Parse the accelerator table with the resource ID `name`
+    fn parse_accelerators(&mut self, name: Option<u32>, data: &[u8])
+            -> io::Result<()> {
+        let bad_entry = |entry: usize, what: &str| {
+            malformed(&format!("accelerator table {} entry {}: {}",
+                name.map(|x| x.to_string())
+                    .unwrap_or_else(|| "(named)".into()),
+                entry, what))
+        };
+
+        let mut accelerators = Vec::new();
+        for entry in 0.. {
+            let off = entry * 8;
+            let (flags, key, id) = match (u16_at(data, off),
+                    u16_at(data, off + 2), u16_at(data, off + 4)) {
+                (Some(flags), Some(key), Some(id)) => (flags, key, id),
+                _ => return Err(bad_entry(entry, "truncated")),
+            };
+            if flags & !(ACCEL_FLAGS | MF_END) != 0 {
+                return Err(bad_entry(entry,
+                    &format!("unknown flags {:#x}", flags)));
+            }
+
+            accelerators.push(Accelerator {
+                flags: flags & !MF_END,
+                key,
+                id: id as u32,
+            });
+
+            if flags & MF_END != 0 {
+                break;
+            }
+        }
+
+        self.accelerators.extend(accelerators);
+        Ok(())
+    }
+
+    /// Build a dictionary of plausible fuzzer actions from these resources
+    pub fn actions(&self) -> Vec<FuzzerAction> {
+        let mut ids = BTreeSet::new();
+        let mut keys = BTreeSet::new();
+
+        ids.extend(self.menu_ids.iter().cloned());
+        ids.extend(self.string_ids.iter().cloned());
+        for dialog in self.dialogs.iter() {
+            ids.extend(dialog.controls.iter().cloned());
+        }
+        for accel in self.accelerators.iter() {
+            // Accelerators translate to `WM_COMMAND`s, but plain virtual
+            // keys are also worth pressing directly
+            ids.insert(accel.id);
+            if accel.flags == FVIRTKEY {
+                keys.insert(accel.key as usize);
+            }
+        }
+
+        ids.into_iter()
+            .map(|id| FuzzerAction::MenuAction {
+                menu_id:  MenuId(id),
+                menu_set: 0,
+            })
+            .chain(keys.into_iter().map(|key| {
+                FuzzerAction::KeyPress { key: VirtualKey(key), focus: None }
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RVA and file offset of the resource section of test PE files
+    const RSRC_RVA: usize = 0x1000;
+    const RSRC_OFF: usize = 0x200;
+
+    /// Write the little-endian `value` of `size` bytes into `buf` at `off`
+    fn put(buf: &mut [u8], off: usize, size: usize, value: u32) {
+        buf[off..off + size].copy_from_slice(&value.to_le_bytes()[..size]);
+    }
+
+    /// Append a resource directory with the ID entries `entries` of (ID,
+    /// offset) to `rsrc`, returning its offset
+    fn append_dir(rsrc: &mut Vec<u8>, entries: &[(u32, u32)]) -> u32 {
+        let off = rsrc.len();
+        rsrc.resize(off + 16 + entries.len() * 8, 0);
+        put(rsrc, off + 14, 2, entries.len() as u32);
+        for (ii, &(id, entry)) in entries.iter().enumerate() {
+            put(rsrc, off + 16 + ii * 8, 4, id);
+            put(rsrc, off + 20 + ii * 8, 4, entry);
+        }
+        off as u32
+    }
+
+    /// Build a 32-bit PE image holding the resources `resources` of (type,
+    /// name ID, data)
+    fn pe_with(resources: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        // Root directory first, it's filled in once the rest is laid out
+        let mut rsrc = vec![0u8; 16 + resources.len() * 8];
+        let mut types = Vec::new();
+        for (typ, name, data) in resources {
+            let data_off = rsrc.len();
+            rsrc.extend_from_slice(data);
+            rsrc.resize(align4(rsrc.len()), 0);
+
+            let entry = rsrc.len();
+            rsrc.resize(entry + 16, 0);
+            put(&mut rsrc, entry, 4, (RSRC_RVA + data_off) as u32);
+            put(&mut rsrc, entry + 4, 4, data.len() as u32);
+
+            let langs = append_dir(&mut rsrc, &[(0x409, entry as u32)]);
+            let names = append_dir(&mut rsrc, &[(*name, langs | 1 << 31)]);
+            types.push((*typ, names | 1 << 31));
+        }
+        let mut root = Vec::new();
+        append_dir(&mut root, &types);
+        rsrc[..root.len()].copy_from_slice(&root);
+
+        let mut pe = vec![0u8; RSRC_OFF];
+        pe[..2].copy_from_slice(b"MZ");
+        put(&mut pe, 0x3c, 4, 0x40);
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        put(&mut pe, 0x46, 2, 1);
+        put(&mut pe, 0x54, 2, 0xe0);
+        put(&mut pe, 0x58, 2, 0x10b);
+        put(&mut pe, 0x58 + 96 + 16, 4, RSRC_RVA as u32);
+        put(&mut pe, 0x58 + 0xe0 + 12, 4, RSRC_RVA as u32);
+        put(&mut pe, 0x58 + 0xe0 + 16, 4, rsrc.len() as u32);
+        put(&mut pe, 0x58 + 0xe0 + 20, 4, RSRC_OFF as u32);
+        pe.extend_from_slice(&rsrc);
+        pe
+    }
+
+    /// Encode `values` as little-endian u16s
+    fn words(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect()
+    }
+
+    /// Encode `text` as a NUL-terminated UTF-16 string
+    fn utf16z(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// Parse the resources of a PE image holding `resources`
+    fn parse(resources: &[(u32, u32, Vec<u8>)]) -> io::Result<Resources> {
+        let mut ret = Resources::default();
+        ret.parse_pe(&pe_with(resources))?;
+        Ok(ret)
+    }
+
+    #[test]
+    fn accelerators_become_commands_and_keys() {
+        // F1, and Ctrl+S as a chord which is only sent as a command
+        let table = words(&[FVIRTKEY, 0x70, 100, 0,
+            FVIRTKEY | 0x08 | MF_END, b'S' as u16, 101, 0]);
+        let resources = parse(&[(RT_ACCELERATOR, 1, table)]).unwrap();
+
+        assert_eq!(resources.accelerators, vec![
+            Accelerator { flags: FVIRTKEY,        key: 0x70,        id: 100 },
+            Accelerator { flags: FVIRTKEY | 0x08, key: b'S' as u16, id: 101 },
+        ]);
+        let actions = resources.actions();
+        assert_eq!(actions.len(), 3);
+        assert!(actions.contains(&FuzzerAction::KeyPress {
+            key: VirtualKey(0x70), focus: None,
+        }));
+    }
+
+    #[test]
+    fn bad_accelerators_name_the_entry() {
+        let truncated = words(&[FVIRTKEY, 0x70, 100, 0, FVIRTKEY, 0x71]);
+        let err = parse(&[(RT_ACCELERATOR, 7, truncated)]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("accelerator table 7 entry 1"),
+            "{}", err);
+
+        let unknown = words(&[0x40 | MF_END, 0x70, 100, 0]);
+        let err = parse(&[(RT_ACCELERATOR, 3, unknown)]).unwrap_err();
+        assert!(err.to_string().contains("accelerator table 3 entry 0"),
+            "{}", err);
+    }
+
+    #[test]
+    fn menus_yield_item_ids() {
+        // File popup holding Open and Exit, then a top-level Help item
+        let mut menu = vec![0, 0, MF_POPUP];
+        menu.extend(utf16z("File"));
+        menu.extend(&[0, 200]);
+        menu.extend(utf16z("Open"));
+        menu.extend(&[MF_END, 201]);
+        menu.extend(utf16z("Exit"));
+        menu.extend(&[MF_END, 202]);
+        menu.extend(utf16z("Help"));
+
+        let resources = parse(&[(RT_MENU, 1, words(&menu))]).unwrap();
+        assert_eq!(resources.menu_ids,
+            [200, 201, 202].iter().cloned().collect());
+    }
+
+    #[test]
+    fn dialogs_yield_control_ids() {
+        // DLGTEMPLATE without a font, menu, or class, and two items
+        let mut dialog = vec![0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0];
+        dialog.extend(utf16z("Title"));
+        for &id in &[1000, 0xffff] {
+            if dialog.len() % 2 != 0 {
+                dialog.push(0);
+            }
+            dialog.extend(&[0, 0, 0, 0, 0, 0, 0, 0, id]);
+            dialog.extend(&[0xffff, 0x0080, 0, 0]);
+        }
+
+        let resources = parse(&[(RT_DIALOG, 5, words(&dialog))]).unwrap();
+        assert_eq!(resources.dialogs.len(), 1);
+        assert_eq!(resources.dialogs[0].id, Some(5));
+        assert_eq!(resources.dialogs[0].title, "Title");
+        assert_eq!(resources.dialogs[0].controls, vec![1000]);
+    }
+
+    #[test]
+    fn string_tables_yield_ids() {
+        let mut block = vec![0u16; 16];
+        block[3] = 1;
+        block.insert(4, b'x' as u16);
+        let resources = parse(&[(RT_STRING, 2, words(&block))]).unwrap();
+        assert_eq!(resources.string_ids, [19].iter().cloned().collect());
+    }
+
+    #[test]
+    fn manifests_request_elevation() {
+        let manifest = b"<requestedExecutionLevel \
+            level=\"requireAdministrator\"/>".to_vec();
+        assert!(requires_elevation(&pe_with(&[(RT_MANIFEST, 1, manifest)]))
+            .unwrap());
+        assert!(!requires_elevation(&pe_with(&[])).unwrap());
+    }
+
+    #[test]
+    fn malformed_files_are_errors() {
+        assert!(enum_resources(b"not a PE file").is_err());
+        let mut pe = pe_with(&[]);
+        pe[0x40] = b'X';
+        assert!(enum_resources(&pe).is_err());
+    }
+}
@@ -27,6 +27,12 @@ impl Rng {
         }
     }
 
+    /// Get the current seed, `Rng::seeded()` with this seed will produce the
+    /// same sequence of numbers as this RNG from this point on
+    pub fn seed(&self) -> u64 {
+        self.seed.get()
+    }
+
     /// Get a random 64-bit number using xorshift
     pub fn rand(&self) -> usize {
         let mut seed = self.seed.get();
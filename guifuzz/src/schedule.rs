@@ -0,0 +1,354 @@
+//! Weighted input selection in logarithmic time. Selecting an input used to
+//! compute the weight of every input in the corpus, which dominates the
+//! cost of a fuzz case once the corpus grows to tens of thousands of
+//! inputs. Weights are instead kept in sum trees, updated one input at a
+//! time as inputs change, and sampled by walking down from the root.
+
+/// Returns `true` if the weight `weight` can't be selected: zero, negative,
+/// or NaN
+fn is_unselectable(weight: f64) -> bool {
+    weight.is_nan() || weight <= 0.
+}
+
+/// Complete binary tree of weights in heap layout, each inner node holding
+/// the sum of its two children
+#[derive(Clone, Debug, Default)]
+pub struct SumTree {
+    /// Nodes, the root at 1 and the children of node `n` at `2n` and
+    /// `2n + 1`. The leaves, the weights, start at `capacity`.
+    nodes: Vec<f64>,
+
+    /// Number of leaves, a power of two
+    capacity: usize,
+
+    /// Number of weights set
+    len: usize,
+}
+
+impl SumTree {
+    /// Create a new, empty tree
+    pub fn new() -> Self {
+        SumTree::default()
+    }
+
+    /// Number of weights, including zero ones
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no weights were set
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sum of all weights
+    pub fn total(&self) -> f64 {
+        self.nodes.get(1).cloned().unwrap_or(0.)
+    }
+
+    /// Get the weight at `idx`, 0 if it was never set
+    pub fn get(&self, idx: usize) -> f64 {
+        if idx < self.len {
+            self.nodes[self.capacity + idx]
+        } else {
+            0.
+        }
+    }
+
+    /// Set the weight at `idx` to `weight`, which must not be negative.
+    /// Weights between the last one and `idx` are zero. Sums are recomputed
+    /// from the children rather than adjusted, so they don't drift with
+    /// rounding errors over many updates.
+    pub fn set(&mut self, idx: usize, weight: f64) {
+        if idx >= self.capacity {
+            self.grow(idx + 1);
+        }
+        self.len = self.len.max(idx + 1);
+
+        let mut node = self.capacity + idx;
+        self.nodes[node] = weight;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node] + self.nodes[2 * node + 1];
+        }
+    }
+
+    /// Grow the tree to hold at least `len` weights
+    fn grow(&mut self, len: usize) {
+        let capacity = len.next_power_of_two().max(2);
+        let mut nodes = vec![0.; 2 * capacity];
+        if self.len > 0 {
+            nodes[capacity..capacity + self.len].copy_from_slice(
+                &self.nodes[self.capacity..self.capacity + self.len]);
+        }
+        for node in (1..capacity).rev() {
+            nodes[node] = nodes[2 * node] + nodes[2 * node + 1];
+        }
+
+        self.nodes    = nodes;
+        self.capacity = capacity;
+    }
+
+    /// Find the weight `point`, between 0 and `total()`, falls into when the
+    /// weights are laid end to end. Never returns a zero weight, a point
+    /// rounded past the last non-zero weight lands on it. `None` if all
+    /// weights are zero.
+    pub fn find(&self, point: f64) -> Option<usize> {
+        if is_unselectable(self.total()) {
+            return None;
+        }
+
+        let mut point = point;
+        let mut node = 1;
+        while node < self.capacity {
+            let left = self.nodes[2 * node];
+            if point < left || is_unselectable(self.nodes[2 * node + 1]) {
+                node *= 2;
+            } else {
+                point -= left;
+                node = 2 * node + 1;
+            }
+        }
+        Some(node - self.capacity)
+    }
+}
+
+/// Selection weights of the inputs of a corpus, with the inputs of one
+/// shard of the corpus optionally favored
+#[derive(Clone, Debug, Default)]
+pub struct Schedule {
+    /// Weights of all inputs
+    all: SumTree,
+
+    /// Weights of the inputs of each shard, the input at `idx` being at
+    /// `idx / shards.len()` in shard `idx % shards.len()`. Empty until a
+    /// shard is first favored.
+    shards: Vec<SumTree>,
+}
+
+impl Schedule {
+    /// Create a new, empty schedule
+    pub fn new() -> Self {
+        Schedule::default()
+    }
+
+    /// Number of inputs with a weight
+    pub fn len(&self) -> usize {
+        self.all.len()
+    }
+
+    /// Returns `true` if no input has a weight
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty()
+    }
+
+    /// Get the weight of the input at `idx`
+    pub fn weight(&self, idx: usize) -> f64 {
+        self.all.get(idx)
+    }
+
+    /// Forget the weights of all inputs
+    pub fn clear(&mut self) {
+        *self = Schedule::default();
+    }
+
+    /// Set the weight of the input at `idx` to `weight`
+    pub fn set(&mut self, idx: usize, weight: f64) {
+        self.all.set(idx, weight);
+        if !self.shards.is_empty() {
+            let num_shards = self.shards.len();
+            self.shards[idx % num_shards].set(idx / num_shards, weight);
+        }
+    }
+
+    /// Split the weights into `num_shards` shards
+    fn reshard(&mut self, num_shards: usize) {
+        self.shards = vec![SumTree::new(); num_shards];
+        for idx in 0..self.all.len() {
+            self.shards[idx % num_shards].set(idx / num_shards,
+                self.all.get(idx));
+        }
+    }
+
+    /// Select an input with a probability proportional to its weight, with
+    /// the weights of the inputs in shard `shard` of `num_shards` multiplied
+    /// by `shard_weight` if `shard` is `Some((shard, num_shards))`. `point`
+    /// is uniformly random between 0 and 1. `None` if all weights are zero.
+    ///
+    /// Favored inputs are selected exactly as often as if their weights
+    /// were multiplied: either an input is selected by its plain weight, or
+    /// one of the shard's inputs by the extra weight it was given.
+    pub fn sample(&mut self, point: f64, shard: Option<(usize, usize)>,
+            shard_weight: f64) -> Option<usize> {
+        let (shard, num_shards) = match shard {
+            Some((shard, num_shards))
+                    if num_shards > 0 && shard_weight > 1. => {
+                (shard % num_shards, num_shards)
+            }
+            _ => return self.all.find(point * self.all.total()),
+        };
+        if self.shards.len() != num_shards {
+            self.reshard(num_shards);
+        }
+
+        let total = self.all.total();
+        let extra = self.shards[shard].total() * (shard_weight - 1.);
+        let point = point * (total + extra);
+        if point < total || is_unselectable(extra) {
+            self.all.find(point)
+        } else {
+            self.shards[shard].find((point - total) / (shard_weight - 1.))
+                .map(|x| x * num_shards + shard)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of evenly spaced points sampled when checking frequencies
+    const POINTS: usize = 100_000;
+
+    /// Count how often each of `len` inputs is selected by `sample` over
+    /// evenly spaced points between 0 and 1
+    fn frequencies(len: usize, mut sample: impl FnMut(f64) -> Option<usize>)
+            -> Vec<f64> {
+        let mut counts = vec![0usize; len];
+        for ii in 0..POINTS {
+            let idx = sample((ii as f64 + 0.5) / POINTS as f64)
+                .expect("Nothing selected");
+            counts[idx] += 1;
+        }
+        counts.iter().map(|&x| x as f64 / POINTS as f64).collect()
+    }
+
+    /// Check `freqs` are proportional to `weights`
+    fn assert_proportional(freqs: &[f64], weights: &[f64]) {
+        let total: f64 = weights.iter().sum();
+        for (idx, (freq, weight)) in freqs.iter().zip(weights).enumerate() {
+            assert!((freq - weight / total).abs() < 1e-3,
+                "Input {} selected {} of the time, expected {}", idx, freq,
+                weight / total);
+        }
+    }
+
+    /// `weights` with those of the inputs in shard `shard` of `num_shards`
+    /// multiplied by `factor`
+    fn favor(weights: &[f64], shard: usize, num_shards: usize, factor: f64)
+            -> Vec<f64> {
+        weights.iter().enumerate().map(|(idx, &weight)| {
+            if idx % num_shards == shard { weight * factor } else { weight }
+        }).collect()
+    }
+
+    /// Check every inner node of `tree` is the sum of its children
+    fn assert_sums(tree: &SumTree) {
+        for node in 1..tree.capacity {
+            assert_eq!(tree.nodes[node],
+                tree.nodes[2 * node] + tree.nodes[2 * node + 1]);
+        }
+    }
+
+    #[test]
+    fn grow_keeps_weights_and_sums() {
+        let mut tree = SumTree::new();
+        for idx in 0..5 {
+            tree.set(idx, idx as f64 + 1.);
+        }
+        tree.set(100, 10.);
+        assert_sums(&tree);
+
+        assert_eq!(tree.len(), 101);
+        for idx in 0..5 {
+            assert_eq!(tree.get(idx), idx as f64 + 1.);
+        }
+        assert_eq!(tree.get(50), 0.);
+        assert_eq!(tree.get(100), 10.);
+        assert_eq!(tree.get(1000), 0.);
+        assert_eq!(tree.total(), 25.);
+
+        tree.set(2, 0.5);
+        assert_sums(&tree);
+        assert_eq!(tree.total(), 22.5);
+    }
+
+    #[test]
+    fn find_skips_zero_weights() {
+        let mut tree = SumTree::new();
+        assert_eq!(tree.find(0.), None);
+        tree.set(3, 0.);
+        assert_eq!(tree.find(0.), None);
+
+        // Only the first weight is set, the right subtrees are all zero
+        tree.set(0, 1.);
+        assert_eq!(tree.find(0.), Some(0));
+        assert_eq!(tree.find(1.), Some(0));
+
+        // Points rounded past the end land on the last non-zero weight
+        tree.set(1, 2.);
+        assert_eq!(tree.find(3.), Some(1));
+        assert_eq!(tree.find(3.5), Some(1));
+        assert_eq!(tree.find(0.999), Some(0));
+        assert_eq!(tree.find(1.), Some(1));
+    }
+
+    #[test]
+    fn find_ignores_nan_totals() {
+        let mut tree = SumTree::new();
+        tree.set(0, f64::NAN);
+        assert_eq!(tree.find(0.), None);
+    }
+
+    #[test]
+    fn sample_matches_weights() {
+        let weights = [3., 0., 1., 7., 0.5, 0., 2., 4., 1.5];
+        let mut schedule = Schedule::new();
+        for (idx, &weight) in weights.iter().enumerate() {
+            schedule.set(idx, weight);
+        }
+
+        let freqs = frequencies(weights.len(),
+            |point| schedule.sample(point, None, 1.));
+        assert_proportional(&freqs, &weights);
+    }
+
+    #[test]
+    fn sample_multiplies_shard_weights() {
+        let weights = [3., 0., 1., 7., 0.5, 0., 2., 4., 1.5, 6.];
+        let mut schedule = Schedule::new();
+        for (idx, &weight) in weights.iter().enumerate() {
+            schedule.set(idx, weight);
+        }
+
+        for num_shards in 1..5 {
+            for shard in 0..num_shards {
+                let favored = favor(&weights, shard, num_shards, 5.);
+                let freqs = frequencies(weights.len(), |point| {
+                    schedule.sample(point, Some((shard, num_shards)), 5.)
+                });
+                assert_proportional(&freqs, &favored);
+            }
+        }
+    }
+
+    #[test]
+    fn set_updates_shards() {
+        let mut schedule = Schedule::new();
+        for idx in 0..6 {
+            schedule.set(idx, 1.);
+        }
+
+        // Shard the weights, then change and add some
+        schedule.sample(0., Some((1, 3)), 2.);
+        schedule.set(4, 3.);
+        schedule.set(7, 2.);
+        schedule.set(0, 0.);
+
+        let weights = [0., 1., 1., 1., 3., 1., 0., 2.];
+        let favored = favor(&weights, 1, 3, 2.);
+        let freqs = frequencies(weights.len(),
+            |point| schedule.sample(point, Some((1, 3)), 2.));
+        assert_proportional(&freqs, &favored);
+    }
+}
@@ -0,0 +1,186 @@
+//! Snapshots of the databases of a campaign in a stable JSON format, built
+//! with the `serde` feature, so a later run can pick up the corpus,
+//! coverage, and crashes where an earlier one stopped. Modules are saved by
+//! name and inputs are referred to by their position in the snapshot, never
+//! by IDs or hashes which only hold within one run.
+
+use std::io::{self, BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::{Statistics, FuzzInput, FuzzerAction, Provenance, CrashBucket};
+use crate::corpus::{input_hash, intern_module, module_name};
+
+/// Version of the snapshot format, bumped on incompatible changes
+pub const VERSION: u32 = 1;
+
+/// An input of the corpus
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedInput {
+    /// Actions of the input
+    pub actions: Vec<FuzzerAction>,
+
+    /// Wall-clock execution cost of the input in microseconds
+    pub cost_us: u64,
+
+    /// Stability of the input from calibration, if it was calibrated
+    pub stability: Option<f64>,
+
+    /// Tags of the input
+    pub tags: Vec<String>,
+}
+
+/// An entry of the coverage database
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedCoverage {
+    /// Name of the module the coverage is in
+    pub module: String,
+
+    /// Offset of the coverage in the module
+    pub offset: usize,
+
+    /// Position in `Snapshot::inputs` of the input which first reached the
+    /// coverage, if it is in the corpus
+    pub input: Option<usize>,
+}
+
+/// The databases of a campaign
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Version of the format the snapshot was saved in
+    pub version: u32,
+
+    /// Number of fuzz cases run
+    pub fuzz_cases: u64,
+
+    /// Number of crashes
+    pub crashes: u64,
+
+    /// Inputs of the corpus, in insertion order
+    pub inputs: Vec<SavedInput>,
+
+    /// Coverage database, sorted by module and offset
+    pub coverage: Vec<SavedCoverage>,
+
+    /// Crash buckets, sorted by name
+    pub crash_buckets: Vec<CrashBucket>,
+}
+
+impl Snapshot {
+    /// Take a snapshot of `stats`, paging in all corpus inputs
+    pub fn from_statistics(stats: &mut Statistics) -> Self {
+        let mut positions = HashMap::new();
+        let mut inputs = Vec::new();
+        for idx in 0..stats.corpus.len() {
+            let input = match stats.corpus.get(idx) {
+                Some(input) => input,
+                None => continue,
+            };
+            positions.insert(input_hash(&input), inputs.len());
+            inputs.push(SavedInput {
+                actions:   input.to_vec(),
+                cost_us:   stats.corpus.cost(idx).unwrap_or_default()
+                    .as_micros() as u64,
+                stability: stats.corpus.stability(idx),
+                tags:      stats.corpus.tags(idx)
+                    .map(|x| x.iter().cloned().collect())
+                    .unwrap_or_default(),
+            });
+        }
+
+        let mut coverage: Vec<SavedCoverage> = stats.coverage_db.iter()
+            .map(|(&(module, offset), hash)| SavedCoverage {
+                module: module_name(module).to_string(),
+                offset,
+                input:  positions.get(hash).cloned(),
+            }).collect();
+        coverage.sort_by(|a, b| {
+            (&a.module, a.offset).cmp(&(&b.module, b.offset))
+        });
+
+        let mut crash_buckets: Vec<CrashBucket> = stats.crash_db.iter()
+            .cloned().collect();
+        crash_buckets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Snapshot {
+            version:    VERSION,
+            fuzz_cases: stats.fuzz_cases,
+            crashes:    stats.crashes,
+            inputs,
+            coverage,
+            crash_buckets,
+        }
+    }
+
+    /// Add the databases of the snapshot to `stats`
+    pub fn restore(self, stats: &mut Statistics) {
+        let mut inputs: Vec<FuzzInput> = Vec::new();
+        for saved in self.inputs {
+            let input = Arc::new(saved.actions);
+            stats.add_input(&input, Duration::from_micros(saved.cost_us),
+                Provenance::default());
+            if let Some(idx) = stats.corpus.index_of(&input) {
+                if let Some(stability) = saved.stability {
+                    stats.corpus.set_stability(idx, stability);
+                }
+                stats.corpus.add_tags(idx,
+                    &saved.tags.into_iter().collect());
+            }
+            inputs.push(input);
+        }
+
+        // Coverage of inputs which left the corpus is attributed to an
+        // empty input, it is only used to find the coverage's input
+        let empty: FuzzInput = Arc::new(Vec::new());
+        for entry in self.coverage {
+            let module = intern_module(&Arc::new(entry.module));
+            let input = entry.input.and_then(|x| inputs.get(x))
+                .unwrap_or(&empty);
+            stats.coverage_db.insert((module, entry.offset), input);
+        }
+
+        for bucket in self.crash_buckets {
+            stats.crash_db.insert_bucket(bucket);
+        }
+
+        stats.fuzz_cases += self.fuzz_cases;
+        stats.crashes    += self.crashes;
+    }
+
+    /// Save the snapshot to `path`, replacing it only once the snapshot is
+    /// fully written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp, path)
+    }
+
+    /// Load a snapshot saved to `path`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        if snapshot.version != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Snapshot version {} isn't supported, expected {}",
+                    snapshot.version, VERSION)));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// Encode the actions of `input` as JSON
+pub fn input_to_json(input: &[FuzzerAction]) -> String {
+    serde_json::to_string_pretty(input)
+        .expect("Actions always serialize")
+}
+
+/// Decode an input encoded by `input_to_json()`
+pub fn input_from_json(json: &str) -> io::Result<FuzzInput> {
+    Ok(Arc::new(serde_json::from_str(json)?))
+}
@@ -0,0 +1,45 @@
+//! Tab control navigation. Clicks on a tab control land wherever its tabs
+//! happen to be drawn, so the content behind tabs other than the default one
+//! is rarely reached. Switching tabs with `TCM_SETCURFOCUS` makes the control
+//! notify its parent like a click on the tab would, letting the application
+//! swap in the tab's content.
+
+use std::io;
+use std::time::Duration;
+use crate::Window;
+use crate::winbindings::MessageType;
+
+/// Class name of tab controls
+pub const TAB_CONTROL_CLASS: &str = "SysTabControl32";
+
+/// Longest time to wait for a tab control to report its number of tabs
+const QUERY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Returns `true` if `window` is a tab control
+pub fn is_tab_control(window: &Window) -> bool {
+    window.class_name().map(|x| x == TAB_CONTROL_CLASS).unwrap_or(false)
+}
+
+/// Number of tabs of the tab control `window`, 0 if it doesn't answer
+pub fn tab_count(window: &Window) -> usize {
+    window.send_message_timeout(MessageType::TcmGetItemCount, 0, 0,
+        QUERY_TIMEOUT).unwrap_or(0)
+}
+
+/// Switch the tab control `window` to the tab `tab`, modulo its number of
+/// tabs. With `focus` the switch goes through `TCM_SETCURFOCUS`, which
+/// notifies the parent of the change, otherwise through `TCM_SETCURSEL`,
+/// which changes the selection behind the parent's back.
+pub fn switch(window: &Window, tab: u16, focus: bool) -> io::Result<()> {
+    let tabs = tab_count(window);
+    if tabs == 0 {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "No tabs"));
+    }
+
+    let msg = if focus {
+        MessageType::TcmSetCurFocus
+    } else {
+        MessageType::TcmSetCurSel
+    };
+    window.post_message(msg, tab as usize % tabs, 0)
+}
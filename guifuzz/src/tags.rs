@@ -0,0 +1,65 @@
+//! Tagging of corpus inputs by the area of the UI they touched, so input
+//! selection can be restricted to one feature of the target. Tags are
+//! added automatically from the menu items an input uses and the dialogs
+//! the target had open after it, such as `menu:0x131` or `window:Convert`,
+//! and can be added manually through `Statistics::tag_input()`.
+
+use std::collections::BTreeSet;
+use crate::{Config, FuzzerAction, Window};
+
+/// Set of tags of an input
+pub type Tags = BTreeSet<String>;
+
+/// Tags for the menu items used by the actions of `input`
+pub fn action_tags(input: &[FuzzerAction]) -> Tags {
+    input.iter().filter_map(|action| match action {
+        FuzzerAction::MenuAction { menu_id, .. } => {
+            Some(format!("menu:{:#x}", u32::from(*menu_id)))
+        }
+        _ => None,
+    }).collect()
+}
+
+/// Tags for the titles of the dialogs `pid` has open
+pub fn window_tags(pid: u32) -> Tags {
+    Window::enumerate_dialogs(pid).unwrap_or_default().iter()
+        .filter_map(|dialog| dialog.window_text().ok())
+        .filter(|title| !title.is_empty())
+        .map(|title| format!("window:{}", title))
+        .collect()
+}
+
+/// Filter restricting input selection to inputs with some tags
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagFilter {
+    /// Inputs with any of these tags pass, compared ignoring ASCII case.
+    /// Empty lets every input pass.
+    tags: Vec<String>,
+}
+
+impl TagFilter {
+    /// Create a filter from a comma-separated list of tags
+    pub fn parse(tags: &str) -> Self {
+        TagFilter {
+            tags: tags.split(',').map(|x| x.trim())
+                .filter(|x| !x.is_empty()).map(|x| x.to_string()).collect(),
+        }
+    }
+
+    /// Create the filter configured in `config`
+    pub fn from_config(config: &Config) -> Self {
+        config.schedule_tags.as_ref().map(|x| Self::parse(x))
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` if the filter lets every input pass
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Check whether an input with `tags` passes the filter
+    pub fn matches(&self, tags: &Tags) -> bool {
+        self.is_empty() || self.tags.iter()
+            .any(|x| tags.iter().any(|tag| tag.eq_ignore_ascii_case(x)))
+    }
+}
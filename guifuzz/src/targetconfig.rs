@@ -0,0 +1,130 @@
+//! The application being fuzzed: how to start it, which window is its main
+//! window, and how to reset its state between cases. Without a target
+//! configured, the fuzzer targets `calc.exe`.
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use crate::{Config, Window};
+
+/// Target used by all threads, `None` until it is set
+static TARGET: Mutex<Option<TargetConfig>> = Mutex::new(None);
+
+/// The application being fuzzed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TargetConfig {
+    /// Command line to start the target with, the program first
+    pub command_line: Vec<String>,
+
+    /// Title of the target's main window
+    pub window_title: String,
+
+    /// Class of the target's main window. If set, the main window is found
+    /// by its class instead of its title, for targets whose title changes.
+    pub window_class: Option<String>,
+
+    /// Meso file with the basic blocks of the target
+    pub meso: String,
+
+    /// Commands run through `cmd.exe /c` before every case, to delete the
+    /// state the target persists between runs
+    pub reset: Vec<String>,
+
+    /// Files to extract the action dictionary from, the target's image
+    /// first
+    pub resources: Vec<String>,
+}
+
+impl TargetConfig {
+    /// Create the target configured in `config`
+    pub fn from_config(config: &Config) -> Self {
+        let program = match config.target.as_ref() {
+            Some(program) => program,
+            None => return TargetConfig::default(),
+        };
+        let name = Path::new(program).file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut command_line = vec![program.clone()];
+        command_line.extend(config.target_args.iter().cloned());
+
+        TargetConfig {
+            command_line,
+            window_title: config.target_title.clone()
+                .unwrap_or_else(|| name.clone()),
+            window_class: config.target_class.clone(),
+            meso:         config.target_meso.clone()
+                .unwrap_or_else(|| format!("{}.meso", name)),
+            reset:        config.target_reset.clone(),
+            resources:    if config.target_resources.is_empty() {
+                vec![program.clone()]
+            } else {
+                config.target_resources.clone()
+            },
+        }
+    }
+
+    /// File name of the target's program, the name its module is covered
+    /// under
+    pub fn program_name(&self) -> String {
+        Path::new(&self.command_line[0]).file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Command line to start the target with `args` appended
+    pub fn command_line_with(&self, args: &[String]) -> Vec<String> {
+        let mut argv = self.command_line.clone();
+        argv.extend(args.iter().cloned());
+        argv
+    }
+
+    /// Find the main window of the target `pid`
+    pub fn attach(&self, pid: u32) -> io::Result<Window> {
+        match self.window_class.as_ref() {
+            Some(class) => Window::attach_pid_class(pid, class),
+            None => Window::attach_pid(pid, &self.window_title),
+        }
+    }
+
+    /// Run the reset commands, deleting the state the target persisted.
+    /// Commands failing is fine, there may be no state to delete.
+    pub fn reset(&self) -> io::Result<()> {
+        for command in &self.reset {
+            Command::new("cmd.exe").args(["/c", command]).output()?;
+        }
+        Ok(())
+    }
+
+    /// Get the target used by all threads
+    pub fn global() -> Self {
+        TARGET.lock().unwrap().clone().unwrap_or_default()
+    }
+
+    /// Use this target in all threads
+    pub fn set_global(self) {
+        *TARGET.lock().unwrap() = Some(self);
+    }
+}
+
+impl Default for TargetConfig {
+    /// The Windows calculator
+    fn default() -> Self {
+        TargetConfig {
+            command_line: vec!["calc.exe".into()],
+            window_title: "Calculator".into(),
+            window_class: None,
+            meso:         "calc.exe.meso".into(),
+            reset:        vec![
+                r"reg.exe delete HKEY_CURRENT_USER\Software\Microsoft\Calc /f"
+                    .into(),
+            ],
+            resources:    vec![
+                r"C:\Windows\System32\calc.exe".into(),
+                r"C:\Windows\System32\en-US\calc.exe.mui".into(),
+            ],
+        }
+    }
+}
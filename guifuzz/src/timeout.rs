@@ -0,0 +1,136 @@
+//! Per-action deadlines. Some actions, such as clicking a menu item which
+//! opens a modal dialog, leave the target stuck in a modal loop or not
+//! answering at all, wasting the rest of the case. An action which doesn't
+//! complete, and leave the target responsive, within the deadline has the
+//! target's dialogs dismissed and aborts the case. Actions which block on
+//! the target never return on their own, so a watchdog dismisses the dialogs
+//! when the deadline passes while the action is still running, and kills
+//! the target if that doesn't get the action to return either.
+
+use std::fmt;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use crate::{Window, MessageType};
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn TerminateProcess(process: usize, exit_code: u32) -> i32;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+/// `OpenProcess()` access right to terminate a process
+const PROCESS_TERMINATE: u32 = 0x0001;
+
+/// Exit code of targets killed by the watchdog
+const WATCHDOG_EXIT_CODE: u32 = 0xdead_7107;
+
+/// `IDCANCEL` command of dialogs
+const IDCANCEL: usize = 2;
+
+/// Deadline of each action, `None` if actions have no deadline
+static ACTION_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// Error returned by `perform_actions()` when an action missed its deadline
+#[derive(Debug)]
+pub struct ActionTimeout {
+    /// Index of the action in the input
+    pub index: usize,
+
+    /// Kind of the action, as reported by `FuzzerAction::kind()`
+    pub kind: &'static str,
+}
+
+impl fmt::Display for ActionTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Action {} ({}) timed out", self.index, self.kind)
+    }
+}
+
+impl Error for ActionTimeout {}
+
+/// Set the deadline of each action, `None` to disable deadlines
+pub fn set_action_timeout(timeout: Option<Duration>) {
+    *ACTION_TIMEOUT.lock().unwrap() = timeout;
+}
+
+/// Watchdog for a single action, armed with `Watchdog::arm()` before the
+/// action is performed and disarmed when dropped
+pub struct Watchdog {
+    /// Disarms the watchdog thread when dropped, `None` if actions have no
+    /// deadline
+    _disarm: Option<Sender<()>>,
+
+    /// Set once the deadline passed while the watchdog was armed
+    expired: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    /// Start watching an action against the target `pid`. If the action is
+    /// still running at its deadline the target's dialogs are dismissed,
+    /// and if it's still running one deadline later the target is killed.
+    pub fn arm(pid: u32) -> Self {
+        let expired = Arc::new(AtomicBool::new(false));
+        let timeout = match *ACTION_TIMEOUT.lock().unwrap() {
+            Some(timeout) => timeout,
+            None => return Watchdog { _disarm: None, expired },
+        };
+
+        let (disarm, receiver) = mpsc::channel::<()>();
+        let flag = expired.clone();
+        std::thread::spawn(move || {
+            if receiver.recv_timeout(timeout) !=
+                    Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            flag.store(true, Ordering::SeqCst);
+            dismiss_dialogs(pid);
+
+            if receiver.recv_timeout(timeout) !=
+                    Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            unsafe {
+                let process = OpenProcess(PROCESS_TERMINATE, 0, pid);
+                if process != 0 {
+                    TerminateProcess(process, WATCHDOG_EXIT_CODE);
+                    CloseHandle(process);
+                }
+            }
+        });
+
+        Watchdog { _disarm: Some(disarm), expired }
+    }
+
+    /// Check whether the deadline passed while the action was running
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst)
+    }
+}
+
+/// Check whether the action started at `start` met its deadline. The target
+/// must have answered `window` by then, so an action which left the target
+/// busy misses it as well.
+pub fn action_expired(window: &Window, start: Instant) -> bool {
+    let timeout = match *ACTION_TIMEOUT.lock().unwrap() {
+        Some(timeout) => timeout,
+        None => return false,
+    };
+
+    match timeout.checked_sub(start.elapsed()) {
+        Some(remaining) => window.is_hung(remaining),
+        None => true,
+    }
+}
+
+/// Try to get the target `pid` out of modal loops by cancelling and closing
+/// all of its open dialogs
+pub fn dismiss_dialogs(pid: u32) {
+    for dialog in Window::enumerate_dialogs(pid).unwrap_or_default() {
+        let _ = dialog.post_message(MessageType::Command, IDCANCEL, 0);
+        let _ = dialog.close();
+    }
+}
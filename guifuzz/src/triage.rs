@@ -0,0 +1,178 @@
+//! Crash triage, deciding which crashes are the same bug. The debugger names
+//! crashes after how the faulting address looked, so one bug faulting on
+//! different addresses lands in several buckets. Crashes are instead
+//! bucketed by their signature: the exception code, the faulting
+//! instruction as a module offset, and a hash of the innermost frames of
+//! the call stack.
+
+use std::fmt;
+use crate::CrashInfo;
+
+/// Number of distinct innermost frames of the call stack hashed into a
+/// signature. Outer frames are the message loop, which only differs with
+/// how the window procedure was reached.
+pub const STACK_FRAMES: usize = 5;
+
+/// What identifies a crash for bucketing
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrashSignature {
+    /// Exception code, such as `0xc0000005` for an access violation
+    pub exception_code: u32,
+
+    /// Faulting instruction as `module+0xoffset`, or its address if it isn't
+    /// in a module
+    pub location: String,
+
+    /// Hash of the innermost frames of the call stack
+    pub stack_hash: u64,
+}
+
+impl CrashSignature {
+    /// Triage the crash with the machine state `info` and the symbolized call
+    /// stack `stack`, innermost frame first
+    pub fn new(info: &CrashInfo, stack: &[String]) -> Self {
+        let location = stack.first().map(|x| frame_location(x))
+            .filter(|x| in_module(x))
+            .map(|x| x.to_string())
+            .unwrap_or_else(|| format!("{:#x}", info.pc));
+
+        CrashSignature {
+            exception_code: info.exception_code,
+            location,
+            stack_hash:     stack_hash(stack),
+        }
+    }
+
+    /// Name of the bucket of crashes with this signature, usable as a
+    /// directory name
+    pub fn bucket_name(&self) -> String {
+        format!("crash_{:08x}_{}_{:016x}", self.exception_code, self.location,
+            self.stack_hash)
+    }
+}
+
+impl fmt::Display for CrashSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exception {:#010x} at {}, stack hash {:016x}",
+            self.exception_code, self.location, self.stack_hash)
+    }
+}
+
+/// Location of the symbolized stack frame `frame`, without its symbol
+fn frame_location(frame: &str) -> &str {
+    frame.split(" (").next().unwrap_or(frame)
+}
+
+/// Returns `true` if the frame location `location` is a module offset rather
+/// than an address, which changes from run to run
+fn in_module(location: &str) -> bool {
+    location.contains('+')
+}
+
+/// FNV-1a hash of the innermost `STACK_FRAMES` frames of `stack`, so it is
+/// the same across builds of the fuzzer. Frames outside of modules are
+/// hashed as unknown and recursion is collapsed into a single frame, so
+/// stack overflows of different depths hash the same.
+fn stack_hash(stack: &[String]) -> u64 {
+    let mut frames: Vec<&str> = Vec::new();
+    for frame in stack.iter().map(|x| frame_location(x)) {
+        if frames.len() >= STACK_FRAMES {
+            break;
+        }
+
+        let frame = if in_module(frame) { frame } else { "?" };
+        if frames.last() != Some(&frame) {
+            frames.push(frame);
+        }
+    }
+
+    frames.iter()
+        .flat_map(|x| x.bytes().chain(std::iter::once(0)))
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, x| {
+            (hash ^ x as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Name of the bucket of the crash the debugger named `name`. Crashes with
+/// captured machine state are bucketed by their signature, others keep the
+/// debugger's name.
+pub fn bucket(name: &str, info: Option<&CrashInfo>, stack: &[String])
+        -> String {
+    info.map(|x| CrashSignature::new(x, stack).bucket_name())
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Machine state of an access violation at `pc`
+    fn access_violation(pc: u64) -> CrashInfo {
+        CrashInfo {
+            exception_code: 0xc0000005,
+            pc,
+            access:         Some((0, 0)),
+            registers:      Vec::new(),
+            code_bytes:     Vec::new(),
+        }
+    }
+
+    /// A symbolized call stack of the frames `frames`
+    fn stack(frames: &[&str]) -> Vec<String> {
+        frames.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn location_is_the_innermost_module_offset() {
+        let sig = CrashSignature::new(&access_violation(0x7ff0_1234),
+            &stack(&["calc.exe+0x1234 (CCalcEngine::Divide+0x34)",
+                     "calc.exe+0x5678"]));
+        assert_eq!(sig.exception_code, 0xc0000005);
+        assert_eq!(sig.location, "calc.exe+0x1234");
+
+        // Addresses outside of modules fall back to the PC
+        let sig = CrashSignature::new(&access_violation(0x4141),
+            &stack(&["0x4141", "calc.exe+0x5678"]));
+        assert_eq!(sig.location, "0x4141");
+        let sig = CrashSignature::new(&access_violation(0x4141), &[]);
+        assert_eq!(sig.location, "0x4141");
+    }
+
+    #[test]
+    fn stack_hash_ignores_symbols_and_outer_frames() {
+        let frames = ["a.dll+0x1", "a.dll+0x2", "a.dll+0x3", "a.dll+0x4",
+            "a.dll+0x5"];
+        let mut outer = stack(&frames);
+        outer.push("user32.dll+0x100".into());
+        let mut symbols = stack(&frames);
+        symbols[0] += " (Foo+0x1)";
+
+        let hash = stack_hash(&stack(&frames));
+        assert_eq!(stack_hash(&outer), hash);
+        assert_eq!(stack_hash(&symbols), hash);
+        assert_ne!(stack_hash(&stack(&frames[1..])), hash);
+    }
+
+    #[test]
+    fn stack_hash_collapses_recursion_and_addresses() {
+        assert_eq!(
+            stack_hash(&stack(&["a.dll+0x1", "a.dll+0x2", "a.dll+0x2",
+                "a.dll+0x2", "a.dll+0x3"])),
+            stack_hash(&stack(&["a.dll+0x1", "a.dll+0x2", "a.dll+0x3"])));
+        assert_eq!(
+            stack_hash(&stack(&["0x1234", "a.dll+0x1"])),
+            stack_hash(&stack(&["0x5678", "a.dll+0x1"])));
+    }
+
+    #[test]
+    fn buckets_fall_back_to_the_debugger_name() {
+        let frames = stack(&["a.dll+0x1"]);
+        assert_eq!(bucket("crash_1", None, &frames), "crash_1");
+
+        let info = access_violation(0x1000);
+        let name = bucket("crash_1", Some(&info), &frames);
+        assert_eq!(name, CrashSignature::new(&info, &frames).bucket_name());
+        assert!(name.starts_with("crash_c0000005_a.dll+0x1_"));
+    }
+}
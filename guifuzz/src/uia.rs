@@ -0,0 +1,480 @@
+//! UI Automation backend, used to operate controls semantically through
+//! their control patterns rather than with raw clicks
+
+use std::io;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+use crate::Window;
+use crate::winbindings::Guid;
+
+#[link(name="Ole32")]
+extern "system" {
+    fn CoInitializeEx(reserved: usize, coinit: u32) -> i32;
+    fn CoCreateInstance(clsid: *const Guid, outer: usize, context: u32,
+        iid: *const Guid, object: *mut *mut ComObject) -> i32;
+}
+
+#[link(name="OleAut32")]
+extern "system" {
+    fn SysStringLen(bstr: *mut u16) -> u32;
+    fn SysFreeString(bstr: *mut u16);
+    fn VariantClear(variant: *mut Variant) -> i32;
+}
+
+/// Initialize COM for the thread as a single-threaded apartment
+const COINIT_APARTMENTTHREADED: u32 = 0x2;
+
+/// Create the COM object in our own process
+const CLSCTX_INPROC_SERVER: u32 = 0x1;
+
+/// `TreeScope_Descendants`, search all elements below an element
+const TREE_SCOPE_DESCENDANTS: u32 = 0x4;
+
+/// `TreeScope_Children`, search the elements directly below an element
+const TREE_SCOPE_CHILDREN: u32 = 0x2;
+
+/// Most elements hashed into a snapshot, bounding the walk of trees which
+/// change while they are walked
+const MAX_SNAPSHOT_ELEMENTS: usize = 4096;
+
+/// `VARIANT` type for a 32-bit signed integer
+const VT_I4: u16 = 3;
+
+/// `VARIANT` type for a double
+const VT_R8: u16 = 5;
+
+/// `VARIANT` type for a `BSTR` string
+const VT_BSTR: u16 = 8;
+
+/// `UIA_RangeValueValuePropertyId`
+const RANGE_VALUE_PROPERTY: i32 = 30047;
+
+/// `UIA_RangeValueMinimumPropertyId`
+const RANGE_MINIMUM_PROPERTY: i32 = 30049;
+
+/// `UIA_RangeValueMaximumPropertyId`
+const RANGE_MAXIMUM_PROPERTY: i32 = 30050;
+
+/// `UIA_ToggleToggleStatePropertyId`
+const TOGGLE_STATE_PROPERTY: i32 = 30086;
+
+/// Class ID of `CUIAutomation`
+const CLSID_CUIAUTOMATION: Guid = Guid {
+    data1: 0xff48dba4,
+    data2: 0x60ef,
+    data3: 0x4201,
+    data4: [0xaa, 0x87, 0x54, 0x10, 0x3e, 0xef, 0x59, 0x4e],
+};
+
+/// Interface ID of `IUIAutomation`
+const IID_IUIAUTOMATION: Guid = Guid {
+    data1: 0x30cbe57d,
+    data2: 0xd406,
+    data3: 0x4ff6,
+    data4: [0xb5, 0xbc, 0x6f, 0x2c, 0x2d, 0x1b, 0x1d, 0x89],
+};
+
+/// UI Automation control patterns which can be invoked on an element
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UiaPattern {
+    /// `InvokePattern.Invoke()`, like clicking a button
+    Invoke,
+
+    /// `TogglePattern.Toggle()`, like checking a check box
+    Toggle,
+
+    /// `SelectionItemPattern.Select()`, like picking a list item
+    Select,
+
+    /// `ExpandCollapsePattern.Expand()`, like opening a combo box
+    Expand,
+
+    /// `ExpandCollapsePattern.Collapse()`
+    Collapse,
+}
+
+/// All patterns, in the order they're tried
+pub const PATTERNS: &[UiaPattern] = &[
+    UiaPattern::Invoke, UiaPattern::Toggle, UiaPattern::Select,
+    UiaPattern::Expand, UiaPattern::Collapse,
+];
+
+impl UiaPattern {
+    /// Get the `UIA_*PatternId`, the pattern's interface ID, and the vtable
+    /// index of the method to call
+    fn ids(&self) -> (i32, Guid, usize) {
+        match self {
+            UiaPattern::Invoke => (10000, Guid {
+                data1: 0xfb377fbe,
+                data2: 0x8ea6,
+                data3: 0x46d5,
+                data4: [0x9c, 0x73, 0x64, 0x99, 0x64, 0x2d, 0x30, 0x59],
+            }, 3),
+            UiaPattern::Toggle => (10015, Guid {
+                data1: 0x94cf8058,
+                data2: 0x9b8d,
+                data3: 0x4ab9,
+                data4: [0x8b, 0xfd, 0x4c, 0xd0, 0xa3, 0x3c, 0x8c, 0x70],
+            }, 3),
+            UiaPattern::Select => (10010, Guid {
+                data1: 0xa8efa66a,
+                data2: 0x0fda,
+                data3: 0x421a,
+                data4: [0x91, 0x94, 0x38, 0x02, 0x1f, 0x35, 0x78, 0xea],
+            }, 3),
+            UiaPattern::Expand | UiaPattern::Collapse => {
+                let method = if *self == UiaPattern::Expand { 3 } else { 4 };
+                (10005, Guid {
+                    data1: 0x619be086,
+                    data2: 0x1f4e,
+                    data3: 0x4ee4,
+                    data4: [0xba, 0xfa, 0x21, 0x01, 0x28, 0x73, 0x87, 0x30],
+                }, method)
+            }
+        }
+    }
+}
+
+/// Rust implementation of `VARIANT`, only used for property values
+#[repr(C)]
+struct Variant {
+    vt:       u16,
+    reserved: [u16; 3],
+    val:      [usize; 2],
+}
+
+/// Value of a UI Automation property
+#[derive(Clone, Debug, PartialEq)]
+enum Property {
+    Empty,
+    Int(i32),
+    Double(f64),
+    Str(String),
+}
+
+impl Property {
+    /// Get the property as a double, for range values
+    fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Property::Int(val)    => Some(val as f64),
+            Property::Double(val) => Some(val),
+            _ => None,
+        }
+    }
+}
+
+/// Convert a `BSTR` to a `String` and free it
+unsafe fn take_bstr(bstr: *mut u16) -> String {
+    if bstr.is_null() {
+        return String::new();
+    }
+
+    let len = SysStringLen(bstr) as usize;
+    let ret = String::from_utf16_lossy(std::slice::from_raw_parts(bstr, len));
+    SysFreeString(bstr);
+    ret
+}
+
+/// Any COM object, accessed through its raw vtable
+#[repr(C)]
+struct ComObject {
+    vtbl: *const usize,
+}
+
+/// An owned reference to a COM object, released on drop
+struct ComPtr(*mut ComObject);
+
+impl ComPtr {
+    /// Get the function at `index` in the object's vtable
+    unsafe fn method(&self, index: usize) -> usize {
+        *(*self.0).vtbl.add(index)
+    }
+
+    /// Call a method which takes one pointer argument
+    unsafe fn call_ptr(&self, index: usize, arg: usize) -> i32 {
+        let func: unsafe extern "system" fn(*mut ComObject, usize) -> i32 =
+            std::mem::transmute(self.method(index));
+        func(self.0, arg)
+    }
+
+    /// Call a method which returns a new COM object through an out pointer
+    /// after the `args`
+    unsafe fn call_out(&self, index: usize, args: &[usize])
+            -> io::Result<ComPtr> {
+        let mut out: *mut ComObject = std::ptr::null_mut();
+        let outp = &mut out as *mut _ as usize;
+        let hr = match args.len() {
+            0 => self.call_ptr(index, outp),
+            1 => {
+                let func: unsafe extern "system" fn(*mut ComObject, usize,
+                    usize) -> i32 = std::mem::transmute(self.method(index));
+                func(self.0, args[0], outp)
+            }
+            2 => {
+                let func: unsafe extern "system" fn(*mut ComObject, usize,
+                    usize, usize) -> i32 =
+                    std::mem::transmute(self.method(index));
+                func(self.0, args[0], args[1], outp)
+            }
+            _ => unreachable!(),
+        };
+
+        if hr < 0 || out.is_null() {
+            Err(io::Error::other(format!("COM call failed: {:#x}", hr)))
+        } else {
+            Ok(ComPtr(out))
+        }
+    }
+}
+
+impl Drop for ComPtr {
+    fn drop(&mut self) {
+        unsafe {
+            // `IUnknown::Release()`
+            let release: unsafe extern "system" fn(*mut ComObject) -> u32 =
+                std::mem::transmute(self.method(2));
+            release(self.0);
+        }
+    }
+}
+
+/// An element in the UI Automation tree
+pub struct UiaElement(ComPtr);
+
+impl UiaElement {
+    /// Returns `true` if the element supports `pattern`
+    pub fn supports(&self, pattern: UiaPattern) -> bool {
+        self.pattern(pattern).is_ok()
+    }
+
+    /// Get the interface for `pattern` on this element
+    fn pattern(&self, pattern: UiaPattern) -> io::Result<ComPtr> {
+        let (id, iid, _) = pattern.ids();
+
+        // `IUIAutomationElement::GetCurrentPatternAs()`
+        unsafe {
+            self.0.call_out(14, &[id as usize, &iid as *const Guid as usize])
+        }
+    }
+
+    /// Get the `UIA_*ControlTypeId` of this element
+    pub fn control_type(&self) -> i32 {
+        // `IUIAutomationElement::get_CurrentControlType()`
+        let mut control_type = 0i32;
+        unsafe {
+            self.0.call_ptr(21, &mut control_type as *mut i32 as usize);
+        }
+        control_type
+    }
+
+    /// Get the name of this element
+    pub fn name(&self) -> String {
+        // `IUIAutomationElement::get_CurrentName()`
+        let mut bstr: *mut u16 = std::ptr::null_mut();
+        unsafe {
+            if self.0.call_ptr(23, &mut bstr as *mut _ as usize) < 0 {
+                return String::new();
+            }
+            take_bstr(bstr)
+        }
+    }
+
+    /// Get the current value of the property `id`
+    fn property(&self, id: i32) -> Property {
+        let mut variant = Variant { vt: 0, reserved: [0; 3], val: [0; 2] };
+
+        // `IUIAutomationElement::GetCurrentPropertyValue()`
+        unsafe {
+            let func: unsafe extern "system" fn(*mut ComObject, i32,
+                *mut Variant) -> i32 = std::mem::transmute(self.0.method(10));
+            if func((self.0).0, id, &mut variant) < 0 {
+                return Property::Empty;
+            }
+
+            let ret = match variant.vt {
+                VT_I4 => Property::Int(variant.val[0] as u32 as i32),
+                VT_R8 => Property::Double(f64::from_bits(
+                    variant.val[0] as u64)),
+                VT_BSTR => {
+                    let bstr = variant.val[0] as *mut u16;
+                    let len  = SysStringLen(bstr) as usize;
+                    Property::Str(String::from_utf16_lossy(
+                        std::slice::from_raw_parts(bstr, len)))
+                }
+                _ => Property::Empty,
+            };
+            VariantClear(&mut variant);
+            ret
+        }
+    }
+
+    /// Check the element's properties for values which should be
+    /// impossible, returning a description of each one found
+    fn violations(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        let describe = || {
+            format!("control type {} {:?}", self.control_type(), self.name())
+        };
+
+        // Range values must be within their minimum and maximum
+        let value   = self.property(RANGE_VALUE_PROPERTY).as_f64();
+        let minimum = self.property(RANGE_MINIMUM_PROPERTY).as_f64();
+        let maximum = self.property(RANGE_MAXIMUM_PROPERTY).as_f64();
+        if let (Some(value), Some(min), Some(max)) = (value, minimum, maximum) {
+            if min > max || min.is_nan() || max.is_nan() {
+                ret.push(format!("{}: range minimum {} above maximum {}",
+                    describe(), min, max));
+            } else if value.is_nan() || value < min || value > max {
+                ret.push(format!("{}: range value {} outside [{}, {}]",
+                    describe(), value, min, max));
+            }
+        }
+
+        // Toggle states are off, on, or indeterminate
+        if let Property::Int(state) = self.property(TOGGLE_STATE_PROPERTY) {
+            if !(0..=2).contains(&state) {
+                ret.push(format!("{}: invalid toggle state {}",
+                    describe(), state));
+            }
+        }
+
+        ret
+    }
+
+    /// Invoke `pattern` on this element
+    pub fn invoke(&self, pattern: UiaPattern) -> io::Result<()> {
+        let (_, _, method) = pattern.ids();
+        let object = self.pattern(pattern)?;
+
+        let hr = unsafe {
+            let func: unsafe extern "system" fn(*mut ComObject) -> i32 =
+                std::mem::transmute(object.method(method));
+            func(object.0)
+        };
+
+        if hr < 0 {
+            Err(io::Error::other(
+                format!("Failed to invoke {:?}: {:#x}", pattern, hr)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Summary of the UI Automation tree of a window at one point in time
+#[derive(Clone, Debug, Default)]
+pub struct UiaSnapshot {
+    /// Hash of the shape of the tree: the control type and number of
+    /// children of every element, in tree order. Names and values are left
+    /// out, they change with nearly every case without the UI changing.
+    pub hash: u64,
+
+    /// Number of elements in the tree
+    pub elements: usize,
+
+    /// Descriptions of property invariants which were violated
+    pub violations: Vec<String>,
+}
+
+/// Connection to UI Automation
+pub struct Uia {
+    /// `IUIAutomation` object
+    automation: ComPtr,
+
+    /// Condition matching every element
+    true_condition: ComPtr,
+}
+
+impl Uia {
+    /// Connect to UI Automation, fails if it is not available
+    pub fn new() -> io::Result<Self> {
+        unsafe {
+            // This may fail if COM is already initialized differently for
+            // this thread, which is fine
+            CoInitializeEx(0, COINIT_APARTMENTTHREADED);
+
+            let mut automation = std::ptr::null_mut();
+            let hr = CoCreateInstance(&CLSID_CUIAUTOMATION, 0,
+                CLSCTX_INPROC_SERVER, &IID_IUIAUTOMATION, &mut automation);
+            if hr < 0 || automation.is_null() {
+                return Err(io::Error::other(
+                    format!("UI Automation is unavailable: {:#x}", hr)));
+            }
+            let automation = ComPtr(automation);
+
+            // `IUIAutomation::CreateTrueCondition()`
+            let true_condition = automation.call_out(21, &[])?;
+
+            Ok(Uia { automation, true_condition })
+        }
+    }
+
+    /// Get the element for `window`
+    pub fn element(&self, window: &Window) -> io::Result<UiaElement> {
+        // `IUIAutomation::ElementFromHandle()`
+        unsafe {
+            self.automation.call_out(6, &[window.hwnd()]).map(UiaElement)
+        }
+    }
+
+    /// Get all elements below `window`, in tree order. Indices into this
+    /// list are used to refer to elements in actions.
+    pub fn elements(&self, window: &Window) -> io::Result<Vec<UiaElement>> {
+        self.find_all(&self.element(window)?, TREE_SCOPE_DESCENDANTS)
+    }
+
+    /// Get all elements in `scope` of `element`
+    fn find_all(&self, element: &UiaElement, scope: u32)
+            -> io::Result<Vec<UiaElement>> {
+        unsafe {
+            // `IUIAutomationElement::FindAll()`
+            let array = element.0.call_out(6, &[scope as usize,
+                self.true_condition.0 as usize])?;
+
+            // `IUIAutomationElementArray::get_Length()`
+            let mut length = 0i32;
+            if array.call_ptr(3, &mut length as *mut i32 as usize) < 0 {
+                return Ok(Vec::new());
+            }
+
+            // `IUIAutomationElementArray::GetElement()`
+            Ok((0..length.max(0) as usize).filter_map(|idx| {
+                array.call_out(4, &[idx]).ok().map(UiaElement)
+            }).collect())
+        }
+    }
+
+    /// Take a snapshot of the UI Automation tree below `window`
+    pub fn snapshot(&self, window: &Window) -> io::Result<UiaSnapshot> {
+        let mut hasher = DefaultHasher::new();
+        let mut elements = 0;
+        let mut violations = Vec::new();
+
+        // Walk the tree depth first, children found on the way are pushed
+        // in reverse so they are popped in order
+        let mut stack = self.find_all(&self.element(window)?,
+            TREE_SCOPE_CHILDREN)?;
+        stack.reverse();
+        while let Some(element) = stack.pop() {
+            if elements >= MAX_SNAPSHOT_ELEMENTS {
+                break;
+            }
+            elements += 1;
+
+            let children = self.find_all(&element, TREE_SCOPE_CHILDREN)
+                .unwrap_or_default();
+            element.control_type().hash(&mut hasher);
+            children.len().hash(&mut hasher);
+            violations.extend(element.violations());
+            stack.extend(children.into_iter().rev());
+        }
+
+        Ok(UiaSnapshot {
+            hash: hasher.finish(),
+            elements,
+            violations,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+//! Compact descriptors of the UI state of the target, captured before and
+//! after a case: the shape of the main window's tree and the values of its
+//! key controls. The diff between the two tells how much a case changed
+//! the UI, for scheduling, and what it changed, for triage.
+
+use std::fmt;
+use std::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use crate::{Window, tabs, ranges};
+use crate::winbindings::MessageType;
+
+/// Longest time to wait for a control to report its value
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Number of characters of control text kept, longer text is cut
+const MAX_TEXT_LEN: usize = 64;
+
+/// Size a change of the window tree's shape adds to a diff, in changed
+/// control values
+pub const SHAPE_CHANGE_SIZE: usize = 8;
+
+/// UI state of the target
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UiState {
+    /// Shape hash of the main window's tree
+    pub shape_hash: u64,
+
+    /// Values of the key controls, keyed by their class and position among
+    /// the controls of that class, such as `Edit#0`
+    pub values: BTreeMap<String, String>,
+}
+
+impl UiState {
+    /// Capture the UI state of the main window `window`
+    pub fn capture(window: &Window) -> Self {
+        let controls = window.enumerate_subwindows()
+            .map(|x| x.to_vec()).unwrap_or_default();
+
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut values = BTreeMap::new();
+        for control in controls {
+            let class = match control.class_name() {
+                Ok(class) => class,
+                Err(_) => continue,
+            };
+            let position = positions.entry(class.clone()).or_insert(0);
+            let key = format!("{}#{}", class, position);
+            *position += 1;
+
+            if let Some(value) = control_value(&control, &class) {
+                values.insert(key, value);
+            }
+        }
+
+        UiState {
+            shape_hash: window.dump_tree().shape_hash(),
+            values,
+        }
+    }
+
+    /// Differences from this state to the later state `new`
+    pub fn diff(&self, new: &UiState) -> StateDiff {
+        let mut values = Vec::new();
+        for (key, old) in &self.values {
+            match new.values.get(key) {
+                Some(value) if value == old => {}
+                value => {
+                    values.push((key.clone(), Some(old.clone()),
+                        value.cloned()));
+                }
+            }
+        }
+        for (key, value) in &new.values {
+            if !self.values.contains_key(key) {
+                values.push((key.clone(), None, Some(value.clone())));
+            }
+        }
+
+        StateDiff {
+            shape_changed: self.shape_hash != new.shape_hash,
+            values,
+        }
+    }
+}
+
+/// Differences between two UI states
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// The shape of the window tree changed
+    pub shape_changed: bool,
+
+    /// Controls whose value changed, as (key, old value, new value), with
+    /// `None` for a control which didn't exist
+    pub values: Vec<(String, Option<String>, Option<String>)>,
+}
+
+impl StateDiff {
+    /// Size of the diff, the number of changed control values plus
+    /// `SHAPE_CHANGE_SIZE` if the shape changed
+    pub fn size(&self) -> usize {
+        self.values.len() +
+            if self.shape_changed { SHAPE_CHANGE_SIZE } else { 0 }
+    }
+
+    /// Returns `true` if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.shape_changed {
+            writeln!(f, "window tree shape changed")?;
+        }
+        for (key, old, new) in &self.values {
+            writeln!(f, "{}: {} -> {}", key,
+                old.as_ref().map(|x| x.as_str()).unwrap_or("(none)"),
+                new.as_ref().map(|x| x.as_str()).unwrap_or("(none)"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Value of the key control `control` of the class `class`, `None` if it
+/// isn't a key control or doesn't answer
+fn control_value(control: &Window, class: &str) -> Option<String> {
+    let query = |msg| {
+        control.send_message_timeout(msg, 0, 0, QUERY_TIMEOUT).ok()
+    };
+    match class {
+        "Button" => query(MessageType::BmGetCheck)
+            .map(|x| format!("check {}", x)),
+        "ComboBox" => query(MessageType::CbGetCurSel)
+            .map(|x| format!("selection {}", x as isize)),
+        "ListBox" => query(MessageType::LbGetCurSel)
+            .map(|x| format!("selection {}", x as isize)),
+        tabs::TAB_CONTROL_CLASS => query(MessageType::TcmGetCurSel)
+            .map(|x| format!("tab {}", x as isize)),
+        ranges::TRACKBAR_CLASS => query(MessageType::TbmGetPos)
+            .map(|x| format!("position {}", x as i32)),
+        _ if class == "Static" || control.is_edit() => {
+            control.window_text().ok()
+                .map(|x| format!("{:?}", x.chars().take(MAX_TEXT_LEN)
+                    .collect::<String>()))
+        }
+        _ => None,
+    }
+}
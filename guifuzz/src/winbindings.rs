@@ -3,7 +3,12 @@ use std::fmt;
 use std::error::Error;
 use std::convert::TryInto;
 use std::ops::Deref;
-use std::collections::BTreeSet;
+use std::time::Duration;
+use std::cell::Cell;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::collections::{BTreeSet, HashMap};
+use crate::windowtree::WindowNode;
+use crate::{Config, Rng, audit, MenuId, VirtualKey};
 
 /// Callback function for `EnumChildWindows()`
 type EnumChildProc = extern "C" fn(hwnd: usize, lparam: usize) -> bool;
@@ -11,6 +16,10 @@ type EnumChildProc = extern "C" fn(hwnd: usize, lparam: usize) -> bool;
 /// Callback function for `EnumWindows()`
 type EnumWindowsProc = extern "C" fn (hwnd: usize, lparam: usize) -> bool;
 
+/// Callback function for `EnumDisplayMonitors()`
+type MonitorEnumProc = extern "system" fn(monitor: usize, hdc: usize,
+    rect: *mut Rect, lparam: usize) -> i32;
+
 #[link(name="User32")]
 extern "system" {
     fn FindWindowW(lpClassName: *mut u16, lpWindowName: *mut u16) -> usize;
@@ -24,8 +33,294 @@ extern "system" {
     fn GetSubMenu(hwnd: usize, pos: i32) -> usize;
     fn GetMenuItemID(menu: usize, pos: i32) -> u32;
     fn GetMenuItemCount(menu: usize) -> i32;
+    fn GetMenuStringW(menu: usize, item: u32, string: *mut u16, chars: i32,
+        flags: u32) -> i32;
     fn EnumWindows(func: EnumWindowsProc, lparam: usize) -> bool;
     fn GetWindowThreadProcessId(hwnd: usize, pid: *mut u32) -> u32;
+    fn GetClassNameW(hwnd: usize, string: *mut u16, chars: i32) -> i32;
+    fn GetDlgItem(hwnd: usize, id: i32) -> usize;
+    fn IsWindow(hwnd: usize) -> bool;
+    fn IsWindowVisible(hwnd: usize) -> bool;
+    fn GetWindowRect(hwnd: usize, rect: *mut Rect) -> bool;
+    fn GetClientRect(hwnd: usize, rect: *mut Rect) -> bool;
+    fn SetWindowPos(hwnd: usize, insert_after: usize, x: i32, y: i32,
+        cx: i32, cy: i32, flags: u32) -> bool;
+    fn ShowWindowAsync(hwnd: usize, cmd: i32) -> bool;
+    fn EnumDisplayMonitors(hdc: usize, clip: *const Rect,
+        func: MonitorEnumProc, lparam: usize) -> bool;
+    fn GetSystemMetrics(index: i32) -> i32;
+    fn LoadKeyboardLayoutW(klid: *const u16, flags: u32) -> usize;
+    fn ActivateKeyboardLayout(hkl: usize, flags: u32) -> usize;
+    fn SendMessageTimeoutW(hwnd: usize, msg: u32, wparam: usize,
+        lparam: usize, flags: u32, timeout: u32, result: *mut usize) -> usize;
+    fn GetAncestor(hwnd: usize, flags: u32) -> usize;
+    fn SetForegroundWindow(hwnd: usize) -> bool;
+    fn SetCursorPos(x: i32, y: i32) -> i32;
+    fn SendInput(count: u32, inputs: *const Input, size: i32) -> u32;
+    fn GetWindow(hwnd: usize, cmd: u32) -> usize;
+    fn GetWindowLongW(hwnd: usize, index: i32) -> i32;
+    fn GetWindowLongPtrW(hwnd: usize, index: i32) -> usize;
+    fn GetGUIThreadInfo(tid: u32, info: *mut GuiThreadInfoRaw) -> i32;
+    fn WaitForInputIdle(process: usize, timeout: u32) -> u32;
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn VirtualAllocEx(process: usize, addr: usize, size: usize,
+        alloc_type: u32, protect: u32) -> usize;
+    fn VirtualFreeEx(process: usize, addr: usize, size: usize,
+        free_type: u32) -> i32;
+    fn WriteProcessMemory(process: usize, addr: usize, buf: *const u8,
+        size: usize, written: *mut usize) -> i32;
+    fn CloseHandle(handle: usize) -> i32;
+    fn GetProcessTimes(process: usize, creation: *mut u64, exit: *mut u64,
+        kernel: *mut u64, user: *mut u64) -> i32;
+}
+
+/// Access rights needed to allocate and write memory in another process
+const PROCESS_VM_ACCESS: u32 = 0x0008 | 0x0020;
+
+/// `GetMenuStringW()` flag selecting items by position
+const MF_BYPOSITION: u32 = 0x0400;
+
+/// Access rights needed to query the creation time of a process
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+/// Access rights needed to wait on a process with `WaitForInputIdle()`,
+/// `PROCESS_QUERY_INFORMATION | SYNCHRONIZE`
+const PROCESS_WAIT_ACCESS: u32 = 0x0400 | 0x0010_0000;
+
+/// `WAIT_TIMEOUT` returned by waits
+const WAIT_TIMEOUT: u32 = 258;
+
+/// Reserve and commit memory with `VirtualAllocEx()`
+const MEM_COMMIT_RESERVE: u32 = 0x1000 | 0x2000;
+
+/// Release memory allocated with `VirtualAllocEx()`
+const MEM_RELEASE: u32 = 0x8000;
+
+/// Longest time to wait for a window to handle a message carrying memory
+/// written with `remote_write()`, before leaving the memory to it
+const REMOTE_MESSAGE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Read-write page protection
+const PAGE_READWRITE: u32 = 0x04;
+
+/// Policy for retrying Win32 calls which fail transiently, for example
+/// while the target is repainting
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Number of attempts made before giving up, at least 1
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled for every retry after it
+    pub base_delay: Duration,
+
+    /// Maximum delay between two attempts
+    pub max_delay: Duration,
+}
+
+/// Retry policy used by all flaky calls, shared between all threads
+static RETRY_POLICY: Mutex<RetryPolicy> = Mutex::new(RetryPolicy {
+    attempts:   3,
+    base_delay: Duration::from_millis(10),
+    max_delay:  Duration::from_millis(200),
+});
+
+impl RetryPolicy {
+    /// Create the retry policy configured in `config`
+    pub fn from_config(config: &Config) -> Self {
+        RetryPolicy {
+            attempts:   config.win32_retries.max(1),
+            base_delay: Duration::from_millis(config.win32_retry_delay_ms),
+            max_delay:  Duration::from_millis(config.win32_retry_delay_ms)
+                * 20,
+        }
+    }
+
+    /// Get the retry policy used by all flaky calls
+    pub fn global() -> Self {
+        *RETRY_POLICY.lock().unwrap()
+    }
+
+    /// Use this policy for all flaky calls
+    pub fn set_global(self) {
+        *RETRY_POLICY.lock().unwrap() = self;
+    }
+
+    /// Call `func` until it succeeds or the attempts run out, sleeping with
+    /// exponential backoff and random jitter between attempts. Errors
+    /// without an OS error code aren't transient and are returned
+    /// immediately.
+    pub fn run<T, F>(&self, mut func: F) -> io::Result<T>
+            where F: FnMut() -> io::Result<T> {
+        let mut delay = self.base_delay;
+        let mut attempt = 1;
+
+        loop {
+            // Randomly fail calls to exercise retries and error handling
+            #[cfg(feature = "chaos")]
+            let result = crate::chaos::fail_call().and_then(|()| func());
+            #[cfg(not(feature = "chaos"))]
+            let result = func();
+
+            match result {
+                Err(ref err) if attempt < self.attempts &&
+                        err.raw_os_error().unwrap_or(0) != 0 => {
+                    // Sleep for between half and all of the delay so
+                    // threads retrying together spread out
+                    let jitter = Rng::new().rand() as u64 %
+                        (delay.as_micros() as u64 / 2 + 1);
+                    std::thread::sleep(delay / 2 +
+                        Duration::from_micros(jitter));
+
+                    delay = std::cmp::min(delay * 2, self.max_delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// `GetWindow()` commands to get the first child and the next sibling
+const GW_CHILD:    u32 = 5;
+const GW_HWNDNEXT: u32 = 2;
+
+/// `GetWindowLongW()` indices of the styles and extended styles
+const GWL_STYLE:   i32 = -16;
+const GWL_EXSTYLE: i32 = -20;
+
+/// `GetWindowLongPtrW()` index of the module instance owning the window
+const GWLP_HINSTANCE: i32 = -6;
+
+/// Maximum depth of a window tree dump, in case of cycles while windows are
+/// being reparented
+const MAX_TREE_DEPTH: usize = 32;
+
+/// Maximum number of children of a single window in a window tree dump, in
+/// case of a sibling cycle while windows are being reordered
+const MAX_TREE_CHILDREN: usize = 4096;
+
+/// `GetAncestor()` flag to get the top-level window
+const GA_ROOT: u32 = 2;
+
+/// `INPUT` types for mouse and keyboard input
+const INPUT_MOUSE:    u32 = 0;
+const INPUT_KEYBOARD: u32 = 1;
+
+/// Mouse input flags for pressing and releasing the left button
+const MOUSEEVENTF_LEFTDOWN: u32 = 0x0002;
+const MOUSEEVENTF_LEFTUP:   u32 = 0x0004;
+
+/// Keyboard input flag for releasing a key
+const KEYEVENTF_KEYUP: u32 = 0x0002;
+
+/// Virtual key of the shift key
+const VK_SHIFT: u16 = 0x10;
+
+/// Virtual key of the control key
+const VK_CONTROL: u16 = 0x11;
+
+/// Rust implementation of `MOUSEINPUT`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct MouseInput {
+    dx:         i32,
+    dy:         i32,
+    mouse_data: u32,
+    flags:      u32,
+    time:       u32,
+    extra_info: usize,
+}
+
+/// Rust implementation of `KEYBDINPUT`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct KeyboardInput {
+    vk:         u16,
+    scan:       u16,
+    flags:      u32,
+    time:       u32,
+    extra_info: usize,
+}
+
+/// Rust implementation of the union in `INPUT`
+#[repr(C)]
+#[derive(Clone, Copy)]
+union InputData {
+    mouse:    MouseInput,
+    keyboard: KeyboardInput,
+}
+
+/// Rust implementation of `INPUT`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Input {
+    kind: u32,
+    data: InputData,
+}
+
+/// Synthesize `inputs` with `SendInput()`
+fn send_input(inputs: &[Input]) -> io::Result<()> {
+    let sent = unsafe {
+        SendInput(inputs.len() as u32, inputs.as_ptr(),
+            std::mem::size_of::<Input>() as i32)
+    };
+
+    if sent as usize == inputs.len() {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Held while driving the real cursor, keyboard, or foreground window,
+/// which the targets of all workers share
+static DESKTOP: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// Number of `DesktopGuard`s alive on this thread
+    static DESKTOP_HELD: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Exclusive use of the desktop, given up when the last guard of the
+/// thread is dropped
+pub struct DesktopGuard {
+    /// Lock on the desktop, `None` if an outer guard of the thread holds it
+    _guard: Option<MutexGuard<'static, ()>>,
+}
+
+impl Drop for DesktopGuard {
+    fn drop(&mut self) {
+        DESKTOP_HELD.with(|x| x.set(x.get() - 1));
+    }
+}
+
+/// Take exclusive use of the desktop, so input driving the real cursor or
+/// foreground window lands on the target of this worker rather than on
+/// whichever target is on top. Taking it again on the same thread doesn't
+/// block, so a whole replay can hold it around actions which take it too.
+pub fn lock_desktop() -> DesktopGuard {
+    let guard = if DESKTOP_HELD.with(|x| x.get()) == 0 {
+        Some(DESKTOP.lock().unwrap_or_else(PoisonError::into_inner))
+    } else {
+        None
+    };
+    DESKTOP_HELD.with(|x| x.set(x.get() + 1));
+    DesktopGuard { _guard: guard }
+}
+
+/// Ways of delivering clicks and key presses to the target
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputBackend {
+    /// Post mouse and keyboard messages directly to the window
+    PostMessage,
+
+    /// Synthesize real input with `SendInput()` after bringing the window
+    /// to the foreground, so it goes through the system input queue
+    SendInput,
 }
 
 #[repr(C)]
@@ -37,8 +332,72 @@ struct Rect {
     bottom: i32,
 }
 
+/// Rust implementation of `GUITHREADINFO`
+#[repr(C)]
+struct GuiThreadInfoRaw {
+    size:      u32,
+    flags:     u32,
+    active:    usize,
+    focus:     usize,
+    capture:   usize,
+    menu:      usize,
+    move_size: usize,
+    caret:     usize,
+    caret_pos: Rect,
+}
+
+/// `GUI_INMENUMODE`, the thread is in a menu loop
+const GUI_INMENUMODE: u32 = 0x4;
+
+/// Input state of the GUI thread owning a window, from `GetGUIThreadInfo()`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GuiThreadInfo {
+    /// Active window
+    pub active: Option<Window>,
+
+    /// Window with the keyboard focus
+    pub focus: Option<Window>,
+
+    /// Window which captured the mouse
+    pub capture: Option<Window>,
+
+    /// Window showing the caret and the caret's position in its client area
+    pub caret: Option<(Window, i32, i32)>,
+
+    /// Whether the thread is in a menu loop
+    pub menu_mode: bool,
+}
+
+impl fmt::Display for GuiThreadInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hwnd = |window: Option<Window>| {
+            window.map(|x| format!("{:#x}", x.hwnd))
+                .unwrap_or("none".into())
+        };
+
+        write!(f, "active={} focus={} capture={}", hwnd(self.active),
+            hwnd(self.focus), hwnd(self.capture))?;
+        if let Some((window, x, y)) = self.caret {
+            write!(f, " caret={:#x}@({}, {})", window.hwnd, x, y)?;
+        }
+        if self.menu_mode {
+            write!(f, " menu")?;
+        }
+        Ok(())
+    }
+}
+
+/// Rust implementation of `GUID`, used for COM interface IDs
+#[repr(C)]
+pub(crate) struct Guid {
+    pub data1: u32,
+    pub data2: u16,
+    pub data3: u16,
+    pub data4: [u8; 8],
+}
+
 /// Convert a Rust UTF-8 `string` into a NUL-terminated UTF-16 vector
-fn str_to_utf16(string: &str) -> Vec<u16> {
+pub(crate) fn str_to_utf16(string: &str) -> Vec<u16> {
     let mut ret: Vec<u16> = string.encode_utf16().collect();
     ret.push(0);
     ret
@@ -75,7 +434,8 @@ impl Deref for WindowListing {
 
 /// Different message types to be sent to `PostMessage()` and `SendMessage()`
 #[repr(u32)]
-enum MessageType {
+#[derive(Clone, Copy, Debug)]
+pub enum MessageType {
     /// Left mouse button down event
     LButtonDown = 0x0201,
 
@@ -94,8 +454,261 @@ enum MessageType {
 
     /// Sends a graceful exit to the window
     Close = 0x0010,
+
+    /// Notifies the window that one of its timers elapsed
+    Timer = 0x0113,
+
+    /// Asks the window to repaint itself
+    Paint = 0x000f,
+
+    /// Notifies the window that its size changed
+    Size = 0x0005,
+
+    /// Notifies the window that the DPI of its monitor changed
+    DpiChanged = 0x02e0,
+
+    /// Notifies the window that a system-wide setting changed
+    SettingChange = 0x001a,
+
+    /// Notifies the window that the theme changed
+    ThemeChanged = 0x031a,
+
+    /// Notifies the window that the display resolution changed
+    DisplayChange = 0x007e,
+
+    /// Notifies the window of a power management event
+    PowerBroadcast = 0x0218,
+
+    /// Asks the window if the session can be ended
+    QueryEndSession = 0x0011,
+
+    /// Notifies the window whether the session is ending
+    EndSession = 0x0016,
+
+    /// Notifies the window of a change to the hardware configuration
+    DeviceChange = 0x0219,
+
+    /// Asks the window to change its input language
+    InputLangChangeRequest = 0x0050,
+
+    /// Notifies the window that its input language changed
+    InputLangChange = 0x0051,
+
+    /// Notifies a clipboard format listener that the clipboard changed
+    ClipboardUpdate = 0x031d,
+
+    /// Notifies the window that the pool of font resources changed
+    FontChange = 0x001d,
+
+    /// Asks an MDI client to create a child window
+    MdiCreate = 0x0220,
+
+    /// Asks an MDI client to close a child window
+    MdiDestroy = 0x0221,
+
+    /// Asks an MDI client to activate a child window
+    MdiActivate = 0x0222,
+
+    /// Asks an MDI client to restore a child window
+    MdiRestore = 0x0223,
+
+    /// Asks an MDI client to activate the next or previous child window
+    MdiNext = 0x0224,
+
+    /// Asks an MDI client to maximize a child window
+    MdiMaximize = 0x0225,
+
+    /// Asks an MDI client to tile its child windows
+    MdiTile = 0x0226,
+
+    /// Asks an MDI client to cascade its child windows
+    MdiCascade = 0x0227,
+
+    /// Asks an MDI client to arrange its minimized child windows
+    MdiIconArrange = 0x0228,
+
+    /// Asks a button for its check state, `BM_GETCHECK`
+    BmGetCheck = 0x00f0,
+
+    /// Asks a combo box for its selected item, `CB_GETCURSEL`
+    CbGetCurSel = 0x0147,
+
+    /// Asks a list box for its selected item, `LB_GETCURSEL`
+    LbGetCurSel = 0x0188,
+
+    /// Asks a trackbar for the position of its thumb, `TBM_GETPOS`
+    TbmGetPos = 0x0400,
+
+    /// Asks a trackbar for the minimum of its range, `TBM_GETRANGEMIN`
+    TbmGetRangeMin = 0x0401,
+
+    /// Asks a trackbar for the maximum of its range, `TBM_GETRANGEMAX`
+    TbmGetRangeMax = 0x0402,
+
+    /// Asks a trackbar to move its thumb to a position, `TBM_SETPOS`
+    TbmSetPos = 0x0405,
+
+    /// Asks an up-down control for its 16-bit range, `UDM_GETRANGE`
+    UdmGetRange = 0x0466,
+
+    /// Asks a property sheet to switch to a page, `PSM_SETCURSEL`
+    PsmSetCurSel = 0x0465,
+
+    /// Tells a property sheet a page's settings changed, enabling Apply,
+    /// `PSM_CHANGED`
+    PsmChanged = 0x0468,
+
+    /// Asks a property sheet to turn Cancel into Close,
+    /// `PSM_CANCELTOCLOSE`
+    PsmCancelToClose = 0x046b,
+
+    /// Asks a property sheet to forward a query to all of its pages,
+    /// `PSM_QUERYSIBLINGS`
+    PsmQuerySiblings = 0x046c,
+
+    /// Tells a property sheet a page's settings are back to saved,
+    /// `PSM_UNCHANGED`
+    PsmUnchanged = 0x046d,
+
+    /// Asks a property sheet to apply the settings of all pages,
+    /// `PSM_APPLY`
+    PsmApply = 0x046e,
+
+    /// Asks a property sheet to press one of its buttons,
+    /// `PSM_PRESSBUTTON`
+    PsmPressButton = 0x0471,
+
+    /// Asks a property sheet for the window of its current page,
+    /// `PSM_GETCURRENTPAGEHWND`
+    PsmGetCurrentPageHwnd = 0x0476,
+
+    /// Asks a date-time picker to show a time, `DTM_SETSYSTEMTIME`
+    DtmSetSystemTime = 0x1002,
+
+    /// Asks a tab control for its number of tabs, `TCM_GETITEMCOUNT`
+    TcmGetItemCount = 0x1304,
+
+    /// Asks a tab control for its selected tab, `TCM_GETCURSEL`
+    TcmGetCurSel = 0x130b,
+
+    /// Asks a tab control to select a tab without notifying its parent,
+    /// `TCM_SETCURSEL`
+    TcmSetCurSel = 0x130c,
+
+    /// Asks a tab control to focus a tab, selecting it and notifying its
+    /// parent, `TCM_SETCURFOCUS`
+    TcmSetCurFocus = 0x1330,
+
+    /// Mouse moved over the window
+    MouseMove = 0x0200,
+
+    /// Mouse hovered over the window, as requested with
+    /// `TrackMouseEvent()`
+    MouseHover = 0x02a1,
+
+    /// Mouse left the window, as requested with `TrackMouseEvent()`
+    MouseLeave = 0x02a3,
+
+    /// Notifies the window that it is being activated or deactivated
+    Activate = 0x0006,
+
+    /// Notifies the window that its application is being activated or
+    /// deactivated
+    ActivateApp = 0x001c,
+
+    /// Notifies the window that it gained the keyboard focus
+    SetFocus = 0x0007,
+
+    /// Notifies the window that it is losing the keyboard focus
+    KillFocus = 0x0008,
+}
+
+/// Longest hover performed by `FuzzerAction::Hover`, in milliseconds
+pub const MAX_HOVER_MS: u16 = 2000;
+
+/// Number of `WM_MOUSEMOVE`s a hover approaches its point with
+const HOVER_MOVES: i32 = 8;
+
+/// Most auto-repeated key downs performed by `FuzzerAction::KeyRepeat`
+pub const MAX_KEY_REPEATS: u16 = 64;
+
+/// Time between auto-repeated key downs, the fastest keyboard repeat rate
+pub const KEY_REPEAT_INTERVAL: Duration = Duration::from_millis(30);
+
+/// `LPARAM` bit of key messages set if the key was already down
+const KEY_PREVIOUS_STATE: usize = 1 << 30;
+
+/// Cached map of PIDs to their top-level window handles, shared between all
+/// threads so polling for a target's window doesn't walk every window on the
+/// system each time
+static WINDOW_CACHE: Mutex<Option<HashMap<u32, Vec<usize>>>> =
+    Mutex::new(None);
+
+/// `SetWindowPos()` flags to not change the Z order or activation, and to
+/// not block if the window's thread is hung
+const SWP_NOZORDER_NOACTIVATE_ASYNC: u32 = 0x0004 | 0x0010 | 0x4000;
+
+/// `GetSystemMetrics()` indices of the virtual screen rectangle
+const SM_XVIRTUALSCREEN:  i32 = 76;
+const SM_YVIRTUALSCREEN:  i32 = 77;
+const SM_CXVIRTUALSCREEN: i32 = 78;
+const SM_CYVIRTUALSCREEN: i32 = 79;
+
+/// `WM_NULL`, a message which does nothing
+const WM_NULL: u32 = 0x0000;
+
+/// Return from `SendMessageTimeoutW()` without waiting if the window's
+/// thread is already considered hung
+const SMTO_ABORTIFHUNG: u32 = 0x0002;
+
+/// Error from `SendMessageTimeoutW()` when the timeout elapsed
+const ERROR_TIMEOUT: i32 = 1460;
+
+/// Don't notify the shell when loading a keyboard layout
+const KLF_NOTELLSHELL: u32 = 0x0080;
+
+/// Load the keyboard layout `klid`, for example "00000409" for US English.
+/// Returns the input locale identifier (`HKL`) for the layout.
+pub fn load_keyboard_layout(klid: &str) -> io::Result<usize> {
+    let hkl = unsafe {
+        LoadKeyboardLayoutW(str_to_utf16(klid).as_ptr(), KLF_NOTELLSHELL)
+    };
+
+    if hkl == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(hkl)
+    }
+}
+
+/// Activate the keyboard layout `hkl` for the calling thread, returning the
+/// previously active layout
+pub fn activate_keyboard_layout(hkl: usize) -> io::Result<usize> {
+    let old = unsafe { ActivateKeyboardLayout(hkl, 0) };
+
+    if old == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(old)
+    }
+}
+
+/// Ways to show a window with `ShowWindowAsync()`
+#[repr(i32)]
+#[derive(Clone, Copy, Debug)]
+pub enum ShowCommand {
+    Minimize = 6,
+    Maximize = 3,
+    Restore  = 9,
 }
 
+/// Window class used by dialog boxes
+const DIALOG_CLASS: &str = "#32770";
+
+/// Notification code for a button being clicked, sent in the high word of the
+/// `WPARAM` of a `WM_COMMAND`
+const BN_CLICKED: usize = 0;
+
 /// Different types of virtual key codes
 #[repr(usize)]
 pub enum VirtualKeyCode {
@@ -124,8 +737,29 @@ struct MenuItemInfo {
     bmp_item:      usize,
 }
 
+/// Get the creation time of the process `pid`, as a `FILETIME`
+fn process_creation_time(pid: u32) -> Option<u64> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process == 0 {
+            return None;
+        }
+
+        let (mut creation, mut exit, mut kernel, mut user) = (0, 0, 0, 0);
+        let ret = GetProcessTimes(process, &mut creation, &mut exit,
+            &mut kernel, &mut user);
+        CloseHandle(process);
+        if ret == 0 {
+            return None;
+        }
+        Some(creation)
+    }
+}
+
 impl Window {
-    /// Find a window with `title`, and return a new `Window` object
+    /// Find a window with `title`, and return a new `Window` object. Any
+    /// process' window may be found, including another worker's target, so
+    /// prefer `attach_pid()`, `attach_pid_class()`, or `attach_newest()`.
     pub fn attach(title: &str) -> io::Result<Self> {
         // Convert the title to UTF-16
         let mut title = str_to_utf16(title); 
@@ -146,61 +780,180 @@ impl Window {
         }
     }
 
+    /// Internal callback for `EnumWindows()` used from the
+    /// `refresh_window_cache()` function
     extern "C" fn enum_windows_handler(hwnd: usize, lparam: usize) -> bool {
-        let param = unsafe {
-            &mut *(lparam as *mut (u32, Option<usize>, String))
+        let windows = unsafe {
+            &mut *(lparam as *mut HashMap<u32, Vec<usize>>)
         };
 
         let mut pid = 0;
-        let tid = unsafe{
-            GetWindowThreadProcessId(hwnd, &mut pid)
-        };
-        if pid == 0 || tid == 0 {
-            return true;
-        }
-
-        if param.0 == pid {
-            // Create a window for this window we are enumerating
-            let tmpwin = Window { hwnd };
-            
-            // Get the title for the window
-            if let Ok(title) = tmpwin.window_text() {
-                // Check if the title matches what we are searching for
-                if &title == &param.2 {
-                    // Match!
-                    param.1 = Some(hwnd);
-                }
-            }
-
-            // Keep enumerating
-            true
-        } else {
-            // Keep enumerating
-            true
+        let tid = unsafe { GetWindowThreadProcessId(hwnd, &mut pid) };
+        if pid != 0 && tid != 0 {
+            windows.entry(pid).or_default().push(hwnd);
         }
+
+        // Keep enumerating
+        true
     }
 
-    /// Return a `Window` object for the `pid`s main window
-    pub fn attach_pid(pid: u32, window_title: &str) -> io::Result<Self> {
-        let mut context: (u32, Option<usize>, String) =
-            (pid, None, window_title.into());
+    /// Walk all top-level windows on the system and rebuild the cached
+    /// PID to window map
+    pub fn refresh_window_cache() -> io::Result<()> {
+        let mut windows: HashMap<u32, Vec<usize>> = HashMap::new();
 
         unsafe {
             if !EnumWindows(Self::enum_windows_handler,
-                    &mut context as *mut _ as usize) {
+                    &mut windows as *mut _ as usize) {
                 // EnumWindows() failed, return out the corresponding error
                 return Err(io::Error::last_os_error());
             }
         }
 
-        if let Some(hwnd) = context.1 {
-            // Create the window object
-            Ok(Window { hwnd })
-        } else {
+        *WINDOW_CACHE.lock().unwrap() = Some(windows);
+        Ok(())
+    }
+
+    /// Get the cached top-level windows for `pid`. Windows which have since
+    /// been destroyed, or whose handle has been reused by another process,
+    /// are filtered out.
+    fn cached_pid_windows(pid: u32) -> WindowListing {
+        let hwnds = WINDOW_CACHE.lock().unwrap().as_ref()
+            .and_then(|cache| cache.get(&pid).cloned())
+            .unwrap_or_default();
+
+        WindowListing {
+            windows: hwnds.into_iter().filter(|&hwnd| {
+                let mut owner = 0;
+                unsafe {
+                    IsWindow(hwnd) &&
+                        GetWindowThreadProcessId(hwnd, &mut owner) != 0 &&
+                        owner == pid
+                }
+            }).map(|hwnd| Window { hwnd }).collect()
+        }
+    }
+
+    /// Find the cached window for `pid` with the title `window_title`
+    fn cached_pid_window(pid: u32, window_title: &str) -> Option<Self> {
+        Self::cached_pid_windows(pid).iter().rfind(|window| {
+            window.window_text().map(|x| x == window_title).unwrap_or(false)
+        }).cloned()
+    }
+
+    /// Find the cached window for `pid` with the class `class`
+    fn cached_pid_window_class(pid: u32, class: &str) -> Option<Self> {
+        Self::cached_pid_windows(pid).iter().rfind(|window| {
+            window.class_name().map(|x| x == class).unwrap_or(false)
+        }).cloned()
+    }
+
+    /// Return a `Window` object for the `pid`s main window. The system-wide
+    /// window list is only walked if the window is not already cached.
+    pub fn attach_pid(pid: u32, window_title: &str) -> io::Result<Self> {
+        #[cfg(feature = "chaos")]
+        crate::chaos::delay_window();
+
+        if let Some(window) = Self::cached_pid_window(pid, window_title) {
+            return Ok(window);
+        }
+
+        // Not cached, rebuild the cache and try again
+        Self::refresh_window_cache()?;
+
+        Self::cached_pid_window(pid, window_title).ok_or_else(|| {
             // Could not find a HWND
-            Err(io::Error::new(io::ErrorKind::Other,
-                "Could not find HWND for pid"))
+            io::Error::other("Could not find HWND for pid")
+        })
+    }
+
+    /// Return a `Window` object for the top-level window of `pid` with the
+    /// class `class`, for targets whose title changes or is shared by other
+    /// windows. The system-wide window list is only walked if the window is
+    /// not already cached.
+    pub fn attach_pid_class(pid: u32, class: &str) -> io::Result<Self> {
+        if let Some(window) = Self::cached_pid_window_class(pid, class) {
+            return Ok(window);
         }
+
+        // Not cached, rebuild the cache and try again
+        Self::refresh_window_cache()?;
+
+        Self::cached_pid_window_class(pid, class).ok_or_else(|| {
+            io::Error::other(
+                format!("Could not find a {} window for pid {}", class, pid))
+        })
+    }
+
+    /// Return a `Window` object for the window titled `title` of the most
+    /// recently started process with such a window, rather than whichever
+    /// window `attach()` happens to find first. This always refreshes the
+    /// window cache.
+    pub fn attach_newest(title: &str) -> io::Result<Self> {
+        Self::refresh_window_cache()?;
+
+        let pids: Vec<u32> = WINDOW_CACHE.lock().unwrap().iter()
+            .flat_map(|x| x.keys().cloned()).collect();
+        pids.into_iter().filter_map(|pid| {
+            let window = Self::cached_pid_window(pid, title)?;
+            Some((process_creation_time(pid)?, window))
+        }).max_by_key(|&(created, _)| created).map(|(_, window)| window)
+            .ok_or_else(|| {
+                io::Error::other(
+                    format!("Could not find a window titled {:?}", title))
+            })
+    }
+
+    /// Get the PID of the process owning this window
+    pub fn owner_pid(&self) -> io::Result<u32> {
+        let mut pid = 0;
+        unsafe {
+            if !IsWindow(self.hwnd) ||
+                    GetWindowThreadProcessId(self.hwnd, &mut pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(pid)
+    }
+
+    /// Check that this window still belongs to `pid`, its handle may have
+    /// been reused by another process's window once the target's was
+    /// destroyed. Returns an error if it doesn't.
+    pub fn verify_owner(&self, pid: u32) -> io::Result<()> {
+        let owner = self.owner_pid()?;
+        if owner != pid {
+            return Err(io::Error::other(
+                format!("Window {:#x} belongs to pid {}, not to pid {}",
+                    self.hwnd, owner, pid)));
+        }
+        Ok(())
+    }
+
+    /// Enumerate all top-level windows belonging to `pid`. This always
+    /// refreshes the window cache so newly created windows are picked up.
+    pub fn enumerate_pid_windows(pid: u32) -> io::Result<WindowListing> {
+        Self::refresh_window_cache()?;
+        Ok(Self::cached_pid_windows(pid))
+    }
+
+    /// Get the PIDs of all processes with a visible top-level window. This
+    /// always refreshes the window cache.
+    pub fn windowed_pids() -> io::Result<Vec<u32>> {
+        Self::refresh_window_cache()?;
+
+        let cache = WINDOW_CACHE.lock().unwrap();
+        Ok(cache.iter().flat_map(|x| x.iter())
+            .filter(|(_, hwnds)| {
+                hwnds.iter().any(|&hwnd| unsafe { IsWindowVisible(hwnd) })
+            })
+            .map(|(&pid, _)| pid).collect())
+    }
+
+    /// Enumerate all open dialog boxes belonging to `pid`
+    pub fn enumerate_dialogs(pid: u32) -> io::Result<Vec<Window>> {
+        Ok(Self::enumerate_pid_windows(pid)?.iter().filter(|window| {
+            window.class_name().map(|x| x == DIALOG_CLASS).unwrap_or(false)
+        }).cloned().collect())
     }
 
     /// Internal callback for `EnumChildWindows()` used from the
@@ -221,6 +974,11 @@ impl Window {
 
     /// Enumerate all of the sub-windows belonging to `Self` recursively
     pub fn enumerate_subwindows(&self) -> io::Result<WindowListing> {
+        RetryPolicy::global().run(|| self.enumerate_subwindows_once())
+    }
+
+    /// Single attempt of `enumerate_subwindows()`
+    fn enumerate_subwindows_once(&self) -> io::Result<WindowListing> {
         // Create a new, empty window listing
         let mut listing = WindowListing::default();
 
@@ -238,6 +996,57 @@ impl Window {
         }
     }
 
+    /// Get the direct children of this window, in Z order
+    pub fn children(&self) -> Vec<Window> {
+        let mut children = Vec::new();
+        let mut child = unsafe { GetWindow(self.hwnd, GW_CHILD) };
+        while child != 0 {
+            children.push(Window { hwnd: child });
+            child = unsafe { GetWindow(child, GW_HWNDNEXT) };
+        }
+        children
+    }
+
+    /// Get the `HINSTANCE` of the module which created this window
+    pub fn instance(&self) -> usize {
+        unsafe { GetWindowLongPtrW(self.hwnd, GWLP_HINSTANCE) }
+    }
+
+    /// Dump the hierarchy of this window and all of its children, with
+    /// their classes, text, rectangles, and styles
+    pub fn dump_tree(&self) -> WindowNode {
+        self.dump_tree_depth(0)
+    }
+
+    /// Implementation of `dump_tree()` for a window at `depth`
+    fn dump_tree_depth(&self, depth: usize) -> WindowNode {
+        let mut rect = Rect::default();
+        let (style, ex_style) = unsafe {
+            GetWindowRect(self.hwnd, &mut rect);
+            (GetWindowLongW(self.hwnd, GWL_STYLE) as u32,
+             GetWindowLongW(self.hwnd, GWL_EXSTYLE) as u32)
+        };
+
+        let mut children = Vec::new();
+        if depth < MAX_TREE_DEPTH {
+            let mut child = unsafe { GetWindow(self.hwnd, GW_CHILD) };
+            while child != 0 && children.len() < MAX_TREE_CHILDREN {
+                children.push(Window { hwnd: child }
+                    .dump_tree_depth(depth + 1));
+                child = unsafe { GetWindow(child, GW_HWNDNEXT) };
+            }
+        }
+
+        WindowNode {
+            class: self.class_name().unwrap_or_default(),
+            text:  self.window_text().unwrap_or_default(),
+            rect:  (rect.left, rect.top, rect.right, rect.bottom),
+            style,
+            ex_style,
+            children,
+        }
+    }
+
     /// Gets the title for the window, or in the case of a control field, gets
     /// the text on the object
     pub fn window_text(&self) -> Result<String, Box<dyn Error>> {
@@ -264,11 +1073,68 @@ impl Window {
         }
 
         // Convert the UTF-16 string into a Rust UTF-8 `String`
-        String::from_utf16(wchar_buffer.as_slice()).map_err(|x| {
+        let text = String::from_utf16(wchar_buffer.as_slice())?;
+
+        #[cfg(feature = "chaos")]
+        let text = crate::chaos::garble_title(text);
+
+        Ok(text)
+    }
+
+    /// Gets the name of the window class of the window
+    pub fn class_name(&self) -> Result<String, Box<dyn Error>> {
+        // Class names are limited to 256 characters
+        let mut wchar_buffer = [0u16; 257];
+
+        let ret = unsafe {
+            GetClassNameW(self.hwnd, wchar_buffer.as_mut_ptr(),
+                wchar_buffer.len() as i32)
+        };
+        if ret == 0 {
+            // GetClassNameW() failed
+            return Err(io::Error::last_os_error().into());
+        }
+
+        // Convert the UTF-16 string into a Rust UTF-8 `String`
+        String::from_utf16(&wchar_buffer[..ret as usize]).map_err(|x| {
             x.into()
         })
     }
 
+    /// Gets the control with `id` in the dialog box represented by `self`
+    pub fn dlg_item(&self, id: u32) -> Option<Window> {
+        let hwnd = unsafe { GetDlgItem(self.hwnd, id as i32) };
+        if hwnd != 0 {
+            Some(Window { hwnd })
+        } else {
+            None
+        }
+    }
+
+    /// Send a message to the dialog box represented by `self` indicating that
+    /// the control with `control_id` was clicked. Fails if the dialog has no
+    /// such control.
+    pub fn use_control_id(&self, control_id: u32) -> io::Result<()> {
+        let control = self.dlg_item(control_id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No such dialog control")
+        })?;
+
+        let wparam = (BN_CLICKED << 16) | (control_id as usize & 0xffff);
+
+        audit::message("post", self.hwnd, MessageType::Command as u32, wparam,
+            control.hwnd);
+        unsafe {
+            if PostMessageW(self.hwnd, MessageType::Command as u32, wparam,
+                    control.hwnd) {
+                // Success!
+                Ok(())
+            } else {
+                // PostMessageW() error
+                Err(io::Error::last_os_error())
+            }
+        }
+    }
+
     /// Does a left click of the current window
     pub fn left_click(&self, state: Option<KeyMouseState>) -> io::Result<()> {
         // Get the state, or create a new, empty state
@@ -276,6 +1142,8 @@ impl Window {
 
         unsafe {
             state.left_mouse = true;
+            audit::message("post", self.hwnd, MessageType::LButtonDown as u32,
+                state.into(), 0);
             if !PostMessageW(self.hwnd, MessageType::LButtonDown as u32,
                     state.into(), 0) {
                 // PostMessageW() failed
@@ -283,6 +1151,8 @@ impl Window {
             }
 
             state.left_mouse = false;
+            audit::message("post", self.hwnd, MessageType::LButtonUp as u32,
+                state.into(), 0);
             if !PostMessageW(self.hwnd, MessageType::LButtonUp as u32,
                     state.into(), 0) {
                 // PostMessageW() failed
@@ -293,8 +1163,103 @@ impl Window {
         Ok(())
     }
 
+    /// Does a left click of the current window using `backend`, with the
+    /// shift and control keys of `state` held down
+    pub fn left_click_with(&self, backend: InputBackend,
+            state: Option<KeyMouseState>) -> io::Result<()> {
+        match backend {
+            InputBackend::PostMessage => self.left_click(state),
+            InputBackend::SendInput => {
+                self.foreground()?;
+
+                let (x, y) = self.center()?;
+                audit::input(&format!("click hwnd={:#x} x={} y={}",
+                    self.hwnd, x, y));
+                if unsafe { SetCursorPos(x, y) } == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                let click = |flags| Input {
+                    kind: INPUT_MOUSE,
+                    data: InputData {
+                        mouse: MouseInput { flags, ..Default::default() },
+                    },
+                };
+                let key = |vk, flags| Input {
+                    kind: INPUT_KEYBOARD,
+                    data: InputData {
+                        keyboard: KeyboardInput {
+                            vk,
+                            flags,
+                            ..Default::default()
+                        },
+                    },
+                };
+
+                // Hold the modifiers down around the click
+                let state = state.unwrap_or_default();
+                let modifiers: Vec<u16> = [
+                    (state.shift, VK_SHIFT), (state.control, VK_CONTROL),
+                ].iter().filter(|x| x.0).map(|x| x.1).collect();
+
+                let mut inputs: Vec<Input> = modifiers.iter()
+                    .map(|&vk| key(vk, 0)).collect();
+                inputs.push(click(MOUSEEVENTF_LEFTDOWN));
+                inputs.push(click(MOUSEEVENTF_LEFTUP));
+                inputs.extend(modifiers.iter().rev()
+                    .map(|&vk| key(vk, KEYEVENTF_KEYUP)));
+                send_input(&inputs)
+            }
+        }
+    }
+
+    /// Presses a key down and releases it using `backend`
+    pub fn press_key_with(&self, backend: InputBackend, key: VirtualKey)
+            -> io::Result<()> {
+        match backend {
+            InputBackend::PostMessage => self.press_key(key),
+            InputBackend::SendInput => {
+                self.foreground()?;
+
+                let key = usize::from(key);
+                audit::input(&format!("key hwnd={:#x} vk={:#x}", self.hwnd,
+                    key));
+                let press = |flags| Input {
+                    kind: INPUT_KEYBOARD,
+                    data: InputData {
+                        keyboard: KeyboardInput {
+                            vk: key as u16,
+                            flags,
+                            ..Default::default()
+                        },
+                    },
+                };
+                send_input(&[press(0), press(KEYEVENTF_KEYUP)])
+            }
+        }
+    }
+
+    /// Bring the top-level window containing this window to the foreground
+    /// so it receives synthesized input
+    pub fn foreground(&self) -> io::Result<()> {
+        unsafe {
+            let root = GetAncestor(self.hwnd, GA_ROOT);
+            if SetForegroundWindow(if root != 0 { root } else { self.hwnd }) {
+                Ok(())
+            } else {
+                Err(io::Error::other(
+                    "Failed to bring window to the foreground"))
+            }
+        }
+    }
+
     /// Presses a key down and releases it
-    pub fn press_key(&self, key: usize) -> io::Result<()> {
+    pub fn press_key(&self, key: VirtualKey) -> io::Result<()> {
+        let key = usize::from(key);
+        audit::message("post", self.hwnd, MessageType::KeyDown as u32, key,
+            0);
+        audit::message("post", self.hwnd, MessageType::KeyUp as u32, key,
+            3 << 30);
         unsafe {
             if !PostMessageW(self.hwnd, MessageType::KeyDown as u32, key, 0) {
                 // PostMessageW() failed
@@ -311,8 +1276,64 @@ impl Window {
         Ok(())
     }
 
+    /// Hold a key down long enough to auto-repeat `repeats` times, then
+    /// release it, using `backend`. Posted repeats carry an increasing
+    /// repeat count and the previous key state as auto-repeat does.
+    pub fn hold_key_with(&self, backend: InputBackend, key: VirtualKey,
+            repeats: u16) -> io::Result<()> {
+        let key = usize::from(key);
+        if backend == InputBackend::SendInput {
+            self.foreground()?;
+            audit::input(&format!("key hwnd={:#x} vk={:#x} repeats={}",
+                self.hwnd, key, repeats));
+        }
+
+        for repeat in 0..=repeats as usize {
+            if repeat > 0 {
+                std::thread::sleep(KEY_REPEAT_INTERVAL);
+            }
+
+            match backend {
+                InputBackend::PostMessage => {
+                    let previous = if repeat > 0 {
+                        KEY_PREVIOUS_STATE
+                    } else {
+                        0
+                    };
+                    self.post_message(MessageType::KeyDown, key,
+                        previous | (repeat + 1))?;
+                }
+                InputBackend::SendInput => send_input(&[Input {
+                    kind: INPUT_KEYBOARD,
+                    data: InputData {
+                        keyboard: KeyboardInput {
+                            vk: key as u16,
+                            ..Default::default()
+                        },
+                    },
+                }])?,
+            }
+        }
+
+        match backend {
+            InputBackend::PostMessage => {
+                self.post_message(MessageType::KeyUp, key, 3 << 30)
+            }
+            InputBackend::SendInput => send_input(&[Input {
+                kind: INPUT_KEYBOARD,
+                data: InputData {
+                    keyboard: KeyboardInput {
+                        vk:    key as u16,
+                        flags: KEYEVENTF_KEYUP,
+                        ..Default::default()
+                    },
+                },
+            }]),
+        }
+    }
+
     /// Recurse into a menu listing, looking for sub menus
-    fn recurse_menu(&self, menu_ids: &mut BTreeSet<u32>, menu_handle: usize)
+    fn recurse_menu(&self, menu_ids: &mut BTreeSet<MenuId>, menu_handle: usize)
             -> io::Result<()> {
         unsafe {
             // Get the number of menu items
@@ -339,7 +1360,7 @@ impl Window {
                     self.recurse_menu(menu_ids, sub_menu)?;
                 } else {
                     // This is a menu identifier, add it to the set
-                    menu_ids.insert(menu_id);
+                    menu_ids.insert(MenuId(menu_id));
                 }
             }
 
@@ -347,19 +1368,86 @@ impl Window {
         }
     }
 
+    /// Recurse into a menu listing like `recurse_menu()`, also getting the
+    /// text of each item
+    fn recurse_menu_items(&self, items: &mut Vec<(MenuId, String)>,
+            menu_handle: usize) -> io::Result<()> {
+        unsafe {
+            let menu_count = GetMenuItemCount(menu_handle);
+            if menu_count == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            for menu_index in 0..menu_count {
+                let menu_id = GetMenuItemID(menu_handle, menu_index);
+                if menu_id == !0 {
+                    let sub_menu = GetSubMenu(menu_handle, menu_index);
+                    if sub_menu == 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    self.recurse_menu_items(items, sub_menu)?;
+                    continue;
+                }
+
+                // Separators have no text
+                let mut text = [0u16; 256];
+                let len = GetMenuStringW(menu_handle, menu_index as u32,
+                    text.as_mut_ptr(), text.len() as i32, MF_BYPOSITION);
+                items.push((MenuId(menu_id),
+                    String::from_utf16_lossy(&text[..len.max(0) as usize])));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Enumerate all window menu items along with their text, in menu order
+    pub fn enum_menu_items(&self) -> io::Result<Vec<(MenuId, String)>> {
+        RetryPolicy::global().run(|| {
+            // No menu isn't worth retrying, see `enum_menus_once()`
+            let menu = unsafe { GetMenu(self.hwnd) };
+            if menu == 0 {
+                return Ok(Vec::new());
+            }
+
+            let mut items = Vec::new();
+            self.recurse_menu_items(&mut items, menu)?;
+            Ok(items)
+        })
+    }
+
     /// Enumerate all window menus, return a set of the menu IDs which can
     /// be used with a `WM_COMMAND` message
-    pub fn enum_menus(&self) -> io::Result<BTreeSet<u32>> {
+    pub fn enum_menus(&self) -> io::Result<BTreeSet<MenuId>> {
+        RetryPolicy::global().run(|| self.enum_menus_once())
+    }
+
+    /// Get the number of top-level items in the window's main menu, 0 if it
+    /// has no menu
+    pub fn menu_item_count(&self) -> usize {
+        unsafe {
+            let menu = GetMenu(self.hwnd);
+            if menu == 0 {
+                return 0;
+            }
+            GetMenuItemCount(menu).max(0) as usize
+        }
+    }
+
+    /// Single attempt of `enum_menus()`
+    fn enum_menus_once(&self) -> io::Result<BTreeSet<MenuId>> {
         // Get the window's main menu
         let menu = unsafe { GetMenu(self.hwnd) };
-        if menu == 0 {
-            // GetMenu() error
-            return Err(io::Error::last_os_error());
-        }
 
         // Create the empty hash set
         let mut menu_ids = BTreeSet::new();
 
+        // The window has no menu, which isn't worth retrying. `GetMenu()`
+        // doesn't set an error for this, so the last error is stale.
+        if menu == 0 {
+            return Ok(menu_ids);
+        }
+
         // Recursively search through the menu
         self.recurse_menu(&mut menu_ids, menu)?;
 
@@ -368,7 +1456,10 @@ impl Window {
 
     /// Send a message to the window, indicating that `menu_id` was clicked.
     /// To get a valid `menu_id`, use the `enum_menus` member function.
-    pub fn use_menu_id(&self, menu_id: u32) -> io::Result<()> {
+    pub fn use_menu_id(&self, menu_id: MenuId) -> io::Result<()> {
+        let menu_id = u32::from(menu_id);
+        audit::message("post", self.hwnd, MessageType::Command as u32,
+            menu_id as usize, 0);
         unsafe {
             if PostMessageW(self.hwnd, MessageType::Command as u32,
                     menu_id.try_into().unwrap(), 0) {
@@ -381,8 +1472,362 @@ impl Window {
         }
     }
 
+    /// Get the raw `HWND` of the window
+    pub fn hwnd(&self) -> usize {
+        self.hwnd
+    }
+
+    /// Get the screen coordinates of the center of the window
+    pub fn center(&self) -> io::Result<(i32, i32)> {
+        let mut rect = Rect::default();
+        unsafe {
+            if !GetWindowRect(self.hwnd, &mut rect) {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2))
+    }
+
+    /// Get the width and height of the window's client area
+    pub fn client_size(&self) -> io::Result<(i32, i32)> {
+        let mut rect = Rect::default();
+        unsafe {
+            if !GetClientRect(self.hwnd, &mut rect) {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok((rect.right - rect.left, rect.bottom - rect.top))
+    }
+
+    /// Hover the mouse over `x`, `y` in the window's client area for
+    /// `duration`, exercising tooltips, hot-tracking, and hover previews.
+    /// A stream of `WM_MOUSEMOVE`s approaches the point, then the
+    /// `WM_MOUSEHOVER` and `WM_MOUSELEAVE` `TrackMouseEvent()` would deliver
+    /// are posted.
+    pub fn hover(&self, x: i32, y: i32, duration: Duration)
+            -> io::Result<()> {
+        let point = |x: i32, y: i32| {
+            ((y as u16 as usize) << 16) | x as u16 as usize
+        };
+
+        // Approach from the top left corner of the client area
+        for step in 1..=HOVER_MOVES {
+            self.post_message(MessageType::MouseMove, 0,
+                point(x * step / HOVER_MOVES, y * step / HOVER_MOVES))?;
+            std::thread::sleep(duration / HOVER_MOVES as u32 / 2);
+        }
+
+        // Rest on the point for the rest of the duration
+        std::thread::sleep(duration / 2);
+        self.post_message(MessageType::MouseHover, 0, point(x, y))?;
+        self.post_message(MessageType::MouseLeave, 0, 0)
+    }
+
+    /// Move the window to `x`, `y` and resize it to `width` by `height`
+    pub fn set_position(&self, x: i32, y: i32, width: i32, height: i32)
+            -> io::Result<()> {
+        unsafe {
+            if SetWindowPos(self.hwnd, 0, x, y, width, height,
+                    SWP_NOZORDER_NOACTIVATE_ASYNC) {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        }
+    }
+
+    /// Minimize, maximize, or restore the window
+    pub fn show(&self, cmd: ShowCommand) -> io::Result<()> {
+        unsafe { ShowWindowAsync(self.hwnd, cmd as i32); }
+        Ok(())
+    }
+
+    /// Internal callback for `EnumDisplayMonitors()` used from the
+    /// `monitors()` function
+    extern "system" fn enum_monitors_handler(_monitor: usize, _hdc: usize,
+            rect: *mut Rect, lparam: usize) -> i32 {
+        let monitors = unsafe { &mut *(lparam as *mut Vec<Rect>) };
+        monitors.push(unsafe { *rect });
+
+        // Keep enumerating
+        1
+    }
+
+    /// Get the rectangles of all monitors in virtual screen coordinates, as
+    /// (left, top, right, bottom)
+    pub fn monitors() -> io::Result<Vec<(i32, i32, i32, i32)>> {
+        let mut monitors: Vec<Rect> = Vec::new();
+
+        unsafe {
+            if !EnumDisplayMonitors(0, std::ptr::null(),
+                    Self::enum_monitors_handler,
+                    &mut monitors as *mut _ as usize) {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(monitors.iter().map(|x| (x.left, x.top, x.right, x.bottom))
+            .collect())
+    }
+
+    /// Get the rectangle of the virtual screen spanning all monitors, as
+    /// (left, top, right, bottom)
+    pub fn virtual_screen() -> (i32, i32, i32, i32) {
+        unsafe {
+            let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+            let top  = GetSystemMetrics(SM_YVIRTUALSCREEN);
+            (left, top,
+             left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+             top  + GetSystemMetrics(SM_CYVIRTUALSCREEN))
+        }
+    }
+
+    /// Returns `true` if the window's thread did not process a message
+    /// within `timeout`
+    pub fn is_hung(&self, timeout: Duration) -> bool {
+        let mut result = 0;
+        let ret = unsafe {
+            SendMessageTimeoutW(self.hwnd, WM_NULL, 0, 0, SMTO_ABORTIFHUNG,
+                timeout.as_millis() as u32, &mut result)
+        };
+
+        // A failure because the window is gone isn't a hang
+        ret == 0 &&
+            io::Error::last_os_error().raw_os_error() == Some(ERROR_TIMEOUT)
+    }
+
+    /// Send `msg` with `wparam` and `lparam` to the window, waiting up to
+    /// `timeout` for it to be processed. Returns the result of the message.
+    pub fn send_message_timeout(&self, msg: MessageType, wparam: usize,
+            lparam: usize, timeout: Duration) -> io::Result<usize> {
+        self.send_raw_message_timeout(msg as u32, wparam, lparam, timeout)
+    }
+
+    /// Send the message number `msg`, which need not be a `MessageType`,
+    /// like `send_message_timeout()`
+    pub fn send_raw_message_timeout(&self, msg: u32, wparam: usize,
+            lparam: usize, timeout: Duration) -> io::Result<usize> {
+        audit::message("send", self.hwnd, msg, wparam, lparam);
+        let mut result = 0;
+        let ret = unsafe {
+            SendMessageTimeoutW(self.hwnd, msg, wparam, lparam,
+                SMTO_ABORTIFHUNG, timeout.as_millis() as u32, &mut result)
+        };
+        if ret == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(result)
+    }
+
+    /// Get the input state of the GUI thread owning this window
+    pub fn gui_thread_info(&self) -> io::Result<GuiThreadInfo> {
+        let info = unsafe {
+            let tid = GetWindowThreadProcessId(self.hwnd,
+                std::ptr::null_mut());
+            if tid == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut info: GuiThreadInfoRaw = std::mem::zeroed();
+            info.size = std::mem::size_of::<GuiThreadInfoRaw>() as u32;
+            if GetGUIThreadInfo(tid, &mut info) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            info
+        };
+
+        let window = |hwnd| if hwnd != 0 { Some(Window { hwnd }) } else {
+            None
+        };
+        Ok(GuiThreadInfo {
+            active:    window(info.active),
+            focus:     window(info.focus),
+            capture:   window(info.capture),
+            caret:     window(info.caret).map(|caret| {
+                (caret, info.caret_pos.left, info.caret_pos.top)
+            }),
+            menu_mode: info.flags & GUI_INMENUMODE != 0,
+        })
+    }
+
+    /// Get the window with the keyboard focus on the thread owning this
+    /// window, if any
+    pub fn focus(&self) -> Option<Window> {
+        self.gui_thread_info().ok().and_then(|info| info.focus)
+    }
+
+    /// Check whether this is an edit control, which takes typed text
+    pub fn is_edit(&self) -> bool {
+        self.class_name().map(|x| x.to_lowercase().contains("edit"))
+            .unwrap_or(false)
+    }
+
+    /// Ask the window to switch to the keyboard layout `hkl`
+    pub fn request_keyboard_layout(&self, hkl: usize) -> io::Result<()> {
+        self.post_message(MessageType::InputLangChangeRequest, 0, hkl)
+    }
+
+    /// Post a raw `msg` with `wparam` and `lparam` to the window
+    pub fn post_message(&self, msg: MessageType, wparam: usize,
+                        lparam: usize) -> io::Result<()> {
+        self.post_raw_message(msg as u32, wparam, lparam)
+    }
+
+    /// Post the message number `msg`, which need not be a `MessageType`,
+    /// with `wparam` and `lparam` to the window
+    pub fn post_raw_message(&self, msg: u32, wparam: usize, lparam: usize)
+            -> io::Result<()> {
+        audit::message("post", self.hwnd, msg, wparam, lparam);
+        unsafe {
+            if PostMessageW(self.hwnd, msg, wparam, lparam) {
+                // Success!
+                Ok(())
+            } else {
+                // PostMessageW() error
+                Err(io::Error::last_os_error())
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the process owning the window to process
+    /// all of its pending input and go idle. Returns `false` if it didn't
+    /// go idle in time.
+    pub fn wait_for_idle(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pid = 0;
+        unsafe {
+            if GetWindowThreadProcessId(self.hwnd, &mut pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let process = OpenProcess(PROCESS_WAIT_ACCESS, 0, pid);
+            if process == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let ret = match WaitForInputIdle(process,
+                    timeout.as_millis() as u32) {
+                0            => Ok(true),
+                WAIT_TIMEOUT => Ok(false),
+                _            => Err(io::Error::last_os_error()),
+            };
+
+            CloseHandle(process);
+            ret
+        }
+    }
+
+    /// Copy `data` into newly allocated memory in the process owning the
+    /// window, returning its address in that process. This is used for
+    /// message parameters which are pointers. The memory lives until it is
+    /// freed with `remote_free()` or the process exits.
+    pub fn remote_write(&self, data: &[u8]) -> io::Result<usize> {
+        let mut pid = 0;
+        unsafe {
+            if GetWindowThreadProcessId(self.hwnd, &mut pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let process = OpenProcess(PROCESS_VM_ACCESS, 0, pid);
+            if process == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let addr = VirtualAllocEx(process, 0, data.len().max(1),
+                MEM_COMMIT_RESERVE, PAGE_READWRITE);
+            let mut written = 0;
+            let ret = if addr == 0 || WriteProcessMemory(process, addr,
+                    data.as_ptr(), data.len(), &mut written) == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(addr)
+            };
+
+            CloseHandle(process);
+            ret
+        }
+    }
+
+    /// Free the memory at `addr` written by `remote_write()` to the process
+    /// owning the window
+    pub fn remote_free(&self, addr: usize) -> io::Result<()> {
+        let mut pid = 0;
+        unsafe {
+            if GetWindowThreadProcessId(self.hwnd, &mut pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let process = OpenProcess(PROCESS_VM_ACCESS, 0, pid);
+            if process == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let ret = if VirtualFreeEx(process, addr, 0, MEM_RELEASE) == 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            };
+
+            CloseHandle(process);
+            ret
+        }
+    }
+
+    /// Send `msg` with `wparam` to the window, with `data` copied into its
+    /// process by `remote_write()` as the `lparam`. The copy is freed once
+    /// the window handled the message. If it doesn't within
+    /// `REMOTE_MESSAGE_TIMEOUT` the copy is left to it, as it may still
+    /// handle the message later.
+    pub fn send_remote(&self, msg: MessageType, wparam: usize, data: &[u8])
+            -> io::Result<usize> {
+        let remote = self.remote_write(data)?;
+        let result = self.send_message_timeout(msg, wparam, remote,
+            REMOTE_MESSAGE_TIMEOUT)?;
+        let _ = self.remote_free(remote);
+        Ok(result)
+    }
+
+    /// Send a `WM_DPICHANGED` to the window for a new `dpi`, suggesting a
+    /// new window rectangle of `left`, `top`, `right`, `bottom`
+    pub fn send_dpi_changed(&self, dpi: u16, rect: (i32, i32, i32, i32))
+            -> io::Result<()> {
+        let rect = Rect {
+            left:   rect.0,
+            top:    rect.1,
+            right:  rect.2,
+            bottom: rect.3,
+        };
+
+        // The suggested rectangle is passed by pointer
+        let bytes = unsafe {
+            std::slice::from_raw_parts(&rect as *const Rect as *const u8,
+                std::mem::size_of::<Rect>())
+        };
+        let dpi = dpi as usize;
+        self.send_remote(MessageType::DpiChanged, (dpi << 16) | dpi, bytes)
+            .map(|_| ())
+    }
+
+    /// Send a `WM_SETTINGCHANGE` to the window for the `SPI_*` `action`,
+    /// with the changed settings area `section` if there is one
+    pub fn send_setting_change(&self, action: u32, section: Option<&str>)
+            -> io::Result<()> {
+        match section {
+            Some(section) => {
+                let section: Vec<u8> = str_to_utf16(section).iter()
+                    .flat_map(|x| x.to_le_bytes().to_vec()).collect();
+                self.send_remote(MessageType::SettingChange, action as usize,
+                    &section).map(|_| ())
+            }
+            None => self.post_message(MessageType::SettingChange,
+                action as usize, 0),
+        }
+    }
+
     /// Attempts to gracefully close the applications
     pub fn close(&self) -> io::Result<()> {
+        audit::message("post", self.hwnd, MessageType::Close as u32, 0, 0);
         unsafe {
             if PostMessageW(self.hwnd, MessageType::Close as u32, 0, 0) {
                 // Success!
@@ -397,7 +1842,9 @@ impl Window {
 
 /// Holds the state of some of the special keyboard and mouse buttons during
 /// certain mouse events
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeyMouseState {
     /// Left mouse button is down
     pub left_mouse: bool,
@@ -434,3 +1881,17 @@ impl Into<usize> for KeyMouseState {
     }
 }
 
+impl From<usize> for KeyMouseState {
+    fn from(state: usize) -> Self {
+        KeyMouseState {
+            left_mouse:   state & 0x0001 != 0,
+            middle_mouse: state & 0x0010 != 0,
+            right_mouse:  state & 0x0002 != 0,
+            shift:        state & 0x0004 != 0,
+            xbutton1:     state & 0x0020 != 0,
+            xbutton2:     state & 0x0040 != 0,
+            control:      state & 0x0008 != 0,
+        }
+    }
+}
+
@@ -0,0 +1,187 @@
+//! Structured dumps of a window hierarchy, and diffs between two dumps, used
+//! to log UI state and to tell whether a case changed the UI at all
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+
+/// One window in a dump of a window hierarchy
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WindowNode {
+    /// Window class name
+    pub class: String,
+
+    /// Window title or control text
+    pub text: String,
+
+    /// Window rectangle in screen coordinates as (left, top, right, bottom)
+    pub rect: (i32, i32, i32, i32),
+
+    /// `GWL_STYLE` window styles
+    pub style: u32,
+
+    /// `GWL_EXSTYLE` extended window styles
+    pub ex_style: u32,
+
+    /// Child windows, in Z order
+    pub children: Vec<WindowNode>,
+}
+
+impl WindowNode {
+    /// Hash of the shape of the tree: the classes and styles of all windows
+    /// and how they are nested. Text and positions are left out as they
+    /// change constantly without the UI being in a meaningfully new state.
+    pub fn shape_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_shape(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Feed the shape of this tree into `hasher`
+    fn hash_shape(&self, hasher: &mut DefaultHasher) {
+        self.class.hash(hasher);
+        self.style.hash(hasher);
+        self.ex_style.hash(hasher);
+        self.children.len().hash(hasher);
+        for child in &self.children {
+            child.hash_shape(hasher);
+        }
+    }
+
+    /// Number of windows in the tree, including this one
+    pub fn count(&self) -> usize {
+        1 + self.children.iter().map(|x| x.count()).sum::<usize>()
+    }
+
+    /// Write this tree indented by `depth` levels
+    fn fmt_depth(&self, f: &mut fmt::Formatter<'_>, depth: usize)
+            -> fmt::Result {
+        writeln!(f, "{:indent$}{}", "", self.describe(), indent = depth * 2)?;
+        for child in &self.children {
+            child.fmt_depth(f, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    /// One line description of this window, without its children
+    fn describe(&self) -> String {
+        let (left, top, right, bottom) = self.rect;
+        format!("{} {:?} ({}, {}, {}, {}) style {:#x} ex_style {:#x}",
+            self.class, self.text, left, top, right, bottom, self.style,
+            self.ex_style)
+    }
+}
+
+impl fmt::Display for WindowNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_depth(f, 0)
+    }
+}
+
+/// Compute the differences between the window trees `old` and `new`, one
+/// line per difference. Children are matched up by position. Returns an
+/// empty list if the trees are the same.
+pub fn diff(old: &WindowNode, new: &WindowNode) -> Vec<String> {
+    let mut ret = Vec::new();
+    diff_node(old, new, "", &mut ret);
+    ret
+}
+
+/// Diff `old` and `new` at `path`, adding differences to `ret`
+fn diff_node(old: &WindowNode, new: &WindowNode, path: &str,
+             ret: &mut Vec<String>) {
+    let path = format!("{}/{}", path, new.class);
+
+    if old.class != new.class {
+        ret.push(format!("{}: class {:?} -> {:?}", path, old.class,
+            new.class));
+    }
+    if old.text != new.text {
+        ret.push(format!("{}: text {:?} -> {:?}", path, old.text, new.text));
+    }
+    if old.rect != new.rect {
+        ret.push(format!("{}: rect {:?} -> {:?}", path, old.rect, new.rect));
+    }
+    if old.style != new.style {
+        ret.push(format!("{}: style {:#x} -> {:#x}", path, old.style,
+            new.style));
+    }
+    if old.ex_style != new.ex_style {
+        ret.push(format!("{}: ex_style {:#x} -> {:#x}", path, old.ex_style,
+            new.ex_style));
+    }
+
+    for (idx, (old, new)) in old.children.iter().zip(new.children.iter())
+            .enumerate() {
+        diff_node(old, new, &format!("{}[{}]", path, idx), ret);
+    }
+    for removed in old.children.iter().skip(new.children.len()) {
+        ret.push(format!("{}: removed {}", path, removed.describe()));
+    }
+    for added in new.children.iter().skip(old.children.len()) {
+        ret.push(format!("{}: added {}", path, added.describe()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A window of class `class` with the children `children`
+    fn node(class: &str, children: Vec<WindowNode>) -> WindowNode {
+        WindowNode { class: class.into(), children, ..WindowNode::default() }
+    }
+
+    /// A dialog with an edit control and a button
+    fn dialog() -> WindowNode {
+        node("#32770", vec![node("Edit", vec![]), node("Button", vec![])])
+    }
+
+    #[test]
+    fn same_trees_have_no_diff() {
+        assert!(diff(&dialog(), &dialog()).is_empty());
+        assert_eq!(dialog().count(), 3);
+    }
+
+    #[test]
+    fn changed_fields_are_reported_by_path() {
+        let mut new = dialog();
+        new.children[0].text  = "hello".into();
+        new.children[1].style = 0x1000_0000;
+
+        assert_eq!(diff(&dialog(), &new), vec![
+            "/#32770[0]/Edit: text \"\" -> \"hello\"".to_string(),
+            "/#32770[1]/Button: style 0x0 -> 0x10000000".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn added_and_removed_children_are_reported() {
+        let mut new = dialog();
+        new.children.push(node("Static", vec![]));
+        let added = diff(&dialog(), &new);
+        assert_eq!(added.len(), 1);
+        assert!(added[0].starts_with("/#32770: added Static"));
+
+        let removed = diff(&new, &dialog());
+        assert_eq!(removed.len(), 1);
+        assert!(removed[0].starts_with("/#32770: removed Static"));
+    }
+
+    #[test]
+    fn shape_hash_ignores_text_and_positions() {
+        let mut moved = dialog();
+        moved.children[0].text = "hello".into();
+        moved.rect = (10, 10, 200, 100);
+        assert_eq!(moved.shape_hash(), dialog().shape_hash());
+
+        let mut restyled = dialog();
+        restyled.children[1].style = 1;
+        assert_ne!(restyled.shape_hash(), dialog().shape_hash());
+
+        // Moving a window to another parent changes the shape
+        let flat = node("#32770", vec![node("Edit", vec![
+            node("Button", vec![])])]);
+        assert_ne!(flat.shape_hash(), dialog().shape_hash());
+    }
+}
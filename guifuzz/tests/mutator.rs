@@ -0,0 +1,167 @@
+//! Property tests of the mutator, running it over many random corpora and
+//! checking invariants which must hold for every output
+
+use std::sync::Arc;
+use std::collections::HashSet;
+use std::time::Duration;
+use guifuzz::{Rng, Statistics, FuzzerAction, FuzzInput, Provenance};
+use guifuzz::{NeedsGeneration, MAX_INPUT_LEN, mutate_with_rng};
+use guifuzz::bench::random_action;
+
+/// Number of random corpora checked per property
+const CASES: u64 = 500;
+
+/// Names `mutate()` records for its mutations
+const MUTATIONS: &[&str] = &[
+    "splice", "delete", "repeat", "insert_slice", "insert_action",
+];
+
+/// Build statistics holding `inputs`
+fn stats_with(inputs: &[Vec<FuzzerAction>]) -> Statistics {
+    let mut stats = Statistics::default();
+    for input in inputs {
+        let input: FuzzInput = Arc::new(input.clone());
+        stats.add_input(&input, Duration::from_millis(100),
+            Provenance::default());
+    }
+    stats
+}
+
+/// Generate a random corpus from `rng`, occasionally with empty or very
+/// long inputs
+fn random_corpus(rng: &Rng) -> Vec<Vec<FuzzerAction>> {
+    (0..rng.rand() % 16 + 1).map(|_| {
+        let len = match rng.rand() % 8 {
+            0 => 0,
+            1 => MAX_INPUT_LEN + rng.rand() % 1024,
+            _ => rng.rand() % 256,
+        };
+        (0..len).map(|_| random_action(rng)).collect()
+    }).collect()
+}
+
+#[test]
+fn empty_corpus_needs_generation() {
+    let mut stats = Statistics::default();
+    let err = mutate_with_rng(&mut stats, &Rng::seeded(1), None,
+        &mut Provenance::default()).unwrap_err();
+    assert!(err.is::<NeedsGeneration>());
+}
+
+#[test]
+fn only_empty_inputs_need_generation() {
+    let mut stats = stats_with(&[Vec::new()]);
+    for seed in 1..CASES {
+        let err = mutate_with_rng(&mut stats, &Rng::seeded(seed), None,
+            &mut Provenance::default()).unwrap_err();
+        assert!(err.is::<NeedsGeneration>());
+    }
+}
+
+#[test]
+fn outputs_are_bounded_and_from_the_corpus() {
+    for seed in 1..CASES {
+        let rng = Rng::seeded(seed);
+        let corpus = random_corpus(&rng);
+        let mut stats = stats_with(&corpus);
+
+        // Every action in an output must come from an input in the corpus,
+        // otherwise a splice read out of bounds of something
+        let known: HashSet<FuzzerAction> =
+            corpus.iter().flatten().cloned().collect();
+
+        let mut provenance = Provenance::default();
+        let output = match mutate_with_rng(&mut stats, &rng, None,
+                &mut provenance) {
+            Ok(output) => output,
+            Err(err) => {
+                assert!(err.is::<NeedsGeneration>(), "seed {}", seed);
+                continue;
+            }
+        };
+
+        assert!(!output.is_empty(), "seed {}", seed);
+        assert!(output.len() <= MAX_INPUT_LEN, "seed {}", seed);
+        assert!(output.iter().all(|x| known.contains(x)), "seed {}", seed);
+
+        // The parent and mutations are recorded
+        let parent = provenance.parent.as_ref().expect("No parent");
+        assert!(stats.corpus.contains(parent), "seed {}", seed);
+        assert!(provenance.mutations.iter().all(|x| MUTATIONS.contains(x)),
+            "seed {}", seed);
+    }
+}
+
+#[test]
+fn shards_stay_in_bounds() {
+    for seed in 1..CASES {
+        let rng = Rng::seeded(seed);
+        let mut stats = stats_with(&random_corpus(&rng));
+        let num_shards = rng.rand() % 16 + 1;
+        let shard = Some((rng.rand() % num_shards, num_shards));
+        let _ = mutate_with_rng(&mut stats, &rng, shard,
+            &mut Provenance::default());
+    }
+}
+
+#[test]
+fn mutations_are_reproducible_from_the_seed() {
+    for seed in 1..CASES {
+        let corpus = random_corpus(&Rng::seeded(seed));
+
+        let mut provenance = Provenance::default();
+        let first = mutate_with_rng(&mut stats_with(&corpus),
+            &Rng::seeded(seed), None, &mut provenance).ok();
+        let second = mutate_with_rng(&mut stats_with(&corpus),
+            &Rng::seeded(provenance.seed), None, &mut Provenance::default())
+            .ok();
+        assert_eq!(first, second, "seed {}", seed);
+    }
+}
+
+/// Build a corpus of `arbitrary::Arbitrary` action sequences from `bytes`
+#[cfg(feature = "arbitrary")]
+fn arbitrary_corpus(bytes: &[u8]) -> Vec<Vec<FuzzerAction>> {
+    use arbitrary::Unstructured;
+    use guifuzz::actiongen::ActionSequence;
+
+    let mut u = Unstructured::new(bytes);
+    let mut corpus = Vec::new();
+    while !u.is_empty() && corpus.len() < 16 {
+        match u.arbitrary::<ActionSequence>() {
+            Ok(sequence) => corpus.push(sequence.0),
+            Err(_) => break,
+        }
+    }
+    corpus
+}
+
+#[test]
+#[cfg(feature = "arbitrary")]
+fn arbitrary_corpora_mutate_within_bounds() {
+    for seed in 1..CASES {
+        let rng = Rng::seeded(seed);
+        let bytes: Vec<u8> = (0..rng.rand() % 8192)
+            .map(|_| rng.rand() as u8).collect();
+        let corpus = arbitrary_corpus(&bytes);
+        assert!(corpus.iter().all(|x| x.len() <= MAX_INPUT_LEN),
+            "seed {}", seed);
+        let known: HashSet<FuzzerAction> =
+            corpus.iter().flatten().cloned().collect();
+
+        let mut stats = stats_with(&corpus);
+        let num_shards = rng.rand() % 4 + 1;
+        let shard = Some((rng.rand() % num_shards, num_shards))
+            .filter(|_| rng.rand() & 1 == 0);
+        match mutate_with_rng(&mut stats, &rng, shard,
+                &mut Provenance::default()) {
+            Ok(output) => {
+                assert!(!output.is_empty(), "seed {}", seed);
+                assert!(output.len() <= MAX_INPUT_LEN, "seed {}", seed);
+                assert!(output.iter().all(|x| known.contains(x)),
+                    "seed {}", seed);
+            }
+            Err(err) => assert!(err.is::<NeedsGeneration>(), "seed {}", seed),
+        }
+    }
+}
@@ -3,14 +3,14 @@
 
 use std::io;
 use std::time::{Duration, Instant};
-use std::collections::{HashSet, HashMap};
+use std::collections::{HashSet, HashMap, VecDeque};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::fs::File;
 use std::ffi::CString;
 use std::io::Write;
 use std::io::BufWriter;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use winapi::um::memoryapi::ReadProcessMemory;
 use winapi::um::memoryapi::WriteProcessMemory;
@@ -40,6 +40,7 @@ use winapi::um::processthreadsapi::SetThreadContext;
 use winapi::um::processthreadsapi::GetThreadContext;
 use winapi::um::processthreadsapi::FlushInstructionCache;
 use winapi::um::processthreadsapi::TerminateProcess;
+use winapi::um::processthreadsapi::SuspendThread;
 use winapi::um::processthreadsapi::OpenProcess;
 use winapi::um::processthreadsapi::CreateProcessA;
 use winapi::um::wow64apiset::IsWow64Process;
@@ -49,14 +50,74 @@ use winapi::um::winnt::HANDLE;
 use winapi::um::minwinbase::DEBUG_EVENT;
 use winapi::um::winbase::DEBUG_PROCESS;
 use winapi::um::winbase::DEBUG_ONLY_THIS_PROCESS;
+use winapi::um::winbase::DebugSetProcessKillOnExit;
 use winapi::um::consoleapi::SetConsoleCtrlHandler;
+use winapi::um::namedpipeapi::CreatePipe;
+use winapi::um::fileapi::ReadFile;
+use winapi::um::handleapi::{CloseHandle, SetHandleInformation};
+use winapi::um::minwinbase::SECURITY_ATTRIBUTES;
+use winapi::um::winbase::{STARTF_USESTDHANDLES, HANDLE_FLAG_INHERIT};
+use winapi::um::processthreadsapi::STARTUPINFOA;
 
 use crate::minidump::dump;
+use crate::stackwalk::stack_walk;
 use crate::handles::Handle;
 
+/// Maximum number of lines of target output kept in the tail
+const OUTPUT_TAIL_LINES: usize = 64;
+
+/// Maximum length of a single line of target output
+const OUTPUT_LINE_LEN: usize = 1024;
+
+/// Tail of the debug strings and standard output/error of the target, shared
+/// with the thread reading the target's output pipe
+pub type OutputTail = Arc<Mutex<VecDeque<String>>>;
+
+/// Append `line` from `source` to the output `tail`, dropping the oldest
+/// line if it is full
+fn push_output(tail: &OutputTail, source: &str, line: &str) {
+    let line = line.trim_end_matches(['\0', '\r', '\n']);
+    let line: String = line.chars().take(OUTPUT_LINE_LEN).collect();
+
+    let mut tail = tail.lock().unwrap();
+    if tail.len() >= OUTPUT_TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(format!("{}: {}", source, line));
+}
+
+/// Number of bytes of code captured at the faulting instruction
+const CRASH_CODE_BYTES: usize = 16;
+
+/// Machine state of the crashing thread at the time of a crash
+#[derive(Clone, Debug, Default)]
+pub struct CrashContext {
+    /// Exception code
+    pub code: u32,
+
+    /// Address of the faulting instruction
+    pub pc: usize,
+
+    /// For access violations, the type of access (0 read, 1 write, 8
+    /// execute) and the address accessed
+    pub access: Option<(usize, usize)>,
+
+    /// General purpose registers of the crashing thread, as (name, value)
+    pub registers: Vec<(&'static str, u64)>,
+
+    /// First bytes of code at the faulting instruction, shorter if they
+    /// could not all be read
+    pub code_bytes: Vec<u8>,
+}
+
 /// Tracks if an exit has been requested via the Ctrl+C/Ctrl+Break handler
 static EXIT_REQUESTED: AtomicBool = AtomicBool::new(false);
 
+/// Held from creating a target's output pipe until the inheritable write end
+/// is closed in the debugger, so targets spawned concurrently by other
+/// workers don't inherit it and keep the pipe open past the target's exit
+static SPAWN_LOCK: Mutex<()> = Mutex::new(());
+
 /// Function invoked on module loads
 /// (debugger, module filename, module base)
 type ModloadFunc = Box<dyn Fn(&mut Debugger, &str, usize)>;
@@ -71,6 +132,21 @@ type DebugEventFunc = Box<dyn Fn(&mut Debugger, &DEBUG_EVENT)>;
 type BreakpointCallback = fn(&mut Debugger, u32, usize, u64) -> bool;
 
 /// Ctrl+C handler so we can remove breakpoints and detach from the debugger
+/// Returns `true` once an exit was requested with Ctrl-C, after which all
+/// debuggers stop running their targets
+pub fn exit_requested() -> bool {
+    EXIT_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Record Ctrl-C in `exit_requested()` rather than exiting, before any
+/// debugger is attached
+pub fn catch_ctrl_c() {
+    unsafe {
+        assert!(SetConsoleCtrlHandler(Some(ctrl_c_handler), 1) != 0,
+            "SetConsoleCtrlHandler() failed");
+    }
+}
+
 unsafe extern "system" fn ctrl_c_handler(_ctrl_type: u32) -> i32 {
     // Store that an exit was requested
     EXIT_REQUESTED.store(true, Ordering::SeqCst);
@@ -166,6 +242,15 @@ pub struct Debugger<'a> {
     /// Tuple is (module, offset, symbol+offset, frequency)
     pub coverage: HashMap<usize, (Arc<String>, usize, String, u64)>,
 
+    /// Index of the action being performed on the target, shared with
+    /// whatever performs them, if coverage is attributed to actions
+    action_counter: Option<Arc<AtomicUsize>>,
+
+    /// Index of the action which was being performed when each coverage
+    /// entry was first hit, keyed by PC. Only populated with an action
+    /// counter set.
+    pub coverage_actions: HashMap<usize, usize>,
+
     /// Set of DLL names and the corresponding DLL base
     modules: HashSet<(String, usize)>,
 
@@ -179,6 +264,10 @@ pub struct Debugger<'a> {
     /// Last time we saved the coverage database
     last_db_save: Instant,
 
+    /// Time between saves of the coverage database, the debuggee is killed
+    /// after each
+    lifetime: Duration,
+
     /// Prints some more status information during runtime
     verbose: bool,
 
@@ -194,6 +283,27 @@ pub struct Debugger<'a> {
     /// Tracks if we want to kill the debuggee
     kill_requested: bool,
 
+    /// Take minidumps of crashes
+    minidumps: bool,
+
+    /// Symbolized call stack of the crashing thread, innermost frame first.
+    /// Only populated when `run()` returns `ExitType::Crash`
+    pub crash_stack: Vec<String>,
+
+    /// Machine state of the crashing thread. Only populated when `run()`
+    /// returns `ExitType::Crash`
+    pub crash_context: Option<CrashContext>,
+
+    /// Thread which crashed, its exception event has not been continued.
+    /// Only populated when `run()` returns `ExitType::Crash`
+    crash_tid: Option<u32>,
+
+    /// Set once we detached from the process with `detach_suspended()`
+    detached: bool,
+
+    /// Tail of the target's debug strings and standard output/error
+    output: OutputTail,
+
     /// Pointer to aligned context structure
     context: &'a mut CONTEXT,
     _context_backing: Vec<u8>,
@@ -208,8 +318,8 @@ fn elapsed_from(start: &Instant) -> f64 {
 // Mesos print with uptime prefix
 macro_rules! mprint {
     ($x:ident, $($arg:tt)*) => {
-        format!("[{:14.6}] ", elapsed_from(&$x.start_time));
-        format!($($arg)*);
+        let _ = format!("[{:14.6}] ", elapsed_from(&$x.start_time));
+        let _ = format!($($arg)*);
     }
 }
 
@@ -221,8 +331,30 @@ impl<'a> Debugger<'a> {
 
     /// Create a new process argv[0], with arguments argv[1..] and attach to it
     pub fn spawn_proc(argv: &[String], follow_fork: bool) -> Debugger<'a> {
-        let mut startup_info = unsafe { std::mem::zeroed() };
+        let mut startup_info: STARTUPINFOA = unsafe { std::mem::zeroed() };
         let mut proc_info = unsafe { std::mem::zeroed() };
+        startup_info.cb = std::mem::size_of::<STARTUPINFOA>() as u32;
+
+        // Create a pipe for the target's stdout and stderr, only the write
+        // end is inherited by the target
+        let spawn_lock = SPAWN_LOCK.lock().unwrap_or_else(|x| x.into_inner());
+        let mut attributes: SECURITY_ATTRIBUTES =
+            unsafe { std::mem::zeroed() };
+        attributes.nLength = std::mem::size_of_val(&attributes) as u32;
+        attributes.bInheritHandle = 1;
+
+        let mut pipe_read  = std::ptr::null_mut();
+        let mut pipe_write = std::ptr::null_mut();
+        let piped = unsafe {
+            CreatePipe(&mut pipe_read, &mut pipe_write, &mut attributes,
+                0) != 0 &&
+                SetHandleInformation(pipe_read, HANDLE_FLAG_INHERIT, 0) != 0
+        };
+        if piped {
+            startup_info.dwFlags    = STARTF_USESTDHANDLES;
+            startup_info.hStdOutput = pipe_write;
+            startup_info.hStdError  = pipe_write;
+        }
 
         let cmdline = CString::new(argv.join(" ")).unwrap();
 
@@ -241,7 +373,7 @@ impl<'a> Debugger<'a> {
                 cmdline_ptr, // lpCommandLine
                 std::ptr::null_mut(), // lpProcessAttributes
                 std::ptr::null_mut(), // lpThreadAttributes
-                0, // bInheritHandles
+                piped as i32, // bInheritHandles
                 flags, // dwCreationFlags
                 std::ptr::null_mut(), // lpEnvironment
                 std::ptr::null_mut(), // lpCurrentDirectory
@@ -250,9 +382,66 @@ impl<'a> Debugger<'a> {
                 "Failed to create process.");
         }
 
+        // Only the target should hold the write end, so the pipe breaks
+        // when the target exits
+        unsafe {
+            if !pipe_write.is_null() { CloseHandle(pipe_write); }
+            if !piped && !pipe_read.is_null() { CloseHandle(pipe_read); }
+        }
+        drop(spawn_lock);
+
         let pid = unsafe { GetProcessId(proc_info.hProcess) };
-        
-        Debugger::attach_internal(pid, true)
+
+        let debugger = Debugger::attach_internal(pid, true);
+
+        if piped {
+            debugger.capture_output(pipe_read as usize);
+        }
+
+        debugger
+    }
+
+    /// Read lines from the target's output `pipe` into the output tail in
+    /// the background, closing the pipe once it breaks
+    fn capture_output(&self, pipe: usize) {
+        let tail = self.output.clone();
+
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            let mut line = Vec::new();
+
+            loop {
+                let mut bread = 0;
+                let ok = unsafe {
+                    ReadFile(pipe as HANDLE, buf.as_mut_ptr() as *mut _,
+                        buf.len() as u32, &mut bread, std::ptr::null_mut())
+                };
+                if ok == 0 || bread == 0 {
+                    break;
+                }
+
+                for &byte in &buf[..bread as usize] {
+                    if byte == b'\n' {
+                        push_output(&tail, "stdout",
+                            &String::from_utf8_lossy(&line));
+                        line.clear();
+                    } else {
+                        line.push(byte);
+                    }
+                }
+            }
+
+            if !line.is_empty() {
+                push_output(&tail, "stdout", &String::from_utf8_lossy(&line));
+            }
+            unsafe { CloseHandle(pipe as HANDLE); }
+        });
+    }
+
+    /// Get the tail of the target's debug strings and standard output,
+    /// oldest line first
+    pub fn output(&self) -> Vec<String> {
+        self.output.lock().unwrap().iter().cloned().collect()
     }
 
     /// Create a new debugger
@@ -264,10 +453,7 @@ impl<'a> Debugger<'a> {
         crate::sedebug::sedebug();
 
         // Register ctrl-c handler
-        unsafe {
-            assert!(SetConsoleCtrlHandler(Some(ctrl_c_handler), 1) != 0,
-                "SetConsoleCtrlHandler() failed");
-        }
+        catch_ctrl_c();
 
         unsafe {
             let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
@@ -321,6 +507,8 @@ impl<'a> Debugger<'a> {
             process_handle:        None,
             thread_handles:        HashMap::new(),
             coverage:              HashMap::new(),
+            action_counter:        None,
+            coverage_actions:      HashMap::new(),
             minmax_breakpoint:     HashMap::new(),
             modules:               HashSet::new(),
             single_step:           HashMap::new(),
@@ -328,7 +516,14 @@ impl<'a> Debugger<'a> {
             debug_event_callbacks: Some(Vec::new()),
             always_freq:           false,
             kill_requested:        false,
+            minidumps:             true,
+            crash_stack:           Vec::new(),
+            crash_context:         None,
+            crash_tid:             None,
+            detached:              false,
+            output:                Arc::new(Mutex::new(VecDeque::new())),
             last_db_save:          Instant::now(),
+            lifetime:              Duration::from_secs(15),
             verbose:               false,
             bp_print:              false,
             pid, start_time,
@@ -405,6 +600,19 @@ impl<'a> Debugger<'a> {
     pub fn set_always_freq(&mut self, val: bool) { self.always_freq = val; }
     pub fn set_verbose(&mut self, val: bool)     { self.verbose     = val; }
     pub fn set_bp_print(&mut self, val: bool)    { self.bp_print    = val; }
+    pub fn set_minidumps(&mut self, val: bool)   { self.minidumps   = val; }
+
+    /// Let the debuggee live `extra` longer before its coverage is saved and
+    /// it is killed, for cases which are slowed down on purpose
+    pub fn extend_lifetime(&mut self, extra: Duration) {
+        self.lifetime += extra;
+    }
+
+    /// Attribute new coverage to the action index in `counter`, which is
+    /// updated by whatever performs actions on the target
+    pub fn set_action_counter(&mut self, counter: Arc<AtomicUsize>) {
+        self.action_counter = Some(counter);
+    }
 
     /// Resolves the file name of a given memory mapped file in the target
     /// process
@@ -687,6 +895,12 @@ impl<'a> Debugger<'a> {
 
                 self.coverage.insert(addr,
                     (bp.modname.clone(), bp.offset, funcoff.clone(), 0));
+
+                // Attribute the coverage to the action being performed
+                if let Some(counter) = &self.action_counter {
+                    self.coverage_actions.insert(addr,
+                        counter.load(Ordering::SeqCst));
+                }
             }
 
             // Update coverage frequencies
@@ -763,6 +977,89 @@ impl<'a> Debugger<'a> {
         }
     }
 
+    /// Find the nearest module loaded below `addr`, returning the module
+    /// name and the offset of `addr` from its base
+    fn nearest_module(&self, addr: usize) -> Option<(&str, usize)> {
+        let mut nearest_module: Option<(&str, usize)> = None;
+        for (module, base) in self.modules.iter() {
+            if let Some(offset) = addr.checked_sub(*base) {
+                if nearest_module.is_none() ||
+                        nearest_module.unwrap().1 > offset {
+                    nearest_module = Some((module, offset));
+                }
+            }
+        }
+        nearest_module
+    }
+
+    /// Walk the stack of `tid` based on the internal context structure and
+    /// produce a symbolized frame for each return address
+    fn symbolized_stack(&self, tid: u32) -> Vec<String> {
+        stack_walk(self.process_handle(), self.thread_handles[&tid],
+                   self.context, 64).into_iter().map(|(pc, symbol)| {
+            let mut frame = if let Some((module, offset)) =
+                    self.nearest_module(pc) {
+                format!("{}+0x{:x}", module, offset)
+            } else {
+                format!("0x{:x}", pc)
+            };
+
+            if let Some(symbol) = symbol {
+                frame += &format!(" ({})", symbol);
+            }
+
+            frame
+        }).collect()
+    }
+
+    /// Capture the machine state of a crash from `exception` and the
+    /// context of the crashing thread, which must already be loaded
+    fn crash_context(&self, exception: &EXCEPTION_RECORD) -> CrashContext {
+        let context = &self.context;
+
+        #[cfg(target_pointer_width = "64")]
+        let (pc, registers) = (context.Rip as usize, vec![
+            ("rax", context.Rax), ("rbx", context.Rbx),
+            ("rcx", context.Rcx), ("rdx", context.Rdx),
+            ("rsi", context.Rsi), ("rdi", context.Rdi),
+            ("rbp", context.Rbp), ("rsp", context.Rsp),
+            ("r8",  context.R8),  ("r9",  context.R9),
+            ("r10", context.R10), ("r11", context.R11),
+            ("r12", context.R12), ("r13", context.R13),
+            ("r14", context.R14), ("r15", context.R15),
+            ("rip", context.Rip), ("rflags", context.EFlags as u64),
+        ]);
+
+        #[cfg(target_pointer_width = "32")]
+        let (pc, registers) = (context.Eip as usize, vec![
+            ("eax", context.Eax as u64), ("ebx", context.Ebx as u64),
+            ("ecx", context.Ecx as u64), ("edx", context.Edx as u64),
+            ("esi", context.Esi as u64), ("edi", context.Edi as u64),
+            ("ebp", context.Ebp as u64), ("esp", context.Esp as u64),
+            ("eip", context.Eip as u64), ("eflags", context.EFlags as u64),
+        ]);
+
+        let access = if exception.ExceptionCode == 0xc0000005 &&
+                exception.NumberParameters >= 2 {
+            Some((exception.ExceptionInformation[0],
+                  exception.ExceptionInformation[1]))
+        } else {
+            None
+        };
+
+        let mut code_bytes = vec![0u8; CRASH_CODE_BYTES];
+        let bread = self.read_mem(pc, &mut code_bytes);
+        code_bytes.truncate(bread);
+
+        CrashContext {
+            code: exception.ExceptionCode,
+            pc,
+            access,
+            registers,
+            code_bytes,
+        }
+    }
+
     /// Get a filename to describe a given crash
     fn get_crash_filename(&self, context: &CONTEXT,
                               exception: &EXCEPTION_RECORD) -> String {
@@ -775,15 +1072,7 @@ impl<'a> Debugger<'a> {
         };
 
         // Search for the nearest module
-        let mut nearest_module: Option<(&str, usize)> = None;
-        for (module, base) in self.modules.iter() {
-            if let Some(offset) = pc.checked_sub(*base) {
-                if nearest_module.is_none() ||
-                        nearest_module.unwrap().1 > offset {
-                    nearest_module = Some((module, offset));
-                }
-            }
-        }
+        let nearest_module = self.nearest_module(pc);
 
         let code = exception.ExceptionCode;
 
@@ -871,6 +1160,43 @@ impl<'a> Debugger<'a> {
         }
     }
 
+    /// Detach from the crashed process, leaving all of its threads
+    /// suspended so another debugger can attach to the live crash. Resuming
+    /// the threads re-executes the faulting instruction, raising the crash
+    /// again in that debugger. Only valid after `run()` returned
+    /// `ExitType::Crash`.
+    pub fn detach_suspended(&mut self) -> io::Result<()> {
+        let tid = self.crash_tid.ok_or_else(|| {
+            io::Error::other("Process has not crashed")
+        })?;
+
+        // Leave the code as it would be without us
+        self.remove_breakpoints();
+
+        unsafe {
+            for &thread in self.thread_handles.values() {
+                if SuspendThread(thread) == !0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            // The crashing thread stays suspended by the count above
+            if ContinueDebugEvent(self.pid, tid, DBG_CONTINUE) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            DebugSetProcessKillOnExit(0);
+            if DebugActiveProcessStop(self.pid) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        self.detached = true;
+        mprint!(self, "Detached from crashed process {}, left suspended\n",
+            self.pid);
+        Ok(())
+    }
+
     /// Run the process forever
     pub fn run(&mut self) -> ExitType {
         let mut event = unsafe { std::mem::zeroed() };
@@ -880,7 +1206,7 @@ impl<'a> Debugger<'a> {
         unsafe { loop {
             // Flush the coverage database on an intervals
             if Instant::now().duration_since(self.last_db_save) >=
-                    Duration::from_secs(15) {
+                    self.lifetime {
                 self.flush_coverage_database();
                 self.last_db_save = Instant::now();
                 self.kill_requested = true;
@@ -1010,7 +1336,15 @@ impl<'a> Debugger<'a> {
 
                             mprint!(self, "Got crash: {}\n", filename);
 
-                            if !Path::new(&filename).is_file() {
+                            // Symbolize the stack of the crashing thread
+                            self.crash_stack = self.symbolized_stack(tid);
+
+                            // Save the machine state for triage
+                            self.crash_context = Some(self.crash_context(
+                                &exception.ExceptionRecord));
+
+                            if self.minidumps &&
+                                    !Path::new(&filename).is_file() {
                                 // Remove all breakpoints in the program
                                 // before minidumping
                                 self.remove_breakpoints();
@@ -1022,7 +1356,8 @@ impl<'a> Debugger<'a> {
                                      &mut self.context);
                             }
 
-                            // Exit out
+                            // Exit out, leaving the exception pending
+                            self.crash_tid = Some(tid);
                             return ExitType::Crash(filename);
                         } else if exception.ExceptionRecord
                                 .ExceptionCode == 0x80000004 {
@@ -1103,8 +1438,27 @@ impl<'a> Debugger<'a> {
                     self.unregister_module(unload_dll.lpBaseOfDll as usize);
                 }
                 OUTPUT_DEBUG_STRING_EVENT => {
-                    // Target attempted to print a debug string, just ignore
-                    // it
+                    // Target printed a debug string, save it in the output
+                    // tail
+                    let info = event.u.DebugString();
+                    let len  = std::cmp::min(
+                        info.nDebugStringLength as usize, OUTPUT_LINE_LEN);
+                    let addr = info.lpDebugStringData as usize;
+
+                    let string = if info.fUnicode != 0 {
+                        let mut buf = vec![0u8; len * 2];
+                        let bread = self.read_mem(addr, &mut buf);
+                        let wide: Vec<u16> = buf[..bread].chunks_exact(2)
+                            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+                            .collect();
+                        String::from_utf16_lossy(&wide)
+                    } else {
+                        let mut buf = vec![0u8; len];
+                        let bread = self.read_mem(addr, &mut buf);
+                        String::from_utf8_lossy(&buf[..bread]).into_owned()
+                    };
+
+                    push_output(&self.output, "debug", &string);
                 }
                 RIP_EVENT => {
                 }
@@ -1125,10 +1479,12 @@ impl<'a> Drop for Debugger<'a> {
         // Flush coverage database one last time
         self.flush_coverage_database();
 
-        // Detach from the process
-        unsafe {
-            assert!(DebugActiveProcessStop(self.pid) != 0,
-                "DebugActiveProcessStop() failed");
+        // Detach from the process, unless it was already left suspended
+        if !self.detached {
+            unsafe {
+                assert!(DebugActiveProcessStop(self.pid) != 0,
+                    "DebugActiveProcessStop() failed");
+            }
         }
 
         // All done, process is safely restored
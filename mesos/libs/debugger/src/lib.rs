@@ -3,6 +3,8 @@ mod minidump;
 mod sedebug;
 mod ffi_helpers;
 mod handles;
+mod stackwalk;
 
 // Make some things public
-pub use debugger::{Debugger, ExitType, BreakpointType};
+pub use debugger::{Debugger, ExitType, BreakpointType, CrashContext};
+pub use debugger::{exit_requested, catch_ctrl_c};
@@ -14,6 +14,7 @@ use winapi::um::winnt::CONTEXT;
 use std::path::Path;
 use crate::handles::Handle;
 use crate::ffi_helpers::win32_string;
+use crate::stackwalk::DBGHELP;
 
 #[repr(C)]
 #[allow(dead_code)]
@@ -95,6 +96,7 @@ pub fn dump(filename: &str, pid: u32, tid: u32, process: HANDLE,
         };
 
         // Take a minidump!
+        let _dbghelp = DBGHELP.lock().unwrap_or_else(|x| x.into_inner());
         let res = MiniDumpWriteDump(process, pid, fd.raw(), 
             MinidumpType::MiniDumpWithFullMemory as u32 |
             MinidumpType::MiniDumpWithHandleData as u32,
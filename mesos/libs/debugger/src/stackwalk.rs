@@ -0,0 +1,134 @@
+//! Module to walk and symbolize the call stack of a thread in the debuggee
+
+use std::sync::Mutex;
+use winapi::um::winnt::CONTEXT;
+use winapi::um::winnt::HANDLE;
+use winapi::um::dbghelp::StackWalk64;
+use winapi::um::dbghelp::SymInitializeW;
+use winapi::um::dbghelp::SymCleanup;
+use winapi::um::dbghelp::SymFromAddrW;
+use winapi::um::dbghelp::SymFunctionTableAccess64;
+use winapi::um::dbghelp::SymGetModuleBase64;
+use winapi::um::dbghelp::STACKFRAME64;
+use winapi::um::dbghelp::SYMBOL_INFOW;
+use winapi::um::dbghelp::AddrModeFlat;
+
+/// Machine type passed to `StackWalk64()` for the bitness we're built for
+#[cfg(target_pointer_width = "64")]
+const MACHINE_TYPE: u32 = 0x8664;
+#[cfg(target_pointer_width = "32")]
+const MACHINE_TYPE: u32 = 0x014c;
+
+/// Maximum number of UTF-16 characters in a symbol name
+const MAX_SYM_NAME: usize = 512;
+
+/// Held around all DbgHelp calls. DbgHelp is single-threaded, and every
+/// worker's debugger walks stacks of and dumps its own target.
+pub static DBGHELP: Mutex<()> = Mutex::new(());
+
+/// Symbol handler for a process, cleaned up on drop
+struct Symbols(HANDLE);
+
+impl Symbols {
+    /// Initialize the symbol handler for `process`, loading symbols for all
+    /// of its modules
+    fn new(process: HANDLE) -> Option<Self> {
+        unsafe {
+            if SymInitializeW(process, std::ptr::null(), 1) == 0 {
+                return None;
+            }
+        }
+
+        Some(Symbols(process))
+    }
+
+    /// Resolve `addr` to "symbol+0xdisplacement"
+    fn resolve(&self, addr: usize) -> Option<String> {
+        // `SYMBOL_INFOW` is followed by the name buffer, so back it with a
+        // u64 buffer to keep it correctly aligned
+        let size = std::mem::size_of::<SYMBOL_INFOW>() +
+            MAX_SYM_NAME * std::mem::size_of::<u16>();
+        let mut backing = vec![0u64; size.div_ceil(8)];
+        let symbol = backing.as_mut_ptr() as *mut SYMBOL_INFOW;
+
+        unsafe {
+            (*symbol).SizeOfStruct =
+                std::mem::size_of::<SYMBOL_INFOW>() as u32;
+            (*symbol).MaxNameLen = MAX_SYM_NAME as u32;
+
+            let mut displacement = 0u64;
+            if SymFromAddrW(self.0, addr as u64, &mut displacement,
+                    symbol) == 0 {
+                return None;
+            }
+
+            let name = std::slice::from_raw_parts(
+                (*symbol).Name.as_ptr(),
+                ((*symbol).NameLen as usize).min(MAX_SYM_NAME));
+            Some(format!("{}+0x{:x}", String::from_utf16_lossy(name),
+                displacement))
+        }
+    }
+}
+
+impl Drop for Symbols {
+    fn drop(&mut self) {
+        unsafe { SymCleanup(self.0); }
+    }
+}
+
+/// Walk the stack of `thread` in `process` starting from `context`. Returns
+/// a list of up to `max_frames` program counters, innermost first, along
+/// with a symbol for each if one could be resolved.
+pub fn stack_walk(process: HANDLE, thread: HANDLE, context: &CONTEXT,
+                  max_frames: usize) -> Vec<(usize, Option<String>)> {
+    let mut frames = Vec::new();
+
+    // `StackWalk64()` modifies the context, so work on a copy
+    let mut context = *context;
+
+    // Declared before the symbol handler so it is held until the handler
+    // is cleaned up
+    let _dbghelp = DBGHELP.lock().unwrap_or_else(|x| x.into_inner());
+    let symbols = Symbols::new(process);
+
+    unsafe {
+        let mut frame: STACKFRAME64 = std::mem::zeroed();
+        frame.AddrPC.Mode    = AddrModeFlat;
+        frame.AddrFrame.Mode = AddrModeFlat;
+        frame.AddrStack.Mode = AddrModeFlat;
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            frame.AddrPC.Offset    = context.Rip;
+            frame.AddrFrame.Offset = context.Rbp;
+            frame.AddrStack.Offset = context.Rsp;
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        {
+            frame.AddrPC.Offset    = context.Eip as u64;
+            frame.AddrFrame.Offset = context.Ebp as u64;
+            frame.AddrStack.Offset = context.Esp as u64;
+        }
+
+        while frames.len() < max_frames {
+            if StackWalk64(MACHINE_TYPE, process, thread, &mut frame,
+                    &mut context as *mut CONTEXT as *mut _, None,
+                    Some(SymFunctionTableAccess64),
+                    Some(SymGetModuleBase64), None) == 0 {
+                break;
+            }
+
+            let pc = frame.AddrPC.Offset as usize;
+            if pc == 0 {
+                break;
+            }
+
+            frames.push((pc,
+                symbols.as_ref().and_then(|symbols| symbols.resolve(pc))));
+        }
+    }
+
+    frames
+}
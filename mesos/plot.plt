@@ -4,9 +4,7 @@ set ylabel "Coverage"
 set logscale x
 set samples 1000000
 set key bottom
+set datafile separator ","
 
-plot "fuzz_stats.txt" u 2:3 w l,\
-	"fuzz_stats_nomutate.txt" u 2:3 w l,\
-	"fuzz_stats_first_mutate.txt" u 2:3 w l,\
-	"fuzz_stats_nomutate2.txt" u 2:3 w l
+plot "fuzz_stats.csv" u 3:4 every ::1 w l
 
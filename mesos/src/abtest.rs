@@ -0,0 +1,168 @@
+//! A/B comparison of two strategy configurations fuzzing the same target
+//! side by side. The workers are split between the two arms, each with its
+//! own corpus and coverage, and the coverage each arm gains per time slice
+//! is compared with a Mann-Whitney U test, which doesn't assume the gains
+//! are normally distributed.
+
+use std::fmt;
+use guifuzz::Campaign;
+use statlog::Sample;
+
+/// Length of the time slices coverage growth is compared over, in seconds
+const SLICE_SECS: f64 = 60.;
+
+/// Take a sample of the statistics of `campaign`
+pub fn sample(campaign: &Campaign) -> Sample {
+    let uptime = campaign.uptime().as_secs_f64();
+    let stats = campaign.lock();
+    Sample {
+        uptime,
+        fuzz_cases:     stats.fuzz_cases,
+        coverage:       stats.coverage_db.len(),
+        corpus:         stats.corpus.len(),
+        crashes:        stats.crashes,
+        unique_crashes: stats.crash_db.len(),
+    }
+}
+
+/// Samples of both arms at the end of each time slice
+#[derive(Clone, Debug, Default)]
+pub struct Comparison {
+    /// Samples of the A arm
+    a: Vec<Sample>,
+
+    /// Samples of the B arm, taken at the same time as those of the A arm
+    b: Vec<Sample>,
+
+    /// Latest samples, included in the report even mid-slice
+    latest: Option<(Sample, Sample)>,
+
+    /// Seconds into the campaign each arm first found a unique crash
+    first_crash: [Option<f64>; 2],
+}
+
+impl Comparison {
+    /// Start a comparison
+    pub fn new() -> Self {
+        Comparison::default()
+    }
+
+    /// Record samples `a` and `b` of the arms taken at the same time,
+    /// keeping them if a time slice ended
+    pub fn record(&mut self, a: Sample, b: Sample) {
+        for (ii, sample) in [a, b].iter().enumerate() {
+            if sample.unique_crashes > 0 && self.first_crash[ii].is_none() {
+                self.first_crash[ii] = Some(sample.uptime);
+            }
+        }
+
+        let slice_end = self.a.last().map(|x| x.uptime).unwrap_or(0.) +
+            SLICE_SECS;
+        if a.uptime >= slice_end {
+            self.a.push(a);
+            self.b.push(b);
+        }
+        self.latest = Some((a, b));
+    }
+
+    /// Coverage gained by an arm in each full time slice
+    fn gains(samples: &[Sample]) -> Vec<f64> {
+        let mut last = 0;
+        samples.iter().map(|x| {
+            let gain = x.coverage.saturating_sub(last);
+            last = x.coverage;
+            gain as f64
+        }).collect()
+    }
+}
+
+/// Standard normal cumulative distribution function, using the erf
+/// approximation 7.1.26 from Abramowitz and Stegun
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / 2f64.sqrt();
+    let t = 1. / (1. + 0.3275911 * z);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 +
+        t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1. - poly * (-z * z).exp();
+    if x >= 0. { (1. + erf) / 2. } else { (1. - erf) / 2. }
+}
+
+/// Mann-Whitney U test of whether `a` and `b` come from the same
+/// distribution. Returns the U statistic of `a` and the two-sided p-value
+/// from the normal approximation, `None` if either is empty.
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> Option<(f64, f64)> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    // Rank all values together, ties get the mean of their ranks
+    let mut values: Vec<(f64, bool)> = a.iter().map(|&x| (x, true))
+        .chain(b.iter().map(|&x| (x, false))).collect();
+    values.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    let mut rank_sum_a = 0.;
+    let mut ii = 0;
+    while ii < values.len() {
+        let tied = values[ii..].iter()
+            .take_while(|x| x.0 == values[ii].0).count();
+        let rank = ii as f64 + (tied as f64 + 1.) / 2.;
+        rank_sum_a += rank * values[ii..ii + tied].iter()
+            .filter(|x| x.1).count() as f64;
+        ii += tied;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let u = rank_sum_a - n_a * (n_a + 1.) / 2.;
+    let mean = n_a * n_b / 2.;
+    let stddev = (n_a * n_b * (n_a + n_b + 1.) / 12.).sqrt();
+    let p = if stddev > 0. {
+        2. * (1. - normal_cdf((u - mean).abs() / stddev))
+    } else {
+        1.
+    };
+    Some((u, p.min(1.)))
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (a, b) = match self.latest {
+            Some(latest) => latest,
+            None => return writeln!(f, "No samples taken"),
+        };
+
+        writeln!(f, "{:24} {:>12} {:>12}", "", "A", "B")?;
+        writeln!(f, "{:24} {:>12} {:>12}", "fuzz cases", a.fuzz_cases,
+            b.fuzz_cases)?;
+        writeln!(f, "{:24} {:>12.2} {:>12.2}", "fuzz cases per second",
+            a.fuzz_cases as f64 / a.uptime.max(1.),
+            b.fuzz_cases as f64 / b.uptime.max(1.))?;
+        writeln!(f, "{:24} {:>12} {:>12}", "coverage", a.coverage,
+            b.coverage)?;
+        writeln!(f, "{:24} {:>12} {:>12}", "inputs", a.corpus, b.corpus)?;
+        writeln!(f, "{:24} {:>12} {:>12}", "crashes", a.crashes, b.crashes)?;
+        writeln!(f, "{:24} {:>12} {:>12}", "unique crashes",
+            a.unique_crashes, b.unique_crashes)?;
+
+        let first_crash: Vec<String> = self.first_crash.iter()
+            .map(|x| x.map(|x| format!("{:.0}s", x))
+                .unwrap_or("none".into()))
+            .collect();
+        writeln!(f, "{:24} {:>12} {:>12}", "first unique crash",
+            first_crash[0], first_crash[1])?;
+
+        let gains_a = Self::gains(&self.a);
+        let gains_b = Self::gains(&self.b);
+        let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len().max(1) as f64;
+        writeln!(f, "{:24} {:>12.2} {:>12.2}",
+            format!("coverage per {:.0}s slice", SLICE_SECS),
+            mean(&gains_a), mean(&gains_b))?;
+
+        match mann_whitney(&gains_a, &gains_b) {
+            Some((u, p)) => writeln!(f, "Mann-Whitney U = {:.1} over {} \
+                slices, p = {:.4}{}", u, gains_a.len(), p,
+                if p < 0.05 { ", significant" } else { "" }),
+            None => writeln!(f, "Not enough slices to compare coverage \
+                growth"),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Pinning of workers and their targets to CPU cores, reducing timing
+//! variance from the scheduler moving them around on many-core machines. A
+//! worker pins its thread, and every target it spawns afterwards is pinned
+//! to the same core.
+
+use std::io;
+use std::cell::Cell;
+
+/// Allow setting the affinity of a process
+const PROCESS_SET_INFORMATION: u32 = 0x0200;
+
+/// Allow querying the affinity of a process, required to set it
+const PROCESS_QUERY_INFORMATION: u32 = 0x0400;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetCurrentThread() -> usize;
+    fn SetThreadAffinityMask(thread: usize, mask: usize) -> usize;
+    fn SetProcessAffinityMask(process: usize, mask: usize) -> i32;
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+thread_local! {
+    /// Affinity mask the current thread was pinned to, 0 if it wasn't
+    static PINNED_MASK: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Affinity mask of `core`
+fn core_mask(core: usize) -> io::Result<usize> {
+    1usize.checked_shl(core as u32).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Core {} is out of range", core))
+    })
+}
+
+/// Pin the current thread to `core`, and targets it spawns from now on
+pub fn pin_current_thread(core: usize) -> io::Result<()> {
+    let mask = core_mask(core)?;
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    PINNED_MASK.with(|x| x.set(mask));
+    Ok(())
+}
+
+/// Pin the process `pid` to the core the current thread is pinned to. Does
+/// nothing if the current thread isn't pinned.
+pub fn pin_process(pid: u32) -> io::Result<()> {
+    let mask = PINNED_MASK.with(|x| x.get());
+    if mask == 0 {
+        return Ok(());
+    }
+
+    unsafe {
+        let process = OpenProcess(
+            PROCESS_SET_INFORMATION | PROCESS_QUERY_INFORMATION, 0, pid);
+        if process == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let ok = SetProcessAffinityMask(process, mask) != 0;
+        let err = io::Error::last_os_error();
+        CloseHandle(process);
+        if ok { Ok(()) } else { Err(err) }
+    }
+}
@@ -0,0 +1,250 @@
+//! Reproducibility bundles of a campaign. A bundle freezes everything a
+//! campaign depends on: its command line and config files, the RNG seeds of
+//! its workers, its corpus and crashes, the meso files, and hashes of the
+//! target binaries. Verifying a bundle checks it is intact and that the
+//! target binaries on this machine are the ones it was made with.
+
+use std::io::{self, Write};
+use std::fs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::fs::OpenOptions;
+use corpusdir::copy_new;
+use guifuzz::{Latency, Pacing};
+
+/// Command line and worker seeds of the campaign, written by the harness
+const CAMPAIGN_TXT: &str = "campaign.txt";
+
+/// Hashes of the target binaries in a bundle
+const BINARIES_TXT: &str = "binaries.txt";
+
+/// Hashes of every file in a bundle
+const MANIFEST_TXT: &str = "MANIFEST.txt";
+
+/// Directories of the campaign which are bundled as a whole
+const BUNDLED_DIRS: &[&str] = &["inputs", "crashes", "cache"];
+
+/// FNV-1a hash of `data`, which unlike `DefaultHasher` is the same across
+/// builds and machines
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &x| {
+        (hash ^ x as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Start the campaign record with the command line `args`
+pub fn record_campaign(args: &[String]) -> io::Result<()> {
+    let lines: Vec<String> = args.iter()
+        .map(|x| format!("arg = {}\n", x)).collect();
+    fs::write(CAMPAIGN_TXT, lines.concat())
+}
+
+/// Add the RNG `seed` of `worker` to the campaign record
+pub fn record_seed(worker: usize, seed: u64) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true)
+        .open(CAMPAIGN_TXT)?;
+    writeln!(file, "worker {} seed = {:#x}", worker, seed)
+}
+
+/// Add the measured `latency` of the target and the `pacing` derived from
+/// it to the campaign record
+pub fn record_pacing(latency: &Latency, pacing: &Pacing) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true)
+        .open(CAMPAIGN_TXT)?;
+    write!(file, "latency message_us = {}\nlatency enumeration_us = {}\n\
+                  pacing menu_settle_ms = {}\npacing attach_poll_ms = {}\n\
+                  pacing idle_timeout_ms = {}\n",
+        latency.message.as_micros(), latency.enumeration.as_micros(),
+        pacing.menu_settle.as_millis(), pacing.attach_poll.as_millis(),
+        pacing.idle_timeout.as_millis())
+}
+
+/// Get the command line recorded for the campaign in the directory `dir`
+pub fn recorded_args(dir: &Path) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(dir.join(CAMPAIGN_TXT))?.lines()
+        .filter(|x| x.starts_with("arg = "))
+        .map(|x| x["arg = ".len()..].to_string())
+        .collect())
+}
+
+/// Find the executable `name` the way spawning it would, directly if it's
+/// a path and in the `PATH` otherwise
+fn resolve_exe(name: &str) -> Option<PathBuf> {
+    if Path::new(name).is_file() {
+        return Some(PathBuf::from(name));
+    }
+
+    let path = std::env::var("PATH").unwrap_or_default();
+    path.split(';').map(|dir| Path::new(dir).join(name))
+        .find(|x| x.is_file())
+}
+
+/// Copy the file `from` to `rel` in the bundle `out`, adding it to the
+/// `manifest`
+fn bundle_file(from: &Path, out: &Path, rel: &Path, manifest: &mut String)
+        -> io::Result<()> {
+    let contents = fs::read(from)?;
+    if let Some(parent) = out.join(rel).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    copy_new(&out.join(rel), &contents)?;
+
+    *manifest += &format!("{} {} {:016x}\n", rel.display(), contents.len(),
+        fnv1a(&contents));
+    Ok(())
+}
+
+/// Bundle every file under the directory `dir`, recursively
+fn bundle_dir(dir: &Path, out: &Path, manifest: &mut String)
+        -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            bundle_dir(&path, out, manifest)?;
+        } else {
+            bundle_file(&path, out, &path, manifest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Bundle the campaign in the current directory into `out`, hashing the
+/// target `binaries`. Config files on the recorded command line are
+/// bundled as well. Returns the number of files bundled.
+pub fn bundle(out: &Path, binaries: &[String]) -> io::Result<usize> {
+    let mut manifest = String::new();
+    fs::create_dir_all(out)?;
+
+    bundle_file(Path::new(CAMPAIGN_TXT), out, Path::new(CAMPAIGN_TXT),
+        &mut manifest)?;
+
+    // Config files may live anywhere, they are bundled by name
+    let args = recorded_args(Path::new("."))?;
+    for pair in args.windows(2).filter(|x| x[0] == "--config") {
+        let path = Path::new(&pair[1]);
+        let name = path.file_name().map(Path::new).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Invalid config path {}", path.display()))
+        })?;
+        bundle_file(path, out, name, &mut manifest)?;
+    }
+
+    for entry in fs::read_dir(".")? {
+        let path = entry?.path();
+        if path.extension().map(|x| x == "meso").unwrap_or(false) {
+            let rel = path.strip_prefix(".").unwrap_or(&path).to_path_buf();
+            bundle_file(&path, out, &rel, &mut manifest)?;
+        }
+    }
+    for dir in BUNDLED_DIRS.iter().map(Path::new).filter(|x| x.is_dir()) {
+        bundle_dir(dir, out, &mut manifest)?;
+    }
+
+    let mut hashes = String::new();
+    for name in binaries {
+        let path = resolve_exe(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound,
+                format!("Target binary {} not found", name))
+        })?;
+        let contents = fs::read(&path)?;
+        hashes += &format!("{} {} {:016x}\n", name, contents.len(),
+            fnv1a(&contents));
+    }
+    fs::write(out.join(BINARIES_TXT), hashes)?;
+
+    let files = manifest.lines().count();
+    fs::write(out.join(MANIFEST_TXT), manifest)?;
+    Ok(files)
+}
+
+/// Result of verifying a bundle
+#[derive(Clone, Debug, Default)]
+pub struct Verification {
+    /// Number of bundled files which are intact
+    pub intact: usize,
+
+    /// Bundled files which are missing or were modified
+    pub damaged: Vec<String>,
+
+    /// Target binaries which are missing on this machine or differ from
+    /// the bundled hashes
+    pub mismatched: Vec<String>,
+
+    /// Names of the crash buckets in the bundle
+    pub crashes: Vec<String>,
+}
+
+impl Verification {
+    /// Returns `true` if the bundle is intact and was made with this
+    /// machine's target binaries
+    pub fn ok(&self) -> bool {
+        self.damaged.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} files intact, {} damaged, {} target binaries differ",
+            self.intact, self.damaged.len(), self.mismatched.len())?;
+        for file in &self.damaged {
+            writeln!(f, "damaged: {}", file)?;
+        }
+        for binary in &self.mismatched {
+            writeln!(f, "binary differs: {}", binary)?;
+        }
+
+        // Saved inputs can't be loaded back yet, so crashes are only
+        // listed rather than replayed
+        writeln!(f, "{} crash buckets bundled, not replayed",
+            self.crashes.len())
+    }
+}
+
+/// Verify the bundle in the directory `bundle` against its manifest and
+/// the target binaries on this machine
+pub fn verify(bundle: &Path) -> io::Result<Verification> {
+    let mut verification = Verification::default();
+
+    for line in fs::read_to_string(bundle.join(MANIFEST_TXT))?.lines() {
+        let fields: Vec<&str> = line.rsplitn(3, ' ').collect();
+        if fields.len() != 3 {
+            continue;
+        }
+        let (hash, rel) = (fields[0], fields[2]);
+
+        let intact = fs::read(bundle.join(rel))
+            .map(|x| format!("{:016x}", fnv1a(&x)) == hash)
+            .unwrap_or(false);
+        if intact {
+            verification.intact += 1;
+        } else {
+            verification.damaged.push(rel.to_string());
+        }
+    }
+
+    for line in fs::read_to_string(bundle.join(BINARIES_TXT))?.lines() {
+        let fields: Vec<&str> = line.rsplitn(3, ' ').collect();
+        if fields.len() != 3 {
+            continue;
+        }
+        let (hash, name) = (fields[0], fields[2]);
+
+        let same = resolve_exe(name).and_then(|x| fs::read(x).ok())
+            .map(|x| format!("{:016x}", fnv1a(&x)) == hash)
+            .unwrap_or(false);
+        if !same {
+            verification.mismatched.push(name.to_string());
+        }
+    }
+
+    if let Ok(buckets) = fs::read_dir(bundle.join("crashes")) {
+        for bucket in buckets {
+            let path = bucket?.path();
+            if let Some(name) = path.file_name().filter(|_| path.is_dir()) {
+                verification.crashes.push(name.to_string_lossy().into());
+            }
+        }
+    }
+
+    Ok(verification)
+}
@@ -0,0 +1,185 @@
+//! Suppression of companion processes. Menu items of the target often open
+//! help URLs in a browser or folders in Explorer, which aren't children of
+//! the target, so its job doesn't catch them, and which pile up and steal
+//! focus. During a case every process which shows up with a window, and
+//! isn't a fuzz target, is killed and recorded along with the action which
+//! was being performed.
+
+use std::io;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::{HashMap, HashSet};
+use guifuzz::Window;
+use target;
+
+/// Interval at which the windows on the system are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `CreateToolhelp32Snapshot()` flag to include all processes
+const TH32CS_SNAPPROCESS: u32 = 0x2;
+
+/// Invalid handle returned by `CreateToolhelp32Snapshot()` on error
+const INVALID_HANDLE_VALUE: usize = !0;
+
+/// Allow terminating a process
+const PROCESS_TERMINATE: u32 = 0x0001;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn CreateToolhelp32Snapshot(flags: u32, pid: u32) -> usize;
+    fn Process32FirstW(snapshot: usize, entry: *mut ProcessEntry32) -> i32;
+    fn Process32NextW(snapshot: usize, entry: *mut ProcessEntry32) -> i32;
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn CloseHandle(handle: usize) -> i32;
+    fn TerminateProcess(process: usize, exit_code: u32) -> i32;
+}
+
+/// Rust implementation of `PROCESSENTRY32W`
+#[repr(C)]
+struct ProcessEntry32 {
+    size:            u32,
+    usage:           u32,
+    pid:             u32,
+    default_heap_id: usize,
+    module_id:       u32,
+    threads:         u32,
+    parent_pid:      u32,
+    base_priority:   i32,
+    flags:           u32,
+    exe_file:        [u16; 260],
+}
+
+/// Get the PID and executable name of every running process
+fn running_processes() -> io::Result<HashMap<u32, String>> {
+    let mut processes = HashMap::new();
+
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut entry: ProcessEntry32 = std::mem::zeroed();
+        entry.size = std::mem::size_of::<ProcessEntry32>() as u32;
+
+        let mut ok = Process32FirstW(snapshot, &mut entry) != 0;
+        while ok {
+            let len = entry.exe_file.iter().position(|&x| x == 0)
+                .unwrap_or(entry.exe_file.len());
+            processes.insert(entry.pid,
+                String::from_utf16_lossy(&entry.exe_file[..len]));
+            ok = Process32NextW(snapshot, &mut entry) != 0;
+        }
+
+        CloseHandle(snapshot);
+    }
+
+    Ok(processes)
+}
+
+/// Kill the process `pid`
+fn kill(pid: u32) -> io::Result<()> {
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, 0, pid);
+        if process == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let killed = TerminateProcess(process, 0) != 0;
+        let err = io::Error::last_os_error();
+        CloseHandle(process);
+        if killed { Ok(()) } else { Err(err) }
+    }
+}
+
+/// A companion process which was killed
+#[derive(Clone, Debug)]
+pub struct Companion {
+    /// PID of the process
+    pub pid: u32,
+
+    /// Executable name of the process
+    pub name: String,
+
+    /// Index of the action which was being performed when the process
+    /// showed up, if known
+    pub action: Option<usize>,
+}
+
+/// Watcher killing companion processes for the duration of a case
+pub struct Suppressor {
+    /// Tells the watcher thread to stop
+    stop: Arc<AtomicBool>,
+
+    /// Watcher thread, returning the companions it killed
+    thread: Option<JoinHandle<Vec<Companion>>>,
+}
+
+impl Suppressor {
+    /// Start killing processes which weren't running yet. `action` holds
+    /// the index of the action being performed, `unknown` while it isn't
+    /// known.
+    pub fn start(action: Arc<AtomicUsize>, unknown: usize)
+            -> io::Result<Self> {
+        let baseline: HashSet<u32> = running_processes()?.keys()
+            .cloned().collect();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut companions = Vec::new();
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(POLL_INTERVAL);
+
+                    let pids = match Window::windowed_pids() {
+                        Ok(pids) => pids,
+                        Err(_) => continue,
+                    };
+                    let new: Vec<u32> = pids.into_iter().filter(|pid| {
+                        !baseline.contains(pid) && !target::is_target(*pid) &&
+                            companions.iter().all(|x: &Companion| {
+                                x.pid != *pid
+                            })
+                    }).collect();
+                    if new.is_empty() {
+                        continue;
+                    }
+
+                    let names = running_processes().unwrap_or_default();
+                    let index = action.load(Ordering::SeqCst);
+                    for pid in new {
+                        if kill(pid).is_ok() {
+                            companions.push(Companion {
+                                pid,
+                                name:   names.get(&pid).cloned()
+                                    .unwrap_or_default(),
+                                action: Some(index).filter(|&x| x != unknown),
+                            });
+                        }
+                    }
+                }
+                companions
+            })
+        };
+
+        Ok(Suppressor { stop, thread: Some(thread) })
+    }
+
+    /// Stop watching, returning the companions which were killed
+    pub fn finish(mut self) -> Vec<Companion> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.take().and_then(|x| x.join().ok()).unwrap_or_default()
+    }
+}
+
+impl Drop for Suppressor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
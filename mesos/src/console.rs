@@ -0,0 +1,202 @@
+//! Console reporting with verbosity levels, colors, and rate limiting. All
+//! messages are also appended to a log file so quiet headless runs lose
+//! nothing.
+
+use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::sync::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use guifuzz::Config;
+
+/// `GetStdHandle()` ID of standard output
+const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+/// Console mode flag enabling ANSI escape sequences
+const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x4;
+
+/// Minimum time between two console messages of the same kind
+const RATE_LIMIT: Duration = Duration::from_secs(1);
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetStdHandle(std_handle: u32) -> usize;
+    fn GetConsoleMode(console: usize, mode: *mut u32) -> i32;
+    fn SetConsoleMode(console: usize, mode: u32) -> i32;
+}
+
+/// How much is printed to the console
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors
+    Quiet,
+
+    /// Errors, new crashes, and statistics
+    Normal,
+
+    /// Everything, including new coverage
+    Verbose,
+}
+
+/// Kinds of messages, deciding their verbosity, color, and rate limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Errors in the fuzzer itself
+    Error,
+
+    /// Host resource and other warnings
+    Warning,
+
+    /// General information and statistics
+    Info,
+
+    /// A new crash bucket was found
+    NewCrash,
+
+    /// An input found new coverage
+    NewCoverage,
+}
+
+impl Kind {
+    /// Lowest verbosity this kind is printed at
+    fn verbosity(&self) -> Verbosity {
+        match self {
+            Kind::Error       => Verbosity::Quiet,
+            Kind::Warning     => Verbosity::Normal,
+            Kind::Info        => Verbosity::Normal,
+            Kind::NewCrash    => Verbosity::Normal,
+            Kind::NewCoverage => Verbosity::Verbose,
+        }
+    }
+
+    /// ANSI color escape sequence for this kind
+    fn color(&self) -> &'static str {
+        match self {
+            Kind::Error       => "\x1b[31m",
+            Kind::Warning     => "\x1b[33m",
+            Kind::Info        => "",
+            Kind::NewCrash    => "\x1b[1;35m",
+            Kind::NewCoverage => "\x1b[36m",
+        }
+    }
+
+    /// Whether messages of this kind are rate limited on the console
+    fn rate_limited(&self) -> bool {
+        match self {
+            Kind::Error | Kind::Warning | Kind::NewCoverage => true,
+            Kind::Info | Kind::NewCrash => false,
+        }
+    }
+}
+
+/// Rate limiting state of one kind of message
+#[derive(Default)]
+struct Limit {
+    /// When a message of this kind was last printed
+    last: Option<Instant>,
+
+    /// Number of messages not printed since then
+    suppressed: u64,
+}
+
+/// Reporter for all console output of the fuzzer, shared between threads
+pub struct Console {
+    /// How much is printed
+    verbosity: Verbosity,
+
+    /// Use ANSI colors
+    color: bool,
+
+    /// Log file every message is appended to
+    log: Option<Mutex<File>>,
+
+    /// Rate limiting state for each kind of message
+    limits: Mutex<HashMap<Kind, Limit>>,
+}
+
+impl Console {
+    /// Create a console reporter using the verbosity and color settings in
+    /// `config`, logging to `log_path`
+    pub fn new(config: &Config, log_path: &str) -> Self {
+        let verbosity = if config.quiet {
+            Verbosity::Quiet
+        } else if config.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+
+        Console {
+            verbosity,
+            color:  config.color && enable_ansi(),
+            log:    OpenOptions::new().create(true).append(true)
+                .open(log_path).ok().map(Mutex::new),
+            limits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Report `message` of `kind`
+    pub fn report(&self, kind: Kind, message: &str) {
+        if let Some(log) = &self.log {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs()).unwrap_or(0);
+            let _ = writeln!(log.lock().unwrap(), "{} {:?}: {}", now, kind,
+                message);
+        }
+
+        if kind.verbosity() > self.verbosity {
+            return;
+        }
+
+        // Drop messages which come too quickly, noting how many were dropped
+        // on the next one printed
+        let mut suffix = String::new();
+        if kind.rate_limited() {
+            let mut limits = self.limits.lock().unwrap();
+            let limit = limits.entry(kind).or_default();
+            if limit.last.map(|x| x.elapsed() < RATE_LIMIT).unwrap_or(false) {
+                limit.suppressed += 1;
+                return;
+            }
+
+            if limit.suppressed > 0 {
+                suffix = format!(" ({} similar suppressed)", limit.suppressed);
+            }
+            limit.last       = Some(Instant::now());
+            limit.suppressed = 0;
+        }
+
+        if self.color && !kind.color().is_empty() {
+            println!("{}{}{}\x1b[0m", kind.color(), message, suffix);
+        } else {
+            println!("{}{}", message, suffix);
+        }
+    }
+
+    /// Report an error in the fuzzer
+    pub fn error(&self, message: &str) {
+        self.report(Kind::Error, message);
+    }
+
+    /// Report a warning
+    pub fn warning(&self, message: &str) {
+        self.report(Kind::Warning, message);
+    }
+
+    /// Report general information
+    pub fn info(&self, message: &str) {
+        self.report(Kind::Info, message);
+    }
+}
+
+/// Enable ANSI escape sequences on the console, returns `false` if output
+/// isn't a console which supports them
+fn enable_ansi() -> bool {
+    unsafe {
+        let console = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        GetConsoleMode(console, &mut mode) != 0 &&
+            SetConsoleMode(console,
+                mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
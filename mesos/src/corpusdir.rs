@@ -0,0 +1,149 @@
+//! Import and export of the corpus as AFL-style directories, one file per
+//! input under `queue/` with stable names, so corpus management and dedupe
+//! tooling written for other fuzzers can be reused. Inputs are copied as
+//! opaque files, the provenance of each is kept in a sidecar file under
+//! `queue/.meta/`, which such tools skip as it is hidden.
+
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Directory the fuzzer saves inputs to
+const INPUTS_DIR: &str = "inputs";
+
+/// Corpus directory formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// AFL-style `queue/` directory
+    Afl,
+}
+
+impl Format {
+    /// Parse a format name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "afl" => Some(Format::Afl),
+            _     => None,
+        }
+    }
+
+    /// Directory holding the inputs of the corpus directory `dir`
+    fn queue(&self, dir: &Path) -> PathBuf {
+        match self {
+            Format::Afl => dir.join("queue"),
+        }
+    }
+}
+
+/// Counts of inputs moved by an import or export
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Transfer {
+    /// Inputs copied
+    pub copied: usize,
+
+    /// Inputs skipped as they were already present
+    pub duplicates: usize,
+}
+
+/// Check whether `name` is a stable input name, the 16 hex digit hash the
+/// fuzzer names inputs by
+fn is_stable_name(name: &str) -> bool {
+    name.len() == 16 && name.chars().all(|x| x.is_ascii_hexdigit())
+}
+
+/// Stable name of an input with the serialized `contents` which was not
+/// named by the fuzzer, such as one renamed by external tooling
+fn content_name(contents: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Copy `contents` to `path` unless it already exists, returning whether it
+/// was copied
+pub fn copy_new(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// Export the fuzzer's inputs to the corpus directory `dir` in `format`.
+/// Inputs already in `dir` are left alone, so exports can be repeated to
+/// sync a growing corpus.
+pub fn export(dir: &Path, format: Format) -> io::Result<Transfer> {
+    let queue = format.queue(dir);
+    let meta  = queue.join(".meta");
+    fs::create_dir_all(&meta)?;
+
+    let mut transfer = Transfer::default();
+    for entry in fs::read_dir(INPUTS_DIR)? {
+        let path = entry?.path();
+        if path.extension().map(|x| x != "input").unwrap_or(true) {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|x| x.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        if copy_new(&queue.join(&name), &fs::read(&path)?)? {
+            transfer.copied += 1;
+        } else {
+            transfer.duplicates += 1;
+        }
+
+        // Provenance is optional, imported inputs may not have any
+        if let Ok(contents) = fs::read(path.with_extension("meta")) {
+            copy_new(&meta.join(&name), &contents)?;
+        }
+    }
+
+    Ok(transfer)
+}
+
+/// Import the inputs in the corpus directory `dir` in `format` into the
+/// fuzzer's inputs. Inputs are read from `dir/queue` if it exists, and from
+/// `dir` itself otherwise, so plain directories of inputs work as well.
+/// Inputs renamed by other tooling get a new name from their contents.
+pub fn import(dir: &Path, format: Format) -> io::Result<Transfer> {
+    let queue = format.queue(dir);
+    let queue = if queue.is_dir() { queue } else { dir.to_path_buf() };
+    let meta  = queue.join(".meta");
+    fs::create_dir_all(INPUTS_DIR)?;
+
+    let mut transfer = Transfer::default();
+    for entry in fs::read_dir(&queue)? {
+        let path = entry?.path();
+        let orig = match path.file_name().and_then(|x| x.to_str()) {
+            Some(name) if !name.starts_with('.') && path.is_file() => {
+                name.to_string()
+            }
+            _ => continue,
+        };
+
+        let contents = fs::read(&path)?;
+        let name = if is_stable_name(&orig) {
+            orig.clone()
+        } else {
+            content_name(&contents)
+        };
+
+        let input = Path::new(INPUTS_DIR).join(format!("{}.input", name));
+        if !copy_new(&input, &contents)? {
+            transfer.duplicates += 1;
+            continue;
+        }
+        transfer.copied += 1;
+
+        let provenance = fs::read(meta.join(&orig)).unwrap_or_else(|_| {
+            format!("imported = {}\n", path.display()).into_bytes()
+        });
+        copy_new(&input.with_extension("meta"), &provenance)?;
+    }
+
+    Ok(transfer)
+}
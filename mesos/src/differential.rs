@@ -0,0 +1,128 @@
+//! Differential fuzzing against a second build of the target. Each case is
+//! replayed against the other build and the two runs are compared for
+//! crashing differently or covering grossly different amounts of code,
+//! which points at behavior a patch changed.
+
+use std::fmt;
+use std::sync::Arc;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Ratio between the coverage of a module in the two builds above which
+/// the runs are considered divergent
+const COVERAGE_RATIO: f64 = 2.0;
+
+/// Modules with fewer blocks than this in both runs are too noisy to
+/// compare
+const MIN_BLOCKS: usize = 32;
+
+/// Name the main executable is compared under, as it usually has a
+/// different file name in each build
+const MAIN_MODULE: &str = "<main>";
+
+/// Number of blocks covered in each module of a run, with the main
+/// executable under `MAIN_MODULE`
+#[derive(Clone, Debug, Default)]
+pub struct CoverageShape(BTreeMap<String, usize>);
+
+impl CoverageShape {
+    /// Compute the shape of the coverage `modules` of a run of the
+    /// executable `exe`
+    pub fn new<'a, I>(exe: &str, modules: I) -> Self
+            where I: IntoIterator<Item = &'a Arc<String>> {
+        let exe = Path::new(exe).file_name()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let mut shape = BTreeMap::new();
+        for module in modules {
+            let name = if module.to_lowercase() == exe {
+                MAIN_MODULE.to_string()
+            } else {
+                module.to_string()
+            };
+            *shape.entry(name).or_insert(0) += 1;
+        }
+        CoverageShape(shape)
+    }
+
+    /// Total number of blocks covered
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+}
+
+/// How a replay against the other build differed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence {
+    /// Only one of the builds crashed, with this crash
+    Crash {
+        /// Whether the build being fuzzed is the one which crashed
+        fuzzed: bool,
+
+        /// Name of the crash
+        name: String,
+    },
+
+    /// A module covered far more in one build than the other, as
+    /// (module, blocks in the fuzzed build, blocks in the other build)
+    Coverage(String, usize, usize),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Crash { fuzzed, name } => {
+                write!(f, "only the {} build crashed: {}",
+                    if *fuzzed { "fuzzed" } else { "other" }, name)
+            }
+            Divergence::Coverage(module, fuzzed, other) => {
+                write!(f, "coverage of {} differs: {} blocks in the fuzzed \
+                    build, {} in the other", module, fuzzed, other)
+            }
+        }
+    }
+}
+
+/// Compare a run of the fuzzed build, which crashed with `crash` and
+/// covered `shape`, with a replay against the other build
+pub fn compare(crash: Option<&str>, shape: &CoverageShape,
+        other_crash: Option<&str>, other_shape: &CoverageShape)
+        -> Vec<Divergence> {
+    let mut divergences = Vec::new();
+
+    match (crash, other_crash) {
+        (Some(name), None) => divergences.push(Divergence::Crash {
+            fuzzed: true,
+            name:   name.to_string(),
+        }),
+        (None, Some(name)) => divergences.push(Divergence::Crash {
+            fuzzed: false,
+            name:   name.to_string(),
+        }),
+        _ => {}
+    }
+
+    // A crash cuts the run short, making coverage meaningless to compare
+    if crash.is_some() || other_crash.is_some() {
+        return divergences;
+    }
+
+    let modules: BTreeSet<&String> = shape.0.keys()
+        .chain(other_shape.0.keys()).collect();
+    for module in modules {
+        let fuzzed = shape.0.get(module).cloned().unwrap_or(0);
+        let other  = other_shape.0.get(module).cloned().unwrap_or(0);
+        if fuzzed.max(other) < MIN_BLOCKS {
+            continue;
+        }
+
+        let ratio = fuzzed.max(other) as f64 / fuzzed.min(other).max(1) as f64;
+        if ratio >= COVERAGE_RATIO {
+            divergences.push(Divergence::Coverage(module.clone(), fuzzed,
+                other));
+        }
+    }
+
+    divergences
+}
@@ -0,0 +1,122 @@
+//! Distillation of the crashes found into a set which can be shared with a
+//! vendor: one reproducer per crash bucket, preferring minimized ones, with
+//! its readable trace and crash details, stripped of machine-specific
+//! paths, listed in a manifest and zipped up.
+
+use std::io;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Directory the fuzzer saves crash buckets to
+const CRASHES_DIR: &str = "crashes";
+
+/// Reproducer saved by crash minimization
+const MINIMIZED: &str = "minimized.input";
+
+/// Details of a crash shared along with its reproducer, if present
+const DETAILS: &[&str] = &["trace.txt", "stack.txt", "context.txt",
+    "launch.txt", "output.txt", "window_tree.txt"];
+
+/// Environment variables holding machine-specific strings, replaced by the
+/// variable's name in everything shared. Longer values are replaced first,
+/// so user names are replaced after the profile paths they are part of.
+const MACHINE_VARS: &[&str] = &["USERPROFILE", "TEMP", "TMP", "COMPUTERNAME",
+    "USERDOMAIN", "USERNAME"];
+
+/// Replace machine-specific paths and names in `text`
+fn strip_machine(text: &str) -> String {
+    let mut values: Vec<(String, String)> = MACHINE_VARS.iter()
+        .filter_map(|var| {
+            std::env::var(var).ok().filter(|x| x.len() > 1)
+                .map(|x| (x, format!("%{}%", var)))
+        })
+        .collect();
+    if let Ok(cwd) = std::env::current_dir() {
+        values.push((cwd.display().to_string(), "%CAMPAIGN%".into()));
+    }
+    values.sort_by_key(|x| std::cmp::Reverse(x.0.len()));
+
+    values.iter().fold(text.to_string(), |text, (value, name)| {
+        text.replace(value.as_str(), name)
+    })
+}
+
+/// Get the reproducer of the crash bucket at `bucket` to share, the
+/// minimized one if the crash was minimized and the shortest one otherwise
+fn pick_reproducer(bucket: &Path) -> io::Result<Option<PathBuf>> {
+    let minimized = bucket.join(MINIMIZED);
+    if minimized.is_file() {
+        return Ok(Some(minimized));
+    }
+
+    let mut shortest: Option<(u64, PathBuf)> = None;
+    for entry in fs::read_dir(bucket)? {
+        let path = entry?.path();
+        if path.extension().map(|x| x != "input").unwrap_or(true) {
+            continue;
+        }
+        let len = fs::metadata(&path)?.len();
+        if shortest.as_ref().map(|x| len < x.0).unwrap_or(true) {
+            shortest = Some((len, path));
+        }
+    }
+    Ok(shortest.map(|x| x.1))
+}
+
+/// Distill the crash buckets of the campaign into the directory `out` and
+/// zip it up into `out` with a `.zip` extension. Returns the number of
+/// crash buckets distilled.
+pub fn distill(out: &Path) -> io::Result<usize> {
+    let mut manifest = String::from("# file, size, hash\n");
+    let mut buckets = 0;
+    fs::create_dir_all(out)?;
+
+    for entry in fs::read_dir(CRASHES_DIR)? {
+        let bucket = entry?.path();
+        let name = match bucket.file_name() {
+            Some(name) if bucket.is_dir() => name.to_owned(),
+            _ => continue,
+        };
+        let reproducer = match pick_reproducer(&bucket)? {
+            Some(reproducer) => reproducer,
+            None => continue,
+        };
+
+        let dest = out.join(&name);
+        fs::create_dir_all(&dest)?;
+        buckets += 1;
+
+        let files = std::iter::once((reproducer, "reproducer.input"))
+            .chain(DETAILS.iter().map(|x| (bucket.join(x), *x)));
+        for (from, to) in files {
+            let contents = match fs::read_to_string(&from) {
+                Ok(contents) => strip_machine(&contents),
+                Err(_) => continue,
+            };
+            fs::write(dest.join(to), &contents)?;
+
+            let mut hasher = DefaultHasher::new();
+            contents.hash(&mut hasher);
+            manifest += &format!("{}/{}, {}, {:016x}\n",
+                name.to_string_lossy(), to, contents.len(), hasher.finish());
+        }
+    }
+
+    fs::write(out.join("MANIFEST.txt"), manifest)?;
+
+    // The `tar` shipped with Windows writes zip files given `-a`
+    let zip = out.with_extension("zip");
+    let status = Command::new("tar.exe")
+        .arg("-a").arg("-c").arg("-f").arg(&zip)
+        .arg("-C").arg(out).arg(".")
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(
+            format!("tar.exe failed to create {}", zip.display())));
+    }
+
+    Ok(buckets)
+}
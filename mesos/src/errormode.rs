@@ -0,0 +1,123 @@
+//! Suppression of Windows Error Reporting "application has stopped working"
+//! dialogs for the duration of a fuzzing campaign
+
+use std::process::Command;
+
+/// Do not display the critical-error-handler message box
+const SEM_FAILCRITICALERRORS: u32 = 0x0001;
+
+/// Do not display the Windows Error Reporting dialog
+const SEM_NOGPFAULTERRORBOX: u32 = 0x0002;
+
+/// Registry key holding the per-user WER policy
+const WER_KEY: &str =
+    r"HKEY_CURRENT_USER\Software\Microsoft\Windows\Windows Error Reporting";
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn SetErrorMode(mode: u32) -> u32;
+}
+
+#[link(name="Wer")]
+extern "system" {
+    fn WerAddExcludedApplication(exe_name: *const u16, all_users: i32) -> i32;
+    fn WerRemoveExcludedApplication(exe_name: *const u16, all_users: i32)
+        -> i32;
+}
+
+/// Convert a Rust UTF-8 `string` into a NUL-terminated UTF-16 vector
+fn str_to_utf16(string: &str) -> Vec<u16> {
+    let mut ret: Vec<u16> = string.encode_utf16().collect();
+    ret.push(0);
+    ret
+}
+
+/// Query the current `DontShowUI` WER policy value, `None` if it is not set
+fn query_dontshowui() -> Option<String> {
+    let output = Command::new("reg.exe").args([
+        "query", WER_KEY, "/v", "DontShowUI",
+    ]).output().ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    // Output looks like "    DontShowUI    REG_DWORD    0x1"
+    let output = String::from_utf8_lossy(&output.stdout);
+    output.lines()
+        .filter(|line| line.trim_start().starts_with("DontShowUI"))
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .map(|value| value.to_string())
+        .next()
+}
+
+/// Set the `DontShowUI` WER policy value to `value`
+fn set_dontshowui(value: &str) {
+    let _ = Command::new("reg.exe").args([
+        "add", WER_KEY, "/v", "DontShowUI", "/t", "REG_DWORD",
+        "/d", value, "/f",
+    ]).output();
+}
+
+/// Holds the original error mode and WER settings, restoring them when
+/// dropped. The settings persist if the process is killed, so the guard has
+/// to be dropped on every way a campaign ends, including Ctrl-C.
+pub struct ErrorModeGuard {
+    /// Error mode of the harness before we changed it
+    orig_mode: u32,
+
+    /// Original `DontShowUI` value, `None` if it was not present
+    orig_dontshowui: Option<String>,
+
+    /// UTF-16 executable names we excluded from WER
+    excluded: Vec<Vec<u16>>,
+}
+
+impl ErrorModeGuard {
+    /// Suppress crash dialogs for this process, all targets it spawns, and
+    /// the executables in `exe_names`
+    pub fn new(exe_names: &[&str]) -> Self {
+        // The error mode is inherited by all processes we create
+        let orig_mode = unsafe {
+            SetErrorMode(SEM_FAILCRITICALERRORS | SEM_NOGPFAULTERRORBOX)
+        };
+
+        // Disable the WER UI for the user, saving the old policy
+        let orig_dontshowui = query_dontshowui();
+        set_dontshowui("1");
+
+        // Exclude the targets from WER entirely
+        let mut excluded = Vec::new();
+        for exe_name in exe_names {
+            let exe_name = str_to_utf16(exe_name);
+            if unsafe { WerAddExcludedApplication(exe_name.as_ptr(), 0) } == 0 {
+                excluded.push(exe_name);
+            }
+        }
+
+        ErrorModeGuard { orig_mode, orig_dontshowui, excluded }
+    }
+}
+
+impl Drop for ErrorModeGuard {
+    fn drop(&mut self) {
+        unsafe {
+            // Remove the WER exclusions we added
+            for exe_name in self.excluded.iter() {
+                WerRemoveExcludedApplication(exe_name.as_ptr(), 0);
+            }
+
+            // Restore the original error mode
+            SetErrorMode(self.orig_mode);
+        }
+
+        // Restore the original WER policy
+        if let Some(value) = &self.orig_dontshowui {
+            set_dontshowui(value);
+        } else {
+            let _ = Command::new("reg.exe").args([
+                "delete", WER_KEY, "/v", "DontShowUI", "/f",
+            ]).output();
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Host resource guardrails for long unattended campaigns
+
+use std::io;
+use std::sync::{Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, Ordering};
+use guifuzz::Config;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetDiskFreeSpaceExW(directory: *const u16, free_to_caller: *mut u64,
+        total: *mut u64, total_free: *mut u64) -> i32;
+}
+
+/// Get the number of bytes free on the disk holding the current directory
+pub fn free_disk_space() -> io::Result<u64> {
+    let dir: Vec<u16> = ".\0".encode_utf16().collect();
+    let mut free = 0u64;
+
+    unsafe {
+        if GetDiskFreeSpaceExW(dir.as_ptr(), &mut free,
+                std::ptr::null_mut(), std::ptr::null_mut()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(free)
+}
+
+/// Enforces limits on the number of live targets and pauses the recording of
+/// large artifacts when the disk is running low
+pub struct Guardrails {
+    /// Number of target processes currently alive
+    live_targets: Mutex<usize>,
+
+    /// Signalled when a target slot is released
+    slot_released: Condvar,
+
+    /// Maximum number of live target processes
+    max_targets: Option<usize>,
+
+    /// Minimum free disk space in bytes before artifacts are paused
+    min_free_disk: u64,
+
+    /// Set when the disk is low and large artifacts should not be saved
+    artifacts_paused: AtomicBool,
+}
+
+/// A reservation for one live target process, released on drop
+pub struct TargetSlot<'a>(&'a Guardrails);
+
+impl<'a> Drop for TargetSlot<'a> {
+    fn drop(&mut self) {
+        *self.0.live_targets.lock().unwrap() -= 1;
+        self.0.slot_released.notify_one();
+    }
+}
+
+impl Guardrails {
+    /// Create new guardrails based on the limits in `config`
+    pub fn new(config: &Config) -> Self {
+        Guardrails {
+            live_targets:     Mutex::new(0),
+            slot_released:    Condvar::new(),
+            max_targets:      config.max_targets,
+            min_free_disk:    config.min_free_disk_mb * 1024 * 1024,
+            artifacts_paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Reserve a slot for a new target process, blocking until one is free
+    pub fn acquire_target(&self) -> TargetSlot<'_> {
+        let mut live = self.live_targets.lock().unwrap();
+        while self.max_targets.map(|max| *live >= max).unwrap_or(false) {
+            live = self.slot_released.wait(live).unwrap();
+        }
+        *live += 1;
+
+        TargetSlot(self)
+    }
+
+    /// Check the free disk space, pausing or resuming artifact recording.
+    /// Returns a warning to display if any limits are hit.
+    pub fn check(&self) -> Option<String> {
+        let free = free_disk_space().unwrap_or(!0);
+        let low  = free < self.min_free_disk;
+        self.artifacts_paused.store(low, Ordering::SeqCst);
+
+        if low {
+            Some(format!("WARNING: only {} MiB of disk free, crash dumps \
+                          are paused", free / 1024 / 1024))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if large artifacts (such as crash dumps) should not be
+    /// saved
+    pub fn artifacts_paused(&self) -> bool {
+        self.artifacts_paused.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,193 @@
+//! Launching the target with fuzzed command lines or through shell verbs
+//! rather than a plain spawn, exercising the parsing in its startup path
+
+use std::io;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use guifuzz::{Config, Rng};
+
+#[link(name="Shell32")]
+extern "system" {
+    fn ShellExecuteExW(info: *mut ShellExecuteInfo) -> i32;
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn GetProcessId(process: usize) -> u32;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+/// `SEE_MASK_NOCLOSEPROCESS`, return a handle to the started process
+const SEE_MASK_NOCLOSEPROCESS: u32 = 0x0040;
+
+/// `SEE_MASK_FLAG_NO_UI`, don't show error dialogs
+const SEE_MASK_FLAG_NO_UI: u32 = 0x0400;
+
+/// `SW_SHOWNORMAL`
+const SW_SHOWNORMAL: i32 = 1;
+
+/// Shell verbs to launch files with
+const VERBS: &[&str] = &["open", "print", "edit"];
+
+/// Arguments many programs parse specially
+const SWITCHES: &[&str] = &[
+    "/p", "/pt", "/s", "/?", "-", "--", "/dde", "-embedding", "/automation",
+    "/n", "/safe", "/regserver", "/unregserver",
+];
+
+/// Odd arguments which stress argument parsing
+const ODD_ARGS: &[&str] = &[
+    "\"", "\"\"", "%s%s%s%n", "%TEMP%", "..\\..\\..\\..\\..\\..\\",
+    "\\\\?\\C:\\", "\\\\.\\pipe\\x", "CON", "NUL:", "C:", "*", "?", "|",
+    "^", "=", "-=-=-", "/:", "/\"",
+];
+
+/// How a target is launched
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Launch {
+    /// Spawned without any arguments
+    Plain,
+
+    /// Spawned with these fuzzed arguments
+    CommandLine(Vec<String>),
+
+    /// A file opened with a shell verb, started by whatever program is
+    /// associated with the file
+    ShellVerb {
+        /// Verb, such as `open` or `print`
+        verb: &'static str,
+
+        /// File the verb is invoked on
+        file: PathBuf,
+    },
+}
+
+impl fmt::Display for Launch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Launch::Plain => write!(f, "plain"),
+            Launch::CommandLine(args) => {
+                write!(f, "command line {}", args.join(" "))
+            }
+            Launch::ShellVerb { verb, file } => {
+                write!(f, "shell verb {} {}", verb, file.display())
+            }
+        }
+    }
+}
+
+/// `SHELLEXECUTEINFOW`
+#[repr(C)]
+struct ShellExecuteInfo {
+    size:       u32,
+    mask:       u32,
+    hwnd:       usize,
+    verb:       *const u16,
+    file:       *const u16,
+    parameters: *const u16,
+    directory:  *const u16,
+    show:       i32,
+    inst_app:   usize,
+    id_list:    usize,
+    class:      *const u16,
+    hkey_class: usize,
+    hot_key:    u32,
+    icon:       usize,
+    process:    usize,
+}
+
+/// Convert a Rust UTF-8 `string` into a NUL-terminated UTF-16 vector
+fn str_to_utf16(string: &str) -> Vec<u16> {
+    let mut ret: Vec<u16> = string.encode_utf16().collect();
+    ret.push(0);
+    ret
+}
+
+/// List the files in the directory `dir` which can be launched with
+fn launch_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Pick how to launch the target for a case. Most cases use a plain spawn,
+/// launch fuzzing is only done occasionally if enabled.
+pub fn pick(rng: &Rng, config: &Config) -> Launch {
+    if !config.launch_fuzzing || rng.rand() & 0xf != 0 {
+        return Launch::Plain;
+    }
+
+    let files = config.launch_files.as_ref()
+        .and_then(|dir| launch_files(Path::new(dir)).ok())
+        .unwrap_or_default();
+    let files = &files[..];
+
+    if !files.is_empty() && rng.rand() & 1 == 0 {
+        Launch::ShellVerb {
+            verb: VERBS[rng.rand() % VERBS.len()],
+            file: files[rng.rand() % files.len()].clone(),
+        }
+    } else {
+        Launch::CommandLine(fuzzed_args(rng, files))
+    }
+}
+
+/// Build between 1 and 4 fuzzed command line arguments, drawing on `files`
+fn fuzzed_args(rng: &Rng, files: &[PathBuf]) -> Vec<String> {
+    (0..rng.rand() % 4 + 1).map(|_| match rng.rand() % 5 {
+        0 if !files.is_empty() => {
+            format!("\"{}\"", files[rng.rand() % files.len()].display())
+        }
+        1 => SWITCHES[rng.rand() % SWITCHES.len()].to_string(),
+        2 => "A".repeat(rng.rand() % 0x1000 + 1),
+        3 => format!("/{}:{}", (b'a' + (rng.rand() % 26) as u8) as char,
+            rng.rand() as i32),
+        _ => ODD_ARGS[rng.rand() % ODD_ARGS.len()].to_string(),
+    }).collect()
+}
+
+/// Invoke the shell `verb` on `file`, returning the PID of the process
+/// which was started. Fails if no new process was started, such as when
+/// an already running instance handled the request.
+pub fn shell_execute(verb: &str, file: &Path) -> io::Result<u32> {
+    let verb = str_to_utf16(verb);
+    let file = str_to_utf16(&file.to_string_lossy());
+
+    let mut info = ShellExecuteInfo {
+        size:       std::mem::size_of::<ShellExecuteInfo>() as u32,
+        mask:       SEE_MASK_NOCLOSEPROCESS | SEE_MASK_FLAG_NO_UI,
+        hwnd:       0,
+        verb:       verb.as_ptr(),
+        file:       file.as_ptr(),
+        parameters: std::ptr::null(),
+        directory:  std::ptr::null(),
+        show:       SW_SHOWNORMAL,
+        inst_app:   0,
+        id_list:    0,
+        class:      std::ptr::null(),
+        hkey_class: 0,
+        hot_key:    0,
+        icon:       0,
+        process:    0,
+    };
+
+    unsafe {
+        if ShellExecuteExW(&mut info) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if info.process == 0 {
+            return Err(io::Error::other(
+                "Shell verb did not start a new process"));
+        }
+
+        let pid = GetProcessId(info.process);
+        CloseHandle(info.process);
+        Ok(pid)
+    }
+}
@@ -2,208 +2,2187 @@ extern crate debugger;
 extern crate guifuzz;
 
 pub mod mesofile;
+pub mod errormode;
+pub mod guardrails;
+pub mod statlog;
+pub mod notify;
+pub mod target;
+pub mod oracle;
+pub mod console;
+pub mod launch;
+pub mod corpusdir;
+pub mod differential;
+pub mod statsmerge;
+pub mod distill;
+pub mod companions;
+pub mod bundle;
+pub mod server;
+pub mod affinity;
+pub mod priority;
+pub mod recorder;
+pub mod smoke;
+pub mod abtest;
 
+use std::any::Any;
 use std::path::Path;
-use std::process::Command;
-use std::collections::{HashMap};
-use std::sync::{Arc, Mutex};
-use std::fs::File;
+use std::error::Error;
 use std::io::Write;
-use std::time::{Instant, Duration};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Instant, Duration, SystemTime, UNIX_EPOCH};
+use std::collections::{HashSet, BTreeSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
-use debugger::{ExitType, Debugger};
 use guifuzz::*;
+use debugger::ExitType;
+use guardrails::Guardrails;
+use statlog::{StatLog, Sample};
+use notify::Notifier;
+use target::{TargetInstance, SuspendedCrash};
+use oracle::{BugOracle, CaseResult, Observations};
+use console::{Console, Kind};
+use launch::Launch;
 
-fn record_input(fuzz_input: FuzzInput) {
+/// Number of worker threads, each fuzzing its own target instance
+const NUM_WORKERS: usize = 10;
+
+/// How long to wait for the target to show its main window before giving
+/// up on a case, longer than the debugger lets targets live
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Action index coverage is attributed to before any action was performed
+const BEFORE_ACTIONS: usize = !0;
+
+/// How often action failure rates are shown
+const ACTION_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the statistics chart is re-rendered
+const CHART_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the snapshot is saved during a campaign, so a campaign which is
+/// killed can still be resumed
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Compute the file name used to save `fuzz_input`
+fn input_filename(fuzz_input: &FuzzInput) -> String {
     let mut hasher = DefaultHasher::new();
     fuzz_input.hash(&mut hasher);
+    format!("{:016x}.input", hasher.finish())
+}
 
+/// Save `fuzz_input` to the inputs directory, with its `provenance` saved
+/// alongside it in a `.meta` file, and an encoding it can be loaded back
+/// from in a `.bytes` file unless it can't be encoded
+fn record_input(fuzz_input: FuzzInput, provenance: &Provenance) {
     let _ = std::fs::create_dir("inputs");
-    std::fs::write(format!("inputs/{:016x}.input", hasher.finish()),
+    let filename = input_filename(&fuzz_input);
+    std::fs::write(format!("inputs/{}", filename),
         format!("{:#?}", fuzz_input)).expect("Failed to save input to disk");
+
+    let meta = filename.trim_end_matches(".input");
+    if let Ok(bytes) = bytecodec::encode(&fuzz_input) {
+        std::fs::write(format!("inputs/{}.bytes", meta), bytes)
+            .expect("Failed to save input encoding to disk");
+    }
+    #[cfg(feature = "serde")]
+    std::fs::write(format!("inputs/{}.json", meta),
+        snapshot::input_to_json(&fuzz_input))
+        .expect("Failed to save input JSON to disk");
+
+    let parent = provenance.parent.as_ref().map(input_filename)
+        .unwrap_or("none".into());
+    std::fs::write(format!("inputs/{}.meta", meta),
+        format!("parent = {}\nmutations = {}\nseed = {:#x}\n\
+                 unix_time = {}\nworker = {}\nlaunch = {}\n\
+                 timed_out = {}\ncoverage_actions = {}\n",
+            parent, provenance.mutations.join(","), provenance.seed,
+            provenance.unix_time,
+            provenance.worker.map(|x| x.to_string())
+                .unwrap_or("none".into()),
+            provenance.launch.as_deref()
+                .unwrap_or("plain"),
+            provenance.timed_out.map(|x| x.to_string())
+                .unwrap_or("none".into()),
+            provenance.coverage_actions.iter().map(|x| x.to_string())
+                .collect::<Vec<_>>().join(",")))
+        .expect("Failed to save input provenance to disk");
+
+    events::emit(FuzzEvent::NewInput {
+        input:  fuzz_input,
+        worker: provenance.worker,
+    });
 }
 
-fn worker(stats: Arc<Mutex<Statistics>>) {
-    // Local stats database
-    let mut local_stats = Statistics::default();
+/// Save the UI state diff `diff` caused by `fuzz_input` along with the
+/// input, unless it changed nothing
+fn record_state_diff(fuzz_input: &FuzzInput, diff: &StateDiff) {
+    if diff.is_empty() {
+        return;
+    }
 
-    // Create an RNG for this thread
-    let rng = Rng::new();
+    let filename = input_filename(fuzz_input);
+    let meta = filename.trim_end_matches(".input");
+    let _ = std::fs::write(format!("inputs/{}.state", meta),
+        format!("size = {}\n{}", diff.size(), diff));
+}
+
+/// Report the `companions` processes killed during the case of
+/// `fuzz_input` and append them to `companions.txt`, with the action which
+/// launched each
+fn record_companions(fuzz_input: &FuzzInput,
+        companions: &[companions::Companion], console: &Console,
+        worker_id: usize) {
+    let mut lines = String::new();
+    for companion in companions {
+        let action = companion.action
+            .and_then(|idx| fuzz_input.get(idx).map(|x| (idx, x)))
+            .map(|(idx, x)| format!("action {} {:?}", idx, x))
+            .unwrap_or("unknown action".into());
+        console.info(&format!("Worker {} killed {} (pid {}) launched by {}",
+            worker_id, companion.name, companion.pid, action));
+        lines += &format!("{} {} {}\n", input_filename(fuzz_input),
+            companion.name, action);
+    }
+
+    let file = std::fs::OpenOptions::new().create(true).append(true)
+        .open("companions.txt");
+    if let Ok(mut file) = file {
+        let _ = file.write_all(lines.as_bytes());
+    }
+}
+
+/// Save `fuzz_input` as a reproducer for the crash bucket `crashname`,
+/// with an encoding the `replay` subcommand can load in a `.bytes` file
+/// unless it can't be encoded, and the tail of the target's `output` while
+/// running it in a `.output` file, deleting the reproducer `evicted` from
+/// the bucket if there is one
+fn record_crash_input(crashname: &str, fuzz_input: &FuzzInput,
+        output: &[String], evicted: Option<&FuzzInput>) {
+    let dir = Path::new("crashes").join(crashname);
+    let _ = std::fs::create_dir_all(&dir);
+
+    if let Some(evicted) = evicted {
+        let path = dir.join(input_filename(evicted));
+        let _ = std::fs::remove_file(path.with_extension("bytes"));
+        let _ = std::fs::remove_file(path.with_extension("output"));
+        #[cfg(feature = "serde")]
+        let _ = std::fs::remove_file(path.with_extension("json"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    let path = dir.join(input_filename(fuzz_input));
+    std::fs::write(&path, format!("{:#?}", fuzz_input))
+        .expect("Failed to save crash to disk");
+    if let Ok(bytes) = bytecodec::encode(fuzz_input) {
+        std::fs::write(path.with_extension("bytes"), bytes)
+            .expect("Failed to save crash encoding to disk");
+    }
+    if !output.is_empty() {
+        let _ = std::fs::write(path.with_extension("output"),
+            output.join("\n") + "\n");
+    }
+    #[cfg(feature = "serde")]
+    std::fs::write(path.with_extension("json"),
+        snapshot::input_to_json(fuzz_input))
+        .expect("Failed to save crash JSON to disk");
+}
+
+/// Load what cases depend on besides their input: the target, prologue and
+/// epilogue configured in `config`, the latter two performed at the start
+/// and after the end of every case, and the menu sets seen by earlier runs
+fn load_case_setup(config: &Config) -> Result<(), Box<dyn Error>> {
+    TargetConfig::from_config(config).set_global();
+    if let Some(path) = config.prologue.as_ref() {
+        let prologue = bytecodec::decode(&std::fs::read(path)
+            .map_err(|err| format!("Failed to read prologue {}: {}", path,
+                err))?);
+        set_prologue(prologue);
+    }
+    if let Some(path) = config.epilogue.as_ref() {
+        let epilogue = bytecodec::decode(&std::fs::read(path)
+            .map_err(|err| format!("Failed to read epilogue {}: {}", path,
+                err))?);
+        set_epilogue(epilogue);
+    }
+    menusets::persist(Path::new("menusets.txt"))
+        .map_err(|err| format!("Failed to load menu sets: {}", err))?;
+    Ok(())
+}
+
+/// Get a printable message from the payload of a panic
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        format!("panicked: {}", msg)
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        format!("panicked: {}", msg)
+    } else {
+        "panicked".into()
+    }
+}
+
+/// State shared by every fuzz case a worker runs
+struct CaseContext<'a> {
+    /// Global stats database
+    stats: &'a Arc<Mutex<Statistics>>,
+
+    /// Resources of the target
+    resources: &'a Arc<Resources>,
+
+    /// Fuzzer configuration
+    config: &'a Arc<Config>,
+
+    /// Limits on live targets and resource usage
+    guardrails: &'a Guardrails,
+
+    /// Oracles checked after each case
+    oracles: &'a [Box<dyn BugOracle>],
+
+    /// Where status messages go
+    console: &'a Console,
+
+    /// The application to fuzz
+    target_config: &'a TargetConfig,
+
+    /// Index of the worker running the cases
+    worker_id: usize,
+}
 
-    loop {
-        // Delete all state invoked with the calc.exe process
-        Command::new("reg.exe").args(&[
-            "delete",
-            r"HKEY_CURRENT_USER\Software\Microsoft\Calc",
-            "/f",
-        ]).output().unwrap();
+/// Run a single fuzz case against a new target instance
+fn fuzz_case(context: &CaseContext, local_stats: &mut Statistics, rng: &Rng)
+        -> Result<(), Box<dyn Error>> {
+    let &CaseContext { stats, resources, config, guardrails, oracles,
+        console, target_config, worker_id } = context;
 
-        std::thread::sleep(Duration::from_millis(rng.rand() as u64 % 500));
+    // Delete all state persisted by earlier runs of the target
+    target_config.reset()?;
 
-        // Create a new calc instance
-        let mut dbg = Debugger::spawn_proc(&["calc.exe".into()], false);
+    std::thread::sleep(Duration::from_millis(rng.rand() as u64 % 500));
 
-        // Load the meso
-        mesofile::load_meso(&mut dbg, Path::new("calc.exe.meso"));
+    // Wait until we're allowed to have another live target
+    let slot = guardrails.acquire_target();
 
-        // Spin up the fuzzer thread
-        let pid = dbg.pid;
-        let thr = {
-            let generate = (rng.rand() & 0x7) == 0;
-            let stats = stats.clone();
+    // Create a new target instance, occasionally with a fuzzed command line
+    // or through a shell verb
+    let launch = launch::pick(rng, config);
+    let mut target = match &launch {
+        Launch::Plain => TargetInstance::spawn(&target_config.command_line),
+        Launch::CommandLine(args) => {
+            TargetInstance::spawn(&target_config.command_line_with(args))
+        }
+        Launch::ShellVerb { verb, file } => {
+            TargetInstance::attach(launch::shell_execute(verb, file)?)
+        }
+    };
+
+    // Don't save crash dumps if we're low on disk
+    target.dbg().set_minidumps(!guardrails.artifacts_paused());
+
+    // Load the meso
+    mesofile::load_meso(target.dbg(), Path::new(&target_config.meso));
+
+    // Attribute coverage to the action which was being performed
+    let action_counter = Arc::new(AtomicUsize::new(BEFORE_ACTIONS));
+    if config.action_coverage {
+        target.dbg().set_action_counter(action_counter.clone());
+    }
+
+    // Spin up the fuzzer thread
+    let pid = target.pid;
+    #[cfg(feature = "chaos")]
+    chaos::kill_later(pid);
+    let thr = {
+        let generate = (rng.rand() & 0x7) == 0;
+        let stats = stats.clone();
+        let resources = resources.clone();
+        let windows = target.windows();
+        let config = config.clone();
+        let target_config = target_config.clone();
+        let action_counter = action_counter.clone();
 
-            std::thread::spawn(move || {
-                while Window::attach_pid(pid, "Calculator").is_err() {
-                    std::thread::sleep(Duration::from_millis(200));
+        // Favor this worker's corpus shard if enabled
+        let shard = if config.shard_corpus {
+            Some((worker_id, NUM_WORKERS))
+        } else {
+            None
+        };
+
+        std::thread::spawn(move || {
+            audit::set_worker(Some(worker_id));
+
+            // Give up on targets which never show their window, such as
+            // when a fuzzed command line makes them exit right away
+            let attach_start = Instant::now();
+            let window = loop {
+                if let Ok(window) = target_config.attach(pid) {
+                    break window;
+                }
+                if attach_start.elapsed() >= ATTACH_TIMEOUT {
+                    return (Vec::new(), Duration::from_secs(0),
+                        Provenance::default(), Observations::default(),
+                        actionstats::take());
                 }
+                std::thread::sleep(Pacing::global().attach_poll);
+            };
+            windows.lock().unwrap().push(window);
+
+            // Target-specific setup from plugins
+            for plugin in plugin::plugins() {
+                plugin.setup(pid, &window);
+            }
+
+            // Bring the target to the UI state cases start from
+            let _ = perform_prologue(pid, InputBackend::PostMessage);
 
-                if generate || stats.lock().unwrap().input_db.len() == 0 {
-                    generator(pid).unwrap_or(Vec::new())
-                } else {
-                    let mutated = mutate(stats).unwrap_or(Vec::new());
-                    let _ = perform_actions(pid, &mutated);
-                    mutated
+            // UI state before the actions, to see what they changed
+            let initial_tree = if config.window_tree {
+                Some(window.dump_tree())
+            } else {
+                None
+            };
+            let initial_state = if config.ui_state {
+                Some(UiState::capture(&window))
+            } else {
+                None
+            };
+
+            // Time the execution of the actions
+            let start = Instant::now();
+
+            let mut provenance = Provenance::default();
+            let actions = if generate {
+                generator(pid, &resources, &config, &mut provenance)
+                    .unwrap_or_default()
+            } else {
+                match mutate(stats, shard, &mut provenance) {
+                    Ok(mutated) => {
+                        let tracked = config.action_coverage ||
+                            config.suppress_companions;
+                        let result = if tracked {
+                            perform_actions_tracked(pid, &mutated,
+                                &action_counter)
+                        } else {
+                            perform_actions(pid, &mutated)
+                        };
+                        if let Err(err) = result {
+                            provenance.timed_out = err
+                                .downcast_ref::<ActionTimeout>()
+                                .map(|x| x.index);
+                        }
+                        mutated
+                    }
+                    Err(ref err) if err.is::<NeedsGeneration>() => {
+                        // Nothing to mutate yet, generate instead
+                        provenance = Provenance::default();
+                        generator(pid, &resources, &config,
+                            &mut provenance).unwrap_or_default()
+                    }
+                    Err(_) => Vec::new(),
                 }
-            })
+            };
+
+            let elapsed = start.elapsed();
+
+            // Look for bugs which don't crash the target while it's alive
+            let observations = Observations::observe(pid, &window, &config,
+                initial_tree.as_ref(), initial_state.as_ref());
+
+            (actions, elapsed, provenance, observations, actionstats::take())
+        })
+    };
+
+    // Kill browsers and such the case launches, attributing them to the
+    // action being performed
+    let suppressor = if config.suppress_companions {
+        Some(companions::Suppressor::start(action_counter.clone(),
+            BEFORE_ACTIONS)?)
+    } else {
+        None
+    };
+
+    // Count the windows the target creates, including transient ones
+    let window_counter = if config.activity_novelty.is_some() {
+        activity::WindowCounter::start(pid)
+    } else {
+        None
+    };
+
+    // Record the target's window to see what led up to a bug
+    let recorder = config.capture_seconds.map(|seconds| {
+        recorder::Recorder::start(target.windows(), config.capture_fps,
+            seconds)
+    });
+
+    // Debug forever
+    let exit_state = target.run();
+    let recording = recorder.map(|x| x.finish()).unwrap_or_default();
+    let windows_created = window_counter.map(|x| x.finish());
+
+    // The target is frozen at its crash until it is torn down, so its
+    // windows still show the UI state the crash happened in
+    let crash_tree = match &exit_state {
+        ExitType::Crash(_) if config.window_tree => {
+            target.windows().lock().unwrap().first().map(|x| x.dump_tree())
+        }
+        _ => None,
+    };
+
+    // Keep new crashes alive for debugging, the fuzzer thread has to stop
+    // acting on them as their windows never go away
+    if let ExitType::Crash(name) = &exit_state {
+        if config.keep_crashes > 0 {
+            let bucket = target.crash_bucket(name);
+            keep_crash_alive(&mut target, &bucket, stats, config, console);
+            abandon_target(pid, true);
+        }
+    }
+
+    // Tear down the target, taking the coverage, the actions it was
+    // attributed to, crash call stack, output, and crash machine state
+    let coverage_actions = std::mem::take(&mut target.dbg().coverage_actions);
+    let (coverage, crash_stack, output, crash_info) = target.finish();
+    let crash_bucket = match &exit_state {
+        ExitType::Crash(name) => {
+            Some(triage::bucket(name, crash_info.as_ref(), &crash_stack))
+        }
+        _ => None,
+    };
+
+    // The target is gone, let another one start
+    drop(slot);
+
+    // Target-specific teardown from plugins
+    for plugin in plugin::plugins() {
+        plugin.teardown(pid);
+    }
+
+    // Connect to the fuzzer thread and get the result
+    let joined = thr.join();
+    abandon_target(pid, false);
+
+    // Only targets whose crash is kept alive may outlive their case
+    #[cfg(feature = "chaos")]
+    {
+        let kept = if let ExitType::Crash(_) = exit_state {
+            config.keep_crashes > 0
+        } else {
+            false
         };
+        if !kept {
+            chaos::check_exited(pid);
+        }
+    }
+    let companions = suppressor.map(|x| x.finish()).unwrap_or_default();
+    let (genres, cost, mut provenance, observations, action_stats) = joined
+        .map_err(|err| format!("Fuzzer thread {}", panic_message(&*err)))?;
+    provenance.worker    = Some(worker_id);
+    provenance.launch    = if launch == Launch::Plain {
+        None
+    } else {
+        Some(launch.to_string())
+    };
+    provenance.unix_time = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs()).unwrap_or(0);
+
+    // Wrap up the fuzz input in an `Arc`
+    let fuzz_input = Arc::new(genres);
+
+    if !companions.is_empty() {
+        record_companions(&fuzz_input, &companions, console, worker_id);
+    }
+
+    // Get the coverage keys of all coverage entries, as (PC, key, index).
+    // All entries of a module share its name's `Arc`, so each module is
+    // only interned once.
+    let mut modules: Vec<(&Arc<String>, ModuleId)> = Vec::new();
+    let keys: Vec<(usize, CoverageKey, usize)> = coverage.iter()
+        .map(|(&pc, (module, offset, _, _))| {
+            let id = match modules.iter().find(|x| Arc::ptr_eq(x.0, module)) {
+                Some(&(_, id)) => id,
+                None => {
+                    let id = intern_module(module);
+                    modules.push((module, id));
+                    id
+                }
+            };
+            let key = (id, *offset);
+            (pc, key, coverage_index(&key))
+        })
+        .collect();
 
-        // Debug forever
-        let exit_state = dbg.run();
+    // Pinpoint the actions which found globally new coverage
+    if config.action_coverage {
+        let stats = stats.lock().unwrap();
+        let actions: BTreeSet<usize> = keys.iter()
+            .filter(|(_, _, idx)| !stats.coverage_db.contains_index(*idx))
+            .filter_map(|(pc, _, _)| coverage_actions.get(pc).cloned())
+            .filter(|&action| action != BEFORE_ACTIONS)
+            .collect();
+        provenance.coverage_actions = actions.into_iter().collect();
+    }
 
-        // Extra-kill the debuggee
-        let _ = dbg.kill();
+    // Tracks if this case found any globally new coverage
+    let mut new_coverage = false;
 
-        // Swap coverage with the debugger and drop it so that the debugger
-        // disconnects its resources from the debuggee so it can exit
-        let mut coverage = HashMap::new();
-        std::mem::swap(&mut dbg.coverage, &mut coverage);
-        std::mem::drop(dbg);
+    // Coverage entries this worker has never seen before
+    let local_new: Vec<(usize, CoverageKey)> = keys.iter()
+        .filter(|(_, _, idx)| !local_stats.coverage_db.contains_index(*idx))
+        .map(|&(_, key, idx)| (idx, key))
+        .collect();
 
-        // Connect to the fuzzer thread and get the result
-        let genres = thr.join();
-        if genres.is_err() {
-            continue;
+    if !local_new.is_empty() {
+        // Save the fuzz input in the input database, and update the
+        // module+offset in the coverage database to reflect that this input
+        // caused this coverage to occur
+        local_stats.corpus.insert(&fuzz_input, cost, provenance.clone());
+        for &(idx, key) in local_new.iter() {
+            local_stats.coverage_db.insert_index(idx, key, &fuzz_input);
         }
-        let genres = genres.unwrap();
 
-        // Wrap up the fuzz input in an `Arc`
-        let fuzz_input = Arc::new(genres);
+        // Claim the entries nobody else has reached, then record them with
+        // the global stats unlocked. Workers racing on the same coverage
+        // only ever record it once.
+        let reservation = stats.lock().unwrap().reserve_coverage(&local_new,
+            &fuzz_input, cost, &provenance);
+        for key in reservation.coverage.iter() {
+            new_coverage = true;
+            console.report(Kind::NewCoverage, &format!(
+                "Worker {} found new coverage {}+{:#x}", worker_id,
+                module_name(key.0), key.1));
+            events::emit(FuzzEvent::NewCoverage {
+                module: module_name(key.0),
+                offset: key.1,
+                worker: worker_id,
+            });
+        }
+        if reservation.input_added {
+            record_input(fuzz_input.clone(), &provenance);
+        }
+    }
 
-        // Go through all coverage entries in the coverage database
-        for (_, (module, offset, _, _)) in coverage.iter() {
-            let key = (module.clone(), *offset);
+    // Save inputs which left the target's UI in a never before seen state,
+    // complementing code coverage
+    if let Some(snapshot) = observations.uia.as_ref() {
+        let mut stats = stats.lock().unwrap();
+        if stats.uia_trees.insert(snapshot.hash) && !new_coverage {
+            new_coverage = true;
+            local_stats.corpus.insert(&fuzz_input, cost, provenance.clone());
+            if stats.add_input(&fuzz_input, cost, provenance.clone()) {
+                record_input(fuzz_input.clone(), &provenance);
+            }
+        }
+    }
+
+    // Save inputs which changed the window tree into a never before seen
+    // shape
+    if let Some(tree) = observations.window_tree.as_ref() {
+        let mut stats = stats.lock().unwrap();
+        let new_shape = stats.window_trees.insert(tree.shape_hash());
+        if new_shape && !observations.ui_changes.is_empty() && !new_coverage {
+            new_coverage = true;
+            local_stats.corpus.insert(&fuzz_input, cost, provenance.clone());
+            if stats.add_input(&fuzz_input, cost, provenance.clone()) {
+                record_input(fuzz_input.clone(), &provenance);
+            }
+        }
+    }
 
-            // Check if this coverage entry is something we've never seen
-            // before
-            if !local_stats.coverage_db.contains_key(&key) {
-                // Coverage entry is new, save the fuzz input in the input
-                // database
-                local_stats.input_db.insert(fuzz_input.clone());
+    // Save inputs which made the target create windows or hold GDI or USER
+    // objects unlike other cases do
+    if let (Some(threshold), Some(windows_created), Some(resources)) =
+            (config.activity_novelty, windows_created,
+             observations.resources.as_ref()) {
+        let activity = Activity {
+            windows_created,
+            gdi_objects:  resources.gdi_objects,
+            user_objects: resources.user_objects,
+        };
+        let mut stats = stats.lock().unwrap();
+        if stats.activity.observe(&activity, threshold) && !new_coverage {
+            new_coverage = true;
+            local_stats.corpus.insert(&fuzz_input, cost, provenance.clone());
+            if stats.add_input(&fuzz_input, cost, provenance.clone()) {
+                record_input(fuzz_input.clone(), &provenance);
+            }
+        }
+    }
 
-                // Update the module+offset in the coverage database to
-                // reflect that this input caused this coverage to occur
-                local_stats.coverage_db.insert(key.clone(),
-                    fuzz_input.clone());
+    // Novelty search, save inputs which are far from the corpus even if
+    // they did not find new coverage
+    if let Some(threshold) = config.novelty_threshold {
+        if !new_coverage {
+            // Sample the corpus so we don't hold the lock for long, inputs
+            // which fail to page in are left out of the sample
+            let sample: Vec<FuzzInput> = {
+                let mut stats = stats.lock().unwrap();
+                (0..std::cmp::min(stats.corpus.len(), 64)).filter_map(|_| {
+                    let idx = rng.rand() % stats.corpus.len();
+                    stats.corpus.get(idx)
+                }).collect()
+            };
 
-                // Get access to global stats
+            let novelty = distance::novelty(&fuzz_input, &sample,
+                config.novelty_neighbors);
+            if novelty.map(|x| x >= threshold).unwrap_or(false) {
                 let mut stats = stats.lock().unwrap();
-                if !stats.coverage_db.contains_key(&key) {
-                    // Save input to global input database
-                    if stats.input_db.insert(fuzz_input.clone()) {
-                        stats.input_list.push(fuzz_input.clone());
-                
-                        record_input(fuzz_input.clone());
-
-                        // Update the action database with known-feasible
-                        // actions
-                        for &action in fuzz_input.iter() {
-                            if stats.unique_action_set.insert(action) {
-                                stats.unique_actions.push(action);
-                            }
-                        }
-                    }
-                    
-                    // Save coverage to global coverage database
-                    stats.coverage_db.insert(key.clone(), fuzz_input.clone());
+                if stats.add_input(&fuzz_input, cost, provenance.clone()) {
+                    record_input(fuzz_input.clone(), &provenance);
+                }
+            }
+        }
+    }
+
+    // Tag inputs which made it into the corpus with the areas of the UI
+    // they touched, for filtered scheduling
+    let mut input_tags = tags::action_tags(&fuzz_input);
+    input_tags.extend(observations.window_tags.iter().cloned());
+    if !input_tags.is_empty() {
+        local_stats.tag_input(&fuzz_input, &input_tags);
+        stats.lock().unwrap().tag_input(&fuzz_input, &input_tags);
+    }
+
+    // Favor inputs in the corpus which changed the UI state, and keep what
+    // they changed with them
+    if let Some(diff) = observations.state_diff.as_ref() {
+        local_stats.record_state_diff(&fuzz_input, diff);
+        if stats.lock().unwrap().record_state_diff(&fuzz_input, diff) {
+            record_state_diff(&fuzz_input, diff);
+        }
+    }
+
+    // Measure exactly which coverage new inputs contribute, as the coverage
+    // of this case includes starting the target
+    if new_coverage && config.isolate_coverage {
+        let coverage = isolated_coverage(&fuzz_input, guardrails)?;
+        record_input_coverage(&fuzz_input, &coverage);
+    }
+
+    // Get access to global stats
+    let mut stats = stats.lock().unwrap();
+
+    // Update fuzz case count
+    local_stats.fuzz_cases += 1;
+    stats.fuzz_cases += 1;
+
+    // Update action failure counts
+    local_stats.action_stats.merge(&action_stats);
+    stats.action_stats.merge(&action_stats);
+
+    // Ask all the oracles if this case found a bug
+    let result = CaseResult {
+        pid,
+        exit_state:   &exit_state,
+        crash_stack:  &crash_stack,
+        crash_info:   crash_info.as_ref(),
+        output:       &output,
+        observations,
+    };
+    if result.observations.hung {
+        events::emit(FuzzEvent::Hang {
+            input:  fuzz_input.clone(),
+            worker: worker_id,
+        });
+    }
+    let mut new_buckets = Vec::new();
+    for bug in oracles.iter().filter_map(|oracle| oracle.check(&result)) {
+        // Update crash information
+        local_stats.crashes += 1;
+        stats.crashes       += 1;
+
+        // Add the crash name and corresponding fuzz input to the crash
+        // database
+        local_stats.crash_db.insert(&bug.name, &fuzz_input,
+            bug.info.as_ref(), config.max_crash_reproducers);
+        let inserted = stats.crash_db.insert(&bug.name, &fuzz_input,
+            bug.info.as_ref(), config.max_crash_reproducers);
+        events::emit(FuzzEvent::Crash {
+            name:       bug.name.clone(),
+            input:      fuzz_input.clone(),
+            new_bucket: inserted.new_bucket,
+            worker:     worker_id,
+        });
+
+        // Only save inputs which were kept as reproducers so a shallow
+        // bug crashing constantly doesn't flood the corpus and disk
+        if inserted.kept {
+            local_stats.corpus.insert(&fuzz_input, cost, provenance.clone());
+            if stats.add_input(&fuzz_input, cost, provenance.clone()) {
+                record_input(fuzz_input.clone(), &provenance);
+            }
+
+            record_crash_input(&bug.name, &fuzz_input, &output,
+                inserted.evicted.as_ref());
+        }
+
+        // Save the details, such as the symbolized call stack, with the
+        // first bug in a bucket
+        if inserted.new_bucket {
+            new_buckets.push(bug.name.clone());
+            console.report(Kind::NewCrash,
+                &format!("Worker {} found new crash {}", worker_id, bug.name));
+            let dir = Path::new("crashes").join(&bug.name);
+            let _ = std::fs::write(dir.join("stack.txt"),
+                bug.details.join("\n") + "\n");
+
+            // Crashes in the startup path need the same launch to reproduce
+            if let Some(launch) = provenance.launch.as_ref() {
+                let _ = std::fs::write(dir.join("launch.txt"),
+                    format!("{}\n", launch));
+            }
+
+            // How the crash was triaged, and the minidump the debugger
+            // saved under its own name for it
+            if let ExitType::Crash(dump) = &exit_state {
+                let info = crash_info.as_ref()
+                    .filter(|_| crash_bucket.as_ref() == Some(&bug.name));
+                if let Some(info) = info {
+                    let _ = std::fs::write(dir.join("triage.txt"),
+                        format!("{}\nminidump: {}\n",
+                            CrashSignature::new(info, &crash_stack), dump));
+                }
+            }
+
+            // Registers and code at the crash
+            if let Some(info) = bug.info.as_ref() {
+                let _ = std::fs::write(dir.join("context.txt"),
+                    info.to_string());
+            }
+
+            // Assertion messages printed right before a crash help triage
+            if !output.is_empty() {
+                let _ = std::fs::write(dir.join("output.txt"),
+                    output.join("\n") + "\n");
+            }
+
+            // What the target looked like leading up to the bug
+            if !recording.is_empty() {
+                if let Err(err) = recorder::save(&recording,
+                        &dir.join("capture")) {
+                    console.warning(&format!("Failed to save the capture \
+                        of {}: {}", bug.name, err));
+                }
+            }
+
+            // What the case changed in the UI state, for a quick look
+            if let Some(diff) = result.observations.state_diff.as_ref() {
+                let _ = std::fs::write(dir.join("state_diff.txt"),
+                    diff.to_string());
+            }
+
+            // UI state at the crash, or after the actions for bugs which
+            // don't crash the target, and what the case changed
+            let tree = crash_tree.as_ref()
+                .or(result.observations.window_tree.as_ref());
+            if let Some(tree) = tree {
+                let mut dump = tree.to_string();
+                if !result.observations.ui_changes.is_empty() {
+                    dump += "\nChanged by the case:\n";
+                    dump += &result.observations.ui_changes.join("\n");
+                    dump += "\n";
+                }
+                let _ = std::fs::write(dir.join("window_tree.txt"), dump);
+            }
+        }
+    }
+
+    // Replaying the input again must not hold up the other workers
+    drop(stats);
+
+    // Learn which actions keep hanging the target for nothing
+    blacklist::record_case(&fuzz_input, provenance.timed_out,
+        new_coverage || !new_buckets.is_empty());
+
+    // Minimize new crashes the debugger caught, other bugs aren't
+    // reproduced by a replay alone
+    if config.minimize_crashes {
+        if let Some(name) = crash_bucket.as_ref() {
+            if new_buckets.contains(name) {
+                let minimized = minimize_crash(&fuzz_input, name, guardrails);
+                console.info(&format!("Worker {} minimized crash {} from {} \
+                    to {} actions", worker_id, name, fuzz_input.len(),
+                    minimized.len()));
+                record_minimized_crash(name, &minimized);
+            }
+        }
+    }
+
+    // Compare with another build of the target
+    if config.differential.is_some() {
+        let crash = crash_bucket.as_deref();
+        let modules: Vec<Arc<String>> = coverage.values()
+            .map(|(module, _, _, _)| module.clone()).collect();
+        check_divergence(&fuzz_input, crash, &modules, config, guardrails,
+            console, worker_id)?;
+    }
+
+    Ok(())
+}
+
+/// Maximum number of replays spent minimizing a crash
+const MAX_MINIMIZE_REPLAYS: usize = 64;
+
+/// Shrink `input`, which crashes the target with the crash `name`, by
+/// removing runs of actions, halving the length of the runs each pass, as
+/// long as what remains still crashes the same way
+fn minimize_crash(input: &FuzzInput, name: &str, guardrails: &Guardrails)
+        -> FuzzInput {
+    let mut best = input.clone();
+    let mut replays = 0;
+
+    let mut run = best.len() / 2;
+    while run > 0 && replays < MAX_MINIMIZE_REPLAYS {
+        let mut start = 0;
+        while start < best.len() && replays < MAX_MINIMIZE_REPLAYS {
+            let mut candidate: Vec<FuzzerAction> = (*best).clone();
+            candidate.drain(start..std::cmp::min(start + run, best.len()));
+            let candidate = Arc::new(candidate);
+
+            replays += 1;
+            let crashed = replay(&candidate, InputBackend::PostMessage, false,
+                    guardrails)
+                .map(|x| x.crash.as_ref().map(|x| x == name).unwrap_or(false))
+                .unwrap_or(false);
+            if crashed {
+                best = candidate;
+            } else {
+                start += run;
+            }
+        }
+        run /= 2;
+    }
+
+    best
+}
+
+/// Save the minimized reproducer `input` of the crash bucket `crashname`,
+/// along with its encoding and a trace of its actions, one per line
+fn record_minimized_crash(crashname: &str, input: &FuzzInput) {
+    let dir = Path::new("crashes").join(crashname);
+    let _ = std::fs::create_dir_all(&dir);
+
+    let trace: Vec<String> = input.iter().enumerate()
+        .map(|(idx, action)| {
+            format!("{:4} {}\n", idx, msgnames::describe(action))
+        })
+        .collect();
+    let _ = std::fs::write(dir.join("minimized.input"),
+        format!("{:#?}", input));
+    if let Ok(bytes) = bytecodec::encode(input) {
+        let _ = std::fs::write(dir.join("minimized.bytes"), bytes);
+    }
+    let _ = std::fs::write(dir.join("trace.txt"), trace.concat());
+}
+
+/// Coverage hit, as module names and offsets
+type CoverageSet = HashSet<(Arc<String>, usize)>;
+
+/// Result of replaying an input with `replay()`
+struct Replay {
+    /// All coverage hit
+    coverage: CoverageSet,
+
+    /// Hash of the UI Automation tree after each action, if tracked
+    ui_states: Vec<Option<u64>>,
+
+    /// Name of the bucket of the crash, if the target crashed
+    crash: Option<String>,
+
+    /// Symbolized call stack of the crash, innermost frame first
+    crash_stack: Vec<String>,
+}
+
+/// Replay `input` in a fresh target delivering input with `backend`. If
+/// `track_ui` is set the actions are performed one at a time and the UI
+/// Automation tree is snapshotted after each one.
+fn replay(input: &FuzzInput, backend: InputBackend, track_ui: bool,
+        guardrails: &Guardrails) -> Result<Replay, Box<dyn Error>> {
+    let target_config = TargetConfig::global();
+    replay_on(&target_config.command_line, Path::new(&target_config.meso),
+        input, backend, track_ui, guardrails)
+}
+
+/// Implementation of `replay()` against the build of the target started
+/// with `argv` and the meso file `meso`
+fn replay_on(argv: &[String], meso: &Path, input: &FuzzInput,
+        backend: InputBackend, track_ui: bool, guardrails: &Guardrails)
+        -> Result<Replay, Box<dyn Error>> {
+    // Delete all state persisted by earlier runs of the target
+    let target_config = TargetConfig::global();
+    target_config.reset()?;
+
+    // Wait until we're allowed to have another live target
+    let _slot = guardrails.acquire_target();
+
+    let mut target = TargetInstance::spawn(argv);
+    target.dbg().set_minidumps(false);
+    mesofile::load_meso(target.dbg(), meso);
+
+    // Give replays slowed down with an action delay the time it adds
+    let actions = prologue().len() + input.len();
+    target.dbg().extend_lifetime(action_delay() * actions as u32);
+
+    let pid = target.pid;
+    let thr = {
+        let input = input.clone();
+        let windows = target.windows();
+
+        std::thread::spawn(move || {
+            // Give up on targets which never show their window, replaying
+            // nothing
+            let attach_start = Instant::now();
+            let window = loop {
+                if let Ok(window) = target_config.attach(pid) {
+                    break window;
+                }
+                if attach_start.elapsed() >= ATTACH_TIMEOUT {
+                    return Vec::new();
+                }
+                std::thread::sleep(Pacing::global().attach_poll);
+            };
+            windows.lock().unwrap().push(window);
+
+            // Hold the desktop for the whole replay with real input, so
+            // other workers can't take the foreground between actions
+            let _desktop = if backend == InputBackend::SendInput {
+                Some(winbindings::lock_desktop())
+            } else {
+                None
+            };
+            let _ = perform_prologue(pid, backend);
+
+            let uia = if track_ui { Uia::new().ok() } else { None };
+            let uia = match uia {
+                Some(uia) => uia,
+                None => {
+                    let _ = perform_actions_with(pid, &input, backend);
+                    return Vec::new();
                 }
+            };
+
+            input.iter().map(|action| {
+                let _ = perform_actions_with(pid, &[*action], backend);
+                uia.snapshot(&window).ok().map(|x| x.hash)
+            }).collect()
+        })
+    };
+
+    let exit_state = target.run();
+    let (coverage, crash_stack, _, crash_info) = target.finish();
+    let crash = match exit_state {
+        ExitType::Crash(name) => {
+            Some(triage::bucket(&name, crash_info.as_ref(), &crash_stack))
+        }
+        _ => None,
+    };
+    let ui_states = thr.join()
+        .map_err(|err| format!("Replay thread {}", panic_message(&*err)))?;
+
+    Ok(Replay {
+        coverage: coverage.values()
+            .map(|(module, offset, _, _)| (module.clone(), *offset))
+            .collect(),
+        ui_states,
+        crash,
+        crash_stack,
+    })
+}
+
+/// Measure the latency of a fresh target, set up like for fuzzing, to pace
+/// the actions of the campaign
+fn measure_latency(guardrails: &Guardrails)
+        -> Result<Latency, Box<dyn Error>> {
+    let _slot = guardrails.acquire_target();
+
+    let target_config = TargetConfig::global();
+    let mut target = TargetInstance::spawn(&target_config.command_line);
+    target.dbg().set_minidumps(false);
+    mesofile::load_meso(target.dbg(), Path::new(&target_config.meso));
+
+    let pid = target.pid;
+    let windows = target.windows();
+    let thr = std::thread::spawn(move || {
+        let attach_start = Instant::now();
+        let window = loop {
+            if let Ok(window) = target_config.attach(pid) {
+                break window;
             }
+            if attach_start.elapsed() >= ATTACH_TIMEOUT {
+                return None;
+            }
+            std::thread::sleep(Pacing::global().attach_poll);
+        };
+        windows.lock().unwrap().push(window);
+
+        let latency = Latency::measure(&window);
+        let _ = window.close();
+        Some(latency)
+    });
+
+    target.run();
+    target.finish();
+    thr.join()
+        .map_err(|err| format!("Pacing thread {}", panic_message(&*err)))?
+        .ok_or_else(|| "Target never showed its window".into())
+}
+
+/// Replay `fuzz_input` against the other build of the target configured
+/// for differential fuzzing, comparing it with the fuzzed build's run which
+/// crashed with `crash` and covered the modules `coverage`. Divergences are
+/// reported and saved to the `divergences` directory.
+fn check_divergence(fuzz_input: &FuzzInput, crash: Option<&str>,
+        coverage: &[Arc<String>], config: &Config, guardrails: &Guardrails,
+        console: &Console, worker_id: usize) -> Result<(), Box<dyn Error>> {
+    let exe = match config.differential.as_ref() {
+        Some(exe) => exe,
+        None => return Ok(()),
+    };
+    let meso = config.differential_meso.clone()
+        .unwrap_or_else(|| {
+            let name = Path::new(exe).file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            format!("{}.meso", name)
+        });
+
+    let other = replay_on(std::slice::from_ref(exe), Path::new(&meso),
+        fuzz_input, InputBackend::PostMessage, false, guardrails)?;
+
+    let shape       = differential::CoverageShape::new(
+        &TargetConfig::global().program_name(), coverage);
+    let other_shape = differential::CoverageShape::new(exe,
+        other.coverage.iter().map(|(module, _)| module));
+    let divergences = differential::compare(crash, &shape,
+        other.crash.as_deref(), &other_shape);
+    if divergences.is_empty() {
+        return Ok(());
+    }
+
+    let summary: Vec<String> =
+        divergences.iter().map(|x| x.to_string()).collect();
+    console.report(Kind::NewCrash, &format!("Worker {} found a divergence \
+        from {}: {}", worker_id, exe, summary.join(", ")));
+
+    let _ = std::fs::create_dir("divergences");
+    std::fs::write(Path::new("divergences").join(input_filename(fuzz_input)),
+        format!("{}\n\n{:#?}\n", summary.join("\n"), fuzz_input))?;
+    Ok(())
+}
+
+/// Set once the campaign reached its completion criteria, workers stop
+/// after finishing the case they are running
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// Check the campaign against the configured completion criteria after
+/// `fuzz_cases` cases, `uptime` seconds, and `unique_crashes` crashes,
+/// returning why it should stop if it is done
+fn completion(config: &Config, fuzz_cases: u64, uptime: f64,
+        unique_crashes: usize) -> Option<String> {
+    if let Some(max) = config.max_cases.filter(|&x| fuzz_cases >= x) {
+        return Some(format!("reached {} fuzz cases", max));
+    }
+    if let Some(max) = config.max_time.filter(|&x| uptime >= x as f64) {
+        return Some(format!("ran for {} seconds", max));
+    }
+    let crashes = config.stop_after_unique_crashes
+        .filter(|&x| unique_crashes >= x);
+    if let Some(max) = crashes {
+        return Some(format!("found {} unique crashes", max));
+    }
+    None
+}
+
+/// Crashed targets kept alive for a human to attach a debugger to, killed
+/// when the harness exits
+static SUSPENDED_CRASHES: Mutex<Vec<SuspendedCrash>> = Mutex::new(Vec::new());
+
+/// Keep the crashed `target` alive, suspended at its crash in the bucket
+/// `name`, if the bucket is new and fewer than the configured number of crashes
+/// are kept already. Prints how to attach to it.
+fn keep_crash_alive(target: &mut TargetInstance, name: &str,
+        stats: &Arc<Mutex<Statistics>>, config: &Config, console: &Console) {
+    if stats.lock().unwrap().crash_db.get(name).is_some() {
+        return;
+    }
+
+    let mut suspended = SUSPENDED_CRASHES.lock().unwrap();
+    if suspended.len() >= config.keep_crashes {
+        return;
+    }
+
+    match target.suspend_crash() {
+        Ok(crash) => {
+            console.info(&format!("Crash {} kept alive in suspended process \
+                {}, attach with `windbg -p {}` and resume its threads with \
+                `~*m` to hit the crash", name, crash.pid, crash.pid));
+            suspended.push(crash);
+        }
+        Err(err) => {
+            console.warning(&format!("Failed to keep crash {} alive: {}",
+                name, err));
+        }
+    }
+}
+
+/// Coverage of starting the target and attaching to it without performing
+/// any actions, measured on first use by `isolated_coverage()`
+static BASELINE_COVERAGE: Mutex<Option<Arc<CoverageSet>>> = Mutex::new(None);
+
+/// Replay `input` in a coverage-isolated run, in a fresh target with a fresh
+/// set of breakpoints, and return exactly the coverage the input hit. The
+/// coverage of starting the target is left out.
+fn isolated_coverage(input: &FuzzInput, guardrails: &Guardrails)
+        -> Result<CoverageSet, Box<dyn Error>> {
+    let baseline = BASELINE_COVERAGE.lock().unwrap().clone();
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => {
+            let baseline = Arc::new(replay(&Arc::new(Vec::new()),
+                InputBackend::PostMessage, false, guardrails)?.coverage);
+            *BASELINE_COVERAGE.lock().unwrap() = Some(baseline.clone());
+            baseline
+        }
+    };
+
+    let coverage = replay(input, InputBackend::PostMessage, false,
+        guardrails)?.coverage;
+    Ok(coverage.difference(&baseline).cloned().collect())
+}
+
+/// Save the isolated `coverage` of `fuzz_input` alongside it in the inputs
+/// directory, one `module+offset` per line
+fn record_input_coverage(fuzz_input: &FuzzInput,
+        coverage: &CoverageSet) {
+    let mut lines: Vec<String> = coverage.iter()
+        .map(|(module, offset)| format!("{}+{:#x}\n", module, offset))
+        .collect();
+    lines.sort();
+
+    let _ = std::fs::create_dir("inputs");
+    let name = input_filename(fuzz_input);
+    let _ = std::fs::write(
+        format!("inputs/{}.cov", name.trim_end_matches(".input")),
+        lines.concat());
+}
+
+/// Calibrate an input from the corpus by running it twice and recording
+/// the fraction of coverage the runs have in common as its stability.
+/// Inputs which were never calibrated are picked first, then random inputs
+/// are recalibrated.
+fn calibrate(stats: &Arc<Mutex<Statistics>>, rng: &Rng,
+        guardrails: &Guardrails) -> Result<(), Box<dyn Error>> {
+    let (idx, input) = {
+        let mut stats = stats.lock().unwrap();
+        if stats.corpus.is_empty() {
+            return Ok(());
         }
 
-        // Get access to global stats
+        let idx = stats.corpus.uncalibrated()
+            .unwrap_or_else(|| rng.rand() % stats.corpus.len());
+        (idx, stats.corpus.load(idx)?)
+    };
+
+    let first  = replay(&input, InputBackend::PostMessage, false,
+        guardrails)?.coverage;
+    let second = replay(&input, InputBackend::PostMessage, false,
+        guardrails)?.coverage;
+
+    let union = first.union(&second).count();
+    let stability = if union == 0 {
+        1.
+    } else {
+        first.intersection(&second).count() as f64 / union as f64
+    };
+
+    stats.lock().unwrap().corpus.set_stability(idx, stability);
+    Ok(())
+}
+
+/// Replay a random corpus input with both the `PostMessage` and `SendInput`
+/// backends and report any difference in the coverage or UI Automation tree,
+/// along with the first action after which the UI differed
+fn check_equivalence(stats: &Arc<Mutex<Statistics>>, rng: &Rng,
+        guardrails: &Guardrails, console: &Console)
+        -> Result<(), Box<dyn Error>> {
+    let input = {
         let mut stats = stats.lock().unwrap();
+        if stats.corpus.is_empty() {
+            return Ok(());
+        }
+        let idx = rng.rand() % stats.corpus.len();
+        stats.corpus.load(idx)?
+    };
 
-        // Update fuzz case count
-        local_stats.fuzz_cases += 1;
-        stats.fuzz_cases += 1;
+    let post = replay(&input, InputBackend::PostMessage, true, guardrails)?;
+    let send = replay(&input, InputBackend::SendInput, true, guardrails)?;
 
-        // Check if this case ended due to a crash
-        if let ExitType::Crash(crashname) = exit_state {
-            // Update crash information
-            local_stats.crashes += 1;
-            stats.crashes       += 1;
+    let coverage_diff = post.coverage.symmetric_difference(&send.coverage)
+        .count();
+    let diverged = post.ui_states.iter().zip(send.ui_states.iter())
+        .position(|(a, b)| a != b);
+    if coverage_diff == 0 && diverged.is_none() {
+        return Ok(());
+    }
 
-            // Add the crashing input to the input databases
-            local_stats.input_db.insert(fuzz_input.clone());
-            if stats.input_db.insert(fuzz_input.clone()) {
-                stats.input_list.push(fuzz_input.clone());
+    let mut report = format!("{}: {} coverage entries differ between \
+        backends", input_filename(&input), coverage_diff);
+    if let Some(idx) = diverged {
+        report += &format!(", UI diverged after action {} {:?}", idx,
+            input[idx]);
+    }
+    console.warning(&report);
 
-                record_input(fuzz_input.clone());
+    let mut log = std::fs::OpenOptions::new().create(true).append(true)
+        .open("equivalence.txt")?;
+    log.write_all(format!("{}\n", report).as_bytes())?;
+    Ok(())
+}
 
-                // Update the action database with known-feasible
-                // actions
-                for &action in fuzz_input.iter() {
-                    if stats.unique_action_set.insert(action) {
-                        stats.unique_actions.push(action);
-                    }
+/// Directory corpora page inputs out to when their memory is limited
+const PAGES_DIR: &str = "pages";
+
+/// Apply the corpus memory limit from `config`, if any, to `corpus`
+fn limit_corpus_memory(corpus: &mut Corpus, config: &Config)
+        -> std::io::Result<()> {
+    match config.corpus_memory_mb {
+        Some(mb) => corpus.set_memory_limit(
+            (mb as usize).saturating_mul(1024 * 1024), Path::new(PAGES_DIR)),
+        None => Ok(()),
+    }
+}
+
+fn worker(stats: Arc<Mutex<Statistics>>, resources: Arc<Resources>,
+        config: Arc<Config>, guardrails: Arc<Guardrails>,
+        oracles: Arc<Vec<Box<dyn BugOracle>>>, console: Arc<Console>,
+        worker_id: usize) {
+    // Local stats database
+    let mut local_stats = Statistics::default();
+    if let Err(err) = limit_corpus_memory(&mut local_stats.corpus, &config) {
+        console.warning(&format!("Worker {} failed to limit corpus memory: \
+            {}", worker_id, err));
+    }
+
+    // Pin the worker, and the targets it spawns, to its core
+    if !config.pin_cores.is_empty() {
+        let core = config.pin_cores[worker_id % config.pin_cores.len()];
+        if let Err(err) = affinity::pin_current_thread(core) {
+            console.warning(&format!("Failed to pin worker {} to core {}: \
+                {}", worker_id, core, err));
+        }
+    }
+
+    // The application to fuzz
+    let target_config = TargetConfig::global();
+
+    // Create an RNG for this thread
+    let rng = Rng::new();
+    if let Err(err) = bundle::record_seed(worker_id, rng.seed()) {
+        console.warning(&format!("Failed to record the seed of worker {}: \
+            {}", worker_id, err));
+    }
+
+    while !STOPPING.load(Ordering::SeqCst) {
+        // Isolate each case so a bug in the fuzzer only loses that case
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            // Periodically measure how stable the corpus inputs are
+            let calibrate_now = config.calibration_interval
+                .map(|x| local_stats.fuzz_cases % x == 0).unwrap_or(false);
+            // A failed calibration is retried at the next interval rather
+            // than in place of the case, which would never advance to it
+            if calibrate_now {
+                if let Err(err) = calibrate(&stats, &rng, &guardrails) {
+                    console.warning(&format!("Worker {} failed to \
+                        calibrate: {}", worker_id, err));
+                }
+            }
+
+            // Periodically check that inputs replay the same with all input
+            // backends
+            let check_now = config.equivalence_interval
+                .map(|x| local_stats.fuzz_cases % x == x - 1)
+                .unwrap_or(false);
+            if check_now {
+                let result = check_equivalence(&stats, &rng, &guardrails,
+                    &console);
+                if let Err(err) = result {
+                    console.warning(&format!("Worker {} failed to check \
+                        backend equivalence: {}", worker_id, err));
                 }
             }
 
-            // Add the crash name and corresponding fuzz input to the crash
-            // database
-            local_stats.crash_db.insert(crashname.clone(), fuzz_input.clone());
-            stats.crash_db.insert(crashname, fuzz_input.clone());
+            let context = CaseContext {
+                stats: &stats,
+                resources: &resources,
+                config: &config,
+                guardrails: &guardrails,
+                oracles: &oracles,
+                console: &console,
+                target_config: &target_config,
+                worker_id,
+            };
+            fuzz_case(&context, &mut local_stats, &rng)
+        }));
+
+        #[cfg(feature = "chaos")]
+        chaos::progress();
+
+        let err = match result {
+            Ok(Ok(())) => continue,
+            Ok(Err(err)) => err.to_string(),
+            Err(payload) => panic_message(&*payload),
+        };
+
+        // The global stats may have been poisoned by a panic while they
+        // were held, they are still consistent enough to keep fuzzing
+        stats.clear_poison();
+
+        local_stats.internal_errors += 1;
+        stats.lock().unwrap_or_else(PoisonError::into_inner)
+            .internal_errors += 1;
+        console.error(&format!("Internal error in fuzz case: {}", err));
+        events::emit(FuzzEvent::WorkerRestart {
+            worker: worker_id,
+            reason: err,
+        });
+    }
+}
+
+/// Add the databases saved in the snapshot at `path` to `campaign`, if
+/// there is one
+#[cfg(feature = "serde")]
+fn resume_snapshot(campaign: &Campaign, path: &Path, console: &Console) {
+    if !path.exists() {
+        return;
+    }
+
+    match snapshot::Snapshot::load(path) {
+        Ok(snapshot) => {
+            let inputs = snapshot.inputs.len();
+            snapshot.restore(&mut campaign.lock());
+            console.info(&format!("Resumed {} inputs from {}", inputs,
+                path.display()));
+        }
+        Err(err) => console.error(&format!("Failed to load snapshot {}: \
+            {}", path.display(), err)),
+    }
+}
+
+/// Snapshots need the `serde` feature
+#[cfg(not(feature = "serde"))]
+fn resume_snapshot(_campaign: &Campaign, path: &Path, console: &Console) {
+    console.warning(&format!("Ignoring snapshot {}, built without the \
+        serde feature", path.display()));
+}
+
+/// Save the databases of `campaign` as a snapshot to `path`
+#[cfg(feature = "serde")]
+fn save_snapshot(campaign: &Campaign, path: &Path, console: &Console) {
+    let snapshot = snapshot::Snapshot::from_statistics(&mut campaign.lock());
+    match snapshot.save(path) {
+        Ok(()) => console.info(&format!("Saved snapshot to {}",
+            path.display())),
+        Err(err) => console.error(&format!("Failed to save snapshot {}: \
+            {}", path.display(), err)),
+    }
+}
+
+/// Snapshots need the `serde` feature
+#[cfg(not(feature = "serde"))]
+fn save_snapshot(_campaign: &Campaign, _path: &Path, _console: &Console) {
+}
+
+/// Create the global state of a campaign run with `config`, with the action
+/// dictionary seeded with `actions`
+fn new_campaign(config: &Config, actions: &[FuzzerAction])
+        -> std::io::Result<Campaign> {
+    let campaign = Campaign::new();
+    campaign.lock().lineage.set_policy(LineagePolicy::from_config(config));
+    campaign.lock().tag_filter = TagFilter::from_config(config);
+    limit_corpus_memory(&mut campaign.lock().corpus, config)?;
+    campaign.lock().add_actions(actions);
+    Ok(campaign)
+}
+
+/// Import or export the corpus as a directory in another fuzzer's format,
+/// as `corpus <import|export> [--format <format>] <dir>`
+fn corpus_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut format = corpusdir::Format::Afl;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--format" {
+            let name = args.next().ok_or("--format needs a value")?;
+            format = corpusdir::Format::from_name(name)
+                .ok_or_else(|| format!("Unknown corpus format {:?}", name))?;
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+
+    let (command, dir) = match positional[..] {
+        [command, dir] => (command, Path::new(dir)),
+        _ => return Err("usage: corpus <import|export> [--format afl] \
+                         <dir>".into()),
+    };
+    let transfer = match command {
+        "export" => corpusdir::export(dir, format)?,
+        "import" => corpusdir::import(dir, format)?,
+        _ => return Err(format!("Unknown corpus command {:?}",
+            command).into()),
+    };
+
+    println!("{}ed {} inputs, {} already present", command, transfer.copied,
+        transfer.duplicates);
+    Ok(())
+}
+
+/// Handle the `stats` subcommand, which merges campaign directories
+fn stats_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() < 3 || args[0] != "merge" {
+        return Err("usage: stats merge <out dir> <campaign dir>...".into());
+    }
+
+    let dirs: Vec<&Path> = args[2..].iter().map(Path::new).collect();
+    print!("{}", statsmerge::merge(&dirs, Path::new(&args[1]))?);
+    Ok(())
+}
+
+/// Handle the `distill` subcommand, which packages the crashes found for
+/// sharing
+fn distill_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() != 1 {
+        return Err("usage: distill <out dir>".into());
+    }
+
+    let out = Path::new(&args[0]);
+    let buckets = distill::distill(out)?;
+    println!("Distilled {} crashes to {}", buckets,
+        out.with_extension("zip").display());
+    Ok(())
+}
+
+/// Coverage of the last case run by the `exec` subcommand, one
+/// `module+0xoffset` entry per line
+const EXEC_COVERAGE: &str = "exec_coverage.txt";
+
+/// Handle the `exec` subcommand, which runs a single case decoded from a
+/// file of bytes, so byte-oriented fuzzers can use the harness as a GUI
+/// target. The case's coverage is saved to `EXEC_COVERAGE`, and the
+/// harness aborts if the target crashed so the fuzzer sees the crash.
+fn exec_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    if args.len() != 1 {
+        return Err("usage: exec <input bytes>".into());
+    }
+
+    let input: FuzzInput = Arc::new(bytecodec::decode(
+        &std::fs::read(&args[0])?));
+    let guardrails = Guardrails::new(&Config::default());
+    let result = replay(&input, InputBackend::PostMessage, false,
+        &guardrails)?;
+
+    let coverage: Vec<String> = result.coverage.iter()
+        .map(|(module, offset)| format!("{}+{:#x}\n", module, offset))
+        .collect();
+    std::fs::write(EXEC_COVERAGE, coverage.concat())?;
+    println!("Ran {} actions, {} coverage entries", input.len(),
+        coverage.len());
+
+    if let Some(crash) = result.crash {
+        println!("Crashed: {}", crash);
+        std::process::abort();
+    }
+    Ok(())
+}
+
+/// Load the saved input at `path`: JSON if it ends in `.json`, bytes if it
+/// ends in `.bytes`, and the `.bytes` encoding saved alongside it if it
+/// ends in `.input`, whose own format can't be loaded back
+fn load_replay_input(path: &Path) -> Result<FuzzInput, Box<dyn Error>> {
+    match path.extension().and_then(|x| x.to_str()) {
+        #[cfg(feature = "serde")]
+        Some("json") => {
+            Ok(snapshot::input_from_json(&std::fs::read_to_string(path)?)?)
+        }
+        #[cfg(not(feature = "serde"))]
+        Some("json") => Err("JSON inputs need the serde feature".into()),
+        Some("input") => {
+            let bytes = path.with_extension("bytes");
+            let bytes = std::fs::read(&bytes).map_err(|err| {
+                format!("Failed to read {}: {}", bytes.display(), err)
+            })?;
+            Ok(Arc::new(bytecodec::decode(&bytes)))
+        }
+        Some("bytes") => {
+            Ok(Arc::new(bytecodec::decode(&std::fs::read(path)?)))
+        }
+        _ => Err(format!("{} is not a .bytes, .input, or .json input",
+            path.display()).into()),
+    }
+}
+
+/// Handle the `replay` subcommand, which reproduces a saved input outside
+/// of the fuzz loop, in a fresh target under the debugger, or against the
+/// already running target `--attach` names. `--delay` waits after each
+/// action to watch the replay in slow motion, the target is let live
+/// longer by the time this adds. `--verbose` prints each action as it is
+/// performed. Other options configure the replay as they do when fuzzing.
+fn replay_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut attach = None;
+    let mut delay = 0;
+    let mut verbose = false;
+    let mut backend = InputBackend::PostMessage;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--attach" => {
+                attach = Some(args.next().ok_or("--attach needs a pid")?
+                    .parse::<u32>()?);
+            }
+            "--delay" => {
+                delay = args.next().ok_or("--delay needs a value")?
+                    .parse()?;
+            }
+            "--verbose" => verbose = true,
+            "--backend" => {
+                backend = match args.next().map(|x| x.as_str()) {
+                    Some("postmessage") => InputBackend::PostMessage,
+                    Some("sendinput")   => InputBackend::SendInput,
+                    _ => return Err("--backend needs postmessage or \
+                                     sendinput".into()),
+                };
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let mut config = Config::default();
+    let positional = config.parse_args(&rest)?;
+    if positional.len() != 1 {
+        return Err("usage: replay <input> [--attach <pid>] [--delay <ms>] \
+                    [--verbose] [--backend <postmessage|sendinput>] \
+                    [--<option> <value>]...".into());
+    }
+    let input = load_replay_input(Path::new(&positional[0]))?;
+
+    priority::set_target_priority(config.target_priority);
+    load_case_setup(&config)?;
+    set_trace_actions(verbose);
+    set_action_delay(Duration::from_millis(delay));
+
+    if let Some(pid) = attach {
+        println!("Replaying {} actions on pid {}", input.len(), pid);
+        perform_prologue(pid, backend)?;
+        perform_actions_with(pid, &input, backend)?;
+        return Ok(());
+    }
+
+    let guardrails = Guardrails::new(&config);
+    let result = replay(&input, backend, false, &guardrails)?;
+    println!("Replayed {} actions, {} coverage entries", input.len(),
+        result.coverage.len());
+    match result.crash {
+        Some(crash) => {
+            println!("Crashed: {}", crash);
+            for frame in &result.crash_stack {
+                println!("    {}", frame);
+            }
+        }
+        None => println!("No crash"),
+    }
+    Ok(())
+}
+
+/// Handle the `serve` subcommand, which runs the harness as an execution
+/// service for other tools. Options limit the targets run at once, and set
+/// their priority, as they do when fuzzing.
+fn serve_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::default();
+    let positional = config.parse_args(args)?;
+    if positional.len() != 1 {
+        return Err("usage: serve <address> [--<option> <value>]...".into());
+    }
+
+    let listener = std::net::TcpListener::bind(&positional[0])?;
+    println!("Serving executions on {}", listener.local_addr()?);
+
+    priority::set_target_priority(config.target_priority);
+    load_case_setup(&config)?;
+    let guardrails = Guardrails::new(&config);
+    let executor: Arc<server::Executor> = Arc::new(move |input| {
+        let result = replay(input, InputBackend::PostMessage, false,
+            &guardrails).map_err(|err| err.to_string())?;
+        Ok(server::Execution {
+            crash:    result.crash,
+            coverage: result.coverage.iter()
+                .map(|(module, offset)| format!("{}+{:#x}", module, offset))
+                .collect(),
+        })
+    });
+    server::serve(listener, executor)?;
+    Ok(())
+}
+
+/// Handle the `smoke` subcommand, which replays the top inputs of the
+/// corpus to check that a target config or input backend works before
+/// launching a campaign. Options other than `--top`, `--min-coverage`, and
+/// `--backend` configure the replays as they do when fuzzing.
+fn smoke_command(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut top = 16;
+    let mut min_coverage = 1;
+    let mut backend = InputBackend::PostMessage;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--top" => {
+                top = args.next().ok_or("--top needs a value")?.parse()?;
+            }
+            "--min-coverage" => {
+                min_coverage = args.next()
+                    .ok_or("--min-coverage needs a value")?.parse()?;
+            }
+            "--backend" => {
+                backend = match args.next().map(|x| x.as_str()) {
+                    Some("postmessage") => InputBackend::PostMessage,
+                    Some("sendinput")   => InputBackend::SendInput,
+                    _ => return Err("--backend needs postmessage or \
+                                     sendinput".into()),
+                };
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let mut config = Config::default();
+    if !config.parse_args(&rest)?.is_empty() {
+        return Err("usage: smoke [--top <inputs>] [--min-coverage \
+                    <entries>] [--backend <postmessage|sendinput>] \
+                    [--<option> <value>]...".into());
+    }
+    priority::set_target_priority(config.target_priority);
+    load_case_setup(&config)?;
+    let guardrails = Guardrails::new(&config);
+
+    let mut report = smoke::Report::new(min_coverage);
+    for (name, input) in smoke::top_inputs(Path::new("inputs"), top)? {
+        match replay(&input, backend, false, &guardrails) {
+            Ok(result) => {
+                println!("{}: {} actions, {} coverage entries", name,
+                    input.len(), result.coverage.len());
+                report.add(&name, result.crash, result.coverage);
+            }
+            Err(err) => report.add_error(&name, &err.to_string()),
+        }
+    }
+
+    print!("{}", report);
+    if !report.ok() {
+        return Err("Smoke test failed".into());
+    }
+    Ok(())
+}
+
+/// Handle the `bundle` and `verify` subcommands, which freeze the campaign
+/// for reproduction and check a frozen campaign
+fn bundle_command(command: &str, args: &[String])
+        -> Result<(), Box<dyn Error>> {
+    if args.len() != 1 {
+        return Err(format!("usage: {} <bundle dir>", command).into());
+    }
+    let dir = Path::new(&args[0]);
+
+    if command == "verify" {
+        let verification = bundle::verify(dir)?;
+        print!("{}", verification);
+        if !verification.ok() {
+            return Err("Bundle verification failed".into());
         }
+        return Ok(());
     }
+
+    // The targets are those of the config the campaign was run with
+    let mut config = Config::default();
+    config.parse_args(&bundle::recorded_args(Path::new("."))?)?;
+    let target_config = TargetConfig::from_config(&config);
+    let mut binaries = vec![target_config.command_line[0].clone()];
+    binaries.extend(config.differential.iter().cloned());
+
+    let files = bundle::bundle(dir, &binaries)?;
+    println!("Bundled {} files to {}", files, dir.display());
+    Ok(())
 }
 
 fn main() {
-    // Global statistics
-    let stats = Arc::new(Mutex::new(Statistics::default()));
+    // Parse the command line
+    let args: Vec<String> = std::env::args().collect();
 
-    // Open a log file
-    let mut log = File::create("fuzz_stats.txt").unwrap();
+    // Corpus import and export takes its own options
+    if args.get(1).map(|x| x == "corpus").unwrap_or(false) {
+        if let Err(err) = corpus_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    // Save the current time
-    let start_time = Instant::now();
+    // Bundling and verifying campaigns takes its own options
+    let bundling = args.get(1).filter(|x| *x == "bundle" || *x == "verify");
+    if let Some(command) = bundling {
+        if let Err(err) = bundle_command(command, &args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    for _ in 0..10 {
-        // Spawn threads
-        let stats = stats.clone();
-        let _ = std::thread::spawn(move || {
-            worker(stats);
+    // Smoke testing takes its own options, and fails the process so
+    // scripts can hold off launching a campaign
+    if args.get(1).map(|x| x == "smoke").unwrap_or(false) {
+        if let Err(err) = smoke_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Serving executions takes its own options
+    if args.get(1).map(|x| x == "serve").unwrap_or(false) {
+        if let Err(err) = serve_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Replaying saved inputs takes its own options, and fails the process
+    // so scripts can tell the input couldn't be replayed
+    if args.get(1).map(|x| x == "replay").unwrap_or(false) {
+        if let Err(err) = replay_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Running single cases for other fuzzers takes its own options
+    if args.get(1).map(|x| x == "exec").unwrap_or(false) {
+        if let Err(err) = exec_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Distilling crashes takes its own options
+    if args.get(1).map(|x| x == "distill").unwrap_or(false) {
+        if let Err(err) = distill_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Merging campaign statistics takes its own options
+    if args.get(1).map(|x| x == "stats").unwrap_or(false) {
+        if let Err(err) = stats_command(&args[2..]) {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut config = Config::default();
+    let positional = match config.parse_args(&args[1..]) {
+        Ok(positional) => positional,
+        Err(err) => {
+            println!("{}", err);
+            println!("usage: {} [bench [corpus size]] [--config <file>] \
+                    [--<option> <value>]...", args[0]);
+            println!("       {} corpus <import|export> [--format afl] \
+                    <dir>", args[0]);
+            println!("       {} replay <input> [--attach <pid>] \
+                    [--delay <ms>] [--verbose]", args[0]);
+            println!("       {} stats merge <out dir> <campaign dir>...",
+                args[0]);
+            println!("       {} distill <out dir>", args[0]);
+            println!("       {} <bundle|verify> <bundle dir>", args[0]);
+            println!("       {} exec <input bytes>", args[0]);
+            println!("       {} serve <address> [--<option> <value>]...",
+                args[0]);
+            println!("       {} smoke [--top <inputs>] [--min-coverage \
+                    <entries>] [--backend <postmessage|sendinput>] \
+                    [--<option> <value>]...", args[0]);
+            std::process::exit(1);
+        }
+    };
+    let config = Arc::new(config);
+
+    // Benchmark the mutation engine instead of fuzzing
+    if positional.first().map(|x| x == "bench").unwrap_or(false) {
+        let size = positional.get(1).and_then(|x| x.parse().ok())
+            .unwrap_or(1000);
+        println!("Benchmarking over a synthetic corpus of {} inputs", size);
+        for result in bench::run(size) {
+            println!("{}", result);
+        }
+        return;
+    }
+
+    // Keep the host usable by running targets at a lower priority
+    priority::set_target_priority(config.target_priority);
+
+    // Retry flaky Win32 calls as configured
+    RetryPolicy::from_config(&config).set_global();
+
+    // Trace the target's GUI state after each action
+    set_trace_actions(config.verbose);
+
+    // Abort cases whose actions get stuck
+    timeout::set_action_timeout(config.action_timeout_ms
+        .map(Duration::from_millis));
+
+    // Check menu IDs against the target's current menus before using them
+    if config.probe_menus {
+        menuprobe::enable();
+    }
+
+    // Learn to avoid actions which keep hanging the target
+    if config.adaptive_blacklist {
+        blacklist::enable();
+    }
+
+    // Console output, also logged to a file
+    let console = Arc::new(Console::new(&config, "fuzz.log"));
+
+    // Start every case from the configured UI state
+    if let Err(err) = load_case_setup(&config) {
+        console.error(&err.to_string());
+        return;
+    }
+
+    // Record the command line, and later the seeds, for bundling
+    if let Err(err) = bundle::record_campaign(&args[1..]) {
+        console.warning(&format!("Failed to record the campaign: {}", err));
+    }
+
+    // Load target-specific plugins
+    for path in &config.plugins {
+        if let Err(err) = plugin::register(path) {
+            console.error(&format!("Failed to load plugin {}: {}", path,
+                err));
+            return;
+        }
+    }
+
+    // UAC prompts stall targets which need administrator rights, unless the
+    // harness is elevated so they are spawned directly elevated
+    if !elevation::is_elevated().unwrap_or(false) {
+        if config.elevate {
+            match elevation::relaunch_elevated() {
+                Ok(()) => console.info("Relaunched elevated"),
+                Err(err) => console.error(&format!(
+                    "Failed to relaunch elevated: {}", err)),
+            }
+            return;
+        }
+
+        let image = TargetConfig::global().resources.first().cloned()
+            .unwrap_or_default();
+        if elevation::target_requires_elevation(&image).unwrap_or(false) {
+            console.warning("Target requires elevation, UAC prompts will \
+                stall cases unless the harness is elevated with --elevate \
+                true");
+        }
+    }
+
+    // Registered messages to fuzz, and the names of all registered messages
+    // to pick them from
+    ipc::set_custom_messages(&config.custom_messages);
+    if config.discover_messages {
+        let names: Vec<String> = ipc::registered_messages().iter()
+            .map(|(msg, name)| format!("{:#06x} {}\n", msg, name)).collect();
+        if let Err(err) = std::fs::write("registered_messages.txt",
+                names.concat()) {
+            console.warning(&format!("Failed to save registered messages: \
+                {}", err));
+        } else {
+            console.info(&format!("Saved {} registered message names",
+                names.len()));
+        }
+    }
+
+    // Record everything injected into targets
+    if let Some(path) = &config.audit_log {
+        if let Err(err) = audit::enable(path,
+                config.audit_log_max_mb.saturating_mul(1024 * 1024),
+                config.audit_log_keep) {
+            console.error(&format!("Failed to open audit log {}: {}", path,
+                err));
+            return;
+        }
+    }
+
+    // Learn which controls raise UAC prompts and stop clicking them
+    if config.skip_elevation {
+        elevation::enable_skip();
+    }
+
+    // Host resource limits
+    let guardrails = Arc::new(Guardrails::new(&config));
+
+    // Suppress crash dialogs for the duration of the campaign. Ctrl-C has to
+    // end the campaign rather than the process so the settings are restored.
+    debugger::catch_ctrl_c();
+    let target_config = TargetConfig::global();
+    let _error_mode = errormode::ErrorModeGuard::new(
+        &[&target_config.program_name()]);
+
+    // Pace actions to how fast this target responds
+    if config.adaptive_pacing {
+        match measure_latency(&guardrails) {
+            Ok(latency) => {
+                let pacing = Pacing::from_latency(&latency);
+                console.info(&format!("Measured {:?} per message and {:?} \
+                    per enumeration, pacing {:?}", latency.message,
+                    latency.enumeration, pacing));
+                if let Err(err) = bundle::record_pacing(&latency, &pacing) {
+                    console.warning(&format!("Failed to record the pacing: \
+                        {}", err));
+                }
+                pacing.set_global();
+            }
+            Err(err) => console.warning(&format!("Failed to measure the \
+                target's latency, using the default pacing: {}", err)),
+        }
+    }
+
+    // Seed the action dictionary from the target's resources
+    let resources = Resources::from_files(&target_config.resources)
+        .unwrap_or_else(|err| {
+            console.warning(&format!("Failed to parse target resources: {}",
+                err));
+            Resources::default()
         });
+    let actions = resources.actions();
+    console.info(&format!("Extracted {} actions from target resources",
+        actions.len()));
+
+    // Global campaign state
+    let campaign = match new_campaign(&config, &actions) {
+        Ok(campaign) => campaign,
+        Err(err) => {
+            console.error(&format!("Failed to limit corpus memory: {}", err));
+            return;
+        }
+    };
+
+    // Pick up where an earlier run stopped
+    if let Some(path) = config.snapshot.as_ref() {
+        resume_snapshot(&campaign, Path::new(path), &console);
+    }
+
+    // Second strategy compared against the configured one, with its own
+    // campaign state and bug detectors
+    let arm_b = match config.ab_config.as_ref() {
+        Some(path) => {
+            let mut config_b = (*config).clone();
+            config_b.ab_config = None;
+            let campaign_b = config_b.load(Path::new(path))
+                .map_err(|err| err.to_string())
+                .and_then(|()| new_campaign(&config_b, &actions)
+                    .map_err(|err| err.to_string()));
+            match campaign_b {
+                Ok(campaign_b) => {
+                    let oracles_b = Arc::new(oracle::oracles(&config_b));
+                    Some((campaign_b, Arc::new(config_b), oracles_b))
+                }
+                Err(err) => {
+                    console.error(&format!("Failed to set up the B arm from \
+                        {}: {}", path, err));
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+    let mut comparison = abtest::Comparison::new();
+
+    let resources = Arc::new(resources);
+
+    // Open a log file
+    let mut log = StatLog::new("fuzz_stats.csv", if config.stats_chart {
+        Some("fuzz_stats.svg".into())
+    } else {
+        None
+    }).expect("Failed to create stats log");
+
+    // Webhook notifications for new crashes and stalls
+    let mut notifier = Notifier::new(&config);
+
+    // Bug detectors run on every case
+    let oracles = Arc::new(oracle::oracles(&config));
+
+    let mut workers = Vec::new();
+    for worker_id in 0..NUM_WORKERS {
+        // Spawn threads, every other one running the B arm if comparing
+        let (stats, config, oracles) = match arm_b.as_ref() {
+            Some((campaign_b, config_b, oracles_b)) if worker_id % 2 == 1 => {
+                (campaign_b.stats(), config_b.clone(), oracles_b.clone())
+            }
+            _ => (campaign.stats(), config.clone(), oracles.clone()),
+        };
+        let resources = resources.clone();
+        let guardrails = guardrails.clone();
+        let console = console.clone();
+
+        workers.push(std::thread::spawn(move || {
+            worker(stats, resources, config, guardrails, oracles, console,
+                worker_id);
+        }));
     }
 
-    loop {
+    // Abort if injected failures deadlock the workers
+    #[cfg(feature = "chaos")]
+    chaos::watchdog();
+
+    let mut last_action_report = Instant::now();
+    let mut last_chart = Instant::now();
+    let mut last_snapshot = Instant::now();
+    let reason = loop {
         std::thread::sleep(Duration::from_millis(1000));
 
+        // Check host resources
+        if let Some(warning) = guardrails.check() {
+            console.warning(&warning);
+        }
+
+        // Send notifications for anything new
+        notifier.check(&campaign);
+
+        // Compare the arms of an A/B comparison
+        if let Some((campaign_b, _, _)) = arm_b.as_ref() {
+            comparison.record(abtest::sample(&campaign),
+                abtest::sample(campaign_b));
+        }
+
+        // Check the corpus survived the injected failures
+        #[cfg(feature = "chaos")]
+        chaos::check_corpus(&mut campaign.lock().corpus);
+
+        // Periodically save the databases
+        if let Some(path) = config.snapshot.as_ref() {
+            if last_snapshot.elapsed() >= SNAPSHOT_INTERVAL {
+                last_snapshot = Instant::now();
+                save_snapshot(&campaign, Path::new(path), &console);
+            }
+        }
+
         // Get access to the global stats
-        let stats = stats.lock().unwrap();
+        let stats = campaign.lock();
 
-        let uptime = (Instant::now() - start_time).as_secs_f64();
+        let uptime = campaign.uptime().as_secs_f64();
         let fuzz_case = stats.fuzz_cases;
-        print!("{:12.2} uptime | {:7} fuzz cases | {:5} uniq actions | \
-                {:8} coverage | {:5} inputs | {:6} crashes [{:6} unique]\n",
+        console.info(&format!("{:12.2} uptime | {:7} fuzz cases | \
+                {:5} uniq actions | {:8} coverage | {:5} inputs | \
+                {:6} crashes [{:6} unique] | {:4} internal errors | \
+                {:5.1}% stable | {:5} invalid menu ids | \
+                {:6.0} ms est. cost",
             uptime, fuzz_case,
             stats.unique_actions.len(),
-            stats.coverage_db.len(), stats.input_db.len(),
-            stats.crashes, stats.crash_db.len());
+            stats.coverage_db.len(), stats.corpus.len(),
+            stats.crashes, stats.crash_db.len(), stats.internal_errors,
+            stats.corpus.mean_stability().unwrap_or(1.) * 100.,
+            menuprobe::invalid_attempts(),
+            stats.corpus.mean_estimated_cost().unwrap_or_default()
+                .as_secs_f64() * 1000.));
+
+        // Periodically show which action types fail, they usually point to
+        // a misconfiguration for the target
+        if last_action_report.elapsed() >= ACTION_REPORT_INTERVAL {
+            last_action_report = Instant::now();
+            let failures = stats.action_stats.to_string();
+            if !failures.is_empty() {
+                console.info(&format!("Action failures: {}", failures));
+            }
+
+            let hanging: Vec<String> = blacklist::down_weighted().iter()
+                .take(8).map(|(action, weight)| {
+                    format!("{:?} {:.0}%", action, weight * 100.)
+                }).collect();
+            if !hanging.is_empty() {
+                console.info(&format!("Down-weighted actions: {}",
+                    hanging.join(" | ")));
+            }
+        }
+
+        log.log(Sample {
+            uptime,
+            fuzz_cases:     fuzz_case,
+            coverage:       stats.coverage_db.len(),
+            corpus:         stats.corpus.len(),
+            crashes:        stats.crashes,
+            unique_crashes: stats.crash_db.len(),
+        }).expect("Failed to write stats log");
+
+        // Re-render the chart every minute
+        if fuzz_case > 0 && last_chart.elapsed() >= CHART_INTERVAL {
+            last_chart = Instant::now();
+            let _ = log.render_chart();
+        }
 
-        write!(log, "{:12.0} {:7} {:8} {:5} {:6} {:6}\n",
-            uptime, fuzz_case, stats.coverage_db.len(), stats.input_db.len(),
-            stats.crashes, stats.crash_db.len()).unwrap();
-        log.flush().unwrap();
+        // Stop once the campaign is done, or on Ctrl-C
+        let reason = completion(&config, fuzz_case, uptime,
+            stats.crash_db.len());
+        if let Some(reason) = reason {
+            break reason;
+        }
+        if debugger::exit_requested() {
+            break "interrupted".into();
+        }
+    };
+
+    // Let the workers finish their cases, saving everything they find
+    console.info(&format!("Stopping, campaign {}", reason));
+    STOPPING.store(true, Ordering::SeqCst);
+    for worker in workers {
+        let _ = worker.join();
     }
+
+    // Flush the final statistics
+    let stats = campaign.lock();
+    log.log(Sample {
+        uptime:         campaign.uptime().as_secs_f64(),
+        fuzz_cases:     stats.fuzz_cases,
+        coverage:       stats.coverage_db.len(),
+        corpus:         stats.corpus.len(),
+        crashes:        stats.crashes,
+        unique_crashes: stats.crash_db.len(),
+    }).expect("Failed to write stats log");
+    let _ = log.render_chart();
+    console.info(&format!("Campaign done: {} fuzz cases, {} coverage, \
+        {} inputs, {} unique crashes", stats.fuzz_cases,
+        stats.coverage_db.len(), stats.corpus.len(), stats.crash_db.len()));
+
+    drop(stats);
+
+    // Save the databases for the next run to resume from
+    if let Some(path) = config.snapshot.as_ref() {
+        save_snapshot(&campaign, Path::new(path), &console);
+    }
+
+    // Final comparison of the arms
+    if let Some((campaign_b, _, _)) = arm_b.as_ref() {
+        comparison.record(abtest::sample(&campaign),
+            abtest::sample(campaign_b));
+        let report = comparison.to_string();
+        console.info(&format!("A/B comparison:\n{}", report));
+        if let Err(err) = std::fs::write("ab_report.txt", report) {
+            console.warning(&format!("Failed to save the A/B comparison: {}",
+                err));
+        }
+    }
+
+    // Kill the crashed targets kept alive for debugging
+    SUSPENDED_CRASHES.lock().unwrap().clear();
 }
 
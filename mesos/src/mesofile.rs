@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use debugger::{Debugger, BreakpointType};
+use guifuzz::intern_module;
 
 /// Grab a native-endianness u32 from a slice of u8s
 fn u32_from_slice(val: &[u8]) -> u32 {
@@ -74,7 +75,10 @@ pub fn load_meso(dbg: &mut Debugger, meso_path: &Path) {
                 .expect("Module name was not valid UTF-8");
             ptr = &ptr[modname_len as usize..];
 
-            cur_modname = Some(Arc::new(modname.into()));
+            // Intern the name up front so coverage keys can refer to it
+            let modname = Arc::new(modname.into());
+            intern_module(&modname);
+            cur_modname = Some(modname);
         } else if record == 1 {
             // Current module name state
             let module: &Arc<String> = cur_modname.as_ref().unwrap();
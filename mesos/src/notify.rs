@@ -0,0 +1,301 @@
+//! Webhook notifications for events in long unattended campaigns, POSTed
+//! with WinHTTP as there's no HTTP client to shell out to on Windows 7
+
+use std::io;
+use std::collections::HashSet;
+use guifuzz::{Config, Campaign};
+
+#[link(name="Winhttp")]
+extern "system" {
+    fn WinHttpCrackUrl(url: *const u16, url_len: u32, flags: u32,
+        components: *mut UrlComponents) -> i32;
+    fn WinHttpOpen(agent: *const u16, access_type: u32, proxy: *const u16,
+        proxy_bypass: *const u16, flags: u32) -> usize;
+    fn WinHttpSetOption(handle: usize, option: u32, buffer: *const u8,
+        len: u32) -> i32;
+    fn WinHttpSetTimeouts(handle: usize, resolve: i32, connect: i32,
+        send: i32, receive: i32) -> i32;
+    fn WinHttpConnect(session: usize, server: *const u16, port: u16,
+        reserved: u32) -> usize;
+    fn WinHttpOpenRequest(connect: usize, verb: *const u16,
+        object: *const u16, version: *const u16, referrer: *const u16,
+        accept_types: *const *const u16, flags: u32) -> usize;
+    fn WinHttpSendRequest(request: usize, headers: *const u16,
+        headers_len: u32, optional: *const u8, optional_len: u32,
+        total_len: u32, context: usize) -> i32;
+    fn WinHttpReceiveResponse(request: usize, reserved: usize) -> i32;
+    fn WinHttpQueryHeaders(request: usize, info_level: u32,
+        name: *const u16, buffer: *mut u32, len: *mut u32,
+        index: *mut u32) -> i32;
+    fn WinHttpCloseHandle(handle: usize) -> i32;
+}
+
+/// `WINHTTP_ACCESS_TYPE_DEFAULT_PROXY`, use the proxy set with `netsh`
+const WINHTTP_ACCESS_TYPE_DEFAULT_PROXY: u32 = 0;
+
+/// `INTERNET_SCHEME_HTTPS`
+const INTERNET_SCHEME_HTTPS: i32 = 2;
+
+/// `WINHTTP_FLAG_SECURE`, use TLS for the request
+const WINHTTP_FLAG_SECURE: u32 = 0x0080_0000;
+
+/// `WINHTTP_OPTION_SECURE_PROTOCOLS`, and the TLS 1.0 to 1.2 protocol
+/// flags. Windows 7 only enables TLS 1.0 by default, which webhook
+/// services no longer accept.
+const WINHTTP_OPTION_SECURE_PROTOCOLS: u32 = 84;
+const SECURE_PROTOCOLS: u32 = 0x0080 | 0x0200 | 0x0800;
+
+/// `WINHTTP_QUERY_STATUS_CODE` as a number, with
+/// `WINHTTP_QUERY_FLAG_NUMBER`
+const WINHTTP_QUERY_STATUS_CODE: u32 = 19 | 0x2000_0000;
+
+/// Timeout of each step of a webhook request, in milliseconds
+const WEBHOOK_TIMEOUT_MS: i32 = 30_000;
+
+/// Rust implementation of `URL_COMPONENTS`
+#[repr(C)]
+struct UrlComponents {
+    struct_size:       u32,
+    scheme:            *mut u16,
+    scheme_length:     u32,
+    n_scheme:          i32,
+    host_name:         *mut u16,
+    host_name_length:  u32,
+    port:              u16,
+    user_name:         *mut u16,
+    user_name_length:  u32,
+    password:          *mut u16,
+    password_length:   u32,
+    url_path:          *mut u16,
+    url_path_length:   u32,
+    extra_info:        *mut u16,
+    extra_info_length: u32,
+}
+
+/// A WinHTTP handle, closed on drop
+struct HttpHandle(usize);
+
+impl HttpHandle {
+    /// Wrap the result of a WinHTTP call returning a handle, 0 on failure
+    fn new(handle: usize) -> io::Result<Self> {
+        if handle == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(HttpHandle(handle))
+    }
+}
+
+impl Drop for HttpHandle {
+    fn drop(&mut self) {
+        unsafe { WinHttpCloseHandle(self.0); }
+    }
+}
+
+/// Convert a Rust UTF-8 `string` into a NUL-terminated UTF-16 vector
+fn str_to_utf16(string: &str) -> Vec<u16> {
+    let mut ret: Vec<u16> = string.encode_utf16().collect();
+    ret.push(0);
+    ret
+}
+
+/// Turn a zero `BOOL` returned by WinHTTP into the last error
+fn check(ret: i32) -> io::Result<()> {
+    if ret == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// POST the JSON `payload` to `url`, returning the HTTP status code
+fn post_json(url: &str, payload: &str) -> io::Result<u32> {
+    let wide_url = str_to_utf16(url);
+
+    // Have WinHTTP point the components into `wide_url`
+    let mut parts = UrlComponents {
+        struct_size:       std::mem::size_of::<UrlComponents>() as u32,
+        scheme:            std::ptr::null_mut(),
+        scheme_length:     !0,
+        n_scheme:          0,
+        host_name:         std::ptr::null_mut(),
+        host_name_length:  !0,
+        port:              0,
+        user_name:         std::ptr::null_mut(),
+        user_name_length:  0,
+        password:          std::ptr::null_mut(),
+        password_length:   0,
+        url_path:          std::ptr::null_mut(),
+        url_path_length:   !0,
+        extra_info:        std::ptr::null_mut(),
+        extra_info_length: !0,
+    };
+    check(unsafe { WinHttpCrackUrl(wide_url.as_ptr(), 0, 0, &mut parts) })?;
+
+    // The path and query string are next to each other in the URL
+    let (host, object) = unsafe {
+        let host = std::slice::from_raw_parts(parts.host_name,
+            parts.host_name_length as usize);
+        let object = std::slice::from_raw_parts(parts.url_path,
+            (parts.url_path_length + parts.extra_info_length) as usize);
+        (String::from_utf16_lossy(host), String::from_utf16_lossy(object))
+    };
+    let secure = parts.n_scheme == INTERNET_SCHEME_HTTPS;
+
+    let agent = str_to_utf16("guifuzz");
+    let session = HttpHandle::new(unsafe {
+        WinHttpOpen(agent.as_ptr(), WINHTTP_ACCESS_TYPE_DEFAULT_PROXY,
+            std::ptr::null(), std::ptr::null(), 0)
+    })?;
+    unsafe {
+        // Older systems don't know about TLS 1.1 and 1.2 and fail this, the
+        // request then goes out with what they support
+        WinHttpSetOption(session.0, WINHTTP_OPTION_SECURE_PROTOCOLS,
+            &SECURE_PROTOCOLS as *const u32 as *const u8, 4);
+        check(WinHttpSetTimeouts(session.0, WEBHOOK_TIMEOUT_MS,
+            WEBHOOK_TIMEOUT_MS, WEBHOOK_TIMEOUT_MS, WEBHOOK_TIMEOUT_MS))?;
+    }
+
+    let host = str_to_utf16(&host);
+    let connection = HttpHandle::new(unsafe {
+        WinHttpConnect(session.0, host.as_ptr(), parts.port, 0)
+    })?;
+
+    let verb   = str_to_utf16("POST");
+    let object = str_to_utf16(&object);
+    let request = HttpHandle::new(unsafe {
+        WinHttpOpenRequest(connection.0, verb.as_ptr(), object.as_ptr(),
+            std::ptr::null(), std::ptr::null(), std::ptr::null(),
+            if secure { WINHTTP_FLAG_SECURE } else { 0 })
+    })?;
+
+    let headers = str_to_utf16("Content-Type: application/json\r\n");
+    let mut status = 0u32;
+    let mut status_len = std::mem::size_of_val(&status) as u32;
+    unsafe {
+        check(WinHttpSendRequest(request.0, headers.as_ptr(), !0,
+            payload.as_ptr(), payload.len() as u32, payload.len() as u32,
+            0))?;
+        check(WinHttpReceiveResponse(request.0, 0))?;
+        check(WinHttpQueryHeaders(request.0, WINHTTP_QUERY_STATUS_CODE,
+            std::ptr::null(), &mut status, &mut status_len,
+            std::ptr::null_mut()))?;
+    }
+
+    Ok(status)
+}
+
+/// Escape `string` for use inside a JSON string literal
+fn json_escape(string: &str) -> String {
+    let mut escaped = String::new();
+    for chr in string.chars() {
+        match chr {
+            '"'  => escaped += "\\\"",
+            '\\' => escaped += "\\\\",
+            '\n' => escaped += "\\n",
+            '\r' => escaped += "\\r",
+            '\t' => escaped += "\\t",
+            x if (x as u32) < 0x20 => {
+                escaped += &format!("\\u{:04x}", x as u32);
+            }
+            x => escaped.push(x),
+        }
+    }
+    escaped
+}
+
+/// Sends notifications on new crash buckets and campaign stalls to a webhook
+pub struct Notifier {
+    /// URL to POST notifications to, `None` disables notifications
+    webhook_url: Option<String>,
+
+    /// Number of seconds without new coverage before a stall is reported
+    stall_secs: Option<u64>,
+
+    /// Crash buckets we have already sent a notification for
+    notified_crashes: HashSet<String>,
+
+    /// Coverage at the last check, and the uptime when it last changed
+    last_coverage: (usize, u64),
+
+    /// Set once a stall has been reported, cleared when coverage increases
+    stalled: bool,
+}
+
+impl Notifier {
+    /// Create a new notifier based on the webhook settings in `config`
+    pub fn new(config: &Config) -> Self {
+        Notifier {
+            webhook_url:      config.webhook_url.clone(),
+            stall_secs:       config.stall_notify_secs,
+            notified_crashes: HashSet::new(),
+            last_coverage:    (0, 0),
+            stalled:          false,
+        }
+    }
+
+    /// POST `message` to the webhook in the background. The payload carries
+    /// the message as both `text` and `content` so it is accepted by Slack
+    /// and Discord as well as generic webhooks.
+    pub fn send(&self, message: &str) {
+        let url = match &self.webhook_url {
+            Some(url) => url.clone(),
+            None => return,
+        };
+
+        let message = json_escape(message);
+        let payload = format!("{{\"text\":\"{}\",\"content\":\"{}\"}}",
+            message, message);
+
+        // Don't stall the campaign on a slow or unreachable webhook
+        std::thread::spawn(move || {
+            match post_json(&url, &payload) {
+                Ok(status) if (200..300).contains(&status) => {}
+                Ok(status) => {
+                    println!("Webhook notification rejected with HTTP status \
+                        {}", status);
+                }
+                Err(err) => {
+                    println!("Failed to send webhook notification: {}", err);
+                }
+            }
+        });
+    }
+
+    /// Check the campaign for new crash buckets and stalls, sending a
+    /// notification for each new event
+    pub fn check(&mut self, campaign: &Campaign) {
+        if self.webhook_url.is_none() {
+            return;
+        }
+
+        let uptime = campaign.uptime().as_secs();
+
+        let (new_crashes, coverage) = {
+            let stats = campaign.lock();
+            let new_crashes: Vec<(String, u64)> = stats.crash_db.iter()
+                .filter(|bucket| !self.notified_crashes.contains(&bucket.name))
+                .map(|bucket| (bucket.name.clone(), bucket.hits))
+                .collect();
+            (new_crashes, stats.coverage_db.len())
+        };
+
+        for (name, hits) in new_crashes {
+            self.send(&format!("guifuzz: new unique crash {} ({} hits) \
+                                after {} seconds", name, hits, uptime));
+            self.notified_crashes.insert(name);
+        }
+
+        if coverage != self.last_coverage.0 {
+            self.last_coverage = (coverage, uptime);
+            self.stalled = false;
+        } else if let Some(stall_secs) = self.stall_secs {
+            let stalled_for = uptime - self.last_coverage.1;
+            if !self.stalled && stalled_for >= stall_secs {
+                self.send(&format!("guifuzz: campaign stalled, no new \
+                                    coverage for {} seconds ({} coverage)",
+                                   stalled_for, coverage));
+                self.stalled = true;
+            }
+        }
+    }
+}
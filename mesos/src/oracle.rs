@@ -0,0 +1,349 @@
+//! Bug oracles, deciding whether a fuzz case found a bug
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use debugger::ExitType;
+use guifuzz::{Config, Window, Uia, UiaSnapshot, Plugin, PluginCase};
+use guifuzz::{WindowNode, CrashInfo, Tags, windowtree, tags, triage};
+use guifuzz::{plugin, InputBackend, verify_epilogue, UiState, StateDiff};
+
+/// Allow querying limited information about a process
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+
+/// `GetGuiResources()` flag for the GDI object count
+const GR_GDIOBJECTS: u32 = 0;
+
+/// `GetGuiResources()` flag for the USER object count
+const GR_USEROBJECTS: u32 = 1;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn GetProcessHandleCount(process: usize, count: *mut u32) -> i32;
+    fn CloseHandle(handle: usize) -> i32;
+}
+
+#[link(name="User32")]
+extern "system" {
+    fn GetGuiResources(process: usize, flags: u32) -> u32;
+}
+
+/// Resource usage of the target process
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessResources {
+    /// Number of open kernel handles
+    pub handles: u32,
+
+    /// Number of GDI objects
+    pub gdi_objects: u32,
+
+    /// Number of USER objects
+    pub user_objects: u32,
+}
+
+impl ProcessResources {
+    /// Query the current resource usage of `pid`
+    pub fn query(pid: u32) -> io::Result<Self> {
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0,
+                pid);
+            if process == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut handles = 0;
+            let ret = GetProcessHandleCount(process, &mut handles);
+            let resources = ProcessResources {
+                handles,
+                gdi_objects:  GetGuiResources(process, GR_GDIOBJECTS),
+                user_objects: GetGuiResources(process, GR_USEROBJECTS),
+            };
+
+            CloseHandle(process);
+            if ret == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(resources)
+        }
+    }
+}
+
+/// State of the target observed by the fuzzer thread after performing the
+/// actions of a case, while the target is still alive
+#[derive(Clone, Debug, Default)]
+pub struct Observations {
+    /// The target's main window stopped processing messages
+    pub hung: bool,
+
+    /// Resource usage of the target, if it could be queried
+    pub resources: Option<ProcessResources>,
+
+    /// Snapshot of the UI Automation tree, if enabled and available
+    pub uia: Option<UiaSnapshot>,
+
+    /// Dump of the main window's tree, if enabled
+    pub window_tree: Option<WindowNode>,
+
+    /// Differences between the window tree before and after the actions
+    pub ui_changes: Vec<String>,
+
+    /// Differences between the UI state before and after the actions, if
+    /// enabled
+    pub state_diff: Option<StateDiff>,
+
+    /// Tags for the dialogs the target had open
+    pub window_tags: Tags,
+
+    /// Why the target didn't respond to the epilogue, if it didn't
+    pub unresponsive: Option<String>,
+}
+
+impl Observations {
+    /// Observe the target `pid` with the main window `window`, using the
+    /// limits from `config`. `initial_tree` is the window tree dumped before
+    /// the actions, if window tree dumps are enabled, and `initial_state`
+    /// the UI state captured before them, if UI states are enabled.
+    pub fn observe(pid: u32, window: &Window, config: &Config,
+                   initial_tree: Option<&WindowNode>,
+                   initial_state: Option<&UiState>) -> Self {
+        let window_tree = initial_tree.map(|_| window.dump_tree());
+        let ui_changes = match (initial_tree, &window_tree) {
+            (Some(old), Some(new)) => windowtree::diff(old, new),
+            _ => Vec::new(),
+        };
+        let state_diff = initial_state
+            .map(|x| x.diff(&UiState::capture(window)));
+
+        let hung = config.hang_timeout_ms.map(|timeout| {
+            window.is_hung(Duration::from_millis(timeout))
+        }).unwrap_or(false);
+        let resources = ProcessResources::query(pid).ok();
+        let uia = if config.uia_snapshot {
+            Uia::new().and_then(|uia| uia.snapshot(window)).ok()
+        } else {
+            None
+        };
+        let window_tags = tags::window_tags(pid);
+
+        // The epilogue acts on the target, so it goes after everything else
+        // is observed. A hung target is already reported.
+        let unresponsive = if hung {
+            None
+        } else {
+            verify_epilogue(pid, window, InputBackend::PostMessage)
+        };
+
+        Observations {
+            hung,
+            resources,
+            uia,
+            window_tree,
+            ui_changes,
+            state_diff,
+            window_tags,
+            unresponsive,
+        }
+    }
+}
+
+/// Everything known about a finished fuzz case
+pub struct CaseResult<'a> {
+    /// PID of the target
+    pub pid: u32,
+
+    /// How the debugger saw the target exit
+    pub exit_state: &'a ExitType,
+
+    /// Symbolized call stack of the crash, if the target crashed
+    pub crash_stack: &'a [String],
+
+    /// Machine state of the crash, if the target crashed
+    pub crash_info: Option<&'a CrashInfo>,
+
+    /// Tail of the target's debug strings and standard output
+    pub output: &'a [String],
+
+    /// State of the target observed after the actions were performed
+    pub observations: Observations,
+}
+
+/// A bug found by an oracle
+pub struct Bug {
+    /// Name of the bucket the bug is saved in
+    pub name: String,
+
+    /// Details saved with the first bug in a bucket, one line per entry
+    pub details: Vec<String>,
+
+    /// Machine state, for bugs which are crashes caught by the debugger
+    pub info: Option<CrashInfo>,
+}
+
+/// Something which can decide if a fuzz case found a bug
+pub trait BugOracle: Send + Sync {
+    /// Check the result of a case, returning a bug if one was found
+    fn check(&self, result: &CaseResult) -> Option<Bug>;
+}
+
+/// Reports crashes caught by the debugger, bucketed by their triaged
+/// signature
+pub struct CrashOracle;
+
+impl BugOracle for CrashOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        match result.exit_state {
+            ExitType::Crash(name) => Some(Bug {
+                name:    triage::bucket(name, result.crash_info,
+                    result.crash_stack),
+                details: result.crash_stack.to_vec(),
+                info:    result.crash_info.cloned(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Reports targets which stopped processing window messages
+pub struct HangOracle;
+
+impl BugOracle for HangOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        if !result.observations.hung {
+            return None;
+        }
+
+        Some(Bug {
+            name:    "hang".into(),
+            details: vec!["Main window stopped processing messages".into()],
+            info:    None,
+        })
+    }
+}
+
+/// Reports targets whose handle, GDI, or USER object counts exceed a
+/// threshold, which usually means they are leaking them
+pub struct LeakOracle {
+    /// Number of objects of any one kind considered a leak
+    pub threshold: u32,
+}
+
+impl BugOracle for LeakOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        let resources = result.observations.resources?;
+
+        let leaks = [
+            ("handles", resources.handles),
+            ("gdi",     resources.gdi_objects),
+            ("user",    resources.user_objects),
+        ];
+        let &(kind, count) = leaks.iter()
+            .find(|&&(_, count)| count >= self.threshold)?;
+
+        Some(Bug {
+            name:    format!("leak_{}", kind),
+            details: vec![format!("{} {} objects, threshold {}", count, kind,
+                self.threshold)],
+            info:    None,
+        })
+    }
+}
+
+/// Reports UI Automation properties with impossible values, such as a range
+/// value outside of its minimum and maximum
+pub struct UiaInvariantOracle;
+
+impl BugOracle for UiaInvariantOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        let snapshot = result.observations.uia.as_ref()?;
+        if snapshot.violations.is_empty() {
+            return None;
+        }
+
+        Some(Bug {
+            name:    "uia_invariant".into(),
+            details: snapshot.violations.clone(),
+            info:    None,
+        })
+    }
+}
+
+/// Reports targets which stopped responding to the epilogue, meaning the
+/// case left them in a broken state without crashing them
+pub struct EpilogueOracle;
+
+impl BugOracle for EpilogueOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        let reason = result.observations.unresponsive.as_ref()?;
+
+        Some(Bug {
+            name:    "state-corrupting".into(),
+            details: vec![reason.clone()],
+            info:    None,
+        })
+    }
+}
+
+/// Reports bugs found by a plugin's `guifuzz_check()`
+pub struct PluginOracle(pub Arc<Plugin>);
+
+impl BugOracle for PluginOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        let resources = result.observations.resources.unwrap_or_default();
+        let case = PluginCase {
+            pid:          result.pid,
+            crashed:      matches!(result.exit_state, ExitType::Crash(_))
+                as u32,
+            hung:         result.observations.hung as u32,
+            handles:      resources.handles,
+            gdi_objects:  resources.gdi_objects,
+            user_objects: resources.user_objects,
+        };
+
+        let name = self.0.check(&case)?;
+        Some(Bug {
+            name:    format!("plugin_{}", name),
+            details: vec![format!("Reported by plugin {}", self.0.path)],
+            info:    None,
+        })
+    }
+}
+
+/// Function deciding whether a case found a bug
+pub type Hook = dyn Fn(&CaseResult) -> Option<Bug> + Send + Sync;
+
+/// Reports bugs using an arbitrary function, for one-off detectors
+pub struct HookOracle(pub Box<Hook>);
+
+impl BugOracle for HookOracle {
+    fn check(&self, result: &CaseResult) -> Option<Bug> {
+        (self.0)(result)
+    }
+}
+
+/// Create the oracles enabled in `config`
+pub fn oracles(config: &Config) -> Vec<Box<dyn BugOracle>> {
+    let mut oracles: Vec<Box<dyn BugOracle>> = vec![Box::new(CrashOracle)];
+
+    if config.hang_timeout_ms.is_some() {
+        oracles.push(Box::new(HangOracle));
+    }
+
+    if let Some(threshold) = config.leak_threshold {
+        oracles.push(Box::new(LeakOracle { threshold }));
+    }
+
+    if config.uia_snapshot {
+        oracles.push(Box::new(UiaInvariantOracle));
+    }
+
+    if config.epilogue.is_some() {
+        oracles.push(Box::new(EpilogueOracle));
+    }
+
+    for plugin in plugin::plugins() {
+        oracles.push(Box::new(PluginOracle(plugin)));
+    }
+
+    oracles
+}
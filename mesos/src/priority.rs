@@ -0,0 +1,70 @@
+//! Lowered scheduling priority of target processes, so a campaign with many
+//! workers leaves the host usable. Only targets are lowered, the harness
+//! keeps its normal priority so its timeouts stay accurate.
+
+use std::io;
+use std::sync::Mutex;
+use guifuzz::TargetPriority;
+
+/// Allow setting the priority of a process
+const PROCESS_SET_INFORMATION: u32 = 0x0200;
+
+/// `BELOW_NORMAL_PRIORITY_CLASS`
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+
+/// `IDLE_PRIORITY_CLASS`
+const IDLE_PRIORITY_CLASS: u32 = 0x0040;
+
+/// `PROCESS_INFORMATION_CLASS` for `ProcessMemoryPriority`
+const PROCESS_MEMORY_PRIORITY: u32 = 0;
+
+/// `MEMORY_PRIORITY_VERY_LOW`
+const MEMORY_PRIORITY_VERY_LOW: u32 = 1;
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn CloseHandle(handle: usize) -> i32;
+    fn SetPriorityClass(process: usize, class: u32) -> i32;
+    fn SetProcessInformation(process: usize, class: u32, info: *const u8,
+        size: u32) -> i32;
+}
+
+/// Priority targets are run at
+static TARGET_PRIORITY: Mutex<TargetPriority> =
+    Mutex::new(TargetPriority::Normal);
+
+/// Run targets spawned from now on at `priority`
+pub fn set_target_priority(priority: TargetPriority) {
+    *TARGET_PRIORITY.lock().unwrap() = priority;
+}
+
+/// Lower the priority of the target `pid` to the configured priority. Does
+/// nothing for normal priority.
+pub fn apply(pid: u32) -> io::Result<()> {
+    let priority = *TARGET_PRIORITY.lock().unwrap();
+    let class = match priority {
+        TargetPriority::Normal => return Ok(()),
+        TargetPriority::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+        TargetPriority::Idle | TargetPriority::Background => {
+            IDLE_PRIORITY_CLASS
+        }
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_SET_INFORMATION, 0, pid);
+        if process == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut ok = SetPriorityClass(process, class) != 0;
+        if ok && priority == TargetPriority::Background {
+            ok = SetProcessInformation(process, PROCESS_MEMORY_PRIORITY,
+                &MEMORY_PRIORITY_VERY_LOW as *const u32 as *const u8,
+                std::mem::size_of::<u32>() as u32) != 0;
+        }
+        let err = io::Error::last_os_error();
+        CloseHandle(process);
+        if ok { Ok(()) } else { Err(err) }
+    }
+}
@@ -0,0 +1,93 @@
+//! Recording of the target's main window during a case, keeping the last
+//! few seconds of low frame rate captures in a ring buffer. They are saved
+//! along with new crashes and hangs, as painting and layout bugs are much
+//! easier to understand when seeing what led up to them.
+
+use std::io;
+use std::fs;
+use std::path::Path;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use guifuzz::Window;
+use guifuzz::capture::{self, Frame};
+
+/// Frames recorded during a case, with the time since recording started
+pub type Recording = Vec<(Duration, Frame)>;
+
+/// Recorder of a target's main window for the duration of a case
+pub struct Recorder {
+    /// Tells the recording thread to stop
+    stop: Arc<AtomicBool>,
+
+    /// Recording thread, returning the frames it kept
+    thread: Option<JoinHandle<Recording>>,
+}
+
+impl Recorder {
+    /// Start recording the first window in `windows`, once the fuzzer
+    /// attached to one, at `fps` frames per second keeping the last
+    /// `seconds` of frames
+    pub fn start(windows: Arc<Mutex<Vec<Window>>>, fps: u64, seconds: u64)
+            -> Self {
+        let fps = fps.max(1);
+        let interval = Duration::from_millis(1000 / fps);
+        let keep = (fps * seconds).max(1) as usize;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let start = Instant::now();
+                let mut frames = VecDeque::with_capacity(keep);
+                while !stop.load(Ordering::SeqCst) {
+                    thread::sleep(interval);
+
+                    let window = windows.lock().unwrap().first().cloned();
+                    let frame = match window.map(|x| capture::capture(&x)) {
+                        Some(Ok(frame)) => frame,
+                        _ => continue,
+                    };
+                    if frames.len() == keep {
+                        frames.pop_front();
+                    }
+                    frames.push_back((start.elapsed(), frame));
+                }
+                frames.into_iter().collect()
+            })
+        };
+
+        Recorder { stop, thread: Some(thread) }
+    }
+
+    /// Stop recording, returning the frames kept
+    pub fn finish(mut self) -> Recording {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.take().and_then(|x| x.join().ok()).unwrap_or_default()
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Save `recording` to the directory `dir` as numbered BMP files, along
+/// with an index of when each frame was captured
+pub fn save(recording: &Recording, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut index = String::new();
+    for (idx, (time, frame)) in recording.iter().enumerate() {
+        let name = format!("frame_{:04}.bmp", idx);
+        fs::write(dir.join(&name), frame.to_bmp())?;
+        index += &format!("{} {:.3}s\n", name, time.as_secs_f64());
+    }
+    fs::write(dir.join("frames.txt"), index)
+}
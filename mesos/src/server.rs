@@ -0,0 +1,99 @@
+//! Execution service mode, running inputs sent over TCP against fresh
+//! targets so external schedulers, cluster managers, and research
+//! frameworks can reuse the harness' execution machinery.
+//!
+//! A request is a little endian `u32` length followed by that many bytes of
+//! an input encoded with `guifuzz::bytecodec`. The response is a `u32`
+//! length followed by that many bytes of text: an `exit = normal`,
+//! `exit = crash <name>`, or `error = <message>` line, followed by one
+//! `module+0xoffset` line per coverage entry. Connections may send any
+//! number of requests, and are served concurrently.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use guifuzz::{FuzzInput, bytecodec};
+
+/// Largest request accepted, bigger requests close the connection
+const MAX_REQUEST: usize = 1024 * 1024;
+
+/// Result of executing an input
+#[derive(Clone, Debug, Default)]
+pub struct Execution {
+    /// Name of the crash, if the target crashed
+    pub crash: Option<String>,
+
+    /// Coverage entries hit, as `module+0xoffset`
+    pub coverage: Vec<String>,
+}
+
+/// Executes an input, returning its result or why it couldn't be executed
+pub type Executor = dyn Fn(&FuzzInput) -> Result<Execution, String> +
+    Send + Sync;
+
+/// Format the response to a request executed with `result`
+fn response(result: Result<Execution, String>) -> String {
+    match result {
+        Ok(execution) => {
+            let exit = match execution.crash {
+                Some(name) => format!("exit = crash {}\n", name),
+                None => "exit = normal\n".to_string(),
+            };
+            let coverage: Vec<String> = execution.coverage.iter()
+                .map(|x| format!("{}\n", x)).collect();
+            exit + &coverage.concat()
+        }
+        Err(err) => format!("error = {}\n", err.replace('\n', " ")),
+    }
+}
+
+/// Serve the requests of one connection until it is closed
+fn serve_connection(mut stream: TcpStream, executor: &Executor)
+        -> io::Result<()> {
+    loop {
+        let mut len = [0u8; 4];
+        match stream.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(());
+            }
+            Err(err) => return Err(err),
+        }
+
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_REQUEST {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Request of {} bytes is too big", len)));
+        }
+        let mut request = vec![0u8; len];
+        stream.read_exact(&mut request)?;
+
+        let input = FuzzInput::new(bytecodec::decode(&request));
+        let reply = response(executor(&input));
+        stream.write_all(&(reply.len() as u32).to_le_bytes())?;
+        stream.write_all(reply.as_bytes())?;
+    }
+}
+
+/// Accept connections on `listener` forever, executing the inputs they
+/// send with `executor`. Each connection is served on its own thread, how
+/// many targets run at once is up to the executor.
+pub fn serve(listener: TcpListener, executor: Arc<Executor>) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let peer = stream.peer_addr().map(|x| x.to_string())
+            .unwrap_or_default();
+        let executor = executor.clone();
+
+        thread::spawn(move || {
+            if let Err(err) = serve_connection(stream, &*executor) {
+                println!("Connection from {} failed: {}", peer, err);
+            }
+        });
+    }
+    Ok(())
+}
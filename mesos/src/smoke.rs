@@ -0,0 +1,110 @@
+//! Smoke testing of a target config or input backend before launching a
+//! full campaign. The top inputs of an existing corpus are replayed one
+//! after the other, and the union of their coverage has to reach a minimum
+//! for the setup to be considered working.
+//!
+//! Inputs are loaded from the `.bytes` files saved alongside each input,
+//! encoded with `guifuzz::bytecodec`. They are ranked by the size of their
+//! isolated coverage when it was measured, then by their number of actions.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::collections::HashSet;
+use guifuzz::{FuzzInput, bytecodec};
+
+/// Load the `top` best inputs saved in the inputs directory `dir`, with
+/// their file names
+pub fn top_inputs(dir: &Path, top: usize)
+        -> io::Result<Vec<(String, FuzzInput)>> {
+    let mut ranked = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map(|x| x != "bytes").unwrap_or(true) {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|x| x.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        let input: FuzzInput = Arc::new(bytecodec::decode(&fs::read(&path)?));
+        let coverage = fs::read_to_string(path.with_extension("cov"))
+            .map(|x| x.lines().count()).unwrap_or(0);
+        ranked.push((coverage, input.len(), name, input));
+    }
+
+    // Best first, ties broken by name so runs are repeatable
+    ranked.sort_by(|a, b| (b.0, b.1, &a.2).cmp(&(a.0, a.1, &b.2)));
+    Ok(ranked.into_iter().take(top)
+        .map(|(_, _, name, input)| (name, input)).collect())
+}
+
+/// Results of a smoke test
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Number of inputs replayed
+    pub inputs: usize,
+
+    /// Inputs which couldn't be replayed, with why
+    pub errors: Vec<(String, String)>,
+
+    /// Inputs which crashed the target, with the name of the crash
+    pub crashes: Vec<(String, String)>,
+
+    /// Union of the coverage of all inputs
+    pub coverage: HashSet<(Arc<String>, usize)>,
+
+    /// Coverage entries the union has to reach
+    pub min_coverage: usize,
+}
+
+impl Report {
+    /// Start a report of a smoke test requiring `min_coverage` entries
+    pub fn new(min_coverage: usize) -> Self {
+        Report { min_coverage, ..Default::default() }
+    }
+
+    /// Add the replay of the input `name`, which crashed with `crash` and
+    /// hit `coverage`
+    pub fn add<I>(&mut self, name: &str, crash: Option<String>, coverage: I)
+            where I: IntoIterator<Item = (Arc<String>, usize)> {
+        self.inputs += 1;
+        if let Some(crash) = crash {
+            self.crashes.push((name.to_string(), crash));
+        }
+        self.coverage.extend(coverage);
+    }
+
+    /// Add the input `name` which couldn't be replayed due to `err`
+    pub fn add_error(&mut self, name: &str, err: &str) {
+        self.inputs += 1;
+        self.errors.push((name.to_string(), err.to_string()));
+    }
+
+    /// Check whether the setup works, every input replayed and together
+    /// they reached the minimum coverage. Crashes are reported but don't
+    /// fail the test, the corpus may well contain known crashes.
+    pub fn ok(&self) -> bool {
+        self.inputs > 0 && self.errors.is_empty() &&
+            self.coverage.len() >= self.min_coverage
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, err) in &self.errors {
+            writeln!(f, "{}: failed to replay: {}", name, err)?;
+        }
+        for (name, crash) in &self.crashes {
+            writeln!(f, "{}: crashed: {}", name, crash)?;
+        }
+        writeln!(f, "Replayed {} inputs, {} coverage entries (minimum {}), \
+                   {} crashes, {} errors: {}",
+            self.inputs, self.coverage.len(), self.min_coverage,
+            self.crashes.len(), self.errors.len(),
+            if self.ok() { "ok" } else { "FAILED" })
+    }
+}
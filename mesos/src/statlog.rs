@@ -0,0 +1,129 @@
+//! Periodic campaign statistics log in CSV form, with an optional SVG chart
+//! of coverage over time
+
+use std::io;
+use std::io::Write;
+use std::fs::File;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Chart dimensions in pixels
+const CHART_WIDTH:  f64 = 800.;
+const CHART_HEIGHT: f64 = 400.;
+const CHART_MARGIN: f64 = 50.;
+
+/// One sample of the campaign statistics
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sample {
+    /// Seconds since the campaign started
+    pub uptime: f64,
+
+    /// Number of fuzz cases executed
+    pub fuzz_cases: u64,
+
+    /// Number of unique coverage entries
+    pub coverage: usize,
+
+    /// Number of inputs in the corpus
+    pub corpus: usize,
+
+    /// Total number of crashes
+    pub crashes: u64,
+
+    /// Number of unique crash buckets
+    pub unique_crashes: usize,
+}
+
+/// Writer for the statistics CSV and chart
+pub struct StatLog {
+    /// CSV file we append samples to
+    csv: File,
+
+    /// All samples taken so far, kept for the chart
+    samples: Vec<Sample>,
+
+    /// Path to write the SVG chart to, if enabled
+    svg_path: Option<String>,
+}
+
+impl StatLog {
+    /// Create a new statistics log at `csv_path`, writing the CSV header. If
+    /// `svg_path` is set a chart is also rendered there by `render_chart()`
+    pub fn new(csv_path: &str, svg_path: Option<String>) -> io::Result<Self> {
+        let mut csv = File::create(csv_path)?;
+        writeln!(csv, "unix_time,uptime,fuzz_cases,coverage,corpus,crashes,\
+                     unique_crashes,execs_per_sec")?;
+
+        Ok(StatLog { csv, samples: Vec::new(), svg_path })
+    }
+
+    /// Record a new sample
+    pub fn log(&mut self, sample: Sample) -> io::Result<()> {
+        let unix_time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs()).unwrap_or(0);
+
+        // Compute the execution rate since the last sample
+        let execs_per_sec = match self.samples.last() {
+            Some(last) if sample.uptime > last.uptime => {
+                (sample.fuzz_cases - last.fuzz_cases) as f64 /
+                    (sample.uptime - last.uptime)
+            }
+            _ => 0.,
+        };
+
+        writeln!(self.csv, "{},{:.0},{},{},{},{},{},{:.2}",
+            unix_time, sample.uptime, sample.fuzz_cases, sample.coverage,
+            sample.corpus, sample.crashes, sample.unique_crashes,
+            execs_per_sec)?;
+        self.csv.flush()?;
+
+        self.samples.push(sample);
+        Ok(())
+    }
+
+    /// Render the coverage and corpus size over time to the SVG chart, if
+    /// enabled
+    pub fn render_chart(&self) -> io::Result<()> {
+        let svg_path = match &self.svg_path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let max_time = self.samples.iter().map(|x| x.uptime)
+            .fold(1., f64::max);
+
+        // Build a polyline for a given statistic, scaled to its own maximum
+        let polyline = |value: &dyn Fn(&Sample) -> f64, color: &str| {
+            let max = self.samples.iter().map(value).fold(1., f64::max);
+            let points: Vec<String> = self.samples.iter().map(|sample| {
+                let x = CHART_MARGIN + sample.uptime / max_time *
+                    (CHART_WIDTH - 2. * CHART_MARGIN);
+                let y = CHART_HEIGHT - CHART_MARGIN - value(sample) / max *
+                    (CHART_HEIGHT - 2. * CHART_MARGIN);
+                format!("{:.1},{:.1}", x, y)
+            }).collect();
+            format!("<polyline fill=\"none\" stroke=\"{}\" points=\"{}\"/>\n",
+                color, points.join(" "))
+        };
+
+        let last = self.samples.last().cloned().unwrap_or_default();
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" \
+             height=\"{h}\">\n\
+             <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n\
+             <line x1=\"{m}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" \
+             stroke=\"black\"/>\n\
+             <line x1=\"{m}\" y1=\"{m}\" x2=\"{m}\" y2=\"{b}\" \
+             stroke=\"black\"/>\n\
+             <text x=\"{m}\" y=\"{t}\">coverage (blue, {cov}), \
+             corpus (green, {corpus}) over {time:.0} seconds</text>\n",
+            w = CHART_WIDTH, h = CHART_HEIGHT, m = CHART_MARGIN,
+            r = CHART_WIDTH - CHART_MARGIN, b = CHART_HEIGHT - CHART_MARGIN,
+            t = CHART_MARGIN / 2., cov = last.coverage, corpus = last.corpus,
+            time = max_time);
+        svg += &polyline(&|x| x.coverage as f64, "blue");
+        svg += &polyline(&|x| x.corpus as f64, "green");
+        svg += "</svg>\n";
+
+        std::fs::write(svg_path, svg)
+    }
+}
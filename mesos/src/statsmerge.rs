@@ -0,0 +1,190 @@
+//! Merging of several campaign directories, such as those of campaigns run
+//! on different machines, into one. Coverage is unioned, crash buckets are
+//! deduplicated by name, and the corpora are combined into one ready to be
+//! handed back out to all machines.
+
+use std::io;
+use std::fs;
+use std::fmt;
+use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use corpusdir::copy_new;
+
+/// Statistics log of a campaign, the last sample holds its totals
+const STATS_CSV: &str = "fuzz_stats.csv";
+
+/// Coverage database of a campaign, as saved by the debugger
+const COVERAGE_TXT: &str = "coverage.txt";
+
+/// Union of the coverage of all campaigns, saved to the merged directory
+const COVERAGE_UNION: &str = "coverage_union.txt";
+
+/// Report of the merged campaign, saved to the merged directory
+const MERGE_REPORT: &str = "merge_report.txt";
+
+/// Combined statistics of merged campaigns
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// Number of campaigns merged
+    pub campaigns: usize,
+
+    /// Total number of fuzz cases of all campaigns
+    pub fuzz_cases: u64,
+
+    /// Total number of crashes of all campaigns, including duplicates
+    pub crashes: u64,
+
+    /// Number of unique coverage entries of all campaigns combined
+    pub coverage: usize,
+
+    /// Number of unique coverage entries of the campaign with the most
+    pub best_coverage: usize,
+
+    /// Number of inputs in the merged corpus
+    pub corpus: usize,
+
+    /// Unique crash buckets, with the campaigns each was found by
+    pub buckets: BTreeMap<String, Vec<String>>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "campaigns      = {}\n\
+                   fuzz_cases     = {}\n\
+                   crashes        = {}\n\
+                   unique_crashes = {}\n\
+                   coverage       = {}\n\
+                   best_coverage  = {}\n\
+                   corpus         = {}\n",
+            self.campaigns, self.fuzz_cases, self.crashes,
+            self.buckets.len(), self.coverage, self.best_coverage,
+            self.corpus)?;
+
+        for (bucket, campaigns) in self.buckets.iter() {
+            writeln!(f, "crash {} found by {}", bucket, campaigns.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a coverage entry of the form `module+0xoffset`
+fn parse_entry(entry: &str) -> Option<(String, usize)> {
+    let plus = entry.trim().rfind('+')?;
+    let (module, offset) = entry.trim().split_at(plus);
+    let offset = usize::from_str_radix(offset[1..].trim_start_matches("0x"),
+        16).ok()?;
+    Some((module.to_string(), offset))
+}
+
+/// Get all coverage entries recorded in the campaign directory `dir`, from
+/// the debugger's coverage database and the isolated coverage of inputs
+fn campaign_coverage(dir: &Path) -> BTreeSet<(String, usize)> {
+    let mut coverage = BTreeSet::new();
+
+    // Lines are `pc | Freq: freq | module+0xoffset | symbol+0xoffset`
+    if let Ok(db) = fs::read_to_string(dir.join(COVERAGE_TXT)) {
+        coverage.extend(db.lines()
+            .filter_map(|x| x.split('|').nth(2))
+            .filter_map(parse_entry));
+    }
+
+    let inputs = fs::read_dir(dir.join("inputs")).into_iter().flatten()
+        .filter_map(|x| x.ok()).map(|x| x.path())
+        .filter(|x| x.extension().map(|x| x == "cov").unwrap_or(false));
+    for path in inputs {
+        if let Ok(cov) = fs::read_to_string(path) {
+            coverage.extend(cov.lines().filter_map(parse_entry));
+        }
+    }
+
+    coverage
+}
+
+/// Get the number of fuzz cases and crashes of the campaign directory `dir`
+/// from the last sample of its statistics log
+fn campaign_totals(dir: &Path) -> (u64, u64) {
+    let csv = fs::read_to_string(dir.join(STATS_CSV)).unwrap_or_default();
+    let last: Vec<&str> = csv.lines().skip(1).last()
+        .map(|x| x.split(',').collect()).unwrap_or_default();
+    let column = |idx: usize| {
+        last.get(idx).and_then(|x| x.parse().ok()).unwrap_or(0)
+    };
+    (column(2), column(5))
+}
+
+/// Copy every file in `from` to `to` which isn't already there, returning
+/// the number of files copied
+fn copy_dir(from: &Path, to: &Path) -> io::Result<usize> {
+    fs::create_dir_all(to)?;
+
+    let mut copied = 0;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name().filter(|_| path.is_file()) {
+            if copy_new(&to.join(name), &fs::read(&path)?)? {
+                copied += 1;
+            }
+        }
+    }
+    Ok(copied)
+}
+
+/// Merge the campaign directories `dirs` into `out`, returning a report of
+/// the combined campaign which is also saved to `out`. Merging into an
+/// existing directory only adds to it.
+pub fn merge(dirs: &[&Path], out: &Path) -> io::Result<Report> {
+    let mut report = Report::default();
+    let mut coverage = BTreeSet::new();
+    fs::create_dir_all(out)?;
+
+    for dir in dirs {
+        let name = dir.display().to_string();
+        report.campaigns += 1;
+
+        let (fuzz_cases, crashes) = campaign_totals(dir);
+        report.fuzz_cases += fuzz_cases;
+        report.crashes    += crashes;
+
+        let campaign = campaign_coverage(dir);
+        report.best_coverage = report.best_coverage.max(campaign.len());
+        coverage.extend(campaign);
+
+        // Inputs are named by the hash of their actions, so the same input
+        // found by several campaigns is only kept once
+        let inputs = dir.join("inputs");
+        if inputs.is_dir() {
+            copy_dir(&inputs, &out.join("inputs"))?;
+        }
+
+        // Crash buckets are named by the crash, so the same crash found by
+        // several campaigns is merged into one bucket
+        if let Ok(buckets) = fs::read_dir(dir.join("crashes")) {
+            for bucket in buckets {
+                let path = bucket?.path();
+                let bucket = match path.file_name() {
+                    Some(bucket) if path.is_dir() => bucket,
+                    _ => continue,
+                };
+
+                copy_dir(&path, &out.join("crashes").join(bucket))?;
+                report.buckets.entry(bucket.to_string_lossy().into_owned())
+                    .or_insert_with(Vec::new).push(name.clone());
+            }
+        }
+    }
+
+    report.coverage = coverage.len();
+    report.corpus = fs::read_dir(out.join("inputs")).into_iter().flatten()
+        .filter_map(|x| x.ok())
+        .filter(|x| x.path().extension().map(|x| x == "input")
+            .unwrap_or(false))
+        .count();
+
+    let union: Vec<String> = coverage.iter()
+        .map(|(module, offset)| format!("{}+{:#x}\n", module, offset))
+        .collect();
+    fs::write(out.join(COVERAGE_UNION), union.concat())?;
+    fs::write(out.join(MERGE_REPORT), report.to_string())?;
+
+    Ok(report)
+}
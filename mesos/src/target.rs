@@ -0,0 +1,285 @@
+//! Ownership of a single target process and everything attached to it, torn
+//! down on drop so no error path can leak a process
+
+use std::io;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use debugger::{Debugger, ExitType, CrashContext};
+use guifuzz::{Window, CrashInfo, triage};
+use {affinity, priority};
+
+/// Allow assigning a process to a job object
+const PROCESS_SET_QUOTA: u32 = 0x0100;
+
+/// Allow terminating a process
+const PROCESS_TERMINATE: u32 = 0x0001;
+
+/// `JOBOBJECTINFOCLASS` for `JobObjectExtendedLimitInformation`
+const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION: u32 = 9;
+
+/// Kill all processes in a job when the last handle to the job is closed
+const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+/// Coverage hit by a target, keyed by address, as recorded by the debugger
+type Coverage = HashMap<usize, (Arc<String>, usize, String, u64)>;
+
+/// PIDs of all live targets of all workers, including suspended crashes
+static TARGET_PIDS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Check whether `pid` is a live target of any worker
+pub fn is_target(pid: u32) -> bool {
+    TARGET_PIDS.lock().unwrap().contains(&pid)
+}
+
+/// Record that the target `pid` is gone
+fn forget_target(pid: u32) {
+    TARGET_PIDS.lock().unwrap().retain(|&x| x != pid);
+}
+
+#[link(name="Kernel32")]
+extern "system" {
+    fn CreateJobObjectW(attributes: usize, name: *const u16) -> usize;
+    fn SetInformationJobObject(job: usize, class: u32, info: *const u8,
+        length: u32) -> i32;
+    fn AssignProcessToJobObject(job: usize, process: usize) -> i32;
+    fn OpenProcess(access: u32, inherit: i32, pid: u32) -> usize;
+    fn CloseHandle(handle: usize) -> i32;
+    fn TerminateProcess(process: usize, exit_code: u32) -> i32;
+}
+
+/// Rust implementation of `JOBOBJECT_EXTENDED_LIMIT_INFORMATION`
+#[repr(C)]
+#[derive(Default)]
+struct JobExtendedLimitInformation {
+    per_process_user_time_limit: i64,
+    per_job_user_time_limit:     i64,
+    limit_flags:                 u32,
+    minimum_working_set_size:    usize,
+    maximum_working_set_size:    usize,
+    active_process_limit:        u32,
+    affinity:                    usize,
+    priority_class:              u32,
+    scheduling_class:            u32,
+    io_counters:                 [u64; 6],
+    process_memory_limit:        usize,
+    job_memory_limit:            usize,
+    peak_process_memory_used:    usize,
+    peak_job_memory_used:        usize,
+}
+
+/// Create a job object which kills all of its processes when closed, and
+/// assign `pid` to it
+fn create_kill_on_close_job(pid: u32) -> io::Result<usize> {
+    unsafe {
+        let job = CreateJobObjectW(0, std::ptr::null());
+        if job == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let info = JobExtendedLimitInformation {
+            limit_flags: JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+            ..Default::default()
+        };
+
+        let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0,
+            pid);
+        if process == 0 ||
+                SetInformationJobObject(job,
+                    JOB_OBJECT_EXTENDED_LIMIT_INFORMATION,
+                    &info as *const _ as *const u8,
+                    std::mem::size_of_val(&info) as u32) == 0 ||
+                AssignProcessToJobObject(job, process) == 0 {
+            let err = io::Error::last_os_error();
+            if process != 0 {
+                CloseHandle(process);
+            }
+            CloseHandle(job);
+            return Err(err);
+        }
+
+        CloseHandle(process);
+        Ok(job)
+    }
+}
+
+/// A running target process being debugged, along with its job object and
+/// any windows the fuzzer attached to. Dropping this kills the target,
+/// detaches the debugger, and closes the job, killing anything the target
+/// spawned.
+pub struct TargetInstance<'a> {
+    /// Debugger attached to the target, `None` once torn down
+    dbg: Option<Debugger<'a>>,
+
+    /// PID of the target process
+    pub pid: u32,
+
+    /// Kill-on-close job object holding the target, `None` if the job could
+    /// not be created
+    job: Option<usize>,
+
+    /// Windows of the target the fuzzer attached to, closed on teardown
+    windows: Arc<Mutex<Vec<Window>>>,
+
+    /// Set once the crashed target was handed off to a `SuspendedCrash`,
+    /// teardown then leaves the process alone
+    suspended: bool,
+}
+
+/// A crashed target left suspended at its crash with no debugger attached,
+/// so a human can attach to it. The process is killed on drop, or when the
+/// harness exits.
+pub struct SuspendedCrash {
+    /// PID of the target process
+    pub pid: u32,
+
+    /// Kill-on-close job object holding the target
+    job: Option<usize>,
+}
+
+impl Drop for SuspendedCrash {
+    fn drop(&mut self) {
+        unsafe {
+            let process = OpenProcess(PROCESS_TERMINATE, 0, self.pid);
+            if process != 0 {
+                TerminateProcess(process, 0);
+                CloseHandle(process);
+            }
+            if let Some(job) = self.job.take() {
+                CloseHandle(job);
+            }
+        }
+        forget_target(self.pid);
+    }
+}
+
+impl<'a> TargetInstance<'a> {
+    /// Spawn the target `argv` under the debugger
+    pub fn spawn(argv: &[String]) -> Self {
+        let dbg = Debugger::spawn_proc(argv, false);
+        let pid = dbg.pid;
+        TARGET_PIDS.lock().unwrap().push(pid);
+        let _ = affinity::pin_process(pid);
+        let _ = priority::apply(pid);
+
+        TargetInstance {
+            dbg:       Some(dbg),
+            pid,
+            job:       create_kill_on_close_job(pid).ok(),
+            windows:   Arc::new(Mutex::new(Vec::new())),
+            suspended: false,
+        }
+    }
+
+    /// Attach the debugger to the already running target `pid`
+    pub fn attach(pid: u32) -> Self {
+        TARGET_PIDS.lock().unwrap().push(pid);
+        let _ = affinity::pin_process(pid);
+        let _ = priority::apply(pid);
+        TargetInstance {
+            dbg:       Some(Debugger::attach(pid)),
+            pid,
+            job:       create_kill_on_close_job(pid).ok(),
+            windows:   Arc::new(Mutex::new(Vec::new())),
+            suspended: false,
+        }
+    }
+
+    /// Get access to the debugger
+    pub fn dbg(&mut self) -> &mut Debugger<'a> {
+        self.dbg.as_mut().unwrap()
+    }
+
+    /// Get the shared list of attached windows, so a fuzzer thread can
+    /// record the windows it attaches to
+    pub fn windows(&self) -> Arc<Mutex<Vec<Window>>> {
+        self.windows.clone()
+    }
+
+    /// Debug the target until it exits or crashes
+    pub fn run(&mut self) -> ExitType {
+        self.dbg().run()
+    }
+
+    /// Detach from the crashed target, leaving it suspended at the crash.
+    /// The target outlives this instance, which can still be torn down to
+    /// get the crash details. Only valid after `run()` returned a crash.
+    pub fn suspend_crash(&mut self) -> io::Result<SuspendedCrash> {
+        self.dbg().detach_suspended()?;
+        self.suspended = true;
+
+        Ok(SuspendedCrash {
+            pid: self.pid,
+            job: self.job.take(),
+        })
+    }
+
+    /// Name of the bucket of the crash `name` the target stopped at, triaged
+    /// by the crash's call stack and machine state. Only valid after
+    /// `run()` returned a crash.
+    pub fn crash_bucket(&mut self, name: &str) -> String {
+        let dbg = self.dbg();
+        let info = dbg.crash_context.clone().map(crash_info);
+        triage::bucket(name, info.as_ref(), &dbg.crash_stack)
+    }
+
+    /// Tear down the target, returning the coverage, symbolized crash call
+    /// stack, tail of debug strings and standard output it produced, and
+    /// machine state of the crash
+    pub fn finish(mut self)
+            -> (Coverage, Vec<String>, Vec<String>, Option<CrashInfo>) {
+        let suspended = self.suspended;
+        let dbg = self.dbg();
+        if !suspended {
+            let _ = dbg.kill();
+        }
+        let coverage = std::mem::take(&mut dbg.coverage);
+        let stack    = std::mem::take(&mut dbg.crash_stack);
+        let output   = dbg.output();
+        let info     = dbg.crash_context.take().map(crash_info);
+
+        // Remaining teardown happens on drop
+        (coverage, stack, output, info)
+    }
+}
+
+/// Convert the debugger's machine state of a crash into a `CrashInfo`
+fn crash_info(context: CrashContext) -> CrashInfo {
+    CrashInfo {
+        exception_code: context.code,
+        pc:             context.pc as u64,
+        access:         context.access
+            .map(|(kind, addr)| (kind as u8, addr as u64)),
+        registers:      context.registers.into_iter()
+            .map(|(name, value)| (name.to_string(), value)).collect(),
+        code_bytes:     context.code_bytes,
+    }
+}
+
+impl<'a> Drop for TargetInstance<'a> {
+    fn drop(&mut self) {
+        // A suspended crash is kept alive, only disconnect the debugger
+        if self.suspended {
+            self.dbg.take();
+            return;
+        }
+
+        // Ask any windows we attached to to close
+        if let Ok(windows) = self.windows.lock() {
+            for window in windows.iter() {
+                let _ = window.close();
+            }
+        }
+
+        // Kill the target and drop the debugger so it disconnects its
+        // resources from the debuggee and the debuggee can exit
+        if let Some(mut dbg) = self.dbg.take() {
+            let _ = dbg.kill();
+        }
+
+        // Closing the job kills anything left in it
+        if let Some(job) = self.job.take() {
+            unsafe { CloseHandle(job); }
+        }
+        forget_target(self.pid);
+    }
+}